@@ -0,0 +1,145 @@
+//! Draws a few shapes in a window which survives the app being suspended
+//! and resumed, as on Android and iOS.
+//!
+//! On desktop: `cargo run --example mobile`. On Android, package it as a
+//! cdylib with cargo-apk or xbuild; the entry point is `android_main`.
+
+use futures::executor::block_on;
+use vger::color::Color;
+use vger::surface::SurfaceState;
+use vger::{OutputMode, RenderOptions, Vger};
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::{Window, WindowBuilder};
+
+struct Gpu {
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+}
+
+fn create_gpu() -> Gpu {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+        .expect("No suitable GPU adapters found on the system!");
+    let (device, queue) = block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: None,
+            features: wgpu::Features::default(),
+            // Mobile GPUs may not meet the desktop defaults.
+            limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+        },
+        None,
+    ))
+    .expect("Unable to find a suitable GPU adapter!");
+
+    Gpu {
+        instance,
+        adapter,
+        device,
+        queue,
+    }
+}
+
+fn draw(vger: &mut Vger, window: &Window) {
+    // Lay out in logical units so touch targets keep their physical size
+    // across screen densities.
+    let scale = window.scale_factor() as f32;
+    let size = window.inner_size();
+    let (width, height) = (size.width as f32 / scale, size.height as f32 / scale);
+
+    vger.begin(width, height, scale);
+
+    let paint = vger.color_paint(Color::CYAN);
+    vger.fill_rect(euclid::rect(24.0, 24.0, width - 48.0, 48.0), 8.0, paint);
+
+    let paint = vger.color_paint(Color::MAGENTA);
+    vger.fill_circle([0.5 * width, 0.5 * height], 0.25 * width.min(height), paint);
+
+    vger.save();
+    vger.translate([24.0, height - 48.0]);
+    vger.text("Suspend and resume me", 24, Color::WHITE, None);
+    vger.restore();
+}
+
+fn run(event_loop: EventLoop<()>) {
+    let window = WindowBuilder::new()
+        .with_title("vger mobile")
+        .with_transparent(true)
+        .build(&event_loop)
+        .unwrap();
+
+    let gpu = create_gpu();
+    let mut surface = SurfaceState::new(wgpu::TextureFormat::Bgra8UnormSrgb, true);
+    let mut vger: Option<Vger> = None;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+
+        match event {
+            // The native window only exists between these two events.
+            Event::Resumed => {
+                let new_surface = unsafe { gpu.instance.create_surface(&window) }.unwrap();
+                let size = window.inner_size();
+                surface.resume(
+                    &gpu.adapter,
+                    &gpu.device,
+                    new_surface,
+                    size.width,
+                    size.height,
+                );
+                if vger.is_none() {
+                    let format = surface.format().unwrap();
+                    let mode = match surface.alpha_mode() {
+                        wgpu::CompositeAlphaMode::PreMultiplied => OutputMode::Premultiplied,
+                        _ => OutputMode::Color,
+                    };
+                    vger = Some(Vger::new_with_mode(&gpu.device, format, mode));
+                }
+                window.request_redraw();
+            }
+            Event::Suspended => surface.suspend(),
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::Resized(size) => {
+                    surface.resize(&gpu.device, size.width, size.height);
+                    window.request_redraw();
+                }
+                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    surface.resize(&gpu.device, new_inner_size.width, new_inner_size.height);
+                    window.request_redraw();
+                }
+                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                _ => (),
+            },
+            Event::RedrawRequested(_) => {
+                if let (Some(vger), Some(frame)) = (vger.as_mut(), surface.acquire(&gpu.device)) {
+                    draw(vger, &window);
+
+                    let view = frame
+                        .texture
+                        .create_view(&wgpu::TextureViewDescriptor::default());
+                    let options = RenderOptions {
+                        clear: Some(Color::new(0.0, 0.0, 0.0, 0.0)),
+                    };
+                    vger.encode_to_view(&gpu.device, &view, &gpu.queue, &options);
+                    frame.present();
+                }
+            }
+            _ => (),
+        }
+    });
+}
+
+#[cfg(target_os = "android")]
+#[no_mangle]
+fn android_main(app: winit::platform::android::activity::AndroidApp) {
+    use winit::event_loop::EventLoopBuilder;
+    use winit::platform::android::EventLoopBuilderExtAndroid;
+
+    run(EventLoopBuilder::new().with_android_app(app).build());
+}
+
+fn main() {
+    run(EventLoop::new());
+}
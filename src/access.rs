@@ -0,0 +1,119 @@
+//! Geometry export for accessibility integrations such as AccessKit.
+
+use std::collections::HashMap;
+
+use crate::defs::*;
+
+/// Identifies a node in the host's accessibility tree.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AccessNode(pub u64);
+
+/// What was drawn for an accessibility node during a frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccessInfo {
+    pub node: AccessNode,
+
+    /// Device pixel bounds of everything drawn for the node.
+    pub bounds: ScreenRect,
+
+    /// Text drawn for the node, with separate draw calls joined by spaces.
+    pub text: String,
+}
+
+/// Collects `AccessInfo` in the order nodes are first drawn.
+#[derive(Default)]
+pub(crate) struct AccessRecorder {
+    infos: Vec<AccessInfo>,
+    index: HashMap<AccessNode, usize>,
+}
+
+impl AccessRecorder {
+    pub fn clear(&mut self) {
+        self.infos.clear();
+        self.index.clear();
+    }
+
+    fn info(&mut self, node: AccessNode, bounds: ScreenRect) -> &mut AccessInfo {
+        let infos = &mut self.infos;
+        let i = *self.index.entry(node).or_insert_with(|| {
+            infos.push(AccessInfo {
+                node,
+                bounds,
+                text: String::new(),
+            });
+            infos.len() - 1
+        });
+        &mut self.infos[i]
+    }
+
+    pub fn add_bounds(&mut self, node: AccessNode, bounds: ScreenRect) {
+        let info = self.info(node, bounds);
+        info.bounds = if info.bounds.is_empty() {
+            bounds
+        } else {
+            info.bounds.union(&bounds)
+        };
+    }
+
+    pub fn add_text(&mut self, node: AccessNode, text: &str) {
+        let info = self.info(node, ScreenRect::zero());
+        if !info.text.is_empty() {
+            info.text.push(' ');
+        }
+        info.text.push_str(text);
+    }
+
+    pub fn infos(&self) -> &[AccessInfo] {
+        &self.infos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_recorder() {
+        let mut recorder = AccessRecorder::default();
+        let a = AccessNode(1);
+        let b = AccessNode(2);
+
+        recorder.add_bounds(
+            b,
+            ScreenRect::new(ScreenPoint::new(0.0, 0.0), [1.0, 1.0].into()),
+        );
+        recorder.add_bounds(
+            a,
+            ScreenRect::new(ScreenPoint::new(10.0, 10.0), [5.0, 5.0].into()),
+        );
+        recorder.add_bounds(
+            a,
+            ScreenRect::new(ScreenPoint::new(20.0, 10.0), [5.0, 5.0].into()),
+        );
+        recorder.add_text(a, "OK");
+        recorder.add_text(a, "Cancel");
+
+        // Text before any bounds doesn't pull in the origin.
+        let c = AccessNode(3);
+        recorder.add_text(c, " ");
+        recorder.add_bounds(
+            c,
+            ScreenRect::new(ScreenPoint::new(5.0, 5.0), [1.0, 1.0].into()),
+        );
+
+        let infos = recorder.infos();
+        assert_eq!(infos.len(), 3);
+        assert_eq!(infos[0].node, b);
+        assert_eq!(infos[1].node, a);
+        assert_eq!(
+            infos[1].bounds,
+            ScreenRect::new(ScreenPoint::new(10.0, 10.0), [15.0, 5.0].into())
+        );
+        assert_eq!(infos[1].text, "OK Cancel");
+        assert_eq!(infos[2].bounds.origin, ScreenPoint::new(5.0, 5.0));
+
+        recorder.clear();
+        assert!(recorder.infos().is_empty());
+    }
+}
@@ -0,0 +1,252 @@
+//! How text is arranged within its `max_width`: alignment, line height,
+//! paragraph spacing and indents, and how fonts are sized.
+
+/// How lines of text are placed within the text's `max_width`. Without a
+/// `max_width`, text is left aligned.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+
+    /// Lines wrapped by `max_width` are stretched to fill it, widening
+    /// the gaps between words, or between characters in lines with no
+    /// spaces, such as Chinese and Japanese. Last lines of paragraphs are
+    /// left aligned.
+    Justify,
+}
+
+impl Default for TextAlign {
+    fn default() -> Self {
+        TextAlign::Left
+    }
+}
+
+/// Distance between the baselines of lines in a paragraph.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineHeight {
+    /// A multiple of the font's line height.
+    Multiple(f32),
+
+    /// A fixed distance in local coordinates, e.g. from a typography
+    /// scale.
+    Absolute(f32),
+}
+
+impl Default for LineHeight {
+    fn default() -> Self {
+        LineHeight::Multiple(1.0)
+    }
+}
+
+/// Layout options for text, part of the state saved by `Vger::save`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TextLayout {
+    pub align: TextAlign,
+    pub line_height: LineHeight,
+
+    /// Space added after each line break, in local coordinates.
+    pub paragraph_spacing: f32,
+
+    /// Indent of the first line of each paragraph, in local coordinates.
+    pub first_line_indent: f32,
+
+    /// Indent of the other lines of each paragraph, e.g. to line wrapped
+    /// lines of a bullet up with the text after the bullet.
+    pub hanging_indent: f32,
+
+    /// As CSS `font-size-adjust`, scales each font, including fallback
+    /// fonts, so its x-height is this fraction of the font size, so mixed
+    /// scripts look the same size.
+    pub size_adjust: Option<f32>,
+}
+
+impl TextLayout {
+    /// Bits identifying the options, for cache keys.
+    pub(crate) fn key(&self) -> [u32; 6] {
+        let (kind, height) = match self.line_height {
+            LineHeight::Multiple(m) => (0, m),
+            LineHeight::Absolute(h) => (1, h),
+        };
+        [
+            self.align as u32 | (kind << 8),
+            height.to_bits(),
+            self.paragraph_spacing.to_bits(),
+            self.first_line_indent.to_bits(),
+            self.hanging_indent.to_bits(),
+            self.size_adjust.map_or(u32::MAX, f32::to_bits),
+        ]
+    }
+
+    pub(crate) fn is_indented(&self) -> bool {
+        self.first_line_indent != 0.0 || self.hanging_indent != 0.0
+    }
+
+    /// Indent of a line, given whether it starts a paragraph.
+    pub(crate) fn indent(&self, first: bool) -> f32 {
+        if first {
+            self.first_line_indent
+        } else {
+            self.hanging_indent
+        }
+    }
+
+    /// How far a line laid out in the width left after its `indent` moves
+    /// right. Indents narrow centered lines on both sides and right
+    /// aligned lines only on the left.
+    pub(crate) fn indent_offset(&self, indent: f32) -> f32 {
+        match self.align {
+            TextAlign::Left | TextAlign::Justify => indent,
+            TextAlign::Center => indent / 2.0,
+            TextAlign::Right => 0.0,
+        }
+    }
+}
+
+/// `size` scaled by `adjust` for a font whose x-height is `aspect` times
+/// its size. Fonts without an x-height keep their size.
+pub(crate) fn adjusted_size(size: f32, adjust: Option<f32>, aspect: Option<f32>) -> f32 {
+    match (adjust, aspect) {
+        (Some(adjust), Some(aspect)) if aspect > 0.0 => size * adjust / aspect,
+        _ => size,
+    }
+}
+
+/// Horizontal offsets justifying a wrapped line to `width`, given each
+/// glyph's character and right edge. Spaces after the last visible glyph
+/// don't stretch.
+pub(crate) fn justify_offsets(chars: &[char], rights: &[f32], width: f32) -> Vec<f32> {
+    let mut offsets = vec![0.0; chars.len()];
+    let last = match chars.iter().rposition(|c| !c.is_whitespace()) {
+        Some(last) => last,
+        None => return offsets,
+    };
+    let extra = width - rights[last];
+    if extra <= 0.0 {
+        return offsets;
+    }
+
+    // Gaps at the start of runs of spaces between words.
+    let is_gap = |i: usize| i > 0 && chars[i].is_whitespace() && !chars[i - 1].is_whitespace();
+    let words = (1..last).filter(|i| is_gap(*i)).count();
+
+    let (step, gaps) = if words > 0 {
+        (extra / words as f32, words)
+    } else {
+        (extra / last.max(1) as f32, last)
+    };
+    if gaps == 0 {
+        return offsets;
+    }
+
+    let mut count = 0;
+    for (i, offset) in offsets.iter_mut().enumerate().take(last + 1) {
+        if words > 0 {
+            if is_gap(i) {
+                count += 1;
+            }
+        } else if i > 0 {
+            count += 1;
+        }
+        *offset = step * count as f32;
+    }
+
+    // Trailing spaces stay with the last glyph.
+    for offset in offsets.iter_mut().skip(last + 1) {
+        *offset = extra;
+    }
+    offsets
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_justify_words() {
+        // "ab cd ef " with unit width glyphs.
+        let chars: Vec<char> = "ab cd ef ".chars().collect();
+        let rights: Vec<f32> = (1..=chars.len()).map(|i| i as f32).collect();
+        let offsets = justify_offsets(&chars, &rights, 12.0);
+
+        // 4 units spread over two word gaps.
+        assert_eq!(offsets, [0.0, 0.0, 2.0, 2.0, 2.0, 4.0, 4.0, 4.0, 4.0]);
+    }
+
+    #[test]
+    fn test_justify_letters() {
+        let chars: Vec<char> = "日本語".chars().collect();
+        let offsets = justify_offsets(&chars, &[10.0, 20.0, 30.0], 40.0);
+        assert_eq!(offsets, [0.0, 5.0, 10.0]);
+    }
+
+    #[test]
+    fn test_layout_key() {
+        let layout = TextLayout::default();
+        let absolute = TextLayout {
+            line_height: LineHeight::Absolute(1.0),
+            ..layout
+        };
+        let spaced = TextLayout {
+            paragraph_spacing: 8.0,
+            ..layout
+        };
+        let right = TextLayout {
+            align: TextAlign::Right,
+            ..layout
+        };
+        let hanging = TextLayout {
+            hanging_indent: 20.0,
+            ..layout
+        };
+        let adjusted = TextLayout {
+            size_adjust: Some(0.5),
+            ..layout
+        };
+        assert_ne!(layout.key(), absolute.key());
+        assert_ne!(layout.key(), hanging.key());
+        assert_ne!(layout.key(), adjusted.key());
+        assert_ne!(layout.key(), spaced.key());
+        assert_ne!(layout.key(), right.key());
+        assert_eq!(layout.key(), TextLayout::default().key());
+    }
+
+    #[test]
+    fn test_indent() {
+        let layout = TextLayout {
+            first_line_indent: 10.0,
+            hanging_indent: 20.0,
+            ..TextLayout::default()
+        };
+        assert!(layout.is_indented());
+        assert!(!TextLayout::default().is_indented());
+        assert_eq!(layout.indent(true), 10.0);
+        assert_eq!(layout.indent(false), 20.0);
+        assert_eq!(layout.indent_offset(20.0), 20.0);
+
+        let center = TextLayout {
+            align: TextAlign::Center,
+            ..layout
+        };
+        assert_eq!(center.indent_offset(20.0), 10.0);
+    }
+
+    #[test]
+    fn test_adjusted_size() {
+        assert_eq!(adjusted_size(20.0, Some(0.5), Some(0.4)), 25.0);
+        assert_eq!(adjusted_size(20.0, None, Some(0.4)), 20.0);
+        assert_eq!(adjusted_size(20.0, Some(0.5), None), 20.0);
+        assert_eq!(adjusted_size(20.0, Some(0.5), Some(0.0)), 20.0);
+    }
+
+    #[test]
+    fn test_justify_nothing() {
+        assert_eq!(justify_offsets(&['a'], &[10.0], 40.0), [0.0]);
+        assert_eq!(justify_offsets(&[' ', ' '], &[1.0, 2.0], 40.0), [0.0, 0.0]);
+        assert_eq!(
+            justify_offsets(&['a', ' ', 'b'], &[10.0, 11.0, 21.0], 20.0),
+            [0.0, 0.0, 0.0]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use crate::defs::*;
+use crate::prim::Prim;
+
+/// Scratch memory for temporaries which only live for a frame.
+///
+/// Everything is cleared by `reset` in `Vger::begin` but keeps its
+/// capacity, so once an app reaches a steady state, recording and
+/// encoding a frame doesn't touch the allocator.
+#[derive(Default)]
+pub(crate) struct FrameArena {
+    /// Bump allocated bytes, e.g. padded texture uploads.
+    pub bytes: Vec<u8>,
+
+    prims: Vec<Prim>,
+    points: Vec<LocalPoint>,
+    vectors: Vec<LocalVector>,
+    floats: Vec<f32>,
+    outlines: Vec<(u16, LocalPoint)>,
+    markers: Vec<(LocalPoint, LocalVector)>,
+}
+
+/// Takes `buf`, empty, leaving an unallocated one in its place.
+fn take<T>(buf: &mut Vec<T>) -> Vec<T> {
+    let mut taken = std::mem::take(buf);
+    taken.clear();
+    taken
+}
+
+/// Hands back a buffer from `take`, keeping whichever is larger.
+fn put<T>(buf: &mut Vec<T>, taken: Vec<T>) {
+    if taken.capacity() > buf.capacity() {
+        *buf = taken;
+    }
+}
+
+fn bytes<T>(buf: &Vec<T>) -> usize {
+    buf.capacity() * std::mem::size_of::<T>()
+}
+
+impl FrameArena {
+    pub fn reset(&mut self) {
+        self.bytes.clear();
+        self.prims.clear();
+        self.points.clear();
+        self.vectors.clear();
+        self.floats.clear();
+        self.outlines.clear();
+        self.markers.clear();
+    }
+
+    /// Releases all memory.
+    pub fn shrink(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Borrows the prim staging buffer, empty. Hand it back with
+    /// `put_prims` so its capacity is reused.
+    pub fn take_prims(&mut self) -> Vec<Prim> {
+        take(&mut self.prims)
+    }
+
+    pub fn put_prims(&mut self, prims: Vec<Prim>) {
+        put(&mut self.prims, prims)
+    }
+
+    /// Borrows the point scratch buffer, empty. Hand it back with
+    /// `put_points` so its capacity is reused.
+    pub fn take_points(&mut self) -> Vec<LocalPoint> {
+        take(&mut self.points)
+    }
+
+    pub fn put_points(&mut self, points: Vec<LocalPoint>) {
+        put(&mut self.points, points)
+    }
+
+    /// Borrows the vector scratch buffer, e.g. for per glyph offsets.
+    pub fn take_vectors(&mut self) -> Vec<LocalVector> {
+        take(&mut self.vectors)
+    }
+
+    pub fn put_vectors(&mut self, vectors: Vec<LocalVector>) {
+        put(&mut self.vectors, vectors)
+    }
+
+    /// Borrows the float scratch buffer, e.g. for parameters along a
+    /// path.
+    pub fn take_floats(&mut self) -> Vec<f32> {
+        take(&mut self.floats)
+    }
+
+    pub fn put_floats(&mut self, floats: Vec<f32>) {
+        put(&mut self.floats, floats)
+    }
+
+    /// Borrows the buffer of glyph ids and origins drawn as outlines.
+    pub fn take_outlines(&mut self) -> Vec<(u16, LocalPoint)> {
+        take(&mut self.outlines)
+    }
+
+    pub fn put_outlines(&mut self, outlines: Vec<(u16, LocalPoint)>) {
+        put(&mut self.outlines, outlines)
+    }
+
+    /// Borrows the buffer of marker positions and directions.
+    pub fn take_markers(&mut self) -> Vec<(LocalPoint, LocalVector)> {
+        take(&mut self.markers)
+    }
+
+    pub fn put_markers(&mut self, markers: Vec<(LocalPoint, LocalVector)>) {
+        put(&mut self.markers, markers)
+    }
+
+    /// Bytes currently reserved by the arena.
+    pub fn capacity(&self) -> usize {
+        self.bytes.capacity()
+            + bytes(&self.prims)
+            + bytes(&self.points)
+            + bytes(&self.vectors)
+            + bytes(&self.floats)
+            + bytes(&self.outlines)
+            + bytes(&self.markers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_reuse() {
+        let mut arena = FrameArena::default();
+
+        let mut points = arena.take_points();
+        points.extend((0..100).map(|i| LocalPoint::new(i as f32, 0.0)));
+        let ptr = points.as_ptr();
+        arena.put_points(points);
+
+        arena.bytes.resize(1000, 0);
+        let capacity = arena.capacity();
+
+        arena.reset();
+        assert_eq!(arena.capacity(), capacity);
+        assert!(arena.bytes.is_empty());
+
+        let points = arena.take_points();
+        assert!(points.is_empty());
+        assert_eq!(points.as_ptr(), ptr);
+
+        // A nested borrow gets a fresh buffer, and the larger one is kept.
+        let inner = arena.take_points();
+        assert_eq!(inner.capacity(), 0);
+        arena.put_points(inner);
+        arena.put_points(points);
+        assert_eq!(arena.take_points().as_ptr(), ptr);
+
+        // The other scratch buffers count towards the capacity too.
+        let before = arena.capacity();
+        let mut floats = arena.take_floats();
+        floats.extend([1.0; 16]);
+        arena.put_floats(floats);
+        assert!(arena.capacity() >= before + 16 * std::mem::size_of::<f32>());
+    }
+}
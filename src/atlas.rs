@@ -1,72 +1,628 @@
 use rect_packer::{Packer, Rect};
 use wgpu::util::DeviceExt;
 
+use crate::shelf::ShelfPacker;
+
+/// A region waiting to be uploaded.
 #[derive(Debug)]
 struct ImageData {
     rect: Rect,
     data: Vec<u8>,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum AtlasContent {
     Mask,
     Color,
 }
 
-pub struct Atlas {
-    packer: Packer,
+impl AtlasContent {
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            AtlasContent::Mask => 1,
+            AtlasContent::Color => 4,
+        }
+    }
+}
+
+/// How an atlas arranges its regions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Packing {
+    /// General rectangle packing, for images of any shape.
+    Rects,
+
+    /// Rows of regions of similar height, which pack glyphs more tightly
+    /// and can be evicted a row at a time. See `ShelfPacker`.
+    Shelves,
+}
+
+impl Default for Packing {
+    fn default() -> Self {
+        Packing::Rects
+    }
+}
+
+/// Finds room for regions of an atlas, for workloads the built-in
+/// `Packing`s don't suit, e.g. a grid for sprites of one size. See
+/// `Atlas::set_packer`.
+pub trait RectPackerImpl: Send {
+    /// Room for a `width` by `height` region, or `None` when full. The
+    /// packer leaves any padding it wants around regions.
+    fn pack(&mut self, width: i32, height: i32) -> Option<Rect>;
+
+    /// Frees a region `pack` returned, and returns whether its space can
+    /// be reused, so it no longer counts toward the atlas usage.
+    fn free(&mut self, rect: Rect) -> bool;
+
+    /// Frees every region.
+    fn reset(&mut self);
+}
+
+impl RectPackerImpl for ShelfPacker {
+    fn pack(&mut self, width: i32, height: i32) -> Option<Rect> {
+        ShelfPacker::pack(self, width, height)
+    }
+
+    fn free(&mut self, rect: Rect) -> bool {
+        ShelfPacker::free(self, rect)
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+enum AtlasPacker {
+    Rects(Packer),
+    Shelves(ShelfPacker),
+    Custom(Box<dyn RectPackerImpl>),
+}
+
+impl AtlasPacker {
+    fn new(packing: Packing, size: u32, padding: i32) -> Self {
+        match packing {
+            Packing::Rects => {
+                AtlasPacker::Rects(Packer::new(Atlas::get_packer_config(size, padding)))
+            }
+            Packing::Shelves => AtlasPacker::Shelves(ShelfPacker::new(size, padding as u32)),
+        }
+    }
+
+    fn pack(&mut self, width: i32, height: i32) -> Option<Rect> {
+        match self {
+            AtlasPacker::Rects(packer) => packer.pack(width, height, false),
+            AtlasPacker::Shelves(packer) => packer.pack(width, height),
+            AtlasPacker::Custom(packer) => packer.pack(width, height),
+        }
+    }
+
+    fn free(&mut self, rect: Rect) -> bool {
+        match self {
+            // Rect packing can't reuse space until it's reset.
+            AtlasPacker::Rects(_) => false,
+            AtlasPacker::Shelves(packer) => packer.free(rect),
+            AtlasPacker::Custom(packer) => packer.free(rect),
+        }
+    }
+}
+
+/// Space for images in an atlas, as used by the glyph cache.
+pub trait AtlasRegions {
+    /// Reserves room for a `width` by `height` image and queues its pixels
+    /// for upload. Returns `None` when the atlas is full.
+    fn add_region(&mut self, data: &[u8], width: u32, height: u32) -> Option<Rect>;
+
+    /// Reserves room for an image which will be written on the gpu, so
+    /// nothing is uploaded.
+    fn reserve_region(&mut self, width: u32, height: u32) -> Option<Rect>;
+
+    /// Frees a region returned by `add_region` or `reserve_region`, if
+    /// the packer can reuse its space. Rect packing only reuses space
+    /// once the atlas is cleared.
+    fn free_region(&mut self, rect: Rect);
+
+    /// Fraction of the atlas area in use, from 0 to 1.
+    fn usage(&self) -> f32;
+
+    /// Frees all regions.
+    fn clear(&mut self);
+
+    /// Frees the shelf containing row `y`, for atlases packed in shelves,
+    /// returning its bounds so regions cached there can be dropped.
+    fn evict_shelf(&mut self, _y: i32) -> Option<Rect> {
+        None
+    }
+
+    /// Width and height in pixels.
+    fn size(&self) -> u32;
+
+    /// Changes the width and height, freeing all regions. An `Atlas`
+    /// makes a texture of the new size in `reallocate`.
+    fn resize(&mut self, size: u32);
+}
+
+/// Packing and bookkeeping for an atlas, without a texture, so it can be
+/// used and tested without a device.
+pub struct AtlasAllocator {
+    packer: AtlasPacker,
+    packing: Packing,
     new_data: Vec<ImageData>,
-    pub atlas_texture: wgpu::Texture,
     area_used: i32,
     did_clear: bool,
     content: AtlasContent,
+
+    /// Width and height in pixels.
+    size: u32,
+
+    /// Pixels between regions and around the edge of the atlas.
+    padding: i32,
+
+    /// Whether uploaded regions are surrounded by copies of their edge
+    /// pixels.
+    extrude: bool,
+}
+
+impl AtlasAllocator {
+    pub fn new(content: AtlasContent) -> Self {
+        Self::with_padding(content, Atlas::RECT_PADDING as u32, false)
+    }
+
+    /// Allocator leaving `padding` pixels between regions. With `extrude`,
+    /// uploaded images get a one pixel border copied from their edges, so
+    /// linear filtering at the edges doesn't pick up their neighbours.
+    pub fn with_padding(content: AtlasContent, padding: u32, extrude: bool) -> Self {
+        Self {
+            packer: AtlasPacker::new(Packing::Rects, Atlas::ATLAS_SIZE, padding as i32),
+            packing: Packing::Rects,
+            new_data: vec![],
+            area_used: 0,
+            did_clear: false,
+            content,
+            size: Atlas::ATLAS_SIZE,
+            padding: padding as i32,
+            extrude,
+        }
+    }
+
+    /// Allocator for an atlas `size` pixels square.
+    pub fn with_size(content: AtlasContent, size: u32) -> Self {
+        let mut allocator = Self::new(content);
+        allocator.size = size;
+        allocator.packer = AtlasPacker::new(Packing::Rects, size, allocator.padding);
+        allocator
+    }
+
+    /// Changes how regions are packed, replacing any custom packer, and
+    /// clears the atlas if anything was packed.
+    pub fn set_packing(&mut self, packing: Packing) {
+        self.packing = packing;
+        self.packer = AtlasPacker::new(packing, self.size, self.padding);
+        if self.area_used > 0 || !self.new_data.is_empty() {
+            self.clear();
+        }
+    }
+
+    /// The built-in packing in use, or `None` with a custom packer.
+    pub fn packing(&self) -> Option<Packing> {
+        match self.packer {
+            AtlasPacker::Custom(_) => None,
+            _ => Some(self.packing),
+        }
+    }
+
+    /// Packs regions with `packer` from now on, clearing the atlas.
+    pub fn set_packer(&mut self, packer: Box<dyn RectPackerImpl>) {
+        self.packer = AtlasPacker::Custom(packer);
+        self.clear();
+    }
+
+    fn pack(&mut self, width: i32, height: i32) -> Option<Rect> {
+        let rect = self.packer.pack(width, height)?;
+        self.area_used += (rect.width + self.padding) * (rect.height + self.padding);
+        Some(rect)
+    }
+
+    /// Changes the padding and extrusion, clearing the atlas since
+    /// existing regions were packed with the old settings.
+    pub fn set_padding(&mut self, padding: u32, extrude: bool) {
+        self.padding = padding as i32;
+        self.extrude = extrude;
+        self.clear();
+    }
+
+    /// Number of regions waiting to be uploaded.
+    pub fn pending(&self) -> usize {
+        self.new_data.len()
+    }
+
+    /// Whether the atlas was cleared since the last upload.
+    pub fn did_clear(&self) -> bool {
+        self.did_clear
+    }
+}
+
+impl AtlasRegions for AtlasAllocator {
+    fn add_region(&mut self, data: &[u8], width: u32, height: u32) -> Option<Rect> {
+        let rect = self.reserve_region(width, height)?;
+        if !self.extrude || width == 0 || height == 0 {
+            self.new_data.push(ImageData {
+                rect,
+                data: data.into(),
+            });
+        } else {
+            let pixel = self.content.bytes_per_pixel();
+            self.new_data.push(ImageData {
+                rect: Rect::new(rect.x - 1, rect.y - 1, rect.width + 2, rect.height + 2),
+                data: extrude(data, width as usize, height as usize, pixel),
+            });
+        }
+        Some(rect)
+    }
+
+    /// Reserved regions get the border extruded ones have, so
+    /// `free_region` treats both alike.
+    fn reserve_region(&mut self, width: u32, height: u32) -> Option<Rect> {
+        if !self.extrude || width == 0 || height == 0 {
+            return self.pack(width as i32, height as i32);
+        }
+        let outer = self.pack(width as i32 + 2, height as i32 + 2)?;
+        Some(Rect::new(
+            outer.x + 1,
+            outer.y + 1,
+            width as i32,
+            height as i32,
+        ))
+    }
+
+    fn free_region(&mut self, rect: Rect) {
+        let rect = if self.extrude && rect.width > 0 && rect.height > 0 {
+            Rect::new(rect.x - 1, rect.y - 1, rect.width + 2, rect.height + 2)
+        } else {
+            rect
+        };
+        if self.packer.free(rect) {
+            self.area_used -= (rect.width + self.padding) * (rect.height + self.padding);
+        }
+    }
+
+    /// Also queues zeros for the shelf's pixels, so edges of evicted
+    /// regions don't bleed into regions packed there later.
+    fn evict_shelf(&mut self, y: i32) -> Option<Rect> {
+        let (bounds, area) = match &mut self.packer {
+            AtlasPacker::Shelves(packer) => packer.evict_shelf(y)?,
+            _ => return None,
+        };
+        self.area_used -= area;
+        let len = (bounds.width * bounds.height) as usize * self.content.bytes_per_pixel();
+        self.new_data.push(ImageData {
+            rect: bounds,
+            data: vec![0; len],
+        });
+        Some(bounds)
+    }
+
+    fn usage(&self) -> f32 {
+        (self.area_used as f32) / (self.size as f32 * self.size as f32)
+    }
+
+    fn size(&self) -> u32 {
+        self.size
+    }
+
+    fn resize(&mut self, size: u32) {
+        self.size = size;
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        if let AtlasPacker::Custom(packer) = &mut self.packer {
+            packer.reset();
+        } else {
+            self.packer = AtlasPacker::new(self.packing, self.size, self.padding);
+        }
+        self.area_used = 0;
+        self.new_data.clear();
+        self.did_clear = true;
+    }
+}
+
+/// Texture operations an `Atlas` needs, so the packing and caching logic
+/// can drive backends other than wgpu, such as a software rasterizer or a
+/// mock in tests.
+pub trait AtlasBackend {
+    /// Whatever is needed to create the texture, e.g. `wgpu::Device`.
+    type Device: ?Sized;
+
+    /// Creates a texture `size` pixels square, initially zero.
+    fn create_texture(device: &Self::Device, size: u32, content: AtlasContent) -> Self;
+
+    /// Writes pixels to `rect`. Rows in `data` are tightly packed.
+    fn upload_region(&mut self, rect: Rect, data: &[u8]);
+
+    /// Copies the pixels in `src` so their top left corner is at (x, y).
+    fn copy_region(&mut self, src: Rect, x: i32, y: i32);
+
+    /// Sets the whole texture to zero.
+    fn clear(&mut self);
+}
+
+/// An `AtlasAllocator` and the texture its regions are uploaded to.
+pub struct Atlas<B: AtlasBackend = WgpuAtlasBackend> {
+    allocator: AtlasAllocator,
+    pub backend: B,
+
+    /// Width and height of the backend's texture, which differs from the
+    /// allocator's after a resize until `reallocate`.
+    texture_size: u32,
 }
 
 impl Atlas {
+    /// Default width and height, used unless the device allows less.
     pub const ATLAS_SIZE: u32 = 4096;
+    /// Smallest width and height `GlyphCache::shrink_atlases` leaves.
+    pub const MIN_SIZE: u32 = 256;
+    /// Default pixels between regions.
     pub const RECT_PADDING: i32 = 6;
 
-    fn get_packer_config() -> rect_packer::Config {
+    fn get_packer_config(size: u32, padding: i32) -> rect_packer::Config {
         rect_packer::Config {
-            width: Atlas::ATLAS_SIZE as i32,
-            height: Atlas::ATLAS_SIZE as i32,
+            width: size as i32,
+            height: size as i32,
 
-            border_padding: Atlas::RECT_PADDING,
-            rectangle_padding: Atlas::RECT_PADDING,
+            border_padding: padding,
+            rectangle_padding: padding,
         }
     }
 
-    pub fn get_texture_desc() -> wgpu::TextureDescriptor<'static> {
-        let texture_size = wgpu::Extent3d {
-            width: Atlas::ATLAS_SIZE,
-            height: Atlas::ATLAS_SIZE,
-            depth_or_array_layers: 1,
-        };
+    /// Describes the atlas texture, e.g. to read it back.
+    pub fn get_texture_desc(&self) -> wgpu::TextureDescriptor<'static> {
+        WgpuAtlasBackend::texture_desc(self.texture_size, self.allocator.content)
+    }
 
-        wgpu::TextureDescriptor {
-            size: texture_size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::R8Unorm,
-            usage: wgpu::TextureUsages::COPY_SRC
-                | wgpu::TextureUsages::COPY_DST
-                | wgpu::TextureUsages::TEXTURE_BINDING,
-            label: Some("atlas_texture"),
-            view_formats: &[wgpu::TextureFormat::R8Unorm],
+    /// `size` clamped to the largest texture `device` supports, since
+    /// creating a larger one panics.
+    pub fn clamped_size(device: &wgpu::Device, size: u32) -> u32 {
+        size.min(device.limits().max_texture_dimension_2d)
+    }
+
+    /// Sends new regions to the gpu, padding uploads in `scratch`, which
+    /// is left empty with its capacity kept for the next frame.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        scratch: &mut Vec<u8>,
+    ) {
+        self.reallocate(device);
+
+        // Uploads flushed earlier keep their offsets.
+        scratch.clear();
+        scratch.append(&mut self.backend.staging);
+        std::mem::swap(&mut self.backend.staging, scratch);
+        self.flush();
+        self.backend.submit(device, encoder);
+        std::mem::swap(&mut self.backend.staging, scratch);
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.backend.texture
+    }
+
+    /// View of a color atlas which decodes sRGB when sampled, so filtering
+    /// happens in linear space. The pixels are stored sRGB encoded either
+    /// way, so uploads don't change.
+    pub fn create_srgb_view(&self) -> wgpu::TextureView {
+        self.backend
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor {
+                format: Some(wgpu::TextureFormat::Rgba8UnormSrgb),
+                ..Default::default()
+            })
+    }
+
+    pub fn create_view(&self) -> wgpu::TextureView {
+        self.backend
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    /// Bytes held by the texture and uploads not yet sent.
+    pub fn memory_usage(&self) -> usize {
+        let pixels = self.allocator.content.bytes_per_pixel();
+        let pending: usize = self
+            .allocator
+            .new_data
+            .iter()
+            .map(|d| d.data.capacity())
+            .sum();
+        (self.texture_size * self.texture_size) as usize * pixels
+            + pending
+            + self.backend.staging.capacity()
+    }
+}
+
+impl<B: AtlasBackend> Atlas<B> {
+    pub fn new(device: &B::Device, content: AtlasContent) -> Self {
+        Self::with_size(device, content, Atlas::ATLAS_SIZE)
+    }
+
+    /// Atlas `size` pixels square. For wgpu, see `Atlas::clamped_size`.
+    pub fn with_size(device: &B::Device, content: AtlasContent, size: u32) -> Self {
+        Self {
+            allocator: AtlasAllocator::with_size(content, size),
+            backend: B::create_texture(device, size, content),
+            texture_size: size,
         }
     }
 
-    pub fn new(device: &wgpu::Device, content: AtlasContent) -> Self {
+    /// Makes a new texture if the atlas was resized, returning whether it
+    /// did. The new texture is empty, so anything bound to the old one
+    /// needs binding again.
+    pub fn reallocate(&mut self, device: &B::Device) -> bool {
+        if self.texture_size == self.allocator.size {
+            return false;
+        }
+        self.texture_size = self.allocator.size;
+        self.backend = B::create_texture(device, self.texture_size, self.allocator.content);
+        // New textures start out zeroed.
+        self.allocator.did_clear = false;
+        true
+    }
+
+    /// Passes pending clears and uploads to the backend.
+    pub fn flush(&mut self) {
+        if self.allocator.did_clear {
+            self.backend.clear();
+            self.allocator.did_clear = false;
+        }
+
+        for data in &self.allocator.new_data {
+            self.backend.upload_region(data.rect, &data.data);
+        }
+        self.allocator.new_data.clear();
+    }
+}
+
+impl<B: AtlasBackend> AtlasRegions for Atlas<B> {
+    fn add_region(&mut self, data: &[u8], width: u32, height: u32) -> Option<Rect> {
+        self.allocator.add_region(data, width, height)
+    }
+
+    fn reserve_region(&mut self, width: u32, height: u32) -> Option<Rect> {
+        self.allocator.reserve_region(width, height)
+    }
+
+    fn free_region(&mut self, rect: Rect) {
+        self.allocator.free_region(rect)
+    }
+
+    fn usage(&self) -> f32 {
+        self.allocator.usage()
+    }
+
+    fn clear(&mut self) {
+        self.allocator.clear()
+    }
+
+    fn evict_shelf(&mut self, y: i32) -> Option<Rect> {
+        self.allocator.evict_shelf(y)
+    }
+
+    fn size(&self) -> u32 {
+        self.allocator.size
+    }
+
+    fn resize(&mut self, size: u32) {
+        self.allocator.resize(size)
+    }
+}
+
+impl<B: AtlasBackend> Atlas<B> {
+    /// See `AtlasAllocator::set_padding`.
+    pub fn set_padding(&mut self, padding: u32, extrude: bool) {
+        self.allocator.set_padding(padding, extrude);
+    }
+
+    /// See `AtlasAllocator::set_packing`.
+    pub fn set_packing(&mut self, packing: Packing) {
+        self.allocator.set_packing(packing);
+    }
+
+    /// See `AtlasAllocator::set_packer`.
+    pub fn set_packer(&mut self, packer: Box<dyn RectPackerImpl>) {
+        self.allocator.set_packer(packer);
+    }
+}
+
+enum AtlasOp {
+    Clear,
+    Upload {
+        rect: Rect,
+        offset: usize,
+        bytes_per_row: usize,
+    },
+    Copy {
+        src: Rect,
+        x: i32,
+        y: i32,
+    },
+}
+
+/// Atlas texture on the gpu. Operations are recorded and encoded by
+/// `submit`.
+pub struct WgpuAtlasBackend {
+    pub texture: wgpu::Texture,
+    size: u32,
+    content: AtlasContent,
+    ops: Vec<AtlasOp>,
+
+    /// Padded pixels for all uploads, sent in one buffer. `Atlas::update`
+    /// lends it the frame arena's bytes, so it's empty between updates.
+    staging: Vec<u8>,
+}
+
+impl AtlasBackend for WgpuAtlasBackend {
+    type Device = wgpu::Device;
+
+    fn create_texture(device: &wgpu::Device, size: u32, content: AtlasContent) -> Self {
+        let desc = Self::texture_desc(size, content);
+
+        Self {
+            texture: device.create_texture(&desc),
+            size,
+            content,
+            ops: vec![],
+            staging: vec![],
+        }
+    }
+
+    fn upload_region(&mut self, rect: Rect, data: &[u8]) {
+        // Each region starts at a multiple of
+        // wgpu::COPY_BYTES_PER_ROW_ALIGNMENT in the staging buffer.
+        let width = rect.width as usize * self.content.bytes_per_pixel();
+        let offset = self.staging.len();
+        pad_rows(data, width, rect.height as usize, &mut self.staging);
+        self.ops.push(AtlasOp::Upload {
+            rect,
+            offset,
+            bytes_per_row: padded_row_width(width),
+        });
+    }
+
+    fn copy_region(&mut self, src: Rect, x: i32, y: i32) {
+        self.ops.push(AtlasOp::Copy { src, x, y });
+    }
+
+    fn clear(&mut self) {
+        // Earlier operations would be overwritten anyway.
+        self.ops.clear();
+        self.staging.clear();
+        self.ops.push(AtlasOp::Clear);
+    }
+}
+
+impl WgpuAtlasBackend {
+    fn texture_desc(size: u32, content: AtlasContent) -> wgpu::TextureDescriptor<'static> {
         let texture_size = wgpu::Extent3d {
-            width: Atlas::ATLAS_SIZE,
-            height: Atlas::ATLAS_SIZE,
+            width: size,
+            height: size,
             depth_or_array_layers: 1,
         };
-        let format = match content {
-            AtlasContent::Mask => wgpu::TextureFormat::R8Unorm,
-            AtlasContent::Color => wgpu::TextureFormat::Rgba8Unorm,
+        // Color atlases can also be viewed as sRGB.
+        let (format, view_formats): (_, &'static [_]) = match content {
+            AtlasContent::Mask => (
+                wgpu::TextureFormat::R8Unorm,
+                &[wgpu::TextureFormat::R8Unorm],
+            ),
+            AtlasContent::Color => (
+                wgpu::TextureFormat::Rgba8Unorm,
+                &[
+                    wgpu::TextureFormat::Rgba8Unorm,
+                    wgpu::TextureFormat::Rgba8UnormSrgb,
+                ],
+            ),
         };
-        let desc = wgpu::TextureDescriptor {
+        wgpu::TextureDescriptor {
             size: texture_size,
             mip_level_count: 1,
             sample_count: 1,
@@ -76,153 +632,460 @@ impl Atlas {
                 | wgpu::TextureUsages::COPY_DST
                 | wgpu::TextureUsages::TEXTURE_BINDING,
             label: Some("atlas_texture"),
-            view_formats: &[format],
+            view_formats,
+        }
+    }
+
+    fn image_copy(&self, x: i32, y: i32) -> wgpu::ImageCopyTexture {
+        wgpu::ImageCopyTexture {
+            texture: &self.texture,
+            mip_level: 0,
+            aspect: wgpu::TextureAspect::All,
+            origin: wgpu::Origin3d {
+                x: x as u32,
+                y: y as u32,
+                z: 0,
+            },
+        }
+    }
+
+    /// Encodes the operations recorded since the last submit.
+    pub fn submit(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        if self.ops.is_empty() {
+            return;
+        }
+
+        let staging = if self.staging.is_empty() {
+            None
+        } else {
+            Some(
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("atlas staging buffer"),
+                    contents: &self.staging,
+                    usage: wgpu::BufferUsages::COPY_SRC,
+                }),
+            )
         };
-        let atlas_texture = device.create_texture(&desc);
 
+        let pixels = self.content.bytes_per_pixel();
+        for op in &self.ops {
+            match op {
+                AtlasOp::Clear => {
+                    // New buffers are zeroed.
+                    let bytes_per_row = self.size as usize * pixels;
+                    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("atlas clear buffer"),
+                        size: (bytes_per_row * self.size as usize) as u64,
+                        usage: wgpu::BufferUsages::COPY_SRC,
+                        mapped_at_creation: false,
+                    });
+                    encoder.copy_buffer_to_texture(
+                        wgpu::ImageCopyBuffer {
+                            buffer: &buffer,
+                            layout: wgpu::ImageDataLayout {
+                                offset: 0,
+                                bytes_per_row: std::num::NonZeroU32::new(bytes_per_row as u32),
+                                rows_per_image: None,
+                            },
+                        },
+                        self.image_copy(0, 0),
+                        wgpu::Extent3d {
+                            width: self.size,
+                            height: self.size,
+                            depth_or_array_layers: 1,
+                        },
+                    );
+                }
+                AtlasOp::Upload {
+                    rect,
+                    offset,
+                    bytes_per_row,
+                } => {
+                    // Empty regions upload nothing, so may have no buffer.
+                    let staging = match &staging {
+                        Some(staging) if rect.width > 0 && rect.height > 0 => staging,
+                        _ => continue,
+                    };
+                    encoder.copy_buffer_to_texture(
+                        wgpu::ImageCopyBuffer {
+                            buffer: staging,
+                            layout: wgpu::ImageDataLayout {
+                                offset: *offset as u64,
+                                bytes_per_row: std::num::NonZeroU32::new(*bytes_per_row as u32),
+                                rows_per_image: None,
+                            },
+                        },
+                        self.image_copy(rect.x, rect.y),
+                        wgpu::Extent3d {
+                            width: rect.width as u32,
+                            height: rect.height as u32,
+                            depth_or_array_layers: 1,
+                        },
+                    );
+                }
+                AtlasOp::Copy { src, x, y } => {
+                    // A texture can't be copied to itself, so go through
+                    // a buffer.
+                    let bytes_per_row = padded_row_width(src.width as usize * pixels);
+                    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("atlas copy buffer"),
+                        size: (bytes_per_row * src.height as usize) as u64,
+                        usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    });
+                    let layout = wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: std::num::NonZeroU32::new(bytes_per_row as u32),
+                        rows_per_image: None,
+                    };
+                    let size = wgpu::Extent3d {
+                        width: src.width as u32,
+                        height: src.height as u32,
+                        depth_or_array_layers: 1,
+                    };
+                    encoder.copy_texture_to_buffer(
+                        self.image_copy(src.x, src.y),
+                        wgpu::ImageCopyBuffer {
+                            buffer: &buffer,
+                            layout,
+                        },
+                        size,
+                    );
+                    encoder.copy_buffer_to_texture(
+                        wgpu::ImageCopyBuffer {
+                            buffer: &buffer,
+                            layout,
+                        },
+                        self.image_copy(*x, *y),
+                        size,
+                    );
+                }
+            }
+        }
+
+        self.ops.clear();
+        self.staging.clear();
+    }
+}
+
+/// Atlas pixels in memory, for software rendering and for tests.
+pub struct MemoryAtlasBackend {
+    pub size: u32,
+    pub content: AtlasContent,
+
+    /// Rows of `size` pixels.
+    pub pixels: Vec<u8>,
+}
+
+impl AtlasBackend for MemoryAtlasBackend {
+    type Device = ();
+
+    fn create_texture(_device: &(), size: u32, content: AtlasContent) -> Self {
+        let len = (size * size) as usize * content.bytes_per_pixel();
         Self {
-            packer: Packer::new(Atlas::get_packer_config()),
-            new_data: vec![],
-            atlas_texture,
-            area_used: 0,
-            did_clear: false,
+            size,
             content,
+            pixels: vec![0; len],
         }
     }
 
-    pub fn add_region(&mut self, data: &[u8], width: u32, height: u32) -> Option<Rect> {
-        if let Some(rect) = self.packer.pack(width as i32, height as i32, false) {
-            self.new_data.push(ImageData {
-                rect,
-                data: data.into(),
-            });
-            self.area_used +=
-                (rect.width + Atlas::RECT_PADDING) * (rect.height + Atlas::RECT_PADDING);
+    fn upload_region(&mut self, rect: Rect, data: &[u8]) {
+        let pixels = self.content.bytes_per_pixel();
+        let width = rect.width as usize * pixels;
+        let stride = self.size as usize * pixels;
+        for (i, row) in data.chunks_exact(width.max(1)).enumerate() {
+            let start = (rect.y as usize + i) * stride + rect.x as usize * pixels;
+            self.pixels[start..start + width].copy_from_slice(row);
+        }
+    }
 
-            Some(rect)
-        } else {
-            None
+    fn copy_region(&mut self, src: Rect, x: i32, y: i32) {
+        let pixels = self.content.bytes_per_pixel();
+        let width = src.width as usize * pixels;
+        let stride = self.size as usize * pixels;
+        let mut row = vec![0; width];
+        for i in 0..src.height as usize {
+            let from = (src.y as usize + i) * stride + src.x as usize * pixels;
+            row.copy_from_slice(&self.pixels[from..from + width]);
+            let to = (y as usize + i) * stride + x as usize * pixels;
+            self.pixels[to..to + width].copy_from_slice(&row);
         }
     }
 
-    pub fn update(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
-        if self.did_clear {
-            // encoder.clear_texture(&self.atlas_texture, &wgpu::ImageSubresourceRange::default());
+    fn clear(&mut self) {
+        self.pixels.fill(0);
+    }
+}
 
-            let sz = Atlas::ATLAS_SIZE as usize;
+/// Surrounds a `width` by `height` image with a copy of its edge pixels.
+fn extrude(data: &[u8], width: usize, height: usize, pixel: usize) -> Vec<u8> {
+    let row_len = width * pixel;
+    let mut out = Vec::with_capacity((width + 2) * (height + 2) * pixel);
+    for y in 0..height + 2 {
+        let src = y.saturating_sub(1).min(height - 1) * row_len;
+        let row = &data[src..src + row_len];
+        out.extend_from_slice(&row[..pixel]);
+        out.extend_from_slice(row);
+        out.extend_from_slice(&row[row_len - pixel..]);
+    }
+    out
+}
 
-            let data = vec![0_u8; sz * sz];
+/// Row length rounded up to `wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`.
+fn padded_row_width(width: usize) -> usize {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize;
+    (width + align - 1) / align * align
+}
 
-            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("atlas temp buffer"),
-                contents: &data,
-                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::MAP_WRITE,
-            });
+/// Appends the `height` rows of `data`, each `width` bytes, to `scratch`
+/// with rows padded to `padded_row_width`. Aligned rows are copied in one
+/// go.
+fn pad_rows(data: &[u8], width: usize, height: usize, scratch: &mut Vec<u8>) {
+    let padded_width = padded_row_width(width);
+    if padded_width == width {
+        scratch.extend_from_slice(&data[..width * height]);
+        return;
+    }
 
-            let image_size = wgpu::Extent3d {
-                width: sz as u32,
-                height: sz as u32,
-                depth_or_array_layers: 1,
-            };
+    scratch.reserve(padded_width * height);
+    for row in data.chunks_exact(width).take(height) {
+        scratch.extend_from_slice(row);
+        scratch.resize(scratch.len() + padded_width - width, 0);
+    }
+}
 
-            encoder.copy_buffer_to_texture(
-                wgpu::ImageCopyBuffer {
-                    buffer: &buffer,
-                    layout: wgpu::ImageDataLayout {
-                        offset: 0,
-                        bytes_per_row: std::num::NonZeroU32::new((sz * 4) as u32),
-                        rows_per_image: None,
-                    },
-                },
-                wgpu::ImageCopyTexture {
-                    texture: &self.atlas_texture,
-                    mip_level: 0,
-                    aspect: wgpu::TextureAspect::All,
-                    origin: wgpu::Origin3d { x: 0, y: 0, z: 0 },
-                },
-                image_size,
-            );
-
-            self.did_clear = false;
-        }
-
-        for data in &self.new_data {
-            // Pad data to wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
-            let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as i32;
-            let pixels = match self.content {
-                AtlasContent::Mask => 1,
-                AtlasContent::Color => 4,
-            };
-            let width = data.rect.width * pixels;
-            let padding = (align - width % align) % align;
-            let padded_width = width + padding;
-            let mut padded_data = vec![];
-            padded_data.reserve((padded_width * data.rect.height) as usize);
-
-            let mut i = 0;
-            for _ in 0..data.rect.height {
-                for _ in 0..width {
-                    padded_data.push(data.data[i]);
-                    i += 1;
-                }
-                while (padded_data.len() % wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize) != 0 {
-                    padded_data.push(0);
-                }
-            }
+#[cfg(test)]
+mod tests {
 
-            assert!(padded_data.len() == (padded_width * data.rect.height) as usize);
+    use super::*;
 
-            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("atlas temp buffer"),
-                contents: &padded_data,
-                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::MAP_WRITE,
-            });
+    #[test]
+    fn test_allocator() {
+        let mut atlas = AtlasAllocator::new(AtlasContent::Mask);
+        assert_eq!(atlas.usage(), 0.0);
 
-            let image_size = wgpu::Extent3d {
-                width: data.rect.width as u32,
-                height: data.rect.height as u32,
-                depth_or_array_layers: 1,
-            };
+        let rect = atlas.add_region(&[0; 100], 10, 10).unwrap();
+        assert_eq!((rect.width, rect.height), (10, 10));
+        assert_eq!(atlas.pending(), 1);
+        assert!(atlas.usage() > 0.0);
 
-            encoder.copy_buffer_to_texture(
-                wgpu::ImageCopyBuffer {
-                    buffer: &buffer,
-                    layout: wgpu::ImageDataLayout {
-                        offset: 0,
-                        bytes_per_row: std::num::NonZeroU32::new(padded_width as u32),
-                        rows_per_image: None,
-                    },
-                },
-                wgpu::ImageCopyTexture {
-                    texture: &self.atlas_texture,
-                    mip_level: 0,
-                    aspect: wgpu::TextureAspect::All,
-                    origin: wgpu::Origin3d {
-                        x: data.rect.x as u32,
-                        y: data.rect.y as u32,
-                        z: 0,
-                    },
-                },
-                image_size,
-            );
+        let usage = atlas.usage();
+        let reserved = atlas.reserve_region(10, 10).unwrap();
+        assert_ne!((reserved.x, reserved.y), (rect.x, rect.y));
+        assert_eq!(atlas.pending(), 1);
+        assert!(atlas.usage() > usage);
+
+        let size = Atlas::ATLAS_SIZE;
+        assert!(atlas.add_region(&[], size + 1, 1).is_none());
+
+        atlas.clear();
+        assert_eq!(atlas.pending(), 0);
+        assert_eq!(atlas.usage(), 0.0);
+        assert!(atlas.did_clear());
+    }
+
+    #[test]
+    fn test_size() {
+        let mut atlas = AtlasAllocator::with_size(AtlasContent::Mask, 256);
+        assert_eq!(atlas.size(), 256);
+        assert!(atlas.reserve_region(300, 10).is_none());
+        atlas.reserve_region(128, 128).unwrap();
+        assert!(atlas.usage() > 0.25);
+
+        // Clearing keeps the size.
+        atlas.clear();
+        assert!(atlas.reserve_region(300, 10).is_none());
+
+        let atlas: Atlas<MemoryAtlasBackend> = Atlas::with_size(&(), AtlasContent::Color, 64);
+        assert_eq!(atlas.backend.pixels.len(), 64 * 64 * 4);
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut atlas: Atlas<MemoryAtlasBackend> = Atlas::with_size(&(), AtlasContent::Mask, 64);
+        atlas.add_region(&[1; 4], 2, 2).unwrap();
+        atlas.resize(128);
+        assert_eq!(atlas.size(), 128);
+        assert_eq!(atlas.usage(), 0.0);
+
+        // The texture follows when reallocated, and only once.
+        assert!(atlas.add_region(&[2; 10000], 100, 100).is_some());
+        assert_eq!(atlas.backend.pixels.len(), 64 * 64);
+        assert!(atlas.reallocate(&()));
+        assert!(!atlas.reallocate(&()));
+        atlas.flush();
+        assert_eq!(atlas.backend.pixels.len(), 128 * 128);
+        assert_eq!(
+            atlas.backend.pixels.iter().filter(|p| **p == 2).count(),
+            10000
+        );
+    }
+
+    #[test]
+    fn test_shelf_packing() {
+        let mut atlas = AtlasAllocator::with_size(AtlasContent::Mask, 256);
+        assert!(atlas.evict_shelf(0).is_none());
+        atlas.set_packing(Packing::Shelves);
+        assert_eq!(atlas.packing(), Some(Packing::Shelves));
+
+        let a = atlas.reserve_region(10, 20).unwrap();
+        let b = atlas.reserve_region(12, 18).unwrap();
+        assert_eq!(a.y, b.y);
+
+        let usage = atlas.usage();
+        atlas.reserve_region(10, 5).unwrap();
+        let pending = atlas.pending();
+        let bounds = atlas.evict_shelf(a.y).unwrap();
+        assert_eq!((bounds.y, bounds.height), (a.y, 20));
+        assert!(atlas.usage() < usage);
+
+        // The shelf's pixels are zeroed.
+        assert_eq!(atlas.pending(), pending + 1);
+        assert!(atlas.new_data.last().unwrap().data.iter().all(|&p| p == 0));
+        assert_eq!(atlas.reserve_region(10, 20).unwrap(), a);
+    }
+
+    /// Packs regions in a grid of 16 pixel cells.
+    struct GridPacker {
+        next: i32,
+        freed: Vec<Rect>,
+    }
+
+    impl RectPackerImpl for GridPacker {
+        fn pack(&mut self, width: i32, height: i32) -> Option<Rect> {
+            if width > 16 || height > 16 {
+                return None;
+            }
+            if let Some(cell) = self.freed.pop() {
+                return Some(Rect::new(cell.x, cell.y, width, height));
+            }
+            let (x, y) = (self.next % 16 * 16, self.next / 16 * 16);
+            self.next += 1;
+            Some(Rect::new(x, y, width, height))
         }
 
-        self.new_data.clear();
+        fn free(&mut self, rect: Rect) -> bool {
+            self.freed.push(rect);
+            true
+        }
+
+        fn reset(&mut self) {
+            self.next = 0;
+            self.freed.clear();
+        }
     }
 
-    pub fn create_view(&self) -> wgpu::TextureView {
-        self.atlas_texture
-            .create_view(&wgpu::TextureViewDescriptor::default())
+    #[test]
+    fn test_custom_packer() {
+        let mut atlas = AtlasAllocator::with_padding(AtlasContent::Mask, 0, false);
+        atlas.set_packer(Box::new(GridPacker {
+            next: 0,
+            freed: vec![],
+        }));
+        assert_eq!(atlas.packing(), None);
+
+        let a = atlas.reserve_region(10, 10).unwrap();
+        let b = atlas.reserve_region(16, 8).unwrap();
+        assert_eq!((a.x, b.x), (0, 16));
+        assert!(atlas.reserve_region(20, 20).is_none());
+
+        let usage = atlas.usage();
+        atlas.free_region(a);
+        assert!(atlas.usage() < usage);
+        assert_eq!(atlas.reserve_region(4, 4).unwrap().x, 0);
+
+        // Clearing resets the custom packer rather than replacing it.
+        atlas.clear();
+        assert_eq!(atlas.packing(), None);
+        assert_eq!(atlas.reserve_region(16, 16).unwrap().x, 0);
+
+        atlas.set_packing(Packing::Rects);
+        assert_eq!(atlas.packing(), Some(Packing::Rects));
     }
 
-    pub fn usage(&self) -> f32 {
-        (self.area_used as f32) / ((Atlas::ATLAS_SIZE * Atlas::ATLAS_SIZE) as f32)
+    #[test]
+    fn test_memory_backend() {
+        let mut atlas: Atlas<MemoryAtlasBackend> = Atlas::new(&(), AtlasContent::Mask);
+        let rect = atlas.add_region(&[1, 2, 3, 4, 5, 6], 3, 2).unwrap();
+        atlas.flush();
+        assert_eq!(atlas.allocator.pending(), 0);
+
+        let stride = Atlas::ATLAS_SIZE as usize;
+        let at = |x: i32, y: i32| (y as usize) * stride + x as usize;
+        let pixels = &atlas.backend.pixels;
+        assert_eq!(pixels[at(rect.x, rect.y)], 1);
+        assert_eq!(pixels[at(rect.x + 2, rect.y + 1)], 6);
+        assert_eq!(pixels[at(rect.x + 3, rect.y)], 0);
+
+        atlas.backend.copy_region(rect, 0, 0);
+        assert_eq!(atlas.backend.pixels[at(1, 1)], 5);
+
+        atlas.clear();
+        atlas.flush();
+        assert!(atlas.backend.pixels.iter().all(|p| *p == 0));
     }
 
-    pub fn clear(&mut self) {
-        self.packer = Packer::new(Atlas::get_packer_config());
-        self.area_used = 0;
-        self.new_data.clear();
-        self.did_clear = true;
+    #[test]
+    fn test_padding() {
+        let mut atlas = AtlasAllocator::with_padding(AtlasContent::Mask, 1, false);
+        let a = atlas.reserve_region(8, 8).unwrap();
+        let b = atlas.reserve_region(8, 8).unwrap();
+        assert_eq!((a.x, a.y), (1, 1));
+        assert_eq!((b.x - a.x).abs() + (b.y - a.y).abs(), 9);
+
+        atlas.set_padding(0, false);
+        assert!(atlas.did_clear());
+        let a = atlas.reserve_region(8, 8).unwrap();
+        assert_eq!((a.x, a.y), (0, 0));
+    }
+
+    #[test]
+    fn test_extrude() {
+        assert_eq!(
+            extrude(&[1, 2, 3, 4], 2, 2, 1),
+            [1, 1, 2, 2, 1, 1, 2, 2, 3, 3, 4, 4, 3, 3, 4, 4]
+        );
+
+        let mut atlas: Atlas<MemoryAtlasBackend> = Atlas::new(&(), AtlasContent::Mask);
+        atlas.set_padding(2, true);
+        let rect = atlas.add_region(&[1, 2, 3, 4], 2, 2).unwrap();
+        assert_eq!((rect.x, rect.y, rect.width, rect.height), (3, 3, 2, 2));
+        atlas.flush();
+
+        let stride = Atlas::ATLAS_SIZE as usize;
+        let at = |x: i32, y: i32| atlas.backend.pixels[(y as usize) * stride + x as usize];
+        assert_eq!(at(rect.x, rect.y), 1);
+        assert_eq!(at(rect.x - 1, rect.y - 1), 1);
+        assert_eq!(at(rect.x + 2, rect.y + 1), 4);
+        assert_eq!(at(rect.x + 3, rect.y + 1), 0);
+
+        // Reserved regions are freed like added ones.
+        let mut atlas = AtlasAllocator::with_size(AtlasContent::Color, 64);
+        atlas.set_packing(Packing::Shelves);
+        atlas.set_padding(1, true);
+        let rect = atlas.reserve_region(8, 8).unwrap();
+        assert_eq!((rect.x, rect.y), (2, 2));
+        atlas.free_region(rect);
+        assert_eq!(atlas.usage(), 0.0);
+    }
+
+    #[test]
+    fn test_pad_rows() {
+        let mut scratch = vec![];
+
+        let data: Vec<u8> = (0..6).collect();
+        pad_rows(&data, 3, 2, &mut scratch);
+        assert_eq!(scratch.len(), 512);
+        assert_eq!(&scratch[..4], &[0, 1, 2, 0]);
+        assert_eq!(&scratch[256..260], &[3, 4, 5, 0]);
+
+        // Regions are appended after each other.
+        let data = vec![1; 512];
+        pad_rows(&data, 256, 2, &mut scratch);
+        assert_eq!(scratch.len(), 1024);
+        assert_eq!(scratch[512], 1);
+
+        assert_eq!(padded_row_width(0), 0);
+        assert_eq!(padded_row_width(257), 512);
     }
 }
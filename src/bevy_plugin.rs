@@ -0,0 +1,229 @@
+//! Drawing with vger in bevy apps. Enabled with the `bevy` feature.
+//!
+//! ```ignore
+//! use bevy::prelude::*;
+//! use vger::bevy_plugin::{VgerDraw, VgerPlugin};
+//!
+//! fn ui(draw: VgerDraw) {
+//!     draw.draw(|vger| {
+//!         let paint = vger.color_paint(vger::Color::CYAN);
+//!         vger.fill_rect(euclid::rect(10.0, 10.0, 100.0, 40.0), 4.0, paint);
+//!     });
+//! }
+//!
+//! App::new()
+//!     .add_plugins(DefaultPlugins)
+//!     .add_plugin(VgerPlugin)
+//!     .add_startup_system(|mut commands: Commands| {
+//!         commands.spawn(Camera2dBundle::default());
+//!     })
+//!     .add_system(ui)
+//!     .run();
+//! ```
+
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use bevy::core_pipeline::core_2d;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::render::render_graph::{
+    Node, NodeRunError, RenderGraph, RenderGraphContext, SlotInfo, SlotType,
+};
+use bevy::render::render_resource::TextureFormat;
+use bevy::render::renderer::{RenderContext, RenderDevice, RenderQueue};
+use bevy::render::texture::BevyDefault;
+use bevy::render::view::{ExtractedView, ExtractedWindows, ViewTarget, WindowSystem};
+use bevy::render::{Extract, ExtractSchedule, RenderApp, RenderSet};
+use bevy::window::PrimaryWindow;
+
+use crate::Vger;
+
+type DrawFn = Box<dyn FnOnce(&mut Vger) + Send + Sync>;
+
+/// Draws the primary window's 2D camera with vger over bevy's output,
+/// after tonemapping and upscaling, so the UI isn't tonemapped and HDR
+/// views work. The device is bevy's.
+pub struct VgerPlugin;
+
+impl Plugin for VgerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VgerDrawList>();
+
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(render_app) => render_app,
+            Err(_) => return,
+        };
+
+        render_app
+            .init_resource::<VgerRenderer>()
+            .init_resource::<ExtractedVgerFrame>()
+            .add_system(extract_vger.in_schedule(ExtractSchedule))
+            .add_system(
+                prepare_vger
+                    .in_set(RenderSet::Prepare)
+                    .after(WindowSystem::Prepare),
+            );
+
+        let node = VgerNode::new(&mut render_app.world);
+        let mut graph = render_app.world.resource_mut::<RenderGraph>();
+        if let Some(graph_2d) = graph.get_sub_graph_mut(core_2d::graph::NAME) {
+            graph_2d.add_node(VgerNode::NAME, node);
+            graph_2d.add_slot_edge(
+                graph_2d.input_node().id,
+                core_2d::graph::input::VIEW_ENTITY,
+                VgerNode::NAME,
+                VgerNode::IN_VIEW,
+            );
+            graph_2d.add_node_edge(core_2d::graph::node::UPSCALING, VgerNode::NAME);
+        }
+    }
+}
+
+/// Drawing queued by systems this frame, in the main world.
+#[derive(Resource, Default)]
+struct VgerDrawList(Mutex<Vec<DrawFn>>);
+
+/// Queues vger drawing from a system. Closures run in the order queued,
+/// in logical window coordinates, once the frame reaches the render world.
+#[derive(SystemParam)]
+pub struct VgerDraw<'w, 's> {
+    list: Res<'w, VgerDrawList>,
+    marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> VgerDraw<'w, 's> {
+    pub fn draw(&self, draw: impl FnOnce(&mut Vger) + Send + Sync + 'static) {
+        self.list.0.lock().unwrap().push(Box::new(draw));
+    }
+}
+
+/// Drawing and window size moved to the render world.
+#[derive(Resource, Default)]
+struct ExtractedVgerFrame {
+    draws: Vec<DrawFn>,
+
+    /// Logical width and height, and scale factor.
+    window: Option<(f32, f32, f32)>,
+}
+
+#[derive(Resource, Default)]
+struct VgerRenderer {
+    vger: Option<Vger>,
+
+    /// Format of the window's texture, which `vger` was created for.
+    format: Option<TextureFormat>,
+}
+
+fn extract_vger(
+    mut frame: ResMut<ExtractedVgerFrame>,
+    list: Extract<Res<VgerDrawList>>,
+    windows: Extract<Query<&Window, With<PrimaryWindow>>>,
+) {
+    frame.draws = std::mem::take(&mut *list.0.lock().unwrap());
+    frame.window = windows.get_single().ok().map(|window| {
+        (
+            window.width(),
+            window.height(),
+            window.scale_factor() as f32,
+        )
+    });
+}
+
+fn prepare_vger(
+    mut renderer: ResMut<VgerRenderer>,
+    mut frame: ResMut<ExtractedVgerFrame>,
+    windows: Res<ExtractedWindows>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+) {
+    let (width, height, scale) = match frame.window {
+        Some(window) => window,
+        None => return,
+    };
+    let device = device.wgpu_device();
+
+    // Drawn into the window's texture, whose format may not be bevy's
+    // default.
+    let format = windows
+        .primary
+        .and_then(|primary| windows.windows.get(&primary))
+        .and_then(|window| window.swap_chain_texture_format)
+        .unwrap_or_else(TextureFormat::bevy_default);
+    if renderer.format != Some(format) {
+        renderer.vger = Some(Vger::new(device, format));
+        renderer.format = Some(format);
+    }
+    let vger = renderer.vger.as_mut().unwrap();
+
+    vger.begin(width, height, scale);
+    for draw in frame.draws.drain(..) {
+        draw(vger);
+    }
+    vger.prepare(device, &queue);
+}
+
+/// Render graph node drawing the prepared frame over the view's output
+/// texture, after upscaling.
+pub struct VgerNode {
+    query: QueryState<&'static ViewTarget, With<ExtractedView>>,
+}
+
+impl VgerNode {
+    pub const NAME: &'static str = "vger";
+    pub const IN_VIEW: &'static str = "view";
+
+    pub fn new(world: &mut World) -> Self {
+        Self {
+            query: QueryState::new(world),
+        }
+    }
+}
+
+impl Node for VgerNode {
+    fn input(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(VgerNode::IN_VIEW, SlotType::Entity)]
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.query.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let view_entity = graph.get_input_entity(VgerNode::IN_VIEW)?;
+        let target = match self.query.get_manual(world, view_entity) {
+            Ok(target) => target,
+            Err(_) => return Ok(()),
+        };
+        let vger = match &world.resource::<VgerRenderer>().vger {
+            Some(vger) => vger,
+            None => return Ok(()),
+        };
+
+        // The upscaled output is neither multisampled nor HDR.
+        let attachment = wgpu::RenderPassColorAttachment {
+            view: target.out_texture(),
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: true,
+            },
+        };
+        let mut rpass =
+            render_context
+                .command_encoder()
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("vger bevy pass"),
+                    color_attachments: &[Some(attachment)],
+                    depth_stencil_attachment: None,
+                });
+        vger.render(&mut rpass);
+
+        Ok(())
+    }
+}
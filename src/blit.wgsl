@@ -0,0 +1,39 @@
+
+struct BlitParams {
+    /// Multiplies colors, e.g. to set UI brightness on HDR targets.
+    scale: vec4<f32>,
+};
+
+@group(0)
+@binding(0)
+var source: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var source_samp: sampler;
+
+@group(0)
+@binding(2)
+var<uniform> params: BlitParams;
+
+struct BlitOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// One triangle covering the target.
+@vertex
+fn vs_blit(@builtin(vertex_index) vid: u32) -> BlitOutput {
+    var out: BlitOutput;
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Source is premultiplied and linear. No tonemapping is applied.
+@fragment
+fn fs_blit(in: BlitOutput) -> @location(0) vec4<f32> {
+    let c = textureSample(source, source_samp, in.uv);
+    return vec4<f32>(c.rgb * params.scale.x, c.a);
+}
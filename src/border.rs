@@ -0,0 +1,101 @@
+//! Image borders sliced as in CSS `border-image`.
+
+use crate::defs::*;
+
+/// How to cut an image into a border. Sides are in the order top, right,
+/// bottom, left.
+#[derive(Copy, Clone, Debug)]
+pub struct BorderImage {
+    /// Insets into the image, in image pixels, separating the corners and
+    /// edges from the middle.
+    pub slice: [f32; 4],
+
+    /// Widths of the border in local coordinates. The corners are scaled
+    /// to fit and the edges stretched between them.
+    pub widths: [f32; 4],
+
+    /// Whether to draw the middle of the image inside the border.
+    pub fill: bool,
+}
+
+/// Destination quad and source rect in image pixels, as min and max
+/// coordinates, of each nonempty piece of the border.
+pub(crate) fn nine_slice(
+    rect: LocalRect,
+    image_width: f32,
+    image_height: f32,
+    border: &BorderImage,
+) -> Vec<([f32; 4], [f32; 4])> {
+    let [top, right, bottom, left] = border.slice;
+    let [w_top, w_right, w_bottom, w_left] = border.widths;
+
+    let xs = [0.0, left, image_width - right, image_width];
+    let ys = [0.0, top, image_height - bottom, image_height];
+    let dxs = [
+        rect.min_x(),
+        rect.min_x() + w_left,
+        rect.max_x() - w_right,
+        rect.max_x(),
+    ];
+    let dys = [
+        rect.min_y(),
+        rect.min_y() + w_top,
+        rect.max_y() - w_bottom,
+        rect.max_y(),
+    ];
+
+    let mut pieces = vec![];
+    for row in 0..3 {
+        for col in 0..3 {
+            if row == 1 && col == 1 && !border.fill {
+                continue;
+            }
+            let dest = [dxs[col], dys[row], dxs[col + 1], dys[row + 1]];
+            let src = [xs[col], ys[row], xs[col + 1], ys[row + 1]];
+            if dest[2] > dest[0] && dest[3] > dest[1] && src[2] > src[0] && src[3] > src[1] {
+                pieces.push((dest, src));
+            }
+        }
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_nine_slice() {
+        let border = BorderImage {
+            slice: [10.0, 10.0, 10.0, 10.0],
+            widths: [5.0, 5.0, 5.0, 5.0],
+            fill: false,
+        };
+        let rect = LocalRect::new(LocalPoint::new(0.0, 0.0), LocalSize::new(100.0, 50.0));
+        let pieces = nine_slice(rect, 30.0, 30.0, &border);
+        assert_eq!(pieces.len(), 8);
+
+        // Top left corner.
+        assert_eq!(pieces[0], ([0.0, 0.0, 5.0, 5.0], [0.0, 0.0, 10.0, 10.0]));
+
+        // Right edge stretches the image's right edge.
+        assert_eq!(
+            pieces[4],
+            ([95.0, 5.0, 100.0, 45.0], [20.0, 10.0, 30.0, 20.0])
+        );
+
+        let filled = BorderImage {
+            fill: true,
+            ..border
+        };
+        assert_eq!(nine_slice(rect, 30.0, 30.0, &filled).len(), 9);
+
+        // Zero width sides are skipped.
+        let sides = BorderImage {
+            widths: [5.0, 0.0, 5.0, 0.0],
+            ..border
+        };
+        assert_eq!(nine_slice(rect, 30.0, 30.0, &sides).len(), 2);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::defs::*;
+
+/// View onto an infinite canvas whose coordinates are too large for f32.
+///
+/// World positions are made relative to the camera in f64 before being
+/// converted to f32, so geometry millions of units from the world origin
+/// still renders without wobbling.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera64 {
+    /// World position drawn at the local origin.
+    pub origin: WorldPoint64,
+
+    /// Local units per world unit.
+    pub zoom: f64,
+}
+
+impl Default for Camera64 {
+    fn default() -> Self {
+        Self {
+            origin: WorldPoint64::origin(),
+            zoom: 1.0,
+        }
+    }
+}
+
+impl Camera64 {
+    pub fn new(origin: WorldPoint64, zoom: f64) -> Self {
+        Self { origin, zoom }
+    }
+
+    /// Offset of a world position from the camera, in local units.
+    pub fn relative(&self, position: WorldPoint64) -> LocalVector {
+        let d = (position - self.origin) * self.zoom;
+        LocalVector::new(d.x as f32, d.y as f32)
+    }
+
+    /// World position of a local point, e.g. for picking.
+    pub fn to_world(&self, p: LocalPoint) -> WorldPoint64 {
+        self.origin + WorldVector64::new(p.x as f64, p.y as f64) / self.zoom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_relative() {
+        let camera = Camera64::new(WorldPoint64::new(1e8 + 0.25, -3e6), 1000.0);
+        let p = WorldPoint64::new(1e8 + 0.75, -3e6 + 0.5);
+
+        // In f32, both positions round to the same value.
+        assert_eq!((1e8 + 0.25) as f32, (1e8 + 0.75) as f32);
+
+        assert_eq!(camera.relative(p), LocalVector::new(500.0, 500.0));
+        assert_eq!(camera.to_world(LocalPoint::new(500.0, 500.0)), p);
+    }
+}
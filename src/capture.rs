@@ -0,0 +1,260 @@
+//! Reading rendered pixels back to the CPU.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::defs::*;
+
+/// RGBA pixels, 8 bits per channel, in rows from the top.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImageBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl ImageBuffer {
+    /// The pixel at `(x, y)`, if in bounds.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let i = ((y * self.width + x) * 4) as usize;
+        let p = &self.pixels[i..i + 4];
+        Some([p[0], p[1], p[2], p[3]])
+    }
+}
+
+/// Why a capture failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CaptureError {
+    /// Only 8 bit RGBA and BGRA targets can be captured.
+    UnsupportedFormat(wgpu::TextureFormat),
+
+    /// The readback buffer couldn't be mapped, e.g. because the device
+    /// was lost.
+    Map,
+
+    /// The pixel read is outside the texture.
+    OutOfBounds,
+}
+
+impl std::fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaptureError::UnsupportedFormat(format) => {
+                write!(f, "can't capture {:?} textures", format)
+            }
+            CaptureError::Map => write!(f, "failed to map readback buffer"),
+            CaptureError::OutOfBounds => write!(f, "pixel is outside the texture"),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+#[derive(Default)]
+struct MapState {
+    result: Option<Result<(), wgpu::BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+struct Readback {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_row: u32,
+    bgra: bool,
+    state: Arc<Mutex<MapState>>,
+}
+
+/// Pixels being copied back from the GPU. Completes once the copy has
+/// finished, which on native needs the device to be polled, e.g. with
+/// `device.poll(wgpu::Maintain::Wait)`.
+pub struct Capture {
+    inner: Result<Option<Readback>, CaptureError>,
+}
+
+impl Future for Capture {
+    type Output = Result<ImageBuffer, CaptureError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let readback = match &self.inner {
+            Err(err) => return Poll::Ready(Err(err.clone())),
+            Ok(None) => return Poll::Ready(Ok(ImageBuffer::default())),
+            Ok(Some(readback)) => readback,
+        };
+
+        {
+            let mut state = readback.state.lock().unwrap();
+            match state.result {
+                None => {
+                    state.waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+                Some(Err(_)) => return Poll::Ready(Err(CaptureError::Map)),
+                Some(Ok(())) => (),
+            }
+        }
+
+        let row = readback.width as usize * 4;
+        let mut pixels = Vec::with_capacity(row * readback.height as usize);
+        {
+            let data = readback.buffer.slice(..).get_mapped_range();
+            for y in 0..readback.height as usize {
+                let start = y * readback.padded_row as usize;
+                pixels.extend_from_slice(&data[start..start + row]);
+            }
+        }
+        readback.buffer.unmap();
+
+        if readback.bgra {
+            for p in pixels.chunks_mut(4) {
+                p.swap(0, 2);
+            }
+        }
+
+        Poll::Ready(Ok(ImageBuffer {
+            width: readback.width,
+            height: readback.height,
+            pixels,
+        }))
+    }
+}
+
+/// One RGBA pixel being copied back from the GPU, as for `Capture`.
+pub struct PixelRead(Capture);
+
+impl Future for PixelRead {
+    type Output = Result<[u8; 4], CaptureError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx).map(|result| {
+            result.and_then(|image| image.pixel(0, 0).ok_or(CaptureError::OutOfBounds))
+        })
+    }
+}
+
+/// The pixel of `texture` at `(x, y)`, copied through a single row sized
+/// buffer.
+pub(crate) fn read_pixel(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    x: u32,
+    y: u32,
+) -> PixelRead {
+    let rect = ScreenRect::new(
+        ScreenPoint::new(x as f32, y as f32),
+        ScreenSize::new(1.0, 1.0),
+    );
+    PixelRead(capture(device, queue, texture, rect))
+}
+
+/// Pixels of `texture` within `rect`, rounded out to whole pixels and
+/// clipped to the texture. The texture needs `COPY_SRC` usage.
+pub(crate) fn capture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    rect: ScreenRect,
+) -> Capture {
+    let bgra = match texture.format() {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        format => {
+            return Capture {
+                inner: Err(CaptureError::UnsupportedFormat(format)),
+            }
+        }
+    };
+
+    let x0 = rect.min_x().floor().max(0.0) as u32;
+    let y0 = rect.min_y().floor().max(0.0) as u32;
+    let x1 = (rect.max_x().ceil().max(0.0) as u32).min(texture.width());
+    let y1 = (rect.max_y().ceil().max(0.0) as u32).min(texture.height());
+    if x1 <= x0 || y1 <= y0 {
+        return Capture { inner: Ok(None) };
+    }
+    let (width, height) = (x1 - x0, y1 - y0);
+
+    // Rows must be aligned for the copy, so strip the padding afterwards.
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_row = (width * 4 + align - 1) / align * align;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("capture buffer"),
+        size: (padded_row * height) as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("capture encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d { x: x0, y: y0, z: 0 },
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(padded_row),
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let state = Arc::new(Mutex::new(MapState::default()));
+    let callback_state = state.clone();
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            let mut state = callback_state.lock().unwrap();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+
+    Capture {
+        inner: Ok(Some(Readback {
+            buffer,
+            width,
+            height,
+            padded_row,
+            bgra,
+            state,
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_pixel() {
+        let image = ImageBuffer {
+            width: 2,
+            height: 1,
+            pixels: vec![1, 2, 3, 4, 5, 6, 7, 8],
+        };
+        assert_eq!(image.pixel(1, 0), Some([5, 6, 7, 8]));
+        assert_eq!(image.pixel(2, 0), None);
+        assert_eq!(image.pixel(0, 1), None);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::defs::*;
+use crate::PaintIndex;
+
+/// Axis-aligned bar from `y0` to `y1`, `width` wide starting at `x`.
+#[derive(Copy, Clone, Debug)]
+pub struct BarSpec {
+    pub x: f32,
+    pub width: f32,
+    pub y0: f32,
+    pub y1: f32,
+    pub paint: PaintIndex,
+}
+
+/// One candle of a candlestick chart.
+#[derive(Copy, Clone, Debug)]
+pub struct Candle {
+    /// Left edge of the body.
+    pub x: f32,
+
+    /// Width of the body.
+    pub width: f32,
+
+    pub open: f32,
+    pub close: f32,
+    pub low: f32,
+    pub high: f32,
+}
+
+/// Ring progress indicator for `Vger::draw_gauge`. Angles are in
+/// radians, clockwise from the positive x axis as y points down.
+#[derive(Copy, Clone, Debug)]
+pub struct Gauge {
+    pub center: LocalPoint,
+
+    /// Radius of the middle of the ring.
+    pub radius: f32,
+
+    /// Where the value arc starts.
+    pub start: f32,
+
+    /// Angle the value arc covers, negative to go counterclockwise.
+    pub sweep: f32,
+
+    /// Width of the ring.
+    pub thickness: f32,
+}
+
+/// `stroke_arc` rotation and aperture for an arc from `start` covering
+/// `sweep`, clamped to a full circle.
+pub fn arc_rotation_aperture(start: f32, sweep: f32) -> (f32, f32) {
+    let (start, sweep) = if sweep < 0.0 {
+        (start + sweep, -sweep)
+    } else {
+        (start, sweep)
+    };
+    let aperture = (sweep / 2.0).min(std::f32::consts::PI);
+
+    // Arcs are centered on the angle pi - rotation.
+    (std::f32::consts::PI - (start + aperture), aperture)
+}
+
+/// Pie or donut slice for `Vger::fill_sector`, with angles as for
+/// `Gauge`.
+#[derive(Copy, Clone, Debug)]
+pub struct Sector {
+    pub center: LocalPoint,
+
+    /// Zero for a pie slice.
+    pub inner_radius: f32,
+
+    pub outer_radius: f32,
+    pub start: f32,
+
+    /// Angle the slice covers, negative to go counterclockwise.
+    pub sweep: f32,
+}
+
+impl Sector {
+    /// Whether `p` is inside the slice, e.g. for hover highlighting.
+    pub fn contains(&self, p: LocalPoint) -> bool {
+        use std::f32::consts::PI;
+
+        let v = p - self.center;
+        let r = v.length();
+        if r < self.inner_radius || r > self.outer_radius {
+            return false;
+        }
+        if self.sweep.abs() >= 2.0 * PI {
+            return true;
+        }
+
+        let (start, sweep) = if self.sweep < 0.0 {
+            (self.start + self.sweep, -self.sweep)
+        } else {
+            (self.start, self.sweep)
+        };
+        (v.y.atan2(v.x) - start).rem_euclid(2.0 * PI) <= sweep
+    }
+}
+
+/// Snaps a coordinate to the device pixel grid.
+pub fn snap(x: f32, device_px_ratio: f32) -> f32 {
+    (x * device_px_ratio).round() / device_px_ratio
+}
+
+/// Snaps an interval to the device pixel grid, keeping it at least one
+/// device pixel wide.
+pub fn snap_interval(a: f32, b: f32, device_px_ratio: f32) -> (f32, f32) {
+    let lo = snap(a.min(b), device_px_ratio);
+    let hi = snap(a.max(b), device_px_ratio).max(lo + 1.0 / device_px_ratio);
+    (lo, hi)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_snap() {
+        assert_eq!(snap(10.3, 1.0), 10.0);
+        assert_eq!(snap(10.3, 2.0), 10.5);
+        assert_eq!(snap_interval(5.2, 1.1, 1.0), (1.0, 5.0));
+        assert_eq!(snap_interval(5.1, 5.2, 1.0), (5.0, 6.0));
+        assert_eq!(snap_interval(5.1, 5.2, 2.0), (5.0, 5.5));
+    }
+
+    #[test]
+    fn test_arc_rotation_aperture() {
+        use std::f32::consts::PI;
+
+        // A quarter from the top, clockwise, is centered at -PI/4.
+        let (rotation, aperture) = arc_rotation_aperture(-PI / 2.0, PI / 2.0);
+        assert!((PI - rotation + PI / 4.0).abs() < 1e-6);
+        assert!((aperture - PI / 4.0).abs() < 1e-6);
+
+        assert_eq!(
+            arc_rotation_aperture(1.0, -0.5),
+            arc_rotation_aperture(0.5, 0.5)
+        );
+        assert_eq!(arc_rotation_aperture(0.0, 10.0).1, PI);
+    }
+
+    #[test]
+    fn test_sector_contains() {
+        use std::f32::consts::PI;
+
+        let sector = Sector {
+            center: LocalPoint::new(0.0, 0.0),
+            inner_radius: 10.0,
+            outer_radius: 20.0,
+            start: -PI / 4.0,
+            sweep: PI / 2.0,
+        };
+        assert!(sector.contains(LocalPoint::new(15.0, 0.0)));
+        assert!(!sector.contains(LocalPoint::new(5.0, 0.0)));
+        assert!(!sector.contains(LocalPoint::new(0.0, 15.0)));
+
+        // Wraps past the negative x axis.
+        let sector = Sector {
+            start: 3.0,
+            sweep: 1.0,
+            ..sector
+        };
+        assert!(sector.contains(LocalPoint::new(-15.0, -1.0)));
+        assert!(sector.contains(LocalPoint::new(-15.0, 1.0)));
+        assert!(!sector.contains(LocalPoint::new(15.0, 0.0)));
+    }
+}
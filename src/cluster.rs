@@ -0,0 +1,67 @@
+//! Grapheme clusters, which text layout treats as units: emoji sequences
+//! such as 👩‍👩‍👧 or 👍🏽 draw as one glyph, and hit-testing shouldn't split
+//! a cluster. See `Vger::glyph_clusters`.
+
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::defs::*;
+
+/// A grapheme cluster of laid out text, such as a letter with combining
+/// accents or an emoji sequence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlyphCluster {
+    /// Byte range of the cluster in the text.
+    pub text_range: Range<usize>,
+
+    /// Range of its glyphs, as returned by `Vger::glyph_positions`.
+    pub glyphs: Range<usize>,
+
+    /// Bounds of its glyphs in local coordinates.
+    pub bounds: LocalRect,
+}
+
+/// Byte ranges of the extended grapheme clusters of `text`.
+pub(crate) fn grapheme_ranges(text: &str) -> Vec<Range<usize>> {
+    text.grapheme_indices(true)
+        .map(|(i, grapheme)| i..i + grapheme.len())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn lens(text: &str) -> Vec<usize> {
+        grapheme_ranges(text)
+            .into_iter()
+            .map(|range| text[range].chars().count())
+            .collect()
+    }
+
+    #[test]
+    fn test_emoji_sequences() {
+        // Woman, woman, girl joined by ZWJs.
+        assert_eq!(lens("👩\u{200D}👩\u{200D}👧"), [5]);
+
+        // Skin tone modifier.
+        assert_eq!(lens("a👍🏽b"), [1, 2, 1]);
+
+        // Variation selector for emoji presentation.
+        assert_eq!(lens("❤\u{FE0F}!"), [2, 1]);
+
+        // Flags are pairs of regional indicators.
+        assert_eq!(lens("🇯🇵🇫🇷"), [2, 2]);
+
+        // Keycap.
+        assert_eq!(lens("1\u{FE0F}\u{20E3}"), [3]);
+    }
+
+    #[test]
+    fn test_combining_marks() {
+        assert_eq!(lens("e\u{301}a"), [2, 1]);
+        assert_eq!(grapheme_ranges("ab"), [0..1, 1..2]);
+        assert!(grapheme_ranges("").is_empty());
+    }
+}
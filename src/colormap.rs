@@ -0,0 +1,212 @@
+//! Colormaps for heatmap paints. A colormap is a row of `LUT_SIZE`
+//! colors in the color atlas, which the shader samples with a value, so
+//! values don't need coloring on the CPU. See `Vger::heatmap_paint`.
+
+use crate::color::Color;
+use crate::hash::ImageKey;
+
+/// Entries in a colormap's lookup table.
+pub const LUT_SIZE: u32 = 256;
+
+/// Maps heatmap values, from the low end of their range to the high end,
+/// to colors.
+#[derive(Copy, Clone, Debug)]
+pub enum Colormap<'a> {
+    /// Perceptually uniform blue to green to yellow, from matplotlib.
+    Viridis,
+
+    /// Perceptually uniform black to purple to cream, from matplotlib.
+    Magma,
+
+    /// Evenly spaced colors, interpolated. The lookup table is cached in
+    /// the color atlas under `key`, so use a new key after changing the
+    /// colors.
+    Custom {
+        key: &'a ImageKey,
+        colors: &'a [Color],
+    },
+
+    /// A lookup table of RGBA8 pixels, e.g. exported from another
+    /// plotting library, used as is when it's `LUT_SIZE` wide and
+    /// resampled otherwise. Cached under `key` like `Custom`.
+    Lut { key: &'a ImageKey, lut: &'a [u8] },
+}
+
+/// Polynomial fits of the matplotlib colormaps, from lowest degree up, by
+/// Matt Zucker (https://www.shadertoy.com/view/WlfXRN).
+const VIRIDIS: [[f32; 3]; 7] = [
+    [0.277727, 0.00540734, 0.3341],
+    [0.105093, 1.40461, 1.38459],
+    [-0.330862, 0.214848, 0.0950952],
+    [-4.63423, -5.7991, -19.3324],
+    [6.22827, 14.1799, 56.6906],
+    [4.77638, -13.7451, -65.353],
+    [-5.43546, 4.64585, 26.3124],
+];
+
+const MAGMA: [[f32; 3]; 7] = [
+    [-0.00213649, -0.000749655, -0.00538613],
+    [0.251661, 0.677523, 2.49403],
+    [8.35372, -3.57772, 0.314468],
+    [-27.6687, 14.2647, -13.6492],
+    [52.1761, -27.9436, 12.9442],
+    [-50.7685, 29.0466, 4.23415],
+    [18.6557, -11.4898, -5.60196],
+];
+
+fn polynomial(coeffs: &[[f32; 3]; 7], t: f32) -> Color {
+    let mut c = [0.0; 3];
+    for coeff in coeffs.iter().rev() {
+        for (c, k) in c.iter_mut().zip(coeff) {
+            *c = *c * t + k;
+        }
+    }
+    Color::new(
+        c[0].clamp(0.0, 1.0),
+        c[1].clamp(0.0, 1.0),
+        c[2].clamp(0.0, 1.0),
+        1.0,
+    )
+}
+
+impl<'a> Colormap<'a> {
+    /// Key of the lookup table in the color atlas.
+    pub fn key(&self) -> ImageKey {
+        match self {
+            Colormap::Viridis => ImageKey::from_hash(b"vger colormap viridis"),
+            Colormap::Magma => ImageKey::from_hash(b"vger colormap magma"),
+            Colormap::Custom { key, .. } | Colormap::Lut { key, .. } => (*key).clone(),
+        }
+    }
+
+    /// Color at `t` from 0 to 1.
+    pub fn color(&self, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Colormap::Viridis => polynomial(&VIRIDIS, t),
+            Colormap::Magma => polynomial(&MAGMA, t),
+            Colormap::Custom { colors, .. } => match colors.len() {
+                0 => Color::new(0.0, 0.0, 0.0, 0.0),
+                1 => colors[0],
+                n => {
+                    let x = t * (n - 1) as f32;
+                    let i = (x as usize).min(n - 2);
+                    colors[i].mix(colors[i + 1], x - i as f32)
+                }
+            },
+            Colormap::Lut { lut, .. } => {
+                let entry = |i: usize| {
+                    let c = &lut[4 * i..4 * i + 4];
+                    let f = |c: u8| c as f32 / 255.0;
+                    Color::new(f(c[0]), f(c[1]), f(c[2]), f(c[3]))
+                };
+                match lut.len() / 4 {
+                    0 => Color::new(0.0, 0.0, 0.0, 0.0),
+                    1 => entry(0),
+                    n => {
+                        let x = t * (n - 1) as f32;
+                        let i = (x as usize).min(n - 2);
+                        entry(i).mix(entry(i + 1), x - i as f32)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Nearest value to `t` with an entry in the lookup table, so values
+    /// drawn with the same color share a paint.
+    pub(crate) fn quantize(t: f32) -> f32 {
+        let last = (LUT_SIZE - 1) as f32;
+        (t.clamp(0.0, 1.0) * last).round() / last
+    }
+
+    /// The lookup table as RGBA8 pixels, `LUT_SIZE` wide and one high.
+    pub(crate) fn lut(&self) -> Vec<u8> {
+        let to_u8 = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        (0..LUT_SIZE)
+            .flat_map(|i| {
+                let c = self.color(i as f32 / (LUT_SIZE - 1) as f32);
+                [to_u8(c.r), to_u8(c.g), to_u8(c.b), to_u8(c.a)]
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn close(a: Color, b: Color) -> bool {
+        (a.r - b.r).abs() < 0.02 && (a.g - b.g).abs() < 0.02 && (a.b - b.b).abs() < 0.02
+    }
+
+    #[test]
+    fn test_builtin() {
+        // Ends of the matplotlib tables.
+        assert!(close(
+            Colormap::Viridis.color(0.0),
+            Color::new(0.267, 0.005, 0.329, 1.0)
+        ));
+        assert!(close(
+            Colormap::Viridis.color(1.0),
+            Color::new(0.993, 0.906, 0.144, 1.0)
+        ));
+        assert!(close(
+            Colormap::Magma.color(0.0),
+            Color::new(0.001, 0.0, 0.014, 1.0)
+        ));
+        assert!(close(
+            Colormap::Magma.color(1.0),
+            Color::new(0.987, 0.991, 0.749, 1.0)
+        ));
+        assert_ne!(Colormap::Viridis.key(), Colormap::Magma.key());
+    }
+
+    #[test]
+    fn test_custom() {
+        let key = ImageKey::from_hash(&[1]);
+        let colors = [Color::gray(0.0), Color::gray(1.0), Color::gray(0.0)];
+        let map = Colormap::Custom {
+            key: &key,
+            colors: &colors,
+        };
+        assert_eq!(map.color(0.25), Color::gray(0.5));
+        assert_eq!(map.color(0.5), Color::gray(1.0));
+        assert_eq!(map.color(2.0), Color::gray(0.0));
+        assert_eq!(map.key(), key);
+
+        let lut = map.lut();
+        assert_eq!(lut.len(), 4 * LUT_SIZE as usize);
+        assert_eq!(&lut[..4], &[0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_lut() {
+        let key = ImageKey::from_hash(&[2]);
+        let ramp: Vec<u8> = (0..LUT_SIZE).flat_map(|i| [i as u8, 0, 0, 255]).collect();
+        let map = Colormap::Lut {
+            key: &key,
+            lut: &ramp,
+        };
+        assert_eq!(map.lut(), ramp);
+        assert_eq!(map.color(1.0), Color::new(1.0, 0.0, 0.0, 1.0));
+
+        // Tables of other widths are resampled.
+        let short = [0, 0, 0, 255, 255, 255, 255, 255];
+        let map = Colormap::Lut {
+            key: &key,
+            lut: &short,
+        };
+        assert_eq!(map.color(0.5), Color::gray(0.5));
+        assert_eq!(map.lut().len(), 4 * LUT_SIZE as usize);
+    }
+
+    #[test]
+    fn test_quantize() {
+        assert_eq!(Colormap::quantize(0.0), 0.0);
+        assert_eq!(Colormap::quantize(2.0), 1.0);
+        assert_eq!(Colormap::quantize(0.5001), Colormap::quantize(0.5));
+        assert_eq!(Colormap::quantize(10.0 / 255.0), 10.0 / 255.0);
+    }
+}
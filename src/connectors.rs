@@ -0,0 +1,224 @@
+//! Edge geometry for node editors, drawn with `Vger::stroke_connectors`.
+
+use crate::defs::*;
+
+/// How a connector gets from one port to another.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ConnectorRoute {
+    /// Cubic curve leaving and entering horizontally.
+    Cubic,
+
+    /// Horizontal, vertical, then horizontal lines, with bends rounded
+    /// to `radius`.
+    Orthogonal { radius: f32 },
+}
+
+/// Edge from an output port to an input port.
+#[derive(Copy, Clone, Debug)]
+pub struct Connector {
+    pub from: LocalPoint,
+    pub to: LocalPoint,
+    pub route: ConnectorRoute,
+
+    /// Length of the arrowhead at `to`. Zero for none.
+    pub arrow_size: f32,
+}
+
+/// Piece of a connector's stroke.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Piece {
+    Segment(LocalPoint, LocalPoint),
+    Bezier(LocalPoint, LocalPoint, LocalPoint),
+}
+
+/// Control points for a cubic connector, pulled out horizontally by half
+/// the horizontal distance, and at least `min_pull` so edges running
+/// backwards loop around their nodes.
+pub fn cubic_controls(from: LocalPoint, to: LocalPoint, min_pull: f32) -> (LocalPoint, LocalPoint) {
+    let pull = ((to.x - from.x).abs() / 2.0).max(min_pull);
+    (
+        LocalPoint::new(from.x + pull, from.y),
+        LocalPoint::new(to.x - pull, to.y),
+    )
+}
+
+/// Approximates a cubic bezier with quadratics, within `tolerance`.
+pub fn cubic_to_quads(p: [LocalPoint; 4], tolerance: f32) -> Vec<[LocalPoint; 3]> {
+    // Error of the midpoint approximation shrinks with the cube of the
+    // number of pieces.
+    let d = p[3].to_vector() - p[2].to_vector() * 3.0 + p[1].to_vector() * 3.0 - p[0].to_vector();
+    let err = d.length() * 3.0f32.sqrt() / 36.0;
+    let n = (err / tolerance.max(1e-3)).cbrt().ceil().clamp(1.0, 32.0) as usize;
+
+    let at = |t: f32| {
+        let s = 1.0 - t;
+        (p[0].to_vector() * (s * s * s)
+            + p[1].to_vector() * (3.0 * s * s * t)
+            + p[2].to_vector() * (3.0 * s * t * t)
+            + p[3].to_vector() * (t * t * t))
+            .to_point()
+    };
+    let tangent = |t: f32| {
+        let s = 1.0 - t;
+        (p[1] - p[0]) * (3.0 * s * s)
+            + (p[2] - p[1]) * (6.0 * s * t)
+            + (p[3] - p[2]) * (3.0 * t * t)
+    };
+
+    (0..n)
+        .map(|i| {
+            let (t0, t1) = (i as f32 / n as f32, (i + 1) as f32 / n as f32);
+            let h = (t1 - t0) / 3.0;
+            let (a, b) = (at(t0), at(t1));
+
+            // Average of the quadratic control points matching each end.
+            let c = ((a + tangent(t0) * (1.5 * h)).to_vector()
+                + (b - tangent(t1) * (1.5 * h)).to_vector())
+                * 0.5;
+            [a, c.to_point(), b]
+        })
+        .collect()
+}
+
+/// Corners of an orthogonal route, turning halfway across.
+pub fn orthogonal_route(from: LocalPoint, to: LocalPoint) -> [LocalPoint; 4] {
+    let mid_x = (from.x + to.x) / 2.0;
+    [
+        from,
+        LocalPoint::new(mid_x, from.y),
+        LocalPoint::new(mid_x, to.y),
+        to,
+    ]
+}
+
+/// Pieces of a polyline with corners rounded to `radius`, or less where
+/// the lines are short.
+pub(crate) fn round_corners(points: &[LocalPoint], radius: f32) -> Vec<Piece> {
+    let mut pieces = vec![];
+    let mut start = match points.first() {
+        Some(p) => *p,
+        None => return pieces,
+    };
+
+    for i in 1..points.len() {
+        let p = points[i];
+        if i + 1 == points.len() {
+            if p != start {
+                pieces.push(Piece::Segment(start, p));
+            }
+            break;
+        }
+
+        let next = points[i + 1];
+        let (len_in, len_out) = ((p - points[i - 1]).length(), (next - p).length());
+        let r = radius.min(len_in / 2.0).min(len_out / 2.0);
+        if r <= 0.0 {
+            if p != start {
+                pieces.push(Piece::Segment(start, p));
+            }
+            start = p;
+            continue;
+        }
+
+        let a = p + (points[i - 1] - p) * (r / len_in);
+        let b = p + (next - p) * (r / len_out);
+        if a != start {
+            pieces.push(Piece::Segment(start, a));
+        }
+        pieces.push(Piece::Bezier(a, p, b));
+        start = b;
+    }
+    pieces
+}
+
+/// Triangle for an arrowhead `size` long with its tip at `tip`, pointing
+/// along `direction`.
+pub fn arrowhead(tip: LocalPoint, direction: LocalVector, size: f32) -> [LocalPoint; 3] {
+    let d = if direction.length() > 0.0 {
+        direction.normalize()
+    } else {
+        LocalVector::new(1.0, 0.0)
+    };
+    let n = LocalVector::new(-d.y, d.x);
+    let base = tip - d * size;
+    [tip, base + n * (size / 2.0), base - n * (size / 2.0)]
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn pt(x: f32, y: f32) -> LocalPoint {
+        LocalPoint::new(x, y)
+    }
+
+    #[test]
+    fn test_cubic_controls() {
+        assert_eq!(
+            cubic_controls(pt(0.0, 0.0), pt(100.0, 50.0), 20.0),
+            (pt(50.0, 0.0), pt(50.0, 50.0))
+        );
+
+        // Backwards edges loop out.
+        assert_eq!(
+            cubic_controls(pt(0.0, 0.0), pt(-10.0, 50.0), 20.0),
+            (pt(20.0, 0.0), pt(-30.0, 50.0))
+        );
+    }
+
+    #[test]
+    fn test_cubic_to_quads() {
+        let cubic = [
+            pt(0.0, 0.0),
+            pt(100.0, 0.0),
+            pt(0.0, 100.0),
+            pt(100.0, 100.0),
+        ];
+        let quads = cubic_to_quads(cubic, 0.25);
+        assert!(quads.len() > 1);
+        assert_eq!(quads[0][0], cubic[0]);
+        assert_eq!(quads[quads.len() - 1][2], cubic[3]);
+        for w in quads.windows(2) {
+            assert_eq!(w[0][2], w[1][0]);
+        }
+
+        // A straight cubic is one piece.
+        let line = [pt(0.0, 0.0), pt(1.0, 0.0), pt(2.0, 0.0), pt(3.0, 0.0)];
+        assert_eq!(cubic_to_quads(line, 0.25).len(), 1);
+    }
+
+    #[test]
+    fn test_round_corners() {
+        let route = orthogonal_route(pt(0.0, 0.0), pt(100.0, 40.0));
+        assert_eq!(route[1], pt(50.0, 0.0));
+
+        let pieces = round_corners(&route, 10.0);
+        assert_eq!(
+            pieces,
+            vec![
+                Piece::Segment(pt(0.0, 0.0), pt(40.0, 0.0)),
+                Piece::Bezier(pt(40.0, 0.0), pt(50.0, 0.0), pt(50.0, 10.0)),
+                Piece::Segment(pt(50.0, 10.0), pt(50.0, 30.0)),
+                Piece::Bezier(pt(50.0, 30.0), pt(50.0, 40.0), pt(60.0, 40.0)),
+                Piece::Segment(pt(60.0, 40.0), pt(100.0, 40.0)),
+            ]
+        );
+
+        // Straight across, the middle line is empty.
+        let route = orthogonal_route(pt(0.0, 0.0), pt(100.0, 0.0));
+        assert_eq!(
+            round_corners(&route, 10.0),
+            vec![
+                Piece::Segment(pt(0.0, 0.0), pt(50.0, 0.0)),
+                Piece::Segment(pt(50.0, 0.0), pt(100.0, 0.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arrowhead() {
+        let tri = arrowhead(pt(10.0, 0.0), LocalVector::new(2.0, 0.0), 4.0);
+        assert_eq!(tri, [pt(10.0, 0.0), pt(6.0, 2.0), pt(6.0, -2.0)]);
+    }
+}
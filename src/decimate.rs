@@ -0,0 +1,80 @@
+use crate::defs::*;
+
+/// Bucket min-max decimation. Consecutive points falling in the same
+/// column of width `bucket_width` are reduced to the first, lowest,
+/// highest and last points of the run, in their original order, which
+/// preserves the drawn envelope of a dense series. Works in place, as a
+/// run never keeps more points than it has.
+pub fn decimate_min_max(points: &mut Vec<LocalPoint>, bucket_width: f32) {
+    let column = |p: &LocalPoint| (p.x / bucket_width).floor();
+
+    let mut len = 0;
+    let mut i = 0;
+    while i < points.len() {
+        let bucket = column(&points[i]);
+        let (mut lo, mut hi) = (i, i);
+        let mut j = i + 1;
+        while j < points.len() && column(&points[j]) == bucket {
+            if points[j].y < points[lo].y {
+                lo = j;
+            }
+            if points[j].y > points[hi].y {
+                hi = j;
+            }
+            j += 1;
+        }
+
+        // Kept points are written at or before where they're read from.
+        let mut keep = [i, lo, hi, j - 1];
+        keep.sort_unstable();
+        let mut prev = None;
+        for &k in keep.iter() {
+            if prev != Some(k) {
+                points[len] = points[k];
+                len += 1;
+                prev = Some(k);
+            }
+        }
+        i = j;
+    }
+    points.truncate(len);
+}
+
+/// Whether a polyline has enough points per column that decimating it is
+/// worthwhile.
+pub fn should_decimate(points: &[LocalPoint], bucket_width: f32) -> bool {
+    let (min_x, max_x) = points.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| {
+        (lo.min(p.x), hi.max(p.x))
+    });
+    let columns = ((max_x - min_x) / bucket_width).ceil().max(1.0);
+    points.len() as f32 > 4.0 * columns
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_decimate() {
+        let points: Vec<LocalPoint> = (0..1000)
+            .map(|i| LocalPoint::new(i as f32 * 0.01, ((i * 7) % 13) as f32))
+            .collect();
+        assert!(should_decimate(&points, 1.0));
+
+        let mut out = points.clone();
+        decimate_min_max(&mut out, 1.0);
+        assert!(out.len() <= 40);
+        assert_eq!(out[0], points[0]);
+        assert_eq!(*out.last().unwrap(), *points.last().unwrap());
+
+        let max = |ps: &[LocalPoint]| ps.iter().map(|p| p.y).fold(f32::MIN, f32::max);
+        assert_eq!(max(&out), max(&points));
+
+        let sparse = [LocalPoint::new(0.0, 0.0), LocalPoint::new(10.0, 1.0)];
+        assert!(!should_decimate(&sparse, 1.0));
+        let mut out = sparse.to_vec();
+        decimate_min_max(&mut out, 1.0);
+        assert_eq!(out, sparse.to_vec());
+    }
+}
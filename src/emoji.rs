@@ -0,0 +1,455 @@
+//! Color emoji drawn by `Vger::text` for emoji the built-in font
+//! lacks, from the platform's emoji font.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use swash::scale::image::Content;
+use swash::scale::{Render, ScaleContext, Source, StrikeWith};
+use swash::shape::ShapeContext;
+use swash::FontRef;
+
+/// Where platforms keep their color emoji fonts.
+fn candidate_paths() -> Vec<PathBuf> {
+    let mut paths = vec![];
+
+    if cfg!(target_os = "windows") {
+        let windir = std::env::var_os("WINDIR").unwrap_or_else(|| "C:\\Windows".into());
+        paths.push(Path::new(&windir).join("Fonts").join("seguiemj.ttf"));
+    } else if cfg!(any(target_os = "macos", target_os = "ios")) {
+        paths.push("/System/Library/Fonts/Apple Color Emoji.ttc".into());
+    } else if cfg!(target_os = "android") {
+        paths.push("/system/fonts/NotoColorEmoji.ttf".into());
+    } else if cfg!(unix) {
+        for dir in [
+            "/usr/share/fonts/truetype/noto",
+            "/usr/share/fonts/noto",
+            "/usr/share/fonts/noto-emoji",
+            "/usr/share/fonts/google-noto-emoji",
+            "/usr/share/fonts/TTF",
+            "/usr/local/share/fonts",
+        ] {
+            paths.push(Path::new(dir).join("NotoColorEmoji.ttf"));
+        }
+    }
+
+    paths
+}
+
+/// Color emoji fonts installed on this system, in fallback order: Segoe UI
+/// Emoji on Windows, Apple Color Emoji on macOS and iOS, and Noto Color
+/// Emoji elsewhere.
+pub fn system_emoji_font_paths() -> Vec<PathBuf> {
+    candidate_paths()
+        .into_iter()
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// The bitmap strike to scale emoji at `px` pixels from: the smallest at
+/// least that size, so bitmaps are only scaled down, otherwise the
+/// largest.
+pub fn nearest_strike(strikes: &[u16], px: f32) -> Option<usize> {
+    let larger = strikes
+        .iter()
+        .enumerate()
+        .filter(|(_, ppem)| **ppem as f32 >= px)
+        .min_by_key(|(_, ppem)| **ppem);
+    let largest = strikes.iter().enumerate().max_by_key(|(_, ppem)| **ppem);
+    larger.or(largest).map(|(i, _)| i)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// The table tagged `tag` in the first font of a font file or collection.
+fn find_table<'a>(data: &'a [u8], tag: &[u8; 4]) -> Option<&'a [u8]> {
+    let font = if data.get(..4)? == b"ttcf" {
+        read_u32(data, 12)? as usize
+    } else {
+        0
+    };
+    let tables = read_u16(data, font + 4)? as usize;
+    (0..tables).find_map(|i| {
+        let record = font + 12 + i * 16;
+        if data.get(record..record + 4)? != tag {
+            return None;
+        }
+        let offset = read_u32(data, record + 8)? as usize;
+        let len = read_u32(data, record + 12)? as usize;
+        data.get(offset..offset.checked_add(len)?)
+    })
+}
+
+/// Sizes in pixels per em of a font's color bitmap strikes, in table
+/// order, from its `sbix` or `CBLC` table.
+fn strike_sizes(data: &[u8]) -> Vec<u16> {
+    if let Some(sbix) = find_table(data, b"sbix") {
+        let count = read_u32(sbix, 4).unwrap_or(0) as usize;
+        return (0..count.min(sbix.len() / 4))
+            .filter_map(|i| read_u16(sbix, read_u32(sbix, 8 + i * 4)? as usize))
+            .collect();
+    }
+    if let Some(cblc) = find_table(data, b"CBLC") {
+        let count = read_u32(cblc, 4).unwrap_or(0) as usize;
+        return (0..count.min(cblc.len() / 48))
+            .filter_map(|i| cblc.get(8 + i * 48 + 44).map(|ppem| *ppem as u16))
+            .collect();
+    }
+    vec![]
+}
+
+/// Emoji code points which default to emoji presentation, the
+/// Emoji_Presentation property of Unicode 15.
+const EMOJI_PRESENTATION: &[(u32, u32)] = &[
+    (0x231A, 0x231B),
+    (0x23E9, 0x23EC),
+    (0x23F0, 0x23F0),
+    (0x23F3, 0x23F3),
+    (0x25FD, 0x25FE),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x267F, 0x267F),
+    (0x2693, 0x2693),
+    (0x26A1, 0x26A1),
+    (0x26AA, 0x26AB),
+    (0x26BD, 0x26BE),
+    (0x26C4, 0x26C5),
+    (0x26CE, 0x26CE),
+    (0x26D4, 0x26D4),
+    (0x26EA, 0x26EA),
+    (0x26F2, 0x26F3),
+    (0x26F5, 0x26F5),
+    (0x26FA, 0x26FA),
+    (0x26FD, 0x26FD),
+    (0x2705, 0x2705),
+    (0x270A, 0x270B),
+    (0x2728, 0x2728),
+    (0x274C, 0x274C),
+    (0x274E, 0x274E),
+    (0x2753, 0x2755),
+    (0x2757, 0x2757),
+    (0x2795, 0x2797),
+    (0x27B0, 0x27B0),
+    (0x27BF, 0x27BF),
+    (0x2B1B, 0x2B1C),
+    (0x2B50, 0x2B50),
+    (0x2B55, 0x2B55),
+    (0x1F004, 0x1F004),
+    (0x1F0CF, 0x1F0CF),
+    (0x1F18E, 0x1F18E),
+    (0x1F191, 0x1F19A),
+    (0x1F1E6, 0x1F1FF),
+    (0x1F201, 0x1F201),
+    (0x1F21A, 0x1F21A),
+    (0x1F22F, 0x1F22F),
+    (0x1F232, 0x1F236),
+    (0x1F238, 0x1F23A),
+    (0x1F250, 0x1F251),
+    (0x1F300, 0x1F320),
+    (0x1F32D, 0x1F335),
+    (0x1F337, 0x1F37C),
+    (0x1F37E, 0x1F393),
+    (0x1F3A0, 0x1F3CA),
+    (0x1F3CF, 0x1F3D3),
+    (0x1F3E0, 0x1F3F0),
+    (0x1F3F4, 0x1F3F4),
+    (0x1F3F8, 0x1F43E),
+    (0x1F440, 0x1F440),
+    (0x1F442, 0x1F4FC),
+    (0x1F4FF, 0x1F53D),
+    (0x1F54B, 0x1F54E),
+    (0x1F550, 0x1F567),
+    (0x1F57A, 0x1F57A),
+    (0x1F595, 0x1F596),
+    (0x1F5A4, 0x1F5A4),
+    (0x1F5FB, 0x1F64F),
+    (0x1F680, 0x1F6C5),
+    (0x1F6CC, 0x1F6CC),
+    (0x1F6D0, 0x1F6D2),
+    (0x1F6D5, 0x1F6D7),
+    (0x1F6DC, 0x1F6DF),
+    (0x1F6EB, 0x1F6EC),
+    (0x1F6F4, 0x1F6FC),
+    (0x1F7E0, 0x1F7EB),
+    (0x1F7F0, 0x1F7F0),
+    (0x1F90C, 0x1F93A),
+    (0x1F93C, 0x1F945),
+    (0x1F947, 0x1F9FF),
+    (0x1FA70, 0x1FA7C),
+    (0x1FA80, 0x1FA88),
+    (0x1FA90, 0x1FABD),
+    (0x1FABF, 0x1FAC5),
+    (0x1FACE, 0x1FADB),
+    (0x1FAE0, 0x1FAE8),
+    (0x1FAF0, 0x1FAF8),
+];
+
+/// Selects emoji presentation for the character before it.
+const EMOJI_VARIATION: char = '\u{FE0F}';
+
+/// Whether `text`, a character or a grapheme cluster, is drawn as an
+/// emoji: it starts with a code point which defaults to emoji
+/// presentation, or asks for it with a variation selector, as in ❤️. Other
+/// characters the font lacks aren't looked up in emoji fonts, which
+/// would draw digits and symbols as emoji.
+pub fn is_emoji(text: &str) -> bool {
+    let first = match text.chars().next() {
+        Some(c) => c as u32,
+        None => return false,
+    };
+    let presentation = EMOJI_PRESENTATION
+        .binary_search_by(|(start, end)| {
+            if *end < first {
+                std::cmp::Ordering::Less
+            } else if *start > first {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok();
+    presentation || text.contains(EMOJI_VARIATION)
+}
+
+/// A font file's bytes, read or memory-mapped.
+enum FontData {
+    Owned(Vec<u8>),
+    #[cfg(not(target_arch = "wasm32"))]
+    Mapped(memmap2::Mmap),
+}
+
+impl std::ops::Deref for FontData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FontData::Owned(data) => data,
+            #[cfg(not(target_arch = "wasm32"))]
+            FontData::Mapped(map) => map,
+        }
+    }
+}
+
+/// A color emoji font.
+pub struct EmojiFont {
+    data: FontData,
+    strikes: Vec<u16>,
+}
+
+impl EmojiFont {
+    /// The first font in a font file or collection.
+    pub fn from_bytes(data: Vec<u8>) -> Option<Self> {
+        Self::from_data(FontData::Owned(data))
+    }
+
+    fn from_data(data: FontData) -> Option<Self> {
+        FontRef::from_index(&data, 0)?;
+        let strikes = strike_sizes(&data);
+        Some(Self { data, strikes })
+    }
+
+    /// Maps a font file into memory, so only the parts used to draw are
+    /// read. Emoji fonts are tens of megabytes.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        // Safety: font files aren't changed while apps are running. If one
+        // is, emoji may draw wrongly, as with any font read lazily.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Self::from_data(FontData::Mapped(map))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a font"))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a font"))
+    }
+
+    /// Pixels per em of the font's bitmap strikes, empty for fonts with
+    /// only outlines, such as Segoe UI Emoji.
+    pub fn strikes(&self) -> &[u16] {
+        &self.strikes
+    }
+
+    fn font(&self) -> FontRef<'_> {
+        FontRef::from_index(&self.data, 0).unwrap()
+    }
+
+    pub fn has_char(&self, c: char) -> bool {
+        self.font().charmap().map(c) != 0
+    }
+
+    /// Height of lowercase letters as a fraction of the font size, if
+    /// the font records it.
+    pub fn x_height(&self) -> Option<f32> {
+        let metrics = self.font().metrics(&[]);
+        if metrics.x_height > 0.0 && metrics.units_per_em > 0 {
+            Some(metrics.x_height / metrics.units_per_em as f32)
+        } else {
+            None
+        }
+    }
+}
+
+/// An emoji rasterized for the atlas.
+pub(crate) struct EmojiImage {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub left: i32,
+    pub top: i32,
+
+    /// RGBA rather than a coverage mask.
+    pub colored: bool,
+}
+
+/// Emoji fonts tried in order for characters the built-in font lacks.
+pub(crate) struct EmojiFallback {
+    fonts: Vec<EmojiFont>,
+    context: ScaleContext,
+    shape_context: ShapeContext,
+}
+
+impl EmojiFallback {
+    pub fn new(fonts: Vec<EmojiFont>) -> Self {
+        Self {
+            fonts,
+            context: ScaleContext::new(),
+            shape_context: ShapeContext::new(),
+        }
+    }
+
+    /// The fonts found by `system_emoji_font_paths`, skipping any that
+    /// fail to load. They're memory-mapped, so this is quick.
+    pub fn system() -> Self {
+        Self::new(
+            system_emoji_font_paths()
+                .into_iter()
+                .filter_map(|path| EmojiFont::load(path).ok())
+                .collect(),
+        )
+    }
+
+    /// x-height of the first font that has `c`, as a fraction of its
+    /// size.
+    pub fn x_height(&self, c: char) -> Option<f32> {
+        self.fonts.iter().find(|font| font.has_char(c))?.x_height()
+    }
+
+    /// Rasterizes an emoji, which may be a sequence such as 👩‍👩‍👧 or 👍🏽, at
+    /// `px` pixels per em from the first font that has its first
+    /// character. Sequences are shaped to the font's glyph for the whole
+    /// sequence. Fonts without one draw just the first emoji.
+    pub fn rasterize(&mut self, emoji: &str, px: f32) -> Option<EmojiImage> {
+        let c = emoji.chars().next()?;
+        let font = self.fonts.iter().find(|font| font.has_char(c))?;
+        let font_ref = font.font();
+        let glyph_id = if emoji.len() == c.len_utf8() {
+            font_ref.charmap().map(c)
+        } else {
+            let mut shaper = self.shape_context.builder(font_ref).size(px).build();
+            shaper.add_str(emoji);
+            let mut first = None;
+            shaper.shape_with(|cluster| {
+                if first.is_none() {
+                    first = cluster.glyphs.first().map(|glyph| glyph.id);
+                }
+            });
+            first?
+        };
+
+        let mut sources = vec![];
+        if let Some(strike) = nearest_strike(&font.strikes, px) {
+            sources.push(Source::ColorBitmap(StrikeWith::Index(strike as u32)));
+        }
+        sources.extend([Source::ColorOutline(0), Source::Outline]);
+
+        let mut scaler = self.context.builder(font_ref).size(px).build();
+        let image = Render::new(&sources).render(&mut scaler, glyph_id)?;
+        Some(EmojiImage {
+            width: image.placement.width,
+            height: image.placement.height,
+            left: image.placement.left,
+            top: image.placement.top,
+            colored: image.content != Content::Mask,
+            data: image.data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /// A font file with just one table.
+    fn font_with_table(tag: &[u8; 4], table: &[u8]) -> Vec<u8> {
+        let mut data = vec![0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        data.extend_from_slice(tag);
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&28u32.to_be_bytes());
+        data.extend_from_slice(&(table.len() as u32).to_be_bytes());
+        data.extend_from_slice(table);
+        data
+    }
+
+    #[test]
+    fn test_strike_sizes() {
+        // Version, flags, two strikes and their ppem and ppi.
+        let mut sbix = vec![0, 1, 0, 1, 0, 0, 0, 2];
+        sbix.extend_from_slice(&16u32.to_be_bytes());
+        sbix.extend_from_slice(&20u32.to_be_bytes());
+        sbix.extend_from_slice(&[0, 40, 0, 72, 0, 160, 0, 72]);
+        assert_eq!(strike_sizes(&font_with_table(b"sbix", &sbix)), [40, 160]);
+
+        let mut cblc = vec![0, 3, 0, 0, 0, 0, 0, 1];
+        let mut size = [0; 48];
+        size[44] = 109;
+        size[45] = 109;
+        cblc.extend_from_slice(&size);
+        assert_eq!(strike_sizes(&font_with_table(b"CBLC", &cblc)), [109]);
+
+        assert!(strike_sizes(&font_with_table(b"glyf", &[0; 8])).is_empty());
+        assert!(strike_sizes(&[]).is_empty());
+
+        // Counts past the end of the table are ignored.
+        let truncated = [0, 1, 0, 1, 0xff, 0xff, 0xff, 0xff];
+        assert!(strike_sizes(&font_with_table(b"sbix", &truncated)).is_empty());
+    }
+
+    #[test]
+    fn test_is_emoji() {
+        assert!(is_emoji("😀"));
+        assert!(is_emoji("👩‍👩‍👧"));
+        assert!(is_emoji("🇫🇷"));
+        assert!(is_emoji("⌚"));
+        assert!(is_emoji("❤\u{FE0F}"));
+        assert!(!is_emoji("❤"));
+        assert!(!is_emoji("#"));
+        assert!(!is_emoji("漢"));
+        assert!(!is_emoji("\u{2192}"));
+        assert!(!is_emoji(""));
+
+        // Ranges are sorted, for the binary search.
+        assert!(EMOJI_PRESENTATION.windows(2).all(|w| w[0].1 < w[1].0));
+    }
+
+    #[test]
+    fn test_nearest_strike() {
+        let strikes = [20, 32, 40, 64, 96, 160];
+        assert_eq!(nearest_strike(&strikes, 16.0), Some(0));
+        assert_eq!(nearest_strike(&strikes, 32.0), Some(1));
+        assert_eq!(nearest_strike(&strikes, 33.0), Some(2));
+        assert_eq!(nearest_strike(&strikes, 400.0), Some(5));
+        assert_eq!(nearest_strike(&[160, 20], 24.0), Some(0));
+        assert_eq!(nearest_strike(&[], 24.0), None);
+    }
+}
@@ -0,0 +1,101 @@
+//! Textures created outside vger, e.g. a compositor's client buffers
+//! imported from dmabufs.
+
+use std::collections::HashMap;
+
+/// Handle to a texture registered with `Vger::register_external_texture`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ExternalImage(u64);
+
+pub(crate) struct ExternalTexture {
+    pub texture: wgpu::Texture,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Registered external textures by handle.
+#[derive(Default)]
+pub(crate) struct ExternalTextures {
+    textures: HashMap<ExternalImage, ExternalTexture>,
+    next: u64,
+}
+
+impl ExternalTextures {
+    /// Registers a texture if it can be copied into the color atlas: it
+    /// must be `Rgba8Unorm` with `COPY_SRC` usage.
+    pub fn register(
+        &mut self,
+        texture: wgpu::Texture,
+        desc: &wgpu::TextureDescriptor,
+    ) -> Option<ExternalImage> {
+        if !is_importable(desc) {
+            return None;
+        }
+
+        let image = ExternalImage(self.next);
+        self.next += 1;
+        self.textures.insert(
+            image,
+            ExternalTexture {
+                texture,
+                width: desc.size.width,
+                height: desc.size.height,
+            },
+        );
+        Some(image)
+    }
+
+    pub fn remove(&mut self, image: ExternalImage) -> Option<wgpu::Texture> {
+        self.textures.remove(&image).map(|t| t.texture)
+    }
+
+    pub fn get(&self, image: ExternalImage) -> Option<&ExternalTexture> {
+        self.textures.get(&image)
+    }
+}
+
+impl ExternalImage {
+    /// Key for the image's space in the color atlas.
+    pub(crate) fn key(self) -> u64 {
+        self.0
+    }
+}
+
+fn is_importable(desc: &wgpu::TextureDescriptor) -> bool {
+    desc.format == wgpu::TextureFormat::Rgba8Unorm
+        && desc.usage.contains(wgpu::TextureUsages::COPY_SRC)
+        && desc.size.depth_or_array_layers == 1
+        && desc.sample_count == 1
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_importable() {
+        let mut desc = wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 64,
+                height: 64,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        };
+        assert!(is_importable(&desc));
+
+        desc.format = wgpu::TextureFormat::Bgra8Unorm;
+        assert!(!is_importable(&desc));
+
+        desc.format = wgpu::TextureFormat::Rgba8Unorm;
+        desc.usage = wgpu::TextureUsages::TEXTURE_BINDING;
+        assert!(!is_importable(&desc));
+    }
+}
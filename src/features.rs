@@ -0,0 +1,167 @@
+//! OpenType features applied when shaping text, e.g. tabular figures so
+//! numbers line up in columns. See `Vger::set_font_features`.
+
+use std::ops::Range;
+
+use crate::hash::fnv1a;
+use crate::ruby::source_chars;
+
+/// An OpenType feature setting, for a byte range of the text as in
+/// HarfBuzz, such as `liga`, `calt`, `tnum`, `smcp` or `ss01`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FontFeature {
+    pub tag: [u8; 4],
+
+    /// 0 turns the feature off and 1 on. Larger values pick alternates.
+    pub value: u16,
+
+    /// Byte range of the text the setting applies to.
+    pub range: Range<usize>,
+}
+
+impl FontFeature {
+    /// Sets `tag` to `value` for the whole text.
+    pub fn new(tag: &[u8; 4], value: u16) -> Self {
+        Self {
+            tag: *tag,
+            value,
+            range: 0..usize::MAX,
+        }
+    }
+
+    pub fn on(tag: &[u8; 4]) -> Self {
+        Self::new(tag, 1)
+    }
+
+    pub fn off(tag: &[u8; 4]) -> Self {
+        Self::new(tag, 0)
+    }
+
+    /// Limits the setting to a byte range of the text.
+    pub fn with_range(self, range: Range<usize>) -> Self {
+        Self { range, ..self }
+    }
+}
+
+/// Identifies a set of features in cache keys, 0 for none.
+pub(crate) fn features_hash(features: &[FontFeature]) -> u64 {
+    if features.is_empty() {
+        return 0;
+    }
+    let mut bytes = Vec::with_capacity(features.len() * 22);
+    for feature in features {
+        bytes.extend_from_slice(&feature.tag);
+        bytes.extend_from_slice(&feature.value.to_le_bytes());
+        bytes.extend_from_slice(&(feature.range.start as u64).to_le_bytes());
+        bytes.extend_from_slice(&(feature.range.end as u64).to_le_bytes());
+    }
+    fnv1a(&bytes)
+}
+
+/// Byte offset in `text` of each character of `laid_out`, which is `text`
+/// with line breaks and hyphens inserted where it wrapped. Inserted
+/// characters take the offset of the character before.
+pub(crate) fn source_offsets(text: &str, laid_out: &[char]) -> Vec<usize> {
+    let mut offsets = vec![None; laid_out.len()];
+    for ((byte, _), j) in text.char_indices().zip(source_chars(text, laid_out)) {
+        if let Some(offset) = offsets.get_mut(j) {
+            *offset = Some(byte);
+        }
+    }
+    let mut last = 0;
+    offsets
+        .into_iter()
+        .map(|offset| {
+            last = offset.unwrap_or(last);
+            last
+        })
+        .collect()
+}
+
+/// Runs of characters with the same features, given each character's
+/// byte offset in the text, as character ranges and the indices of the
+/// features that apply.
+pub(crate) fn feature_runs(
+    offsets: &[usize],
+    features: &[FontFeature],
+) -> Vec<(Range<usize>, Vec<usize>)> {
+    let active = |offset: usize| -> Vec<usize> {
+        (0..features.len())
+            .filter(|i| features[*i].range.contains(&offset))
+            .collect()
+    };
+
+    let mut runs: Vec<(Range<usize>, Vec<usize>)> = vec![];
+    for (i, offset) in offsets.iter().enumerate() {
+        let set = active(*offset);
+        match runs.last_mut() {
+            Some((range, last)) if *last == set => range.end = i + 1,
+            _ => runs.push((i..i + 1, set)),
+        }
+    }
+    runs
+}
+
+/// The glyph of each of `len` characters, from shaped clusters given as
+/// the range of characters each came from and its glyphs. Characters
+/// of a cluster take its glyphs in order, so the characters after the
+/// first of a ligature get `None`.
+pub(crate) fn cluster_glyphs<T: Copy>(
+    len: usize,
+    clusters: &[(Range<usize>, Vec<T>)],
+) -> Vec<Option<T>> {
+    let mut glyphs = vec![None; len];
+    for (range, cluster) in clusters {
+        for (i, glyph) in range.clone().zip(cluster) {
+            if let Some(slot) = glyphs.get_mut(i) {
+                *slot = Some(*glyph);
+            }
+        }
+    }
+    glyphs
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_features_hash() {
+        let tnum = [FontFeature::on(b"tnum")];
+        assert_eq!(features_hash(&[]), 0);
+        assert_ne!(features_hash(&tnum), 0);
+        assert_ne!(
+            features_hash(&tnum),
+            features_hash(&[FontFeature::off(b"tnum")])
+        );
+        assert_ne!(
+            features_hash(&tnum),
+            features_hash(&[FontFeature::on(b"tnum").with_range(0..4)])
+        );
+    }
+
+    #[test]
+    fn test_source_offsets() {
+        let laid_out: Vec<char> = "hé-\nllo".chars().collect();
+        assert_eq!(source_offsets("héllo", &laid_out), [0, 1, 1, 1, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_feature_runs() {
+        let features = [
+            FontFeature::off(b"liga"),
+            FontFeature::on(b"tnum").with_range(2..4),
+        ];
+        let runs = feature_runs(&[0, 1, 2, 3, 4], &features);
+        assert_eq!(runs, [(0..2, vec![0]), (2..4, vec![0, 1]), (4..5, vec![0])]);
+        assert_eq!(feature_runs(&[0, 1], &[]), [(0..2, vec![])]);
+    }
+
+    #[test]
+    fn test_cluster_glyphs() {
+        // "ffi" as a ligature, then "x".
+        let clusters = [(0..3, vec![7]), (3..4, vec![9])];
+        assert_eq!(cluster_glyphs(4, &clusters), [Some(7), None, None, Some(9)]);
+    }
+}
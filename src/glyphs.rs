@@ -1,7 +1,25 @@
-use crate::atlas::{Atlas, AtlasContent};
+use crate::atlas::{Atlas, AtlasContent, AtlasRegions, Packing};
+use crate::emoji::{EmojiFallback, EmojiFont};
+use crate::hash::{content_hash, ImageKey, SvgKey};
+use crate::outline::{glyph_outline, GlyphOutline};
 use cosmic_text::{SubpixelBin, SwashContent, SwashImage};
 use rect_packer::Rect;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// The font drawn by `Vger::text`.
+pub(crate) const FONT: &[u8] = include_bytes!("fonts/Anodina-Regular.ttf");
+
+/// Identifies a glyph cache file.
+const GLYPH_FILE_MAGIC: &[u8; 4] = b"vgrg";
+
+/// Bumped when the glyph cache file layout or rasterization changes.
+const GLYPH_FILE_VERSION: u32 = 2;
+
+/// Largest glyph read from a cache file, in pixels per side.
+const MAX_GLYPH_FILE_SIZE: u32 = 4096;
 
 #[derive(Copy, Clone, Debug)]
 pub struct GlyphInfo {
@@ -9,6 +27,10 @@ pub struct GlyphInfo {
     pub metrics: fontdue::Metrics,
 }
 
+/// A glyph rasterized by `get_glyph_mask`, by font, glyph, size, bits of
+/// the device scale and the indices of its subpixel bins.
+type AtlasGlyphKey = (cosmic_text::fontdb::ID, u16, u32, u32, (u32, u32));
+
 #[derive(Copy, Clone, Debug)]
 pub struct AtlasInfo {
     pub rect: Option<Rect>,
@@ -17,60 +39,293 @@ pub struct AtlasInfo {
     pub colored: bool,
 }
 
-pub struct GlyphCache {
-    pub mask_atlas: Atlas,
-    pub color_atlas: Atlas,
+/// Identifies content rendered into the color atlas on the gpu.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LayerKey {
+    /// Copied from an external texture.
+    External(u64),
+
+    /// Converted from a video stream's frames.
+    Video(u64),
+
+    /// Drawn behind a label scrim, rerendered each frame.
+    Backdrop(u64),
+}
+
+/// Subpixel positions glyphs are rasterized at, per axis. Fewer bins mean
+/// fewer cached copies of each glyph, and less to upload for animated
+/// text, at the cost of glyphs being placed less precisely. See
+/// `Vger::set_subpixel_binning`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SubpixelBinning {
+    /// Glyphs are rasterized on whole pixels.
+    Off,
+
+    /// Half pixel steps.
+    Two,
+
+    /// Quarter pixel steps, as cosmic-text bins positions.
+    Four,
+}
+
+impl Default for SubpixelBinning {
+    fn default() -> Self {
+        SubpixelBinning::Four
+    }
+}
+
+impl SubpixelBinning {
+    /// The bin a glyph in `bin` is cached under. A glyph is rasterized at
+    /// the first position drawn in each bin, so positions are off by less
+    /// than a bin.
+    pub fn quantize(self, bin: SubpixelBin) -> SubpixelBin {
+        match (self, bin) {
+            (SubpixelBinning::Off, _) => SubpixelBin::Zero,
+            (SubpixelBinning::Two, SubpixelBin::Zero | SubpixelBin::One) => SubpixelBin::Zero,
+            (SubpixelBinning::Two, SubpixelBin::Two | SubpixelBin::Three) => SubpixelBin::Two,
+            (SubpixelBinning::Four, bin) => bin,
+        }
+    }
+}
+
+/// Called with the usage of the mask and color atlases when they fill up
+/// and everything cached is evicted. Frequent calls mean frames use more
+/// glyphs and images than fit in the atlases, which are at most the
+/// device's largest texture size.
+pub type AtlasFullCallback = Box<dyn FnMut(f32, f32) + Send>;
+
+/// Lookups in the glyph cache since its stats were reset, e.g. to spot
+/// animated text sizes rasterizing glyphs every frame.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GlyphCacheStats {
+    /// Glyphs of text, including emoji.
+    pub glyph_hits: usize,
+    pub glyph_misses: usize,
+
+    pub image_hits: usize,
+    pub image_misses: usize,
+
+    pub svg_hits: usize,
+    pub svg_misses: usize,
+
+    /// Time spent rasterizing on misses, including the callbacks
+    /// producing images and svgs. Not measured on wasm, which lacks a
+    /// clock.
+    pub raster_time: Duration,
+}
+
+/// Runs `f`, adding how long it took to `time`.
+fn timed<T>(time: &mut Duration, f: impl FnOnce() -> T) -> T {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let start = std::time::Instant::now();
+        let result = f();
+        *time += start.elapsed();
+        result
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = time;
+        f()
+    }
+}
+
+/// Position of a subpixel bin, for glyph cache keys and files.
+fn subpixel_bin_index(bin: SubpixelBin) -> u32 {
+    match bin {
+        SubpixelBin::Zero => 0,
+        SubpixelBin::One => 1,
+        SubpixelBin::Two => 2,
+        SubpixelBin::Three => 3,
+    }
+}
+
+/// Whether two atlas regions share any pixels.
+fn overlaps(a: Rect, b: Rect) -> bool {
+    a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+}
+
+/// Caches where glyphs and images were put in the atlases. Generic over
+/// the atlases so the bookkeeping can be tested without a device.
+pub struct GlyphCache<A: AtlasRegions = Atlas> {
+    pub mask_atlas: A,
+    pub color_atlas: A,
     pub font: fontdue::Font,
-    info: HashMap<(char, u32), GlyphInfo>,
-    atlas_infos: HashMap<
-        (
-            cosmic_text::fontdb::ID,
-            u16,
-            u32,
-            (SubpixelBin, SubpixelBin),
-        ),
-        AtlasInfo,
-    >,
-    svg_infos: HashMap<Vec<u8>, HashMap<(u32, u32), AtlasInfo>>,
+    info: BTreeMap<(char, u32), GlyphInfo>,
+    /// Glyphs of shaped text by glyph id and fixed point size.
+    indexed_info: BTreeMap<(u16, u32), GlyphInfo>,
+    atlas_infos: BTreeMap<AtlasGlyphKey, AtlasInfo>,
+    /// Pixels of the glyphs in `atlas_infos`, which only the caller can
+    /// rasterize, kept for `write_glyphs`.
+    atlas_images: BTreeMap<AtlasGlyphKey, SwashImage>,
+    svg_infos: BTreeMap<SvgKey, BTreeMap<(u32, u32), AtlasInfo>>,
+    image_infos: BTreeMap<ImageKey, AtlasInfo>,
+    layer_rects: BTreeMap<LayerKey, Rect>,
+    /// Emoji fonts for characters the font lacks, memory-mapped when the
+    /// cache is made, so drawing doesn't wait on reading them.
+    emoji: EmojiFallback,
+    /// Emoji by text, a character or sequence, and bits of the size,
+    /// `None` for emoji no emoji font has.
+    emoji_infos: BTreeMap<String, BTreeMap<u32, Option<AtlasInfo>>>,
+    on_full: Option<AtlasFullCallback>,
+    stats: GlyphCacheStats,
+    /// Bumped whenever atlas regions may be reused, so regions handed out
+    /// earlier can't be trusted.
+    generation: u64,
+    /// Size the atlases were made with, which they grow back to after
+    /// `shrink_atlases`.
+    max_atlas_size: u32,
+    /// Bumped whenever an atlas texture is reallocated.
+    texture_generation: u64,
+    /// Counts calls to `check_usage`, once per frame.
+    frame: u64,
+    /// Whether the atlases are only ever cleared, never partly evicted,
+    /// so regions are packed in the order they were first used.
+    deterministic: bool,
+    /// Frame each row of the mask atlas holding a region was last used,
+    /// by the region's top, so the least recently used shelves are
+    /// evicted first.
+    shelf_uses: BTreeMap<i32, u64>,
+    /// Outlines of the built-in font's glyphs by id, for glyphs drawn as
+    /// paths. Kept when the atlases are cleared.
+    outlines: BTreeMap<u16, Option<Arc<GlyphOutline>>>,
+    scale_context: swash::scale::ScaleContext,
+}
+
+/// Handle to a glyph cache which several renderers on the same device can
+/// share, so glyphs and images are rasterized and stored once. See
+/// `Vger::with_glyph_cache`.
+#[derive(Clone)]
+pub struct SharedGlyphCache(Arc<Mutex<GlyphCache>>);
+
+impl SharedGlyphCache {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self(Arc::new(Mutex::new(GlyphCache::new(device))))
+    }
+
+    /// See `GlyphCache::with_atlas_size`.
+    pub fn with_atlas_size(device: &wgpu::Device, size: u32) -> Self {
+        Self(Arc::new(Mutex::new(GlyphCache::with_atlas_size(
+            device, size,
+        ))))
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, GlyphCache> {
+        self.0.lock().unwrap()
+    }
 }
 
 impl GlyphCache {
     pub fn new(device: &wgpu::Device) -> Self {
+        Self::with_atlas_size(device, Atlas::ATLAS_SIZE)
+    }
+
+    /// Cache with atlases `size` pixels square, or the largest texture
+    /// the device supports if that's smaller.
+    pub fn with_atlas_size(device: &wgpu::Device, size: u32) -> Self {
+        let size = Atlas::clamped_size(device, size);
+        // Glyphs of a size are about as tall, so pack well in shelves.
+        let mut mask_atlas = Atlas::with_size(device, AtlasContent::Mask, size);
+        mask_atlas.set_packing(Packing::Shelves);
+        Self::with_atlases(
+            mask_atlas,
+            Atlas::with_size(device, AtlasContent::Color, size),
+        )
+    }
+
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        scratch: &mut Vec<u8>,
+    ) {
+        profile_span!("vger::atlas_upload");
+        self.reallocate(device);
+        self.mask_atlas.update(device, encoder, scratch);
+        self.color_atlas.update(device, encoder, scratch);
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.mask_atlas.memory_usage() + self.color_atlas.memory_usage()
+    }
+
+    /// Clears the cache and shrinks the atlases to `Atlas::MIN_SIZE`,
+    /// which is all their live entries need once they're dropped, freeing
+    /// the textures' memory. Glyphs and images are uploaded again as
+    /// they're drawn, and the atlases grow back as they fill.
+    pub fn shrink_atlases(&mut self, device: &wgpu::Device) {
+        self.clear();
+        for atlas in [&mut self.mask_atlas, &mut self.color_atlas] {
+            let size = atlas.size().min(Atlas::MIN_SIZE);
+            atlas.resize(size);
+        }
+        self.reallocate(device);
+    }
+
+    /// Makes new textures for atlases that were resized.
+    fn reallocate(&mut self, device: &wgpu::Device) {
+        let mask = self.mask_atlas.reallocate(device);
+        let color = self.color_atlas.reallocate(device);
+        if mask || color {
+            self.texture_generation += 1;
+        }
+    }
+}
+
+impl<A: AtlasRegions> GlyphCache<A> {
+    pub fn with_atlases(mask_atlas: A, color_atlas: A) -> Self {
+        let max_atlas_size = mask_atlas.size().max(color_atlas.size());
         let settings = fontdue::FontSettings {
             collection_index: 0,
             scale: 100.0,
         };
-        let font = include_bytes!("fonts/Anodina-Regular.ttf") as &[u8];
-
         Self {
-            mask_atlas: Atlas::new(device, AtlasContent::Mask),
-            color_atlas: Atlas::new(device, AtlasContent::Color),
-            font: fontdue::Font::from_bytes(font, settings).unwrap(),
-            info: HashMap::new(),
-            atlas_infos: HashMap::new(),
-            svg_infos: HashMap::new(),
+            mask_atlas,
+            color_atlas,
+            font: fontdue::Font::from_bytes(FONT, settings).unwrap(),
+            info: BTreeMap::new(),
+            indexed_info: BTreeMap::new(),
+            atlas_infos: BTreeMap::new(),
+            atlas_images: BTreeMap::new(),
+            svg_infos: BTreeMap::new(),
+            image_infos: BTreeMap::new(),
+            layer_rects: BTreeMap::new(),
+            emoji: EmojiFallback::system(),
+            emoji_infos: BTreeMap::new(),
+            on_full: None,
+            stats: GlyphCacheStats::default(),
+            generation: 0,
+            max_atlas_size,
+            texture_generation: 0,
+            frame: 0,
+            deterministic: false,
+            shelf_uses: BTreeMap::new(),
+            outlines: BTreeMap::new(),
+            scale_context: swash::scale::ScaleContext::new(),
         }
     }
 
     pub fn get_svg_mask(
         &mut self,
-        hash: &[u8],
+        key: &SvgKey,
         width: u32,
         height: u32,
         image: impl FnOnce() -> Vec<u8>,
     ) -> AtlasInfo {
-        if !self.svg_infos.contains_key(hash) {
-            self.svg_infos.insert(hash.to_vec(), HashMap::new());
+        if !self.svg_infos.contains_key(key) {
+            self.svg_infos.insert(key.clone(), BTreeMap::new());
         }
 
         {
-            let svg_infos = self.svg_infos.get(hash).unwrap();
+            let svg_infos = self.svg_infos.get(key).unwrap();
             if let Some(info) = svg_infos.get(&(width, height)) {
+                self.stats.svg_hits += 1;
                 return info.clone();
             }
         }
 
-        let data = image();
+        self.stats.svg_misses += 1;
+        let data = timed(&mut self.stats.raster_time, image);
         let rect = self.color_atlas.add_region(&data, width, height);
         let info = AtlasInfo {
             rect,
@@ -79,26 +334,102 @@ impl GlyphCache {
             colored: true,
         };
 
-        let svg_infos = self.svg_infos.get_mut(hash).unwrap();
+        let svg_infos = self.svg_infos.get_mut(key).unwrap();
         svg_infos.insert((width, height), info.clone());
 
         info
     }
 
+    pub fn get_image_mask(
+        &mut self,
+        key: &ImageKey,
+        width: u32,
+        height: u32,
+        image: impl FnOnce() -> Vec<u8>,
+    ) -> AtlasInfo {
+        if let Some(info) = self.image_infos.get(key) {
+            match info.rect {
+                // The key was reused for an image of another size.
+                Some(rect) if (rect.width, rect.height) != (width as i32, height as i32) => {
+                    self.color_atlas.free_region(rect);
+                }
+                _ => {
+                    self.stats.image_hits += 1;
+                    return *info;
+                }
+            }
+        }
+
+        self.stats.image_misses += 1;
+        let data = timed(&mut self.stats.raster_time, image);
+        let rect = self.color_atlas.add_region(&data, width, height);
+        let info = AtlasInfo {
+            rect,
+            left: 0,
+            top: 0,
+            colored: true,
+        };
+
+        self.image_infos.insert(key.clone(), info);
+
+        info
+    }
+
+    /// Forgets an image, freeing its atlas space for the packer to reuse,
+    /// so it mustn't be removed while a frame drawing it is recorded.
+    pub fn remove_image(&mut self, key: &ImageKey) {
+        if let Some(rect) = self.image_infos.remove(key).and_then(|info| info.rect) {
+            self.color_atlas.free_region(rect);
+        }
+    }
+
+    /// Where a layer was rendered, if it's still in the color atlas at
+    /// the given size.
+    pub fn get_layer(&self, key: LayerKey, width: u32, height: u32) -> Option<Rect> {
+        self.layer_rects
+            .get(&key)
+            .filter(|rect| (rect.width, rect.height) == (width as i32, height as i32))
+            .copied()
+    }
+
+    /// Forgets a layer, freeing its atlas space for the packer to reuse.
+    pub fn remove_layer(&mut self, key: LayerKey) {
+        if let Some(rect) = self.layer_rects.remove(&key) {
+            self.color_atlas.free_region(rect);
+        }
+    }
+
+    /// Reserves color atlas space for a layer rendered on the gpu.
+    pub fn add_layer(&mut self, key: LayerKey, width: u32, height: u32) -> Option<Rect> {
+        let rect = self.color_atlas.reserve_region(width, height)?;
+        self.layer_rects.insert(key, rect);
+        Some(rect)
+    }
+
+    /// Where a glyph rasterized at `size` times `scale` is in the atlas.
+    /// Glyphs are cached per scale, so moving to a display with another
+    /// scale rasterizes them again.
     pub fn get_glyph_mask<'a>(
         &mut self,
         font_id: cosmic_text::fontdb::ID,
         glyph_id: u16,
         size: u32,
+        scale: f32,
         subpx: (SubpixelBin, SubpixelBin),
         image: impl FnOnce() -> SwashImage,
     ) -> AtlasInfo {
-        let key = (font_id, glyph_id, size, subpx);
-        if let Some(rect) = self.atlas_infos.get(&key) {
-            return *rect;
+        let subpx = (subpixel_bin_index(subpx.0), subpixel_bin_index(subpx.1));
+        let key = (font_id, glyph_id, size, scale.to_bits(), subpx);
+        if let Some(info) = self.atlas_infos.get(&key).copied() {
+            self.stats.glyph_hits += 1;
+            if !info.colored {
+                self.touch(info.rect);
+            }
+            return info;
         }
 
-        let image = image();
+        self.stats.glyph_misses += 1;
+        let image = timed(&mut self.stats.raster_time, image);
         let rect = match image.content {
             SwashContent::Mask => self.mask_atlas.add_region(
                 &image.data,
@@ -117,10 +448,77 @@ impl GlyphCache {
             top: image.placement.top,
             colored: image.content != SwashContent::Mask,
         };
+        if !info.colored {
+            self.touch(info.rect);
+        }
         self.atlas_infos.insert(key, info);
+        self.atlas_images.insert(key, image);
         info
     }
 
+    /// Replaces the emoji fonts, found on the system by default.
+    pub fn set_emoji_fonts(&mut self, fonts: Vec<EmojiFont>) {
+        self.emoji = EmojiFallback::new(fonts);
+        self.emoji_infos.clear();
+    }
+
+    /// Where `c` rasterized at `size` from an emoji font is in the atlas,
+    /// for characters the built-in font lacks.
+    pub fn get_emoji(&mut self, c: char, size: f32) -> Option<AtlasInfo> {
+        self.get_emoji_sequence(c.encode_utf8(&mut [0; 4]), size)
+    }
+
+    /// Like `get_emoji`, for an emoji sequence such as 👩‍👩‍👧 or 👍🏽, drawn
+    /// as one glyph.
+    pub fn get_emoji_sequence(&mut self, emoji: &str, size: f32) -> Option<AtlasInfo> {
+        if let Some(info) = self
+            .emoji_infos
+            .get(emoji)
+            .and_then(|sizes| sizes.get(&size.to_bits()))
+            .copied()
+        {
+            self.stats.glyph_hits += 1;
+            self.touch_emoji(info);
+            return info;
+        }
+
+        self.stats.glyph_misses += 1;
+        let fallback = &mut self.emoji;
+        let image = timed(&mut self.stats.raster_time, || {
+            fallback.rasterize(emoji, size)
+        });
+        let info = image.map(|image| {
+            let atlas = if image.colored {
+                &mut self.color_atlas
+            } else {
+                &mut self.mask_atlas
+            };
+            AtlasInfo {
+                rect: atlas.add_region(&image.data, image.width, image.height),
+                left: image.left,
+                top: image.top,
+                colored: image.colored,
+            }
+        });
+        self.touch_emoji(info);
+        self.emoji_infos
+            .entry(emoji.to_string())
+            .or_default()
+            .insert(size.to_bits(), info);
+        info
+    }
+
+    /// Height of lowercase letters of the built-in font, as a fraction of
+    /// its size.
+    pub fn x_height(&self) -> f32 {
+        self.font.metrics('x', 1000.0).height as f32 / 1000.0
+    }
+
+    /// x-height of the emoji font that has `c`, as a fraction of its size.
+    pub fn emoji_x_height(&self, c: char) -> Option<f32> {
+        self.emoji.x_height(c)
+    }
+
     pub fn get_glyph(&mut self, c: char, size: f32) -> GlyphInfo {
         let factor = 65536.0;
 
@@ -128,10 +526,18 @@ impl GlyphCache {
         let size_fixed_point = (size * factor) as u32;
 
         // Do we already have a glyph?
-        match self.info.get(&(c, size_fixed_point)) {
-            Some(info) => *info,
+        match self.info.get(&(c, size_fixed_point)).copied() {
+            Some(info) => {
+                self.stats.glyph_hits += 1;
+                self.touch(info.rect);
+                info
+            }
             None => {
-                let (metrics, data) = self.font.rasterize(c, size_fixed_point as f32 / factor);
+                self.stats.glyph_misses += 1;
+                let font = &self.font;
+                let (metrics, data) = timed(&mut self.stats.raster_time, || {
+                    font.rasterize(c, size_fixed_point as f32 / factor)
+                });
 
                 /*
                 let mut i = 0;
@@ -150,27 +556,764 @@ impl GlyphCache {
 
                 let info = GlyphInfo { rect, metrics };
 
+                self.touch(rect);
                 self.info.insert((c, size_fixed_point), info);
                 info
             }
         }
     }
 
-    pub fn update(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
-        self.mask_atlas.update(device, encoder);
-        self.color_atlas.update(device, encoder);
+    /// Like `get_glyph`, for a glyph of the font picked by shaping, such as
+    /// a ligature or tabular figure.
+    pub fn get_glyph_indexed(&mut self, id: u16, size: f32) -> GlyphInfo {
+        let size_fixed_point = (size * 65536.0) as u32;
+        if let Some(info) = self.indexed_info.get(&(id, size_fixed_point)).copied() {
+            self.stats.glyph_hits += 1;
+            self.touch(info.rect);
+            return info;
+        }
+
+        self.stats.glyph_misses += 1;
+        let font = &self.font;
+        let (metrics, data) = timed(&mut self.stats.raster_time, || {
+            font.rasterize_indexed(id, size_fixed_point as f32 / 65536.0)
+        });
+        let rect = self
+            .mask_atlas
+            .add_region(&data, metrics.width as u32, metrics.height as u32);
+        let info = GlyphInfo { rect, metrics };
+        self.touch(rect);
+        self.indexed_info.insert((id, size_fixed_point), info);
+        info
+    }
+
+    /// Outline of a glyph of the built-in font at a size of one pixel,
+    /// for drawing it as a path at sizes too large for the atlas.
+    pub(crate) fn get_outline(&mut self, id: u16) -> Option<Arc<GlyphOutline>> {
+        let context = &mut self.scale_context;
+        self.outlines
+            .entry(id)
+            .or_insert_with(|| {
+                let font = swash::FontRef::from_index(FONT, 0)?;
+                glyph_outline(context, font, id).map(Arc::new)
+            })
+            .clone()
+    }
+
+    /// Whether either atlas is over 70% full.
+    pub fn nearly_full(&self) -> bool {
+        self.mask_atlas.usage() > 0.7 || self.color_atlas.usage() > 0.7
     }
 
+    /// Makes room when nearly full. Called once per frame. Atlases shrunk
+    /// by `shrink_atlases` double, up to the size they were made with,
+    /// and everything is evicted to be packed into them again. At that
+    /// size, when only the mask atlas is full, the shelves of glyphs
+    /// least recently used, and not this frame, are evicted until it's
+    /// half full, unless deterministic. Otherwise everything is evicted,
+    /// after telling the `AtlasFullCallback`. There's one page per atlas:
+    /// prims address a single texture, so a full atlas is cleared rather
+    /// than spilling into another page.
     pub fn check_usage(&mut self) {
-        if self.mask_atlas.usage() > 0.7 || self.color_atlas.usage() > 0.7 {
+        if self.grow_atlases() {
+            self.clear();
+        }
+        if !self.deterministic && self.mask_atlas.usage() > 0.7 && self.color_atlas.usage() <= 0.7 {
+            self.evict_shelves(0.5);
+        }
+        if self.nearly_full() {
+            if let Some(on_full) = &mut self.on_full {
+                on_full(self.mask_atlas.usage(), self.color_atlas.usage());
+            }
             self.clear();
         }
+        self.frame += 1;
+    }
+
+    /// Doubles atlases over 70% full which are smaller than they were
+    /// made, returning whether any grew. Their textures are made at the
+    /// next `update`.
+    fn grow_atlases(&mut self) -> bool {
+        let max = self.max_atlas_size;
+        let mut grown = false;
+        for atlas in [&mut self.mask_atlas, &mut self.color_atlas] {
+            if atlas.usage() > 0.7 && atlas.size() < max {
+                let size = (atlas.size() * 2).min(max);
+                atlas.resize(size);
+                grown = true;
+            }
+        }
+        grown
+    }
+
+    /// Notes that a region of the mask atlas was used this frame.
+    fn touch(&mut self, rect: Option<Rect>) {
+        if let Some(rect) = rect {
+            self.shelf_uses.insert(rect.y, self.frame);
+        }
+    }
+
+    fn touch_emoji(&mut self, info: Option<AtlasInfo>) {
+        if let Some(info) = info.filter(|info| !info.colored) {
+            self.touch(info.rect);
+        }
+    }
+
+    /// Evicts mask atlas shelves not used this frame, least recently used
+    /// first, until the atlas is at most `target` full, dropping the
+    /// glyphs cached in them.
+    fn evict_shelves(&mut self, target: f32) {
+        let frame = self.frame;
+        let mut uses: Vec<(u64, i32)> = self
+            .shelf_uses
+            .iter()
+            .filter(|(_, used)| **used < frame)
+            .map(|(y, used)| (*used, *y))
+            .collect();
+        uses.sort_unstable();
+
+        let mut evicted = vec![];
+        for (_, y) in uses {
+            if self.mask_atlas.usage() <= target {
+                break;
+            }
+            if let Some(bounds) = self.mask_atlas.evict_shelf(y) {
+                evicted.push(bounds);
+            }
+        }
+        if evicted.is_empty() {
+            return;
+        }
+
+        let stale = |rect: Option<Rect>| {
+            rect.map_or(false, |rect| evicted.iter().any(|b| overlaps(rect, *b)))
+        };
+        self.info.retain(|_, info| !stale(info.rect));
+        self.indexed_info.retain(|_, info| !stale(info.rect));
+        self.atlas_infos
+            .retain(|_, info| info.colored || !stale(info.rect));
+        let atlas_infos = &self.atlas_infos;
+        self.atlas_images
+            .retain(|key, _| atlas_infos.contains_key(key));
+        for sizes in self.emoji_infos.values_mut() {
+            sizes.retain(|_, info| info.map_or(true, |info| info.colored || !stale(info.rect)));
+        }
+        self.shelf_uses
+            .retain(|y, _| !evicted.iter().any(|b| *y >= b.y && *y < b.y + b.height));
+        self.generation += 1;
+    }
+
+    /// In deterministic mode, where a glyph or image lands in the atlases
+    /// depends only on what was drawn since the mode was enabled, or since
+    /// the atlases were last cleared: enabling clears them, and shelves
+    /// aren't evicted by how recently they were used, only cleared
+    /// together.
+    pub fn set_deterministic(&mut self, enabled: bool) {
+        if enabled && !self.deterministic {
+            self.clear();
+        }
+        self.deterministic = enabled;
+    }
+
+    pub fn set_atlas_full_callback(&mut self, callback: Option<AtlasFullCallback>) {
+        self.on_full = callback;
+    }
+
+    pub fn stats(&self) -> GlyphCacheStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = GlyphCacheStats::default();
+    }
+
+    /// Changes whenever atlas regions may be reused, e.g. when the atlases
+    /// are cleared. Anything holding an atlas region, such as a prim
+    /// recorded earlier, is stale once this differs from when the region
+    /// was handed out.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Changes whenever an atlas texture is replaced, e.g. as the atlases
+    /// grow, so renderers know to bind the new ones.
+    pub fn texture_generation(&self) -> u64 {
+        self.texture_generation
     }
 
     pub fn clear(&mut self) {
+        self.generation += 1;
+        self.shelf_uses.clear();
         self.info.clear();
+        self.indexed_info.clear();
         self.mask_atlas.clear();
         self.color_atlas.clear();
         self.atlas_infos.clear();
+        self.atlas_images.clear();
+        self.svg_infos.clear();
+        self.image_infos.clear();
+        self.layer_rects.clear();
+        self.emoji_infos.clear();
+    }
+
+    /// Writes the glyphs rasterized for `Vger::text`, and those from
+    /// `get_glyph_mask`, so a later run can load them with `read_glyphs`
+    /// instead of rasterizing them again. Glyphs of `Vger::text` are
+    /// rasterized again to be written, so this suits shutdown or a
+    /// background thread rather than the middle of a frame.
+    ///
+    /// Font ids are only meaningful to the font database which made them,
+    /// so `font_key` names each font in a way which lasts between runs,
+    /// e.g. a hash of its family and style. Glyphs of fonts it returns
+    /// `None` for aren't written.
+    pub fn write_glyphs(
+        &self,
+        w: &mut impl Write,
+        font_key: impl Fn(cosmic_text::fontdb::ID) -> Option<u64>,
+    ) -> io::Result<()> {
+        let checksum = content_hash(FONT);
+        w.write_all(GLYPH_FILE_MAGIC)?;
+        w.write_all(&GLYPH_FILE_VERSION.to_le_bytes())?;
+        w.write_all(&(checksum.len() as u32).to_le_bytes())?;
+        w.write_all(&checksum)?;
+        w.write_all(&(self.info.len() as u32).to_le_bytes())?;
+        for &(c, size) in self.info.keys() {
+            let (metrics, data) = self.font.rasterize(c, size as f32 / 65536.0);
+            for v in [c as u32, size, metrics.width as u32, metrics.height as u32] {
+                w.write_all(&v.to_le_bytes())?;
+            }
+            w.write_all(&data)?;
+        }
+
+        let mut images: Vec<_> = self
+            .atlas_images
+            .iter()
+            .filter_map(|(key, image)| Some((font_key(key.0)?, key, image)))
+            .collect();
+        images.sort_unstable_by_key(|(font, key, _)| (*font, key.1, key.2, key.3, key.4));
+        w.write_all(&(images.len() as u32).to_le_bytes())?;
+        for (font, (_, glyph_id, size, scale, (x, y)), image) in images {
+            w.write_all(&font.to_le_bytes())?;
+            let placement = image.placement;
+            for v in [
+                *glyph_id as u32,
+                *size,
+                *scale,
+                *x,
+                *y,
+                (image.content != SwashContent::Mask) as u32,
+                placement.left as u32,
+                placement.top as u32,
+                placement.width,
+                placement.height,
+            ] {
+                w.write_all(&v.to_le_bytes())?;
+            }
+            w.write_all(&image.data)?;
+        }
+        Ok(())
+    }
+
+    /// Adds glyphs written by `write_glyphs` to the atlases, returning how
+    /// many were added. `font_id` finds the font named by a `font_key`
+    /// when writing, and glyphs of fonts it returns `None` for are
+    /// skipped. Fails with `InvalidData` if the file is from another
+    /// built-in font or version of vger.
+    pub fn read_glyphs(
+        &mut self,
+        r: &mut impl Read,
+        font_id: impl Fn(u64) -> Option<cosmic_text::fontdb::ID>,
+    ) -> io::Result<usize> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        let read_u32 = |r: &mut dyn Read| -> io::Result<u32> {
+            let mut bytes = [0; 4];
+            r.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        };
+
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if magic != *GLYPH_FILE_MAGIC || read_u32(r)? != GLYPH_FILE_VERSION {
+            return Err(invalid("not a glyph cache file for this version"));
+        }
+        let checksum = content_hash(FONT);
+        if read_u32(r)? as usize != checksum.len() {
+            return Err(invalid("font checksum mismatch"));
+        }
+        let mut file_checksum = vec![0; checksum.len()];
+        r.read_exact(&mut file_checksum)?;
+        if file_checksum != checksum {
+            return Err(invalid("font checksum mismatch"));
+        }
+
+        let count = read_u32(r)?;
+        let mut added = 0;
+        for _ in 0..count {
+            let c = char::from_u32(read_u32(r)?).ok_or_else(|| invalid("bad glyph"))?;
+            let size = read_u32(r)?;
+            let (width, height) = (read_u32(r)?, read_u32(r)?);
+            if width > MAX_GLYPH_FILE_SIZE || height > MAX_GLYPH_FILE_SIZE {
+                return Err(invalid("bad glyph"));
+            }
+            let mut data = vec![0; (width * height) as usize];
+            r.read_exact(&mut data)?;
+
+            let metrics = self.font.metrics(c, size as f32 / 65536.0);
+            if metrics.width as u32 != width || metrics.height as u32 != height {
+                return Err(invalid("glyph doesn't match font"));
+            }
+            if self.info.contains_key(&(c, size)) {
+                continue;
+            }
+            let rect = self.mask_atlas.add_region(&data, width, height);
+            if rect.is_none() && width > 0 && height > 0 {
+                return Ok(added);
+            }
+            self.touch(rect);
+            self.info.insert((c, size), GlyphInfo { rect, metrics });
+            added += 1;
+        }
+
+        let count = read_u32(r)?;
+        for _ in 0..count {
+            let mut font = [0; 8];
+            r.read_exact(&mut font)?;
+            let font = u64::from_le_bytes(font);
+            let glyph_id = read_u32(r)?;
+            if glyph_id > u16::MAX as u32 {
+                return Err(invalid("bad glyph"));
+            }
+            let (size, scale) = (read_u32(r)?, read_u32(r)?);
+            let subpx = (read_u32(r)?, read_u32(r)?);
+            if subpx.0 > 3 || subpx.1 > 3 {
+                return Err(invalid("bad glyph"));
+            }
+            let colored = match read_u32(r)? {
+                0 => false,
+                1 => true,
+                _ => return Err(invalid("bad glyph")),
+            };
+            let (left, top) = (read_u32(r)? as i32, read_u32(r)? as i32);
+            let (width, height) = (read_u32(r)?, read_u32(r)?);
+            if width > MAX_GLYPH_FILE_SIZE || height > MAX_GLYPH_FILE_SIZE {
+                return Err(invalid("bad glyph"));
+            }
+            let channels = if colored { 4 } else { 1 };
+            let mut data = vec![0; (width * height * channels) as usize];
+            r.read_exact(&mut data)?;
+
+            let key = match font_id(font) {
+                Some(id) => (id, glyph_id as u16, size, scale, subpx),
+                None => continue,
+            };
+            if self.atlas_infos.contains_key(&key) {
+                continue;
+            }
+            let rect = if colored {
+                self.color_atlas.add_region(&data, width, height)
+            } else {
+                self.mask_atlas.add_region(&data, width, height)
+            };
+            if rect.is_none() && width > 0 && height > 0 {
+                break;
+            }
+            if !colored {
+                self.touch(rect);
+            }
+            self.atlas_infos.insert(
+                key,
+                AtlasInfo {
+                    rect,
+                    left,
+                    top,
+                    colored,
+                },
+            );
+
+            let mut image = SwashImage::new();
+            image.content = if colored {
+                SwashContent::Color
+            } else {
+                SwashContent::Mask
+            };
+            image.placement = swash::zeno::Placement {
+                left,
+                top,
+                width,
+                height,
+            };
+            image.data = data;
+            self.atlas_images.insert(key, image);
+            added += 1;
+        }
+        Ok(added)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::atlas::AtlasAllocator;
+
+    fn cache() -> GlyphCache<AtlasAllocator> {
+        GlyphCache::with_atlases(
+            AtlasAllocator::new(AtlasContent::Mask),
+            AtlasAllocator::new(AtlasContent::Color),
+        )
+    }
+
+    #[test]
+    fn test_glyph_keys() {
+        let mut cache = cache();
+
+        let a = cache.get_glyph('a', 12.0);
+        assert_eq!(cache.mask_atlas.pending(), 1);
+
+        // Cached, including sizes which round to the same key.
+        let a2 = cache.get_glyph('a', 12.000001);
+        assert_eq!(a.rect, a2.rect);
+        assert_eq!(cache.mask_atlas.pending(), 1);
+
+        let b = cache.get_glyph('a', 24.0);
+        assert_ne!(a.rect, b.rect);
+        assert_eq!(cache.mask_atlas.pending(), 2);
+        assert_eq!(cache.color_atlas.pending(), 0);
+    }
+
+    #[test]
+    fn test_image_size() {
+        let mut cache = cache();
+        let key = ImageKey::from_hash(&[1]);
+
+        let a = cache.get_image_mask(&key, 4, 4, || vec![0; 64]);
+        let a2 = cache.get_image_mask(&key, 4, 4, || panic!("cached"));
+        assert_eq!(a.rect, a2.rect);
+
+        // Reusing the key at another size doesn't return the old region.
+        let b = cache.get_image_mask(&key, 8, 2, || vec![0; 64]);
+        let rect = b.rect.unwrap();
+        assert_eq!((rect.width, rect.height), (8, 2));
+        assert_eq!(cache.color_atlas.pending(), 2);
+    }
+
+    #[test]
+    fn test_subpixel_binning() {
+        let bins = [
+            SubpixelBin::Zero,
+            SubpixelBin::One,
+            SubpixelBin::Two,
+            SubpixelBin::Three,
+        ];
+        let quantize = |binning: SubpixelBinning| {
+            bins.iter()
+                .map(|bin| binning.quantize(*bin))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(quantize(SubpixelBinning::Off), [SubpixelBin::Zero; 4]);
+        assert_eq!(
+            quantize(SubpixelBinning::Two),
+            [
+                SubpixelBin::Zero,
+                SubpixelBin::Zero,
+                SubpixelBin::Two,
+                SubpixelBin::Two
+            ]
+        );
+        assert_eq!(quantize(SubpixelBinning::Four), bins);
+    }
+
+    #[test]
+    fn test_glyph_file() {
+        let mut cache = cache();
+        let a = cache.get_glyph('a', 12.0);
+        cache.get_glyph('b', 24.0);
+
+        // Glyphs rasterized by the caller, from a font database.
+        let mut fonts = cosmic_text::fontdb::Database::new();
+        fonts.load_font_data(FONT.to_vec());
+        let font_id = fonts.faces().next().unwrap().id;
+        let subpx = (SubpixelBin::Zero, SubpixelBin::Two);
+        let square = |content| {
+            let mut image = SwashImage::new();
+            image.content = content;
+            image.placement = swash::zeno::Placement {
+                left: 1,
+                top: -2,
+                width: 3,
+                height: 3,
+            };
+            let channels = if content == SwashContent::Mask { 1 } else { 4 };
+            image.data = vec![255; 9 * channels];
+            image
+        };
+        cache.get_glyph_mask(font_id, 7, 20, 2.0, subpx, || square(SwashContent::Mask));
+        cache.get_glyph_mask(font_id, 8, 20, 2.0, subpx, || square(SwashContent::Color));
+
+        let mut bytes = vec![];
+        cache.write_glyphs(&mut bytes, |_| Some(42)).unwrap();
+
+        let mut loaded = cache();
+        let read = loaded.read_glyphs(&mut bytes.as_slice(), |key| {
+            assert_eq!(key, 42);
+            Some(font_id)
+        });
+        assert_eq!(read.unwrap(), 4);
+        assert_eq!(loaded.mask_atlas.pending(), 3);
+        assert_eq!(loaded.color_atlas.pending(), 1);
+        let a2 = loaded.get_glyph('a', 12.0);
+        assert_eq!(
+            a2.rect.map(|r| (r.width, r.height)),
+            a.rect.map(|r| (r.width, r.height))
+        );
+        let mask = loaded.get_glyph_mask(font_id, 7, 20, 2.0, subpx, || panic!("cached"));
+        assert_eq!((mask.left, mask.top, mask.colored), (1, -2, false));
+        let color = loaded.get_glyph_mask(font_id, 8, 20, 2.0, subpx, || panic!("cached"));
+        assert!(color.colored);
+        assert_eq!(loaded.mask_atlas.pending(), 3);
+
+        // Glyphs already cached aren't added again.
+        let read = loaded.read_glyphs(&mut bytes.as_slice(), |_| Some(font_id));
+        assert_eq!(read.unwrap(), 0);
+
+        // Glyphs of fonts which can't be found are skipped.
+        let read = cache().read_glyphs(&mut bytes.as_slice(), |_| None);
+        assert_eq!(read.unwrap(), 2);
+
+        // Files for another font are rejected.
+        let mut other = bytes.clone();
+        other[12] ^= 1;
+        let err = cache()
+            .read_glyphs(&mut other.as_slice(), |_| None)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(cache()
+            .read_glyphs(&mut &bytes[..bytes.len() - 1], |_| None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_emoji() {
+        let mut cache = cache();
+        cache.set_emoji_fonts(vec![]);
+        assert!(cache.get_emoji('😀', 32.0).is_none());
+        assert_eq!(cache.color_atlas.pending(), 0);
+    }
+
+    #[test]
+    fn test_images() {
+        let mut cache = cache();
+        let key = ImageKey::from_hash(b"image");
+        let mut count = 0;
+
+        for _ in 0..2 {
+            cache.get_image_mask(&key, 4, 4, || {
+                count += 1;
+                vec![0; 64]
+            });
+        }
+        assert_eq!(count, 1);
+
+        cache.remove_image(&key);
+        cache.get_image_mask(&key, 4, 4, || {
+            count += 1;
+            vec![0; 64]
+        });
+        assert_eq!(count, 2);
+        assert_eq!(cache.color_atlas.pending(), 2);
+
+        // Its space is freed, for packers which reuse it.
+        let mut color = AtlasAllocator::new(AtlasContent::Color);
+        color.set_packing(Packing::Shelves);
+        let mut cache = GlyphCache::with_atlases(AtlasAllocator::new(AtlasContent::Mask), color);
+        cache.get_image_mask(&key, 4, 4, || vec![0; 64]);
+        assert!(cache.color_atlas.usage() > 0.0);
+        cache.remove_image(&key);
+        assert_eq!(cache.color_atlas.usage(), 0.0);
+    }
+
+    #[test]
+    fn test_layers() {
+        let mut cache = cache();
+        let key = LayerKey::Backdrop(1);
+        assert!(cache.get_layer(key, 10, 10).is_none());
+
+        let rect = cache.add_layer(key, 10, 10).unwrap();
+        assert_eq!(cache.get_layer(key, 10, 10), Some(rect));
+        assert!(cache.get_layer(LayerKey::External(1), 10, 10).is_none());
+        assert_eq!(cache.color_atlas.pending(), 0);
+
+        // Resized layers are rendered again.
+        assert!(cache.get_layer(key, 20, 10).is_none());
+
+        cache.remove_layer(key);
+        assert!(cache.get_layer(key, 10, 10).is_none());
+
+        cache.clear();
+        assert!(cache.get_layer(key, 10, 10).is_none());
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut cache = cache();
+        let key = ImageKey::from_hash(b"image");
+        for _ in 0..3 {
+            cache.get_image_mask(&key, 4, 4, || vec![0; 64]);
+        }
+        cache.get_glyph('a', 12.0);
+        cache.get_glyph('a', 12.0);
+        cache.get_glyph('a', 13.0);
+
+        let stats = cache.stats();
+        assert_eq!((stats.image_hits, stats.image_misses), (2, 1));
+        assert_eq!((stats.glyph_hits, stats.glyph_misses), (1, 2));
+        assert_eq!((stats.svg_hits, stats.svg_misses), (0, 0));
+
+        cache.reset_stats();
+        assert_eq!(cache.stats(), GlyphCacheStats::default());
+    }
+
+    #[test]
+    fn test_check_usage() {
+        let mut cache = cache();
+        let data = vec![0; 1000 * 1000 * 4];
+
+        let first = ImageKey::from_hash(b"first");
+        cache.get_image_mask(&first, 1000, 1000, || data.clone());
+        cache.check_usage();
+        assert_eq!(cache.color_atlas.pending(), 1);
+
+        for i in 0..11u8 {
+            let key = ImageKey::from_hash(&[i]);
+            cache.get_image_mask(&key, 1000, 1000, || data.clone());
+        }
+        assert!(cache.color_atlas.usage() > 0.7);
+
+        // Over 70% full, so everything is dropped.
+        let calls = Arc::new(Mutex::new(vec![]));
+        let log = calls.clone();
+        cache.set_atlas_full_callback(Some(Box::new(move |mask, color| {
+            log.lock().unwrap().push((mask, color));
+        })));
+        cache.check_usage();
+        assert_eq!(calls.lock().unwrap().len(), 1);
+        assert!(calls.lock().unwrap()[0].1 > 0.7);
+        assert_eq!(cache.color_atlas.pending(), 0);
+        assert!(cache.color_atlas.did_clear());
+        let mut count = 0;
+        cache.get_image_mask(&first, 1000, 1000, || {
+            count += 1;
+            data.clone()
+        });
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_grow_atlases() {
+        let mut cache = cache();
+        cache.color_atlas.resize(Atlas::MIN_SIZE);
+        let key = ImageKey::from_hash(b"image");
+        cache.get_image_mask(&key, 220, 220, || vec![0; 220 * 220 * 4]);
+        assert!(cache.color_atlas.usage() > 0.7);
+
+        // Shrunk atlases grow rather than telling the callback.
+        let calls = Arc::new(Mutex::new(0));
+        let log = calls.clone();
+        cache.set_atlas_full_callback(Some(Box::new(move |_, _| {
+            *log.lock().unwrap() += 1;
+        })));
+        cache.check_usage();
+        assert_eq!(cache.color_atlas.size(), 2 * Atlas::MIN_SIZE);
+        assert_eq!(cache.mask_atlas.size(), Atlas::ATLAS_SIZE);
+        assert_eq!(*calls.lock().unwrap(), 0);
+        assert_eq!(cache.generation(), 1);
+        assert!(cache.image_infos.is_empty());
+    }
+
+    #[test]
+    fn test_evict_shelves() {
+        let mut mask = AtlasAllocator::with_size(AtlasContent::Mask, 512);
+        mask.set_packing(Packing::Shelves);
+        let mut cache = GlyphCache::with_atlases(mask, AtlasAllocator::new(AtlasContent::Color));
+
+        // Capitals taller than the small glyphs' shelves, so they don't
+        // share them.
+        let mut fill = (40..=56)
+            .rev()
+            .flat_map(|size| ('A'..='Z').map(move |c| (c, size as f32)));
+        let mut fill_to = |cache: &mut GlyphCache<AtlasAllocator>, usage: f32| {
+            while cache.mask_atlas.usage() <= usage {
+                let (c, size) = fill.next().unwrap();
+                cache.get_glyph(c, size);
+            }
+        };
+
+        // A frame drawing a glyph which isn't drawn again.
+        let old = cache.get_glyph('a', 12.0);
+        fill_to(&mut cache, 0.6);
+        cache.check_usage();
+        assert_eq!(cache.generation(), 0);
+
+        // The next frame fills the atlas.
+        let used = cache.get_glyph('b', 12.0);
+        fill_to(&mut cache, 0.7);
+        cache.check_usage();
+
+        // The last frame's shelves were evicted rather than everything.
+        assert!(cache.mask_atlas.usage() <= 0.5);
+        assert!(!cache.mask_atlas.did_clear());
+        assert_eq!(cache.generation(), 1);
+        assert!(!cache.info.contains_key(&('a', 12 * 65536)));
+        assert!(old.rect.is_some());
+
+        cache.reset_stats();
+        assert_eq!(cache.get_glyph('b', 12.0).rect, used.rect);
+        assert_eq!(cache.stats().glyph_hits, 1);
+        cache.get_glyph('a', 12.0);
+        assert_eq!(cache.stats().glyph_misses, 1);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let pack = |earlier: &[char]| {
+            let mut mask = AtlasAllocator::with_size(AtlasContent::Mask, 512);
+            mask.set_packing(Packing::Shelves);
+            let mut cache =
+                GlyphCache::with_atlases(mask, AtlasAllocator::new(AtlasContent::Color));
+            for c in earlier {
+                cache.get_glyph(*c, 40.0);
+            }
+            cache.check_usage();
+            cache.set_deterministic(true);
+            ('a'..='z')
+                .map(|c| cache.get_glyph(c, 20.0).rect)
+                .collect::<Vec<_>>()
+        };
+
+        // Enabling forgets where earlier glyphs were packed.
+        assert_eq!(pack(&[]), pack(&['X', 'Y', 'Z']));
+
+        // Nearly full, everything is cleared rather than the least recently
+        // used shelves.
+        let fill_twice = |deterministic: bool| {
+            let mut mask = AtlasAllocator::with_size(AtlasContent::Mask, 512);
+            mask.set_packing(Packing::Shelves);
+            let mut cache =
+                GlyphCache::with_atlases(mask, AtlasAllocator::new(AtlasContent::Color));
+            cache.set_deterministic(deterministic);
+            let mut fill = (40..=56)
+                .rev()
+                .flat_map(|size| ('A'..='Z').map(move |c| (c, size as f32)));
+            for usage in [0.6, 0.7] {
+                while cache.mask_atlas.usage() <= usage {
+                    let (c, size) = fill.next().unwrap();
+                    cache.get_glyph(c, size);
+                }
+                cache.check_usage();
+            }
+            cache.info.len()
+        };
+        assert_eq!(fill_twice(true), 0);
+        assert!(fill_twice(false) > 0);
     }
 }
@@ -6,6 +6,7 @@ pub struct GPUVec<T: Copy> {
     capacity: usize,
     data: Vec<T>,
     label: String,
+    usage: BufferUsages,
 }
 
 impl<T: Copy> GPUVec<T> {
@@ -22,6 +23,7 @@ impl<T: Copy> GPUVec<T> {
             capacity,
             data: vec![],
             label: label.into(),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
         }
     }
 
@@ -38,14 +40,36 @@ impl<T: Copy> GPUVec<T> {
             capacity: 1,
             data: vec![],
             label: label.into(),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         }
     }
 
-    /// Updates the underlying gpu buffer with self.data.
+    /// Fixed size array in a uniform buffer, for shaders declaring an
+    /// array of `capacity` elements.
+    pub fn new_uniform_array(device: &wgpu::Device, capacity: usize, label: &str) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (size_of::<T>() * capacity) as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            buffer,
+            capacity,
+            data: vec![],
+            label: label.into(),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        }
+    }
+
+    /// Updates the underlying gpu buffer with self.data. Returns whether
+    /// the buffer was reallocated, in which case bind groups must be
+    /// recreated.
     ///
     /// We'd like to write directly to the mapped buffer, but that seemed
     /// tricky with wgpu.
-    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> bool {
         let mut realloc = false;
         while self.data.len() > self.capacity {
             self.capacity *= 2;
@@ -53,18 +77,46 @@ impl<T: Copy> GPUVec<T> {
         }
 
         if realloc {
-            self.buffer = device.create_buffer(&wgpu::BufferDescriptor {
-                label: Some(self.label.as_str()),
-                size: (size_of::<T>() * self.capacity) as u64,
-                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            });
+            self.realloc(device);
         }
 
         let sz = self.data.len() * size_of::<T>();
         queue.write_buffer(&self.buffer, 0, unsafe {
             std::slice::from_raw_parts_mut(self.data[..].as_ptr() as *mut u8, sz)
-        })
+        });
+
+        realloc
+    }
+
+    fn realloc(&mut self, device: &wgpu::Device) {
+        self.buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(self.label.as_str()),
+            size: (size_of::<T>() * self.capacity) as u64,
+            usage: self.usage,
+            mapped_at_creation: false,
+        });
+    }
+
+    /// Shrinks the gpu buffer to fit the current data, but not below
+    /// `min_capacity`, and releases spare cpu memory. Returns whether the
+    /// buffer was reallocated. The buffer's contents are lost until the
+    /// next `update`.
+    pub fn shrink(&mut self, device: &wgpu::Device, min_capacity: usize) -> bool {
+        self.data.shrink_to_fit();
+
+        let capacity = self.data.len().next_power_of_two().max(min_capacity);
+        if capacity < self.capacity {
+            self.capacity = capacity;
+            self.realloc(device);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Bytes held by the gpu buffer and the cpu copy.
+    pub fn memory_usage(&self) -> usize {
+        (self.capacity + self.data.capacity()) * size_of::<T>()
     }
 
     pub fn bind_group_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
@@ -80,6 +132,19 @@ impl<T: Copy> GPUVec<T> {
         }
     }
 
+    pub fn uniform_bind_group_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
     pub fn bind_group_entry(&self, binding: u32) -> wgpu::BindGroupEntry {
         wgpu::BindGroupEntry {
             binding,
@@ -106,4 +171,22 @@ impl<T: Copy> GPUVec<T> {
     pub fn len(&self) -> usize {
         self.data.len()
     }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.data.get(index)
+    }
+}
+
+impl<T: Copy> std::ops::Index<usize> for GPUVec<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.data[index]
+    }
+}
+
+impl<T: Copy> std::ops::IndexMut<usize> for GPUVec<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        &mut self.data[index]
+    }
 }
@@ -0,0 +1,86 @@
+/// Stable hash of some content bytes.
+///
+/// Uses blake3 when the `blake3` feature is enabled, otherwise 64-bit FNV-1a.
+/// Either way the result only depends on the bytes, so it can be persisted.
+pub fn content_hash(data: &[u8]) -> Vec<u8> {
+    #[cfg(feature = "blake3")]
+    {
+        blake3::hash(data).as_bytes().to_vec()
+    }
+
+    #[cfg(not(feature = "blake3"))]
+    {
+        fnv1a(data).to_le_bytes().to_vec()
+    }
+}
+
+pub(crate) fn fnv1a(data: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for b in data {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h
+}
+
+/// Identifies an RGBA image in the color atlas.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct ImageKey(Vec<u8>);
+
+impl ImageKey {
+    /// Key from a hash the caller already has.
+    pub fn from_hash(hash: &[u8]) -> Self {
+        Self(hash.to_vec())
+    }
+
+    /// Key computed from the image bytes.
+    pub fn from_content(data: &[u8]) -> Self {
+        Self(content_hash(data))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Identifies an SVG document in the color atlas. The same key can be
+/// rasterized at several sizes.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct SvgKey(Vec<u8>);
+
+impl SvgKey {
+    /// Key from a hash the caller already has.
+    pub fn from_hash(hash: &[u8]) -> Self {
+        Self(hash.to_vec())
+    }
+
+    /// Key computed from the SVG source.
+    pub fn from_content(data: &[u8]) -> Self {
+        Self(content_hash(data))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(content_hash(b"vger"), content_hash(b"vger"));
+        assert_ne!(content_hash(b"vger"), content_hash(b"vgr"));
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_keys() {
+        let key = SvgKey::from_content(b"<svg/>");
+        assert_eq!(key, SvgKey::from_hash(&content_hash(b"<svg/>")));
+        assert_eq!(ImageKey::from_hash(&[1, 2, 3]).as_bytes(), &[1, 2, 3]);
+    }
+}
@@ -0,0 +1,311 @@
+//! Helpers for drawing apps: smoothing raw pointer samples into strokes.
+
+use crate::defs::*;
+use crate::{PaintIndex, Vger};
+
+/// One-euro filter for a single value. Smooths jitter when the input moves
+/// slowly while keeping lag low when it moves quickly.
+///
+/// See https://gery.casiez.net/1euro/
+#[derive(Clone, Debug)]
+pub struct OneEuroFilter {
+    /// Minimum cutoff frequency (Hz). Lower values smooth more at low speeds.
+    pub min_cutoff: f32,
+
+    /// Speed coefficient. Higher values reduce lag at high speeds.
+    pub beta: f32,
+
+    /// Cutoff frequency for the derivative (Hz).
+    pub d_cutoff: f32,
+
+    prev: Option<(f32, f32)>,
+}
+
+impl OneEuroFilter {
+    pub fn new(min_cutoff: f32, beta: f32) -> Self {
+        Self {
+            min_cutoff,
+            beta,
+            d_cutoff: 1.0,
+            prev: None,
+        }
+    }
+
+    fn alpha(cutoff: f32, dt: f32) -> f32 {
+        let tau = 1.0 / (2.0 * std::f32::consts::PI * cutoff);
+        1.0 / (1.0 + tau / dt)
+    }
+
+    /// Filters a value sampled `dt` seconds after the previous one.
+    pub fn filter(&mut self, x: f32, dt: f32) -> f32 {
+        let (prev_x, prev_dx) = match self.prev {
+            Some(prev) if dt > 0.0 => prev,
+            Some((prev_x, _)) => return prev_x,
+            None => {
+                self.prev = Some((x, 0.0));
+                return x;
+            }
+        };
+
+        let dx = (x - prev_x) / dt;
+        let a_d = Self::alpha(self.d_cutoff, dt);
+        let edx = prev_dx + a_d * (dx - prev_dx);
+
+        let cutoff = self.min_cutoff + self.beta * edx.abs();
+        let a = Self::alpha(cutoff, dt);
+        let x_hat = prev_x + a * (x - prev_x);
+
+        self.prev = Some((x_hat, edx));
+        x_hat
+    }
+
+    pub fn reset(&mut self) {
+        self.prev = None;
+    }
+}
+
+/// Evaluates a uniform Catmull-Rom spline between `p1` and `p2`.
+pub fn catmull_rom(
+    p0: LocalPoint,
+    p1: LocalPoint,
+    p2: LocalPoint,
+    p3: LocalPoint,
+    t: f32,
+) -> LocalPoint {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let (p0, p1, p2, p3) = (
+        p0.to_vector(),
+        p1.to_vector(),
+        p2.to_vector(),
+        p3.to_vector(),
+    );
+    ((p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5)
+        .to_point()
+}
+
+/// A stroke built incrementally from pointer samples.
+///
+/// Samples are smoothed with a one-euro filter and interpolated with a
+/// Catmull-Rom spline. A spline segment only becomes final once the sample
+/// after it arrives, so each new sample tessellates exactly one segment and
+/// earlier geometry never moves while the stroke is in progress.
+pub struct InkStroke {
+    /// Stroke width at full pressure.
+    pub width: f32,
+
+    /// Number of points generated per spline segment.
+    pub subdivisions: usize,
+
+    filter_x: OneEuroFilter,
+    filter_y: OneEuroFilter,
+    last_time: Option<f64>,
+
+    /// Smoothed samples: position, width and time.
+    samples: Vec<(LocalPoint, f32, f64)>,
+
+    /// Final tessellated points with widths.
+    points: Vec<(LocalPoint, f32)>,
+}
+
+impl InkStroke {
+    pub fn new(width: f32) -> Self {
+        Self {
+            width,
+            subdivisions: 8,
+            filter_x: OneEuroFilter::new(1.0, 0.01),
+            filter_y: OneEuroFilter::new(1.0, 0.01),
+            last_time: None,
+            samples: vec![],
+            points: vec![],
+        }
+    }
+
+    /// Replaces the smoothing filter parameters.
+    pub fn set_smoothing(&mut self, min_cutoff: f32, beta: f32) {
+        self.filter_x = OneEuroFilter::new(min_cutoff, beta);
+        self.filter_y = OneEuroFilter::new(min_cutoff, beta);
+    }
+
+    /// Adds a raw pointer sample. `time` is in seconds and `pressure` is
+    /// from 0 to 1 (use 1 for devices without pressure).
+    pub fn add_sample(&mut self, position: LocalPoint, pressure: f32, time: f64) {
+        let dt = match self.last_time {
+            Some(last) => (time - last) as f32,
+            None => 0.0,
+        };
+        self.last_time = Some(time);
+
+        let p = LocalPoint::new(
+            self.filter_x.filter(position.x, dt),
+            self.filter_y.filter(position.y, dt),
+        );
+
+        if let Some((last, _, _)) = self.samples.last() {
+            if *last == p {
+                return;
+            }
+        }
+
+        self.samples.push((p, self.width * pressure, time));
+
+        // The segment ending at the previous sample is now final.
+        let n = self.samples.len();
+        if n >= 3 {
+            self.tessellate(n - 3);
+        }
+    }
+
+    /// Tessellates the segment from sample j to sample j + 1.
+    fn tessellate(&mut self, j: usize) {
+        let (p0, _, _) = self.samples[j.saturating_sub(1)];
+        let (p1, w1, _) = self.samples[j];
+        let (p2, w2, _) = self.samples[j + 1];
+        let (p3, _, _) = self.samples[j + 2];
+
+        if self.points.is_empty() {
+            self.points.push((p1, w1));
+        }
+
+        for k in 1..=self.subdivisions {
+            let t = k as f32 / self.subdivisions as f32;
+            let p = catmull_rom(p0, p1, p2, p3, t);
+            self.points.push((p, w1 + (w2 - w1) * t));
+        }
+    }
+
+    /// Points of the tentative tail: the part of the stroke which may
+    /// still change as more samples arrive.
+    fn tail(&self) -> Vec<(LocalPoint, f32)> {
+        let n = self.samples.len();
+        let mut tail = vec![];
+        if n == 0 {
+            return tail;
+        }
+
+        let start = if n >= 2 { n - 2 } else { 0 };
+        let p0 = self.samples[start.saturating_sub(1)].0;
+        let (p1, w1, _) = self.samples[start];
+        let (p2, w2, _) = self.samples[n - 1];
+
+        tail.push((p1, w1));
+        if n >= 2 {
+            for k in 1..=self.subdivisions {
+                let t = k as f32 / self.subdivisions as f32;
+                tail.push((catmull_rom(p0, p1, p2, p2, t), w1 + (w2 - w1) * t));
+            }
+        }
+        tail
+    }
+
+    /// Final points generated so far. Only grows while the stroke is in
+    /// progress.
+    pub fn points(&self) -> &[(LocalPoint, f32)] {
+        &self.points
+    }
+
+    /// Predicts where the pointer will be `ahead` seconds after the last
+    /// sample by extrapolating the recent velocity.
+    pub fn predict(&self, ahead: f32) -> Option<LocalPoint> {
+        let n = self.samples.len();
+        if n < 2 {
+            return self.samples.last().map(|s| s.0);
+        }
+        let (a, _, ta) = self.samples[n - 2];
+        let (b, _, tb) = self.samples[n - 1];
+        let dt = (tb - ta) as f32;
+        if dt <= 0.0 {
+            return Some(b);
+        }
+        Some(b + (b - a) * (ahead / dt))
+    }
+
+    /// Finalizes the stroke once the pointer is released.
+    pub fn finish(&mut self) {
+        let tail = self.tail();
+        let skip = if self.points.is_empty() { 0 } else { 1 };
+        self.points.extend(tail.into_iter().skip(skip));
+        self.samples.clear();
+        self.last_time = None;
+        self.filter_x.reset();
+        self.filter_y.reset();
+    }
+
+    /// Draws the stroke, including the tentative tail and an optional
+    /// predicted point.
+    pub fn draw(&self, vger: &mut Vger, predict: Option<f32>, paint_index: PaintIndex) {
+        let mut points = self.points.clone();
+        let tail = self.tail();
+        let skip = if points.is_empty() { 0 } else { 1 };
+        points.extend(tail.into_iter().skip(skip));
+
+        if let (Some(ahead), Some(&(last, w))) = (predict, points.last()) {
+            if let Some(p) = self.predict(ahead) {
+                if p != last {
+                    points.push((p, w));
+                }
+            }
+        }
+
+        if points.len() == 1 {
+            let (p, w) = points[0];
+            vger.fill_circle(p, 0.5 * w, paint_index);
+        } else {
+            vger.stroke_polyline_var(&points, paint_index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_one_euro() {
+        let mut f = OneEuroFilter::new(1.0, 0.0);
+        assert_eq!(f.filter(1.0, 0.0), 1.0);
+
+        // Constant input stays constant.
+        assert_eq!(f.filter(1.0, 0.01), 1.0);
+
+        // A step is smoothed, but moves towards the target.
+        let x = f.filter(2.0, 0.01);
+        assert!(x > 1.0 && x < 2.0);
+    }
+
+    #[test]
+    fn test_catmull_rom() {
+        let p = |x: f32| LocalPoint::new(x, 0.0);
+        assert_eq!(catmull_rom(p(0.0), p(1.0), p(2.0), p(3.0), 0.0), p(1.0));
+        assert_eq!(catmull_rom(p(0.0), p(1.0), p(2.0), p(3.0), 0.5), p(1.5));
+        assert_eq!(catmull_rom(p(0.0), p(1.0), p(2.0), p(3.0), 1.0), p(2.0));
+    }
+
+    #[test]
+    fn test_stable_points() {
+        let mut stroke = InkStroke::new(4.0);
+        stroke.set_smoothing(1000.0, 0.0);
+
+        for i in 0..3 {
+            stroke.add_sample(LocalPoint::new(i as f32 * 10.0, 0.0), 1.0, i as f64 * 0.01);
+        }
+        let before = stroke.points().to_vec();
+        assert!(!before.is_empty());
+
+        stroke.add_sample(LocalPoint::new(30.0, 10.0), 1.0, 0.03);
+        assert!(stroke.points().len() > before.len());
+        assert_eq!(&stroke.points()[..before.len()], &before[..]);
+
+        let p = stroke.predict(0.01).unwrap();
+        assert!(p.x > 30.0);
+
+        stroke.finish();
+        let (last, _) = *stroke.points().last().unwrap();
+        assert!((last - stroke.points()[0].0).length() > 30.0);
+    }
+}
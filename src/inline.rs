@@ -0,0 +1,82 @@
+//! Boxes reserved in text for icons, images and other content the caller
+//! draws. See `Vger::text_with_objects`.
+
+/// Marks where an inline object goes in text.
+pub const OBJECT_REPLACEMENT: char = '\u{FFFC}';
+
+/// A box text flows around, in local coordinates.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct InlineObject {
+    pub width: f32,
+    pub height: f32,
+
+    /// How far the bottom of the box is above the baseline. Negative
+    /// values drop it below, like a descender.
+    pub baseline_shift: f32,
+}
+
+impl InlineObject {
+    pub fn new(width: f32, height: f32, baseline_shift: f32) -> Self {
+        Self {
+            width,
+            height,
+            baseline_shift,
+        }
+    }
+
+    /// Extent above the baseline.
+    pub(crate) fn ascent(&self) -> f32 {
+        (self.height + self.baseline_shift).max(0.0)
+    }
+
+    /// Extent below the baseline.
+    pub(crate) fn descent(&self) -> f32 {
+        (-self.baseline_shift).max(0.0)
+    }
+}
+
+/// Horizontal offsets making room for objects in a laid out line of
+/// `chars`, where the first `widths.len()` object replacement characters
+/// are `widths` wide and were laid out `placeholder` wide. Also returns
+/// how much wider the line became.
+pub(crate) fn object_offsets(chars: &[char], widths: &[f32], placeholder: f32) -> (Vec<f32>, f32) {
+    let mut offsets = Vec::with_capacity(chars.len());
+    let mut widths = widths.iter();
+    let mut shift = 0.0;
+    for c in chars {
+        offsets.push(shift);
+        if *c == OBJECT_REPLACEMENT {
+            if let Some(width) = widths.next() {
+                shift += width - placeholder;
+            }
+        }
+    }
+    (offsets, shift)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_object_offsets() {
+        let chars: Vec<char> = "a\u{FFFC}b\u{FFFC}c\u{FFFC}".chars().collect();
+        let (offsets, extra) = object_offsets(&chars, &[10.0, 4.0], 2.0);
+        assert_eq!(offsets, [0.0, 0.0, 8.0, 8.0, 10.0, 10.0]);
+        assert_eq!(extra, 10.0);
+
+        assert_eq!(object_offsets(&['a'], &[], 2.0), (vec![0.0], 0.0));
+    }
+
+    #[test]
+    fn test_extents() {
+        let icon = InlineObject::new(16.0, 16.0, -4.0);
+        assert_eq!(icon.ascent(), 12.0);
+        assert_eq!(icon.descent(), 4.0);
+
+        let raised = InlineObject::new(16.0, 10.0, 6.0);
+        assert_eq!(raised.ascent(), 16.0);
+        assert_eq!(raised.descent(), 0.0);
+    }
+}
@@ -1,8 +1,12 @@
 use cosmic_text::{SubpixelBin, SwashImage};
-use fontdue::layout::{CoordinateSystem, Layout, LayoutSettings, TextStyle};
+use fontdue::layout::{
+    CoordinateSystem, GlyphPosition, HorizontalAlign, Layout, LayoutSettings, TextStyle,
+};
 
-mod path;
-use path::*;
+#[macro_use]
+mod profile;
+
+use vger_core::path::*;
 
 mod scene;
 use scene::*;
@@ -10,30 +14,193 @@ use scene::*;
 mod prim;
 use prim::*;
 
-pub mod defs;
 use defs::*;
+pub use vger_core::defs;
 
 mod paint;
 use paint::*;
+pub use paint::{Noise, NoiseKind, PatternSpace, SpreadMode};
 
 mod gpu_vec;
 use gpu_vec::*;
 
-pub mod color;
+mod arena;
+use arena::FrameArena;
+
+mod decimate;
+use decimate::*;
+
+mod validation;
+use validation::validate_prim;
+pub use validation::ValidationError;
+
 pub use color::Color;
+pub use vger_core::color;
 
 pub mod atlas;
+use atlas::AtlasRegions;
+
+pub mod shelf;
+
+pub mod hash;
+pub use hash::{ImageKey, SvgKey};
+
+pub mod colormap;
+pub use colormap::Colormap;
 
 mod glyphs;
-use glyphs::GlyphCache;
+pub use glyphs::{AtlasFullCallback, GlyphCacheStats, SharedGlyphCache, SubpixelBinning};
+use glyphs::{AtlasInfo, GlyphCache, LayerKey};
+
+pub mod theme;
+pub use theme::Theme;
+
+pub mod ink;
+
+pub mod markers;
+use markers::*;
+
+pub mod chart;
+use chart::*;
+
+#[cfg(feature = "numbers")]
+pub mod numbers;
+#[cfg(feature = "numbers")]
+pub use numbers::NumberFormat;
+
+pub mod camera;
+pub use camera::Camera64;
+
+pub mod tiles;
+
+pub mod blit;
+
+pub mod surface;
+
+pub mod texture_pool;
+pub use texture_pool::TexturePool;
+
+#[cfg(feature = "bevy")]
+pub mod bevy_plugin;
+
+pub mod border;
+use border::nine_slice;
+pub use border::BorderImage;
+
+pub mod morph;
+pub use morph::Morph;
+
+pub mod table;
+use table::table_bars;
+pub use table::TableBorder;
+
+pub mod emoji;
+pub use emoji::EmojiFont;
+
+mod wrap;
+#[cfg(feature = "hyphenate")]
+pub use hyphenation;
+
+pub mod inline;
+use inline::object_offsets;
+pub use inline::{InlineObject, OBJECT_REPLACEMENT};
+
+pub mod ruby;
+pub use ruby::Ruby;
+use ruby::{can_overhang, place_ruby, source_chars};
+
+pub mod features;
+pub use features::FontFeature;
+use features::{cluster_glyphs, feature_runs, source_offsets};
+
+mod outline;
+
+pub mod cluster;
+use cluster::grapheme_ranges;
+pub use cluster::GlyphCluster;
+
+pub mod align;
+use align::{adjusted_size, justify_offsets};
+pub use align::{LineHeight, TextAlign, TextLayout};
+
+pub mod whitespace;
+pub use whitespace::WhitespaceMarks;
+use whitespace::{line_marks, Mark};
+
+pub mod line_cache;
+pub use line_cache::LineLayoutCache;
+use line_cache::{LineGlyph, LineKey, LineRun};
+
+pub mod external;
+pub use external::ExternalImage;
+use external::ExternalTextures;
+
+pub mod video;
+use video::VideoConverter;
+pub use video::{VideoFrame, VideoPlanes, YuvMatrix};
+
+pub mod access;
+use access::AccessRecorder;
+pub use access::{AccessInfo, AccessNode};
+
+pub mod connectors;
+use connectors::*;
+pub use connectors::{Connector, ConnectorRoute};
+
+pub mod pick;
+use pick::PickIndex;
+pub use pick::PickTag;
+
+pub mod capture;
+pub use capture::{Capture, CaptureError, ImageBuffer, PixelRead};
+
+pub use display_list::{DisplayList, DrawCommand};
+pub use vger_core::display_list;
+pub use vger_core::wire;
+
+pub mod remote;
+pub use remote::{ClientId, SceneServer};
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+/// Fails to compile if a type loses its thread safety.
+#[allow(dead_code)]
+fn assert_send_sync<T: Send + Sync>() {}
+
+// Types handed between threads. Gpu handles are only `Send` and `Sync` on
+// native targets.
+const _: fn() = || {
+    assert_send_sync::<Color>();
+    assert_send_sync::<PaintIndex>();
+    assert_send_sync::<Theme>();
+    assert_send_sync::<Camera64>();
+    assert_send_sync::<ImageBuffer>();
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        assert_send_sync::<Vger>();
+        assert_send_sync::<SharedGlyphCache>();
+        assert_send_sync::<Capture>();
+    }
+};
 
 #[allow(dead_code)]
 #[derive(Copy, Clone, Debug)]
 struct Uniforms {
     size: [f32; 2],
+
+    /// Seconds, for animated paints.
+    time: f32,
+
+    /// 1 when the color atlas is sampled through an sRGB view.
+    srgb_atlas: f32,
+
+    /// Sizes of the mask and color atlases, to normalize atlas
+    /// coordinates.
+    atlas_size: [f32; 2],
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct PaintIndex {
     index: usize,
 }
@@ -45,6 +212,273 @@ pub struct LineMetrics {
     pub bounds: LocalRect,
 }
 
+/// Shadow drawn behind text, as in CSS `text-shadow`.
+#[derive(Copy, Clone, Debug)]
+pub struct TextShadow {
+    pub offset: LocalVector,
+
+    /// Blur radius. The shadow is a gaussian blur of the glyphs with a
+    /// standard deviation of half the radius.
+    pub blur: f32,
+
+    pub color: Color,
+}
+
+/// Look of a label drawn with `Vger::draw_label_scrim`.
+#[derive(Copy, Clone, Debug)]
+pub struct LabelScrim {
+    pub text_size: u32,
+    pub text_color: Color,
+    pub max_width: Option<f32>,
+
+    /// Space between the text and the edge of the scrim.
+    pub padding: f32,
+
+    /// Corner radius of the scrim.
+    pub radius: f32,
+
+    /// Blur radius of the backdrop, with a standard deviation of half the
+    /// radius.
+    pub blur: f32,
+
+    /// Drawn over the blurred backdrop, usually translucent.
+    pub tint: Color,
+}
+
+impl Default for LabelScrim {
+    fn default() -> Self {
+        Self {
+            text_size: 14,
+            text_color: Color::WHITE,
+            max_width: None,
+            padding: 6.0,
+            radius: 6.0,
+            blur: 8.0,
+            tint: Color::new(0.0, 0.0, 0.0, 0.4),
+        }
+    }
+}
+
+/// How images from the color atlas are filtered. The atlas has no mip
+/// levels, so anything beyond bilinear filtering is done in the shader.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageSampling {
+    /// One bilinear sample. Fastest, but shimmers when minified or
+    /// strongly rotated.
+    Linear,
+
+    /// Catmull-Rom bicubic filtering, for sharper magnified photos.
+    Bicubic,
+
+    /// Averages up to 16 bilinear samples over each pixel's footprint in
+    /// the image, which stops shimmer under rotation, perspective and
+    /// minification, as for map tiles.
+    Anisotropic,
+}
+
+impl ImageSampling {
+    /// `Prim::flags` asking the shader for this sampling.
+    fn flags(self) -> u32 {
+        match self {
+            ImageSampling::Linear => 0,
+            ImageSampling::Bicubic => IMAGE_BICUBIC,
+            ImageSampling::Anisotropic => IMAGE_FOOTPRINT,
+        }
+    }
+}
+
+/// An emoji drawn where the layout placed a glyph the font lacks. `ymin`
+/// is the placed glyph's offset from the baseline, in device pixels.
+fn emoji_prim(
+    glyph: &fontdue::layout::GlyphPosition,
+    ymin: i32,
+    emoji: &AtlasInfo,
+    rect: rect_packer::Rect,
+    scale: f32,
+) -> Prim {
+    let mut prim = Prim::default();
+    prim.prim_type = if emoji.colored {
+        PrimType::ColorGlyph
+    } else {
+        PrimType::Glyph
+    } as u32;
+
+    // Layout y runs up from the baseline.
+    let x = glyph.x + emoji.left as f32;
+    let top = glyph.y - ymin as f32 + emoji.top as f32;
+    prim.quad_bounds = [
+        x / scale,
+        (top - rect.height as f32) / scale,
+        (x + rect.width as f32) / scale,
+        top / scale,
+    ];
+    prim.tex_bounds = [
+        rect.x as f32,
+        (rect.y + rect.height) as f32,
+        (rect.x + rect.width) as f32,
+        rect.y as f32,
+    ];
+    prim
+}
+
+/// Origin in device pixels of glyph `id` of `font` at `px` pixels, which
+/// layout put at `(x, y)`. Layout positions the glyph's bounds, not its
+/// origin.
+fn outline_origin(font: &fontdue::Font, id: u16, x: f32, y: f32, px: f32) -> LocalPoint {
+    let metrics = font.metrics_indexed(id, px);
+    LocalPoint::new(x - metrics.xmin as f32, y - metrics.ymin as f32)
+}
+
+/// How `line_prims` draws a line: the font size in device pixels, the
+/// device pixel ratio, and the prim whose paint and scissor glyphs get.
+struct LineStyle<'a> {
+    scaled_size: f32,
+    scale: f32,
+    base: &'a Prim,
+}
+
+/// Where `line_prims` adds glyphs. With `outlines`, glyphs of the built-in
+/// font go there instead of `prims`, with their origins in device pixels,
+/// for `Vger::fill_outlines`.
+struct LineOutput<'a> {
+    prims: &'a mut Vec<Prim>,
+    outlines: Option<&'a mut Vec<(u16, LocalPoint)>>,
+}
+
+/// Appends glyph prims for a cached line, offset by `offset` in local
+/// space.
+fn line_prims(
+    glyph_cache: &mut GlyphCache,
+    run: &LineRun,
+    style: &LineStyle,
+    offset: LocalVector,
+    out: &mut LineOutput,
+) {
+    let LineStyle {
+        scaled_size,
+        scale,
+        base,
+    } = *style;
+    for glyph in &run.glyphs {
+        if let Some(outlines) = out.outlines.as_mut() {
+            let id = glyph
+                .glyph_id
+                .unwrap_or_else(|| glyph_cache.font.lookup_glyph_index(glyph.c));
+            if id != 0 {
+                let origin = outline_origin(&glyph_cache.font, id, glyph.x, glyph.y, scaled_size);
+                outlines.push((id, origin + offset * scale));
+                continue;
+            }
+        }
+        let info = match glyph.glyph_id {
+            Some(id) => glyph_cache.get_glyph_indexed(id, scaled_size),
+            None => glyph_cache.get_glyph(glyph.c, scaled_size),
+        };
+        if let Some(rect) = info.rect {
+            let mut prim = *base;
+            prim.quad_bounds = [
+                glyph.x / scale,
+                glyph.y / scale,
+                (glyph.x + rect.width as f32) / scale,
+                (glyph.y + rect.height as f32) / scale,
+            ];
+            prim.tex_bounds = [
+                rect.x as f32,
+                (rect.y + rect.height) as f32,
+                (rect.x + rect.width) as f32,
+                rect.y as f32,
+            ];
+            prim.offset_quad(offset);
+            out.prims.push(prim);
+        }
+    }
+}
+
+/// Shadow of a glyph prim, sampling the glyph's atlas `rect` with the
+/// quad grown to fit the blur.
+fn glyph_shadow(
+    glyph: &Prim,
+    rect: rect_packer::Rect,
+    shadow: &TextShadow,
+    paint: u32,
+    scale: f32,
+) -> Prim {
+    let mut prim = *glyph;
+    prim.prim_type = PrimType::GlyphShadow as u32;
+    prim.paint = paint;
+
+    let blur = shadow.blur.max(0.0);
+    let (dx, dy) = (shadow.offset.x, shadow.offset.y);
+    let q = glyph.quad_bounds;
+    prim.quad_bounds = [
+        q[0] + dx - blur,
+        q[1] + dy - blur,
+        q[2] + dx + blur,
+        q[3] + dy + blur,
+    ];
+
+    // Texture y runs opposite to the quad's.
+    let t = glyph.tex_bounds;
+    let b = blur * scale;
+    prim.tex_bounds = [t[0] - b, t[1] + b, t[2] + b, t[3] - b];
+
+    prim.radius = b;
+    prim.cvs = [
+        rect.x as f32,
+        rect.y as f32,
+        (rect.x + rect.width) as f32,
+        (rect.y + rect.height) as f32,
+        0.0,
+        0.0,
+    ];
+    prim
+}
+
+/// Arc prim without paint or scissor.
+fn arc_prim(c: LocalPoint, radius: f32, width: f32, rotation: f32, aperture: f32) -> Prim {
+    let mut prim = Prim::default();
+    prim.prim_type = PrimType::Arc as u32;
+    prim.radius = radius;
+    prim.cvs = [
+        c.x,
+        c.y,
+        rotation.sin(),
+        rotation.cos(),
+        aperture.sin(),
+        aperture.cos(),
+    ];
+    prim.width = width;
+    prim
+}
+
+/// Center line and corner radius of a focus ring stroke.
+fn focus_ring(rect: LocalRect, radius: f32, width: f32, offset: f32) -> (LocalRect, f32) {
+    let grow = offset.max(0.0) + width / 2.0;
+    let ring = rect.inflate(grow, grow);
+    let max_radius = ring.width().min(ring.height()) / 2.0;
+    (ring, (radius.max(0.0) + grow).min(max_radius))
+}
+
+/// A glyph for `Vger::render_glyph_at_scale`: where its origin goes, and
+/// which glyph of which font at what size and subpixel offset.
+struct GlyphPlacement {
+    x: f32,
+    y: f32,
+    font_id: cosmic_text::fontdb::ID,
+    glyph_id: u16,
+    size: u32,
+    subpx: (SubpixelBin, SubpixelBin),
+}
+
+/// Views `Vger::uniform_bind_group` binds: the mask atlas, a view in
+/// place of the color atlas, and one for layer prims.
+#[derive(Copy, Clone)]
+struct AtlasBindings<'a> {
+    mask: &'a wgpu::TextureView,
+    color: &'a wgpu::TextureView,
+    layer: &'a wgpu::TextureView,
+}
+
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
 pub(crate) struct Scissor {
@@ -63,30 +497,267 @@ impl Scissor {
     }
 }
 
+/// Drawing state saved and restored by `save` and `restore`.
+#[derive(Copy, Clone)]
+struct State {
+    transform: LocalToWorld,
+    scissor: Scissor,
+    z_index: i32,
+    access_node: Option<AccessNode>,
+    pick_tag: Option<PickTag>,
+    image_sampling: ImageSampling,
+    image_corner_radius: f32,
+    blend_mode: BlendMode,
+    text_layout: TextLayout,
+}
+
+impl State {
+    fn new() -> Self {
+        Self {
+            transform: LocalToWorld::identity(),
+            scissor: Scissor::new(),
+            z_index: 0,
+            access_node: None,
+            pick_tag: None,
+            image_sampling: ImageSampling::Linear,
+            image_corner_radius: 0.0,
+            blend_mode: BlendMode::Normal,
+            text_layout: TextLayout::default(),
+        }
+    }
+}
+
+/// What a renderer writes to its target.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Colors, blended over the target.
+    Color,
+
+    /// Colors premultiplied by alpha, with alpha accumulated correctly,
+    /// for transparent windows and overlays. Configure the surface with
+    /// `CompositeAlphaMode::PreMultiplied` and clear to transparent.
+    Premultiplied,
+
+    /// Coverage only, in every channel, for generating masks. Use with a
+    /// single channel format such as `R8Unorm`.
+    Coverage,
+
+    /// Premultiplied colors converted from sRGB to linear, for a float
+    /// target such as `Rgba16Float`. Composite the result over a linear
+    /// HDR scene with `blit::Blitter`.
+    Linear,
+}
+
+/// How drawing is combined with what's under it. See
+/// `Vger::set_blend_mode`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Composited over the target by alpha.
+    Normal,
+
+    /// Added to the target, scaled by alpha, so overlapping drawing
+    /// accumulates brightness, saturating at white in unorm targets. For
+    /// glowing particles, visualizers and heatmap dots.
+    Additive,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::Normal
+    }
+}
+
+/// Options for `Vger::encode_to_view`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RenderOptions {
+    /// Color to clear the target to before drawing. With `None`, vger
+    /// draws over the target's existing content, e.g. a 3D scene rendered
+    /// by the host.
+    pub clear: Option<Color>,
+}
+
+/// How well transforms were shared this frame, from
+/// `Vger::transform_stats`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TransformStats {
+    /// Transforms looked up for drawing.
+    pub requested: usize,
+
+    /// Distinct transforms written to the transform buffer.
+    pub stored: usize,
+}
+
+/// Restores the state saved by `Vger::save_guard` when dropped. Draw
+/// through the guard while it's alive.
+pub struct SaveGuard<'a> {
+    vger: &'a mut Vger,
+}
+
+impl<'a> std::ops::Deref for SaveGuard<'a> {
+    type Target = Vger;
+
+    fn deref(&self) -> &Vger {
+        self.vger
+    }
+}
+
+impl<'a> std::ops::DerefMut for SaveGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Vger {
+        self.vger
+    }
+}
+
+impl<'a> Drop for SaveGuard<'a> {
+    fn drop(&mut self) {
+        self.vger.restore();
+    }
+}
+
+/// `Vger` owns the device-side state and is used from one thread at a
+/// time, but can be moved to a render thread. Recording that happens
+/// elsewhere goes into a `DisplayList`. On the web, gpu handles are not
+/// `Send`, so neither is `Vger`.
 pub struct Vger {
-    scenes: [Scene; 3],
+    scenes: [Scene; 4],
     cur_scene: usize,
     cur_layer: usize,
-    cur_z_index: i32,
-    tx_stack: Vec<LocalToWorld>,
-    scissor_stack: Vec<Scissor>,
+    state_stack: Vec<State>,
     device_px_ratio: f32,
     screen_size: ScreenSize,
     paint_count: usize,
     pipeline: wgpu::RenderPipeline,
+    /// Whether to draw frames with `draw_indirect`.
+    indirect: bool,
+    /// `pipeline` with prims in a uniform buffer, for small frames.
+    small_pipeline: wgpu::RenderPipeline,
+    /// Whether to pack frame prims at half size. See `set_packed_prims`.
+    packed: bool,
+    /// `pipeline` with prims unpacked from `Scene::packed_prims`.
+    packed_pipeline: wgpu::RenderPipeline,
+    /// `pipeline`, `small_pipeline` and `packed_pipeline` with additive
+    /// blending, for `BlendMode::Additive`.
+    additive_pipeline: wgpu::RenderPipeline,
+    additive_small_pipeline: wgpu::RenderPipeline,
+    additive_packed_pipeline: wgpu::RenderPipeline,
+    layer_pipeline: wgpu::RenderPipeline,
+    textures: TexturePool,
+    /// Sizes of the cached layers kept in `textures`, by key.
+    layer_sizes: std::collections::HashMap<u64, (u32, u32)>,
+    external_textures: ExternalTextures,
+    /// Created when a video is first drawn.
+    video_converter: Option<VideoConverter>,
+    /// Seconds, for animated paints. See `set_time`.
+    time: f32,
+    /// Whether the color atlas is sampled through an sRGB view, for
+    /// `OutputMode::Linear`.
+    srgb_atlas: bool,
+    /// Whether the overlay was drawn since it was last prepared.
+    overlay_dirty: bool,
+    /// Glyph cache generation the overlay was drawn in. Its atlas regions
+    /// are stale once the generation changes.
+    overlay_generation: u64,
+    /// Glyph cache generation the frame's atlas regions were looked up in.
+    /// See `upload_frame`.
+    frame_generation: u64,
+    /// Glyph cache texture generation `uniform_bind_group` binds.
+    atlas_textures: u64,
     uniform_bind_group: wgpu::BindGroup,
+    /// What `uniform_bind_group` is made of, for the bind groups of cached
+    /// layers, which bind a layer's texture in place of the color atlas.
+    uniform_bind_group_layout: wgpu::BindGroupLayout,
+    mask_texture_view: wgpu::TextureView,
+    glyph_sampler: wgpu::Sampler,
+    color_glyph_sampler: wgpu::Sampler,
     uniforms: GPUVec<Uniforms>,
     xform_count: usize,
     scissor_count: usize,
     path_scanner: PathScanner,
     pen: LocalPoint,
-    pub glyph_cache: GlyphCache,
+    /// Glyphs, images and layers, possibly shared with other renderers.
+    glyph_cache: SharedGlyphCache,
     layout: Layout,
+    /// Glyphs from `layout`, aligned.
+    glyphs: Vec<GlyphPosition>,
+    /// Characters of `glyphs`, including any hyphens inserted.
+    chars: Vec<char>,
+    /// Baselines of the lines of `layout`, after paragraph spacing.
+    baselines: Vec<f32>,
+    /// Objects replacing `OBJECT_REPLACEMENT` characters in the text
+    /// being laid out, and where the layout put them.
+    inline_objects: Vec<InlineObject>,
+    object_rects: Vec<LocalRect>,
+    /// OpenType features shaping applies to text, and the glyphs it picked
+    /// by character, `None` for characters merged into a ligature. Empty
+    /// when text isn't shaped.
+    font_features: Vec<FontFeature>,
+    glyph_ids: Vec<Option<u16>>,
+    shape_context: swash::shape::ShapeContext,
+    /// Ranges of `glyphs` drawn as one emoji, such as ZWJ sequences.
+    emoji_sequences: Vec<std::ops::Range<usize>>,
+    #[cfg(feature = "hyphenate")]
+    hyphenator: Option<hyphenation::Standard>,
+    /// Layouts for `text_line`, kept between frames.
+    line_cache: LineLayoutCache,
+    /// Whether text baselines are snapped to device pixels. See
+    /// `set_baseline_snapping`.
+    snap_baselines: bool,
+    subpixel_binning: SubpixelBinning,
+    /// Device pixel size from which glyphs are drawn as paths. See
+    /// `set_outline_threshold`.
+    outline_threshold: Option<f32>,
+    theme: Theme,
+    decimate_polylines: bool,
+    camera: Camera64,
+    validation: bool,
+    validation_errors: Vec<ValidationError>,
+
+    /// Per-frame scratch memory, reset in `begin`.
+    arena: FrameArena,
+    memory_budget: Option<usize>,
+    /// Whether buffers were trimmed since usage was last well under the
+    /// budget. See `check_budget`.
+    budget_trimmed: bool,
+    access: AccessRecorder,
+    picks: PickIndex,
+    /// Whether prims are drawn one at a time. See `set_split_draws`.
+    split_draws: bool,
+    /// Whether a cached layer is being recorded. See `record_layer`.
+    recording_layer: bool,
+    /// Open debug groups, outermost first.
+    debug_groups: Vec<String>,
+    /// Scene and index of the label for `debug_groups`, once drawn under.
+    debug_label: Option<(usize, u32)>,
 }
 
 impl Vger {
+    /// Default size from which glyphs are drawn as paths, in device pixels.
+    pub const OUTLINE_THRESHOLD: f32 = 256.0;
+
     /// Create a new renderer given a device and output pixel format.
     pub fn new(device: &wgpu::Device, texture_format: wgpu::TextureFormat) -> Self {
+        Self::new_with_mode(device, texture_format, OutputMode::Color)
+    }
+
+    /// Create a new renderer writing either colors or coverage.
+    pub fn new_with_mode(
+        device: &wgpu::Device,
+        texture_format: wgpu::TextureFormat,
+        mode: OutputMode,
+    ) -> Self {
+        Self::with_glyph_cache(device, texture_format, mode, SharedGlyphCache::new(device))
+    }
+
+    /// Create a renderer using a glyph cache shared with other renderers
+    /// on `device`, e.g. one per window, from `shared_glyph_cache()`.
+    /// Renderers sharing a cache should each finish a frame, from `begin`
+    /// to `encode`, before another starts, since the cache may be cleared
+    /// when a frame is prepared.
+    pub fn with_glyph_cache(
+        device: &wgpu::Device,
+        texture_format: wgpu::TextureFormat,
+        mode: OutputMode,
+        glyph_cache: SharedGlyphCache,
+    ) -> Self {
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: None,
             source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
@@ -94,14 +765,19 @@ impl Vger {
             ))),
         });
 
-        let scenes = [Scene::new(device), Scene::new(device), Scene::new(device)];
+        let scenes = [
+            Scene::new(device),
+            Scene::new(device),
+            Scene::new(device),
+            Scene::new(device),
+        ];
 
         let uniform_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
                     wgpu::BindGroupLayoutEntry {
                         binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Uniform,
                             has_dynamic_offset: false,
@@ -141,14 +817,28 @@ impl Vger {
                         ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
                 ],
                 label: Some("uniform_bind_group_layout"),
             });
 
-        let glyph_cache = GlyphCache::new(device);
-
-        let mask_texture_view = glyph_cache.mask_atlas.create_view();
-        let color_texture_view = glyph_cache.color_atlas.create_view();
+        // Linear output wants images filtered in linear space, which an
+        // sRGB view of the color atlas does in hardware.
+        let srgb_atlas = mode == OutputMode::Linear;
+        let (mask_texture_view, color_texture_view, layer_texture_view, atlas_textures) = {
+            let glyph_cache = glyph_cache.lock();
+            let (mask, color, layer) = Self::atlas_views(&glyph_cache, srgb_atlas);
+            (mask, color, layer, glyph_cache.texture_generation())
+        };
 
         let uniforms = GPUVec::new_uniforms(device, "uniforms");
 
@@ -168,60 +858,281 @@ impl Vger {
             ..Default::default()
         });
 
-        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &uniform_bind_group_layout,
+        let uniform_bind_group = Self::uniform_bind_group(
+            device,
+            &uniform_bind_group_layout,
+            &uniforms,
+            AtlasBindings {
+                mask: &mask_texture_view,
+                color: &color_texture_view,
+                layer: &layer_texture_view,
+            },
+            &glyph_sampler,
+            &color_glyph_sampler,
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[
+                &Scene::bind_group_layout(device),
+                &uniform_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline =
+            |layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, blend| {
+                Self::create_pipeline(device, layout, shader, texture_format, mode, blend)
+            };
+        let pipeline = create_pipeline(&pipeline_layout, &shader, BlendMode::Normal);
+        let additive_pipeline = create_pipeline(&pipeline_layout, &shader, BlendMode::Additive);
+
+        let small_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("small scene"),
+            source: wgpu::ShaderSource::Wgsl(small_scene_shader().into()),
+        });
+        let small_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    &Scene::small_bind_group_layout(device),
+                    &uniform_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let small_pipeline =
+            create_pipeline(&small_pipeline_layout, &small_shader, BlendMode::Normal);
+        let additive_small_pipeline =
+            create_pipeline(&small_pipeline_layout, &small_shader, BlendMode::Additive);
+
+        let packed_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("packed scene"),
+            source: wgpu::ShaderSource::Wgsl(packed_scene_shader().into()),
+        });
+        let packed_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    &Scene::packed_bind_group_layout(device),
+                    &uniform_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let packed_pipeline =
+            create_pipeline(&packed_pipeline_layout, &packed_shader, BlendMode::Normal);
+        let additive_packed_pipeline =
+            create_pipeline(&packed_pipeline_layout, &packed_shader, BlendMode::Additive);
+
+        // Layers are cached premultiplied, in the color atlas's format.
+        let layer_pipeline = Self::create_pipeline(
+            device,
+            &pipeline_layout,
+            &shader,
+            wgpu::TextureFormat::Rgba8Unorm,
+            OutputMode::Premultiplied,
+            BlendMode::Normal,
+        );
+
+        let layout = Layout::new(CoordinateSystem::PositiveYUp);
+
+        Self {
+            scenes,
+            cur_scene: 0,
+            cur_layer: 0,
+            state_stack: vec![],
+            device_px_ratio: 1.0,
+            screen_size: ScreenSize::new(512.0, 512.0),
+            paint_count: 0,
+            pipeline,
+            small_pipeline,
+            packed: false,
+            packed_pipeline,
+            additive_pipeline,
+            additive_small_pipeline,
+            additive_packed_pipeline,
+            indirect: false,
+            layer_pipeline,
+            textures: TexturePool::new(texture_pool::DEFAULT_BUDGET),
+            layer_sizes: std::collections::HashMap::new(),
+            external_textures: ExternalTextures::default(),
+            video_converter: None,
+            time: 0.0,
+            srgb_atlas,
+            overlay_dirty: false,
+            overlay_generation: 0,
+            frame_generation: 0,
+            atlas_textures,
+            uniforms,
+            uniform_bind_group,
+            uniform_bind_group_layout,
+            mask_texture_view,
+            glyph_sampler,
+            color_glyph_sampler,
+            xform_count: 0,
+            scissor_count: 0,
+            path_scanner: PathScanner::new(),
+            pen: LocalPoint::zero(),
+            glyph_cache,
+            layout,
+            glyphs: vec![],
+            chars: vec![],
+            baselines: vec![],
+            inline_objects: vec![],
+            object_rects: vec![],
+            font_features: vec![],
+            glyph_ids: vec![],
+            shape_context: swash::shape::ShapeContext::new(),
+            emoji_sequences: vec![],
+            #[cfg(feature = "hyphenate")]
+            hyphenator: None,
+            line_cache: LineLayoutCache::new(),
+            snap_baselines: false,
+            subpixel_binning: SubpixelBinning::default(),
+            outline_threshold: Some(Self::OUTLINE_THRESHOLD),
+            theme: Theme::new(),
+            decimate_polylines: false,
+            camera: Camera64::default(),
+            validation: false,
+            validation_errors: vec![],
+            arena: FrameArena::default(),
+            memory_budget: None,
+            budget_trimmed: false,
+            access: AccessRecorder::default(),
+            picks: PickIndex::default(),
+            split_draws: false,
+            recording_layer: false,
+            debug_groups: vec![],
+            debug_label: None,
+        }
+    }
+
+    /// Views of the mask atlas, the color atlas, sRGB decoded when
+    /// `srgb_atlas`, and the color atlas for layers, which hold colors as
+    /// rendered so are read without decoding.
+    fn atlas_views(
+        glyph_cache: &GlyphCache,
+        srgb_atlas: bool,
+    ) -> (wgpu::TextureView, wgpu::TextureView, wgpu::TextureView) {
+        let color_view = if srgb_atlas {
+            glyph_cache.color_atlas.create_srgb_view()
+        } else {
+            glyph_cache.color_atlas.create_view()
+        };
+        (
+            glyph_cache.mask_atlas.create_view(),
+            color_view,
+            glyph_cache.color_atlas.create_view(),
+        )
+    }
+
+    /// Makes `uniform_bind_group` again if the atlas textures were
+    /// replaced since it was made, e.g. by `trim` or as the atlases grow.
+    fn bind_atlases(&mut self, device: &wgpu::Device) {
+        let glyph_cache = self.glyph_cache.lock();
+        if glyph_cache.texture_generation() == self.atlas_textures {
+            return;
+        }
+        self.atlas_textures = glyph_cache.texture_generation();
+        let (mask_view, color_view, layer_view) = Self::atlas_views(&glyph_cache, self.srgb_atlas);
+        self.uniform_bind_group = Self::uniform_bind_group(
+            device,
+            &self.uniform_bind_group_layout,
+            &self.uniforms,
+            AtlasBindings {
+                mask: &mask_view,
+                color: &color_view,
+                layer: &layer_view,
+            },
+            &self.glyph_sampler,
+            &self.color_glyph_sampler,
+        );
+        self.mask_texture_view = mask_view;
+    }
+
+    /// Binds the uniforms, the atlas views and the samplers.
+    fn uniform_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniforms: &GPUVec<Uniforms>,
+        atlases: AtlasBindings,
+        glyph_sampler: &wgpu::Sampler,
+        color_glyph_sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
             entries: &[
                 uniforms.bind_group_entry(0),
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::TextureView(&mask_texture_view),
+                    resource: wgpu::BindingResource::TextureView(atlases.mask),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&color_texture_view),
+                    resource: wgpu::BindingResource::TextureView(atlases.color),
                 },
                 wgpu::BindGroupEntry {
                     binding: 3,
-                    resource: wgpu::BindingResource::Sampler(&glyph_sampler),
+                    resource: wgpu::BindingResource::Sampler(glyph_sampler),
                 },
                 wgpu::BindGroupEntry {
                     binding: 4,
-                    resource: wgpu::BindingResource::Sampler(&color_glyph_sampler),
+                    resource: wgpu::BindingResource::Sampler(color_glyph_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(atlases.layer),
                 },
             ],
             label: Some("vger bind group"),
-        });
+        })
+    }
 
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[
-                &Scene::bind_group_layout(device),
-                &uniform_bind_group_layout,
-            ],
-            push_constant_ranges: &[],
-        });
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        texture_format: wgpu::TextureFormat,
+        mode: OutputMode,
+        blend: BlendMode,
+    ) -> wgpu::RenderPipeline {
+        // Other modes output premultiplied colors.
+        let (entry_point, src_factor) = match mode {
+            OutputMode::Color => ("fs_main", wgpu::BlendFactor::SrcAlpha),
+            OutputMode::Premultiplied => ("fs_premultiplied", wgpu::BlendFactor::One),
+            OutputMode::Coverage => ("fs_mask", wgpu::BlendFactor::One),
+            OutputMode::Linear => ("fs_linear", wgpu::BlendFactor::One),
+        };
 
         let blend_comp = wgpu::BlendComponent {
             operation: wgpu::BlendOperation::Add,
-            src_factor: wgpu::BlendFactor::SrcAlpha,
+            src_factor,
             dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
         };
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        // Additive drawing leaves the target's alpha composited as usual.
+        let color_comp = match blend {
+            BlendMode::Normal => blend_comp,
+            BlendMode::Additive => wgpu::BlendComponent {
+                dst_factor: wgpu::BlendFactor::One,
+                ..blend_comp
+            },
+        };
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: None,
-            layout: Some(&pipeline_layout),
+            layout: Some(layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: "vs_main",
                 buffers: &[],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
+                module: shader,
+                entry_point,
                 targets: &[Some(wgpu::ColorTargetState {
                     format: texture_format,
                     blend: Some(wgpu::BlendState {
-                        color: blend_comp,
+                        color: color_comp,
                         alpha: blend_comp,
                     }),
                     write_mask: wgpu::ColorWrites::ALL,
@@ -235,365 +1146,2279 @@ impl Vger {
             depth_stencil: None,
             multisample: wgpu::MultisampleState::default(),
             multiview: None,
-        });
-
-        let layout = Layout::new(CoordinateSystem::PositiveYUp);
-
-        Self {
-            scenes,
-            cur_scene: 0,
-            cur_layer: 0,
-            cur_z_index: 0,
-            tx_stack: vec![],
-            scissor_stack: vec![],
-            device_px_ratio: 1.0,
-            screen_size: ScreenSize::new(512.0, 512.0),
-            paint_count: 0,
-            pipeline,
-            uniforms,
-            uniform_bind_group,
-            xform_count: 0,
-            scissor_count: 0,
-            path_scanner: PathScanner::new(),
-            pen: LocalPoint::zero(),
-            glyph_cache,
-            layout,
-        }
-    }
+        })
+    }
 
     /// Begin rendering.
     pub fn begin(&mut self, window_width: f32, window_height: f32, device_px_ratio: f32) {
         self.device_px_ratio = device_px_ratio;
         self.cur_layer = 0;
         self.screen_size = ScreenSize::new(window_width, window_height);
+        let atlas_size = self.atlas_size();
         self.uniforms.clear();
         self.uniforms.push(Uniforms {
             size: [window_width, window_height],
+            time: self.time,
+            srgb_atlas: if self.srgb_atlas { 1.0 } else { 0.0 },
+            atlas_size,
         });
         self.cur_scene = (self.cur_scene + 1) % 3;
         self.scenes[self.cur_scene].clear();
-        self.tx_stack.clear();
-        self.tx_stack.push(LocalToWorld::identity());
-        self.scissor_stack.clear();
-        self.scissor_stack.push(Scissor::new());
+        {
+            let mut glyph_cache = self.glyph_cache.lock();
+            glyph_cache.reset_stats();
+            self.frame_generation = glyph_cache.generation();
+        }
+        self.state_stack.clear();
+        self.state_stack.push(State::new());
         self.paint_count = 0;
         self.xform_count = 0;
         self.add_xform();
         self.scissor_count = 0;
         self.pen = LocalPoint::zero();
+        self.arena.reset();
+        self.access.clear();
+        self.picks.clear();
+        self.debug_groups.clear();
+        self.debug_label = None;
+        self.line_cache.next_frame();
     }
 
-    /// Saves rendering state (transform and scissor rect).
-    pub fn save(&mut self) {
-        self.tx_stack.push(*self.tx_stack.last().unwrap());
-        self.scissor_stack.push(*self.scissor_stack.last().unwrap());
+    /// Sizes of the mask and color atlases, which change as they grow.
+    fn atlas_size(&self) -> [f32; 2] {
+        let glyph_cache = self.glyph_cache.lock();
+        [
+            glyph_cache.mask_atlas.size() as f32,
+            glyph_cache.color_atlas.size() as f32,
+        ]
     }
 
-    /// Restores rendering state (transform and scissor rect).
-    pub fn restore(&mut self) {
-        self.tx_stack.pop();
-        self.scissor_stack.pop();
+    fn state(&self) -> &State {
+        self.state_stack.last().unwrap()
     }
 
-    /// Encode all rendering to a command buffer.
-    pub fn encode(
-        &mut self,
-        device: &wgpu::Device,
-        render_pass: &wgpu::RenderPassDescriptor,
-        queue: &wgpu::Queue,
-    ) {
-        self.scenes[self.cur_scene].update(device, queue);
-        self.uniforms.update(device, queue);
+    /// Saves rendering state (transform, scissor rect, z index,
+    /// accessibility node, pick tag, image sampling and text layout).
+    pub fn save(&mut self) {
+        self.state_stack.push(*self.state_stack.last().unwrap());
+    }
 
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("vger encoder"),
-        });
+    /// Saves rendering state, restoring it when the returned guard is
+    /// dropped.
+    pub fn save_guard(&mut self) -> SaveGuard<'_> {
+        self.save();
+        SaveGuard { vger: self }
+    }
 
-        self.glyph_cache.update(device, &mut encoder);
+    /// Restores rendering state (transform, scissor rect, z index,
+    /// accessibility node, pick tag, image sampling and text layout).
+    pub fn restore(&mut self) {
+        if self.validation && self.state_stack.len() <= 1 {
+            self.validation_errors
+                .push(ValidationError::UnmatchedRestore);
+            return;
+        }
+        self.state_stack.pop();
+    }
 
-        {
-            let mut rpass = encoder.begin_render_pass(render_pass);
+    /// Enables validation mode. Draw calls with NaNs, negative sizes or
+    /// out of range radii are dropped, and unbalanced `save`/`restore`
+    /// calls are caught. Problems are collected for
+    /// `take_validation_errors`.
+    pub fn set_validation(&mut self, enabled: bool) {
+        self.validation = enabled;
+    }
 
-            rpass.set_pipeline(&self.pipeline);
+    /// Makes output depend only on what's drawn from now on, so headless
+    /// renders are byte identical across runs. Enabling clears the glyph
+    /// cache, so glyphs, images and layers are packed into the atlases in
+    /// drawing order rather than wherever earlier frames left room, and
+    /// while enabled a full atlas is cleared rather than having its least
+    /// recently used shelves evicted. The mode is the shared glyph cache's,
+    /// so it applies to every renderer sharing it. Off by default.
+    pub fn set_deterministic(&mut self, enabled: bool) {
+        self.glyph_cache.lock().set_deterministic(enabled);
+    }
 
-            rpass.set_bind_group(
-                0,
-                &self.scenes[self.cur_scene].bind_groups[self.cur_layer],
-                &[], // dynamic offsets
-            );
+    /// Snaps the baselines of text to device pixel rows, so text scrolled
+    /// slowly by fractions of a pixel doesn't shimmer vertically. Glyphs
+    /// keep their subpixel horizontal positions. Off by default.
+    pub fn set_baseline_snapping(&mut self, enabled: bool) {
+        self.snap_baselines = enabled;
+    }
 
-            rpass.set_bind_group(1, &self.uniform_bind_group, &[]);
+    /// Sets how finely `render_glyph` and `render_scaled_glyph` keep
+    /// subpixel positions, `Four` bins per axis by default. Constantly
+    /// animating labels can use fewer, so each glyph is rasterized and
+    /// uploaded fewer times. Text drawn with `text`, `text_line` and
+    /// `draw_texts` isn't binned: its glyphs are rasterized once per size
+    /// and placed at fractional positions when sampled, so moving it
+    /// never rasterizes them again.
+    pub fn set_subpixel_binning(&mut self, binning: SubpixelBinning) {
+        self.subpixel_binning = binning;
+    }
 
-            let n = self.scenes[self.cur_scene].prims[self.cur_layer].len();
-            // println!("encoding {:?} prims", n);
+    /// Sets the device pixel size from which text is drawn as filled paths
+    /// from cached glyph outlines rather than from the atlas, so zoomed
+    /// text doesn't fill the atlas with huge glyphs. `OUTLINE_THRESHOLD`
+    /// by default. `None` always uses the atlas. Shadows of outlined
+    /// glyphs are filled from the outlines too, so they aren't blurred.
+    pub fn set_outline_threshold(&mut self, size: Option<f32>) {
+        self.outline_threshold = size;
+    }
 
-            rpass.draw(/*vertices*/ 0..4, /*instances*/ 0..(n as u32))
-        }
-        queue.submit(Some(encoder.finish()));
+    /// Sets the pixels left between images in the mask or color atlas,
+    /// 6 by default. Less packs small glyphs tighter, more suits blurred
+    /// or distance field images. With `extrude`, images are surrounded by
+    /// copies of their edge pixels so linear filtering doesn't bleed in
+    /// their neighbours. Clears the glyph cache.
+    pub fn set_atlas_padding(&mut self, content: atlas::AtlasContent, padding: u32, extrude: bool) {
+        let mut glyph_cache = self.glyph_cache.lock();
+        let atlas = match content {
+            atlas::AtlasContent::Mask => &mut glyph_cache.mask_atlas,
+            atlas::AtlasContent::Color => &mut glyph_cache.color_atlas,
+        };
+        atlas.set_padding(padding, extrude);
+        glyph_cache.clear();
+    }
 
-        // If we're getting close to full, reset the glyph cache.
-        self.glyph_cache.check_usage();
+    /// Returns and clears the problems found by validation mode.
+    pub fn take_validation_errors(&mut self) -> Vec<ValidationError> {
+        std::mem::take(&mut self.validation_errors)
     }
 
-    fn render(&mut self, prim: Prim) {
-        let prims = self.scenes[self.cur_scene]
-            .depthed_prims
-            .entry(self.cur_z_index)
-            .or_default();
-        prims.push(prim);
+    fn validate(&mut self, prim: &Prim) -> bool {
+        if !self.validation {
+            return true;
+        }
+        match validate_prim(prim) {
+            Ok(()) => true,
+            Err(err) => {
+                self.validation_errors.push(err);
+                false
+            }
+        }
     }
 
-    /// Fills a circle.
-    pub fn fill_circle<Pt: Into<LocalPoint>>(
-        &mut self,
-        center: Pt,
-        radius: f32,
-        paint_index: PaintIndex,
-    ) {
-        let mut prim = Prim::default();
-        prim.prim_type = PrimType::Circle as u32;
-        let c: LocalPoint = center.into();
-        prim.cvs[0] = c.x;
-        prim.cvs[1] = c.y;
-        prim.radius = radius;
-        prim.paint = paint_index.index as u32;
-        prim.quad_bounds = [c.x - radius, c.y - radius, c.x + radius, c.y + radius];
-        prim.tex_bounds = prim.quad_bounds;
-        prim.scissor = self.add_scissor() as u32;
+    /// Device pixels per local unit, as passed to `begin`.
+    pub fn device_px_ratio(&self) -> f32 {
+        self.device_px_ratio
+    }
 
-        self.render(prim);
+    /// Enables min-max decimation of polylines with many more points than
+    /// device pixels across, to keep dense time-series interactive. Points
+    /// are bucketed by device pixel along the local x axis, so zoomed in
+    /// series keep their detail.
+    pub fn set_polyline_decimation(&mut self, enabled: bool) {
+        self.decimate_polylines = enabled;
     }
 
-    /// Strokes an arc.
-    pub fn stroke_arc<Pt: Into<LocalPoint>>(
-        &mut self,
-        center: Pt,
-        radius: f32,
-        width: f32,
-        rotation: f32,
-        aperture: f32,
-        paint_index: PaintIndex,
-    ) {
-        let mut prim = Prim::default();
-        prim.prim_type = PrimType::Arc as u32;
-        prim.radius = radius;
-        let c: LocalPoint = center.into();
-        prim.cvs = [
-            c.x,
-            c.y,
-            rotation.sin(),
-            rotation.cos(),
-            aperture.sin(),
-            aperture.cos(),
-        ];
-        prim.width = width;
-        prim.paint = paint_index.index as u32;
-        prim.quad_bounds = [
-            c.x - radius - width,
-            c.y - radius - width,
-            c.x + radius + width,
-            c.y + radius + width,
-        ];
-        prim.tex_bounds = prim.quad_bounds;
-        prim.scissor = self.add_scissor() as u32;
+    /// Sets the theme that paint variables are resolved against.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
 
-        self.render(prim);
+    /// Handle to this renderer's glyph cache, for `with_glyph_cache`.
+    pub fn shared_glyph_cache(&self) -> SharedGlyphCache {
+        self.glyph_cache.clone()
     }
 
-    /// Fills a rectangle.
-    pub fn fill_rect<Rect: Into<LocalRect>>(
-        &mut self,
-        rect: Rect,
-        radius: f32,
-        paint_index: PaintIndex,
-    ) {
-        let mut prim = Prim::default();
-        prim.prim_type = PrimType::Rect as u32;
-        let r: LocalRect = rect.into();
-        let min = r.min();
-        let max = r.max();
-        prim.cvs[0] = min.x;
-        prim.cvs[1] = min.y;
-        prim.cvs[2] = max.x;
-        prim.cvs[3] = max.y;
-        prim.radius = radius;
-        prim.paint = paint_index.index as u32;
-        prim.quad_bounds = [min.x, min.y, max.x, max.y];
-        prim.tex_bounds = prim.quad_bounds;
-        prim.scissor = self.add_scissor() as u32;
+    /// Locks the glyph cache, e.g. to read back an atlas. Other renderers
+    /// sharing it wait until the guard is dropped.
+    pub fn glyph_cache(&self) -> std::sync::MutexGuard<'_, GlyphCache> {
+        self.glyph_cache.lock()
+    }
 
-        self.render(prim);
+    /// Glyph, image and svg cache lookups for the frame being recorded,
+    /// and time spent rasterizing misses. Lookups by other renderers
+    /// sharing the cache since this frame began are included.
+    pub fn glyph_cache_stats(&self) -> GlyphCacheStats {
+        self.glyph_cache.lock().stats()
     }
 
-    /// Strokes a rectangle.
-    pub fn stroke_rect(
-        &mut self,
-        min: LocalPoint,
-        max: LocalPoint,
-        radius: f32,
-        width: f32,
-        paint_index: PaintIndex,
-    ) {
-        let mut prim = Prim::default();
-        prim.prim_type = PrimType::RectStroke as u32;
-        prim.cvs[0] = min.x;
-        prim.cvs[1] = min.y;
-        prim.cvs[2] = max.x;
-        prim.cvs[3] = max.y;
-        prim.radius = radius;
-        prim.width = width;
-        prim.paint = paint_index.index as u32;
-        prim.quad_bounds = [min.x - width, min.y - width, max.x + width, max.y + width];
-        prim.tex_bounds = prim.quad_bounds;
-        prim.scissor = self.add_scissor() as u32;
+    /// Layouts cached by `text_line`, e.g. to invalidate edited lines.
+    pub fn line_cache_mut(&mut self) -> &mut LineLayoutCache {
+        &mut self.line_cache
+    }
 
-        self.render(prim);
+    /// Saves the glyphs rasterized for `text` and `render_glyph` to a
+    /// file, e.g. at shutdown, for `load_glyph_cache` to restore at the
+    /// next startup. `font_key` names the fonts of `render_glyph` glyphs
+    /// between runs. See `GlyphCache::write_glyphs`.
+    pub fn save_glyph_cache(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        font_key: impl Fn(cosmic_text::fontdb::ID) -> Option<u64>,
+    ) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.glyph_cache.lock().write_glyphs(&mut file, font_key)?;
+        std::io::Write::flush(&mut file)
     }
 
-    /// Strokes a line segment.
-    pub fn stroke_segment<Pt: Into<LocalPoint>>(
+    /// Loads glyphs saved by `save_glyph_cache`, so the first frame
+    /// uploads them rather than rasterizing each one. `font_id` finds the
+    /// font each saved key names. Returns how many glyphs were loaded.
+    /// Files saved with another font or version of vger fail with
+    /// `InvalidData` and can be deleted.
+    pub fn load_glyph_cache(
         &mut self,
-        a: Pt,
-        b: Pt,
-        width: f32,
-        paint_index: PaintIndex,
-    ) {
-        let mut prim = Prim::default();
-        prim.prim_type = PrimType::Segment as u32;
-        let ap: LocalPoint = a.into();
-        let bp: LocalPoint = b.into();
-        prim.cvs[0] = ap.x;
-        prim.cvs[1] = ap.y;
-        prim.cvs[2] = bp.x;
-        prim.cvs[3] = bp.y;
-        prim.width = width;
-        prim.paint = paint_index.index as u32;
-        prim.quad_bounds = [
-            ap.x.min(bp.x) - width * 2.0,
-            ap.y.min(bp.y) - width * 2.0,
-            ap.x.max(bp.x) + width * 2.0,
-            ap.y.max(bp.y) + width * 2.0,
-        ];
-        prim.tex_bounds = prim.quad_bounds;
-        prim.scissor = self.add_scissor() as u32;
+        path: impl AsRef<std::path::Path>,
+        font_id: impl Fn(u64) -> Option<cosmic_text::fontdb::ID>,
+    ) -> std::io::Result<usize> {
+        let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+        self.glyph_cache.lock().read_glyphs(&mut file, font_id)
+    }
 
-        self.render(prim);
+    /// Gets the current theme.
+    pub fn theme_mut(&mut self) -> &mut Theme {
+        &mut self.theme
     }
 
-    /// Strokes a quadratic bezier segment.
-    pub fn stroke_bezier<Pt: Into<LocalPoint>>(
+    /// Encode all rendering to a command buffer.
+    pub fn encode(
         &mut self,
-        a: Pt,
-        b: Pt,
-        c: Pt,
-        width: f32,
-        paint_index: PaintIndex,
+        device: &wgpu::Device,
+        render_pass: &wgpu::RenderPassDescriptor,
+        queue: &wgpu::Queue,
     ) {
-        let mut prim = Prim::default();
-        prim.prim_type = PrimType::Bezier as u32;
-        let ap: LocalPoint = a.into();
-        let bp: LocalPoint = b.into();
-        let cp: LocalPoint = c.into();
-        prim.cvs[0] = ap.x;
-        prim.cvs[1] = ap.y;
-        prim.cvs[2] = bp.x;
-        prim.cvs[3] = bp.y;
-        prim.cvs[4] = cp.x;
-        prim.cvs[5] = cp.y;
-        prim.width = width;
-        prim.paint = paint_index.index as u32;
-        prim.quad_bounds = [
-            ap.x.min(bp.x).min(cp.x) - width,
-            ap.y.min(bp.y).min(cp.y) - width,
-            ap.x.max(bp.x).max(cp.x) + width,
-            ap.y.max(bp.y).max(cp.y) + width,
-        ];
-        prim.tex_bounds = prim.quad_bounds;
-        prim.scissor = self.add_scissor() as u32;
+        profile_span!("vger::encode");
+        self.prepare(device, queue);
 
-        self.render(prim);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("vger encoder"),
+        });
+        {
+            let mut rpass = encoder.begin_render_pass(render_pass);
+            self.render(&mut rpass);
+        }
+        queue.submit(Some(encoder.finish()));
     }
 
-    /// Move the pen to a point (path fills only)
-    pub fn move_to<Pt: Into<LocalPoint>>(&mut self, p: Pt) {
-        self.pen = p.into();
-    }
+    /// First half of `encode`: writes the frame's buffers and uploads new
+    /// glyphs, images and cached layers. Call after drawing and before
+    /// `render`, e.g. from a host's prepare stage.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        profile_span!("vger::prepare");
+        if self.validation && self.state_stack.len() > 1 {
+            self.validation_errors.push(ValidationError::UnmatchedSave {
+                depth: self.state_stack.len() - 1,
+            });
+        }
 
-    /// Makes a quadratic curve to a point (path fills only)
-    pub fn quad_to<Pt: Into<LocalPoint>>(&mut self, b: Pt, c: Pt) {
-        let cp: LocalPoint = c.into();
-        self.path_scanner
-            .segments
-            .push(PathSegment::new(self.pen, b.into(), cp));
-        self.pen = cp;
-    }
+        self.upload_frame(device, queue);
+        self.prepare_overlay(device, queue);
+        self.check_budget(device);
 
-    fn add_cv<Pt: Into<LocalPoint>>(&mut self, p: Pt) {
-        self.scenes[self.cur_scene].cvs.push(p.into())
+        // If we're getting close to full, make room in the glyph cache.
+        // Glyphs already uploaded stay in the texture until the next prepare.
+        self.glyph_cache.lock().check_usage();
     }
 
-    /// Fills a path.
-    pub fn fill(&mut self, paint_index: PaintIndex) {
-        let scissor = self.add_scissor();
-
-        self.path_scanner.init();
-
-        while self.path_scanner.next() {
-            let mut prim = Prim::default();
-            prim.prim_type = PrimType::PathFill as u32;
-            prim.paint = paint_index.index as u32;
-            prim.scissor = scissor as u32;
-            prim.start = self.scenes[self.cur_scene].cvs.len() as u32;
+    /// Writes the frame's buffers and renders what it draws into the
+    /// atlases.
+    fn upload_frame(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let scene = &mut self.scenes[self.cur_scene];
 
-            let mut x_interval = Interval {
-                a: std::f32::MAX,
-                b: std::f32::MIN,
-            };
+        // Layers, external textures and videos are written to atlas regions
+        // looked up while drawing. If the cache was cleared since, e.g. by a
+        // renderer sharing it, the regions may belong to others now.
+        if self.glyph_cache.lock().generation() != self.frame_generation {
+            scene.layers.clear();
+            scene.externals.clear();
+            scene.videos.clear();
+            if self.validation {
+                self.validation_errors.push(ValidationError::StaleAtlas);
+            }
+        }
 
-            let mut index = self.path_scanner.first;
-            while let Some(a) = index {
-                for i in 0..3 {
-                    let p = self.path_scanner.segments[a].cvs[i];
-                    self.add_cv(p);
-                    x_interval.a = x_interval.a.min(p.x);
-                    x_interval.b = x_interval.b.max(p.x);
-                }
-                prim.count += 1;
+        for (index, name) in &scene.paint_vars {
+            scene.paints[*index] = self.theme.resolve(name);
+        }
 
-                index = self.path_scanner.segments[a].next;
+        {
+            profile_span!("vger::scene_upload");
+            scene.update(device, queue, self.packed, self.split_draws);
+            // A flush may have grown the atlases since `begin`.
+            if self.uniforms.len() == 1 {
+                self.uniforms[0].atlas_size = self.atlas_size();
             }
+            self.uniforms.update(device, queue);
+        }
 
-            prim.quad_bounds[0] = x_interval.a;
-            prim.quad_bounds[1] = self.path_scanner.interval.a;
-            prim.quad_bounds[2] = x_interval.b;
-            prim.quad_bounds[3] = self.path_scanner.interval.b;
-            prim.tex_bounds = prim.quad_bounds;
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("vger prepare encoder"),
+        });
 
-            self.render(prim);
-        }
+        self.glyph_cache
+            .lock()
+            .update(device, &mut encoder, &mut self.arena.bytes);
+        self.bind_atlases(device);
+        self.render_layers(device, &mut encoder);
+        self.copy_external_textures(&mut encoder);
+        self.convert_videos(device, &mut encoder);
 
-        self.path_scanner.segments.clear();
+        queue.submit(Some(encoder.finish()));
+        self.textures.recycle();
+        let textures = &self.textures;
+        self.layer_sizes.retain(|key, _| textures.is_kept(*key));
     }
 
-    fn setup_layout(&mut self, text: &str, size: u32, max_width: Option<f32>) {
-        let scale = self.device_px_ratio;
+    /// Draws what's been recorded so far this frame into `view`, then
+    /// carries on with the same state, paints and transforms. If the
+    /// atlases are getting full they're cleared afterwards, so a frame can
+    /// use more new glyphs and images than fit at once. Finish the frame
+    /// with a pass which loads rather than clears the target. The overlay
+    /// is only drawn at the end.
+    ///
+    /// Paints from images or colormaps refer to atlas regions, so make
+    /// them again after a flush which cleared the atlases, rather than
+    /// reusing those made before.
+    pub fn flush(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, view: &wgpu::TextureView) {
+        profile_span!("vger::flush");
+        self.upload_frame(device, queue);
 
-        self.layout.reset(&LayoutSettings {
-            max_width: max_width.map(|w| w * scale),
-            ..LayoutSettings::default()
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("vger flush encoder"),
         });
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("vger flush pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            self.render_scene(&mut rpass, &self.scenes[self.cur_scene], self.cur_layer);
+        }
+        queue.submit(Some(encoder.finish()));
 
-        let scaled_size = size as f32 * scale;
+        self.scenes[self.cur_scene].clear_prims();
+        let mut glyph_cache = self.glyph_cache.lock();
+        glyph_cache.check_usage();
+        self.frame_generation = glyph_cache.generation();
+    }
 
-        self.layout.append(
-            &[&self.glyph_cache.font],
-            &TextStyle::new(text, scaled_size, 0),
-        );
+    /// Whether the atlases are filling up, so a frame drawing many new
+    /// glyphs or images should `flush` before drawing more.
+    pub fn should_flush(&self) -> bool {
+        self.glyph_cache.lock().nearly_full()
     }
 
-    pub fn render_glyph<'a>(
-        &mut self,
-        x: f32,
+    /// Second half of `encode`: draws the prepared frame into a render
+    /// pass, which may be the host's, targeting the format vger was
+    /// created with.
+    pub fn render<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
+        profile_span!("vger::render");
+        self.render_scene(rpass, &self.scenes[self.cur_scene], self.cur_layer);
+
+        // A stale overlay is dropped by the next prepare, when the
+        // atlases are cleared, so it's still good to draw until then.
+        let overlay = &self.scenes[OVERLAY_SCENE];
+        if overlay.small_prims.len() + overlay.prims[0].len() > 0 {
+            self.render_scene(rpass, overlay, 0);
+        }
+    }
+
+    fn render_scene<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        scene: &'a Scene,
+        layer: usize,
+    ) {
+        let (n, pipeline, additive_pipeline) = if layer == 0 && scene.small {
+            rpass.set_bind_group(0, &scene.small_bind_group, &[]);
+            (
+                scene.small_prims.len(),
+                &self.small_pipeline,
+                &self.additive_small_pipeline,
+            )
+        } else if layer == 0 && scene.packed {
+            rpass.set_bind_group(0, &scene.packed_bind_group, &[]);
+            (
+                scene.packed_prims.len(),
+                &self.packed_pipeline,
+                &self.additive_packed_pipeline,
+            )
+        } else {
+            rpass.set_bind_group(
+                0,
+                &scene.bind_groups[layer],
+                &[], // dynamic offsets
+            );
+            (
+                scene.prims[layer].len(),
+                &self.pipeline,
+                &self.additive_pipeline,
+            )
+        };
+        rpass.set_pipeline(pipeline);
+
+        rpass.set_bind_group(1, &self.uniform_bind_group, &[]);
+
+        // Switches pipelines between runs with different blend modes, and
+        // bind groups between runs drawing different cached layers. Runs
+        // of layers without a texture are skipped.
+        let mut blend = BlendMode::Normal;
+        let mut bound = None;
+        let mut set_run = |rpass: &mut wgpu::RenderPass<'a>, run: &DrawRun| {
+            if run.blend != blend {
+                blend = run.blend;
+                rpass.set_pipeline(match blend {
+                    BlendMode::Normal => pipeline,
+                    BlendMode::Additive => additive_pipeline,
+                });
+            }
+            if run.layer != bound {
+                let bind_group = match run.layer {
+                    Some(key) => match scene.layer_bind_groups.get(&key) {
+                        Some(bind_group) => bind_group,
+                        None => return false,
+                    },
+                    None => &self.uniform_bind_group,
+                };
+                rpass.set_bind_group(1, bind_group, &[]);
+                bound = run.layer;
+            }
+            true
+        };
+
+        // Cached layers need their own bind groups, so frames drawing them
+        // are drawn directly.
+        let layer_runs = scene.draws.iter().any(|(_, run)| run.layer.is_some());
+
+        if self.indirect && layer == 0 && !layer_runs {
+            rpass.draw_indirect(&scene.draw_args, 0);
+        } else if layer == 0 && self.split_draws {
+            let whole = [(0..n as u32, DrawRun::default())];
+            let draws = if scene.draws.is_empty() {
+                &whole[..]
+            } else {
+                &scene.draws[..]
+            };
+            for (range, run) in draws {
+                if !set_run(rpass, run) {
+                    continue;
+                }
+                if let Some(label) = run.label {
+                    rpass.push_debug_group(&scene.debug_labels[label as usize]);
+                }
+                for i in range.clone() {
+                    let name = scene
+                        .debug_prim_types
+                        .get(i as usize)
+                        .and_then(|t| PrimType::from_u32(*t))
+                        .map_or("Prim", PrimType::name);
+                    rpass.push_debug_group(name);
+                    rpass.draw(0..4, i..i + 1);
+                    rpass.pop_debug_group();
+                }
+                if run.label.is_some() {
+                    rpass.pop_debug_group();
+                }
+            }
+        } else if layer == 0 && !scene.draws.is_empty() {
+            for (range, run) in &scene.draws {
+                if !set_run(rpass, run) {
+                    continue;
+                }
+                match run.label {
+                    Some(label) => {
+                        rpass.push_debug_group(&scene.debug_labels[label as usize]);
+                        rpass.draw(0..4, range.clone());
+                        rpass.pop_debug_group();
+                    }
+                    None => rpass.draw(0..4, range.clone()),
+                }
+            }
+        } else {
+            rpass.draw(/*vertices*/ 0..4, /*instances*/ 0..(n as u32));
+        }
+    }
+
+    /// Draws the frame's prims with `draw_indirect`, from arguments in a
+    /// gpu buffer, so the command stream doesn't depend on the scene. The
+    /// device needs `DownlevelFlags::INDIRECT_EXECUTION`, which WebGL
+    /// lacks.
+    pub fn set_indirect_draws(&mut self, enabled: bool) {
+        self.indirect = enabled;
+    }
+
+    /// Draws each prim with its own draw call, labelled with its type
+    /// within any debug groups, so a bad prim can be found in a RenderDoc
+    /// or Xcode capture. The prim's index is the draw's first instance.
+    /// Much slower, so only for debugging.
+    /// Indirect draws aren't split.
+    pub fn set_split_draws(&mut self, enabled: bool) {
+        self.split_draws = enabled;
+    }
+
+    /// Stores the frame's prims at half size, with positions and radii as
+    /// 16 bit floats, shrinking the prim buffer for large scenes. Prims are
+    /// only packed where that's accurate to 1/64 of a local unit, and
+    /// glyphs, images and paths are kept at full precision. Paint colors
+    /// are packed as RGBA8, unless they're outside 0 to 1. Small frames
+    /// and the overlay are never packed.
+    pub fn set_packed_prims(&mut self, enabled: bool) {
+        self.packed = enabled;
+    }
+
+    /// Buffer holding the draw arguments of the frame being recorded,
+    /// written by `prepare`: vertex count, instance count, first vertex
+    /// and first instance, as `u32`s. See `set_indirect_draws`.
+    pub fn draw_args_buffer(&self) -> &wgpu::Buffer {
+        &self.scenes[self.cur_scene].draw_args
+    }
+
+    /// Replaces the overlay, drawn over every frame until it's next
+    /// replaced, e.g. a cursor or drag ghost. Drawing starts with a fresh
+    /// state. Cached layers, external textures and videos aren't supported
+    /// in the overlay.
+    ///
+    /// The overlay can be redrawn after the frame is prepared, then shown
+    /// with `encode_overlay`, or `prepare_overlay` and `render`, so it
+    /// follows input without rebuilding the frame.
+    ///
+    /// The overlay is dropped when the atlases are cleared, since its
+    /// glyphs and images would be gone. See `has_overlay`.
+    pub fn draw_overlay(&mut self, draw: impl FnOnce(&mut Vger)) {
+        let frame_scene = self.cur_scene;
+        let counts = (self.paint_count, self.xform_count, self.scissor_count);
+        let depth = self.state_stack.len();
+        let debug_groups = std::mem::take(&mut self.debug_groups);
+        self.debug_label = None;
+
+        self.cur_scene = OVERLAY_SCENE;
+        self.scenes[OVERLAY_SCENE].clear();
+        self.overlay_generation = self.glyph_cache.lock().generation();
+        self.paint_count = 0;
+        self.xform_count = 0;
+        self.scissor_count = 0;
+        self.state_stack.push(State::new());
+        self.add_xform();
+
+        draw(self);
+
+        self.state_stack.truncate(depth);
+        self.debug_groups = debug_groups;
+        self.debug_label = None;
+        self.cur_scene = frame_scene;
+        let (paint_count, xform_count, scissor_count) = counts;
+        self.paint_count = paint_count;
+        self.xform_count = xform_count;
+        self.scissor_count = scissor_count;
+        self.overlay_dirty = true;
+    }
+
+    /// Whether an overlay will be drawn over later frames. It's dropped
+    /// when the atlases are cleared after it was drawn, so draw it again
+    /// when this is false.
+    pub fn has_overlay(&self) -> bool {
+        let overlay = &self.scenes[OVERLAY_SCENE];
+        overlay.small_prims.len() + overlay.prims[0].len() > 0
+            && self.glyph_cache.lock().generation() == self.overlay_generation
+    }
+
+    /// Writes the overlay's buffers if it was drawn since it was last
+    /// prepared. `prepare` does this too.
+    pub fn prepare_overlay(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.glyph_cache.lock().generation() != self.overlay_generation {
+            self.scenes[OVERLAY_SCENE].clear();
+        }
+        if !self.overlay_dirty {
+            return;
+        }
+        self.overlay_dirty = false;
+
+        let scene = &mut self.scenes[OVERLAY_SCENE];
+        for (index, name) in &scene.paint_vars {
+            scene.paints[*index] = self.theme.resolve(name);
+        }
+        scene.update(device, queue, false, self.split_draws);
+
+        // Glyphs new to the overlay.
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("vger overlay encoder"),
+        });
+        self.glyph_cache
+            .lock()
+            .update(device, &mut encoder, &mut self.arena.bytes);
+        self.bind_atlases(device);
+        queue.submit(Some(encoder.finish()));
+    }
+
+    /// Encodes the last prepared frame again with the latest overlay,
+    /// without preparing the frame.
+    pub fn encode_overlay(
+        &mut self,
+        device: &wgpu::Device,
+        render_pass: &wgpu::RenderPassDescriptor,
+        queue: &wgpu::Queue,
+    ) {
+        self.prepare_overlay(device, queue);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("vger overlay encoder"),
+        });
+        {
+            let mut rpass = encoder.begin_render_pass(render_pass);
+            self.render(&mut rpass);
+        }
+        queue.submit(Some(encoder.finish()));
+    }
+
+    /// Size in device pixels of the window, which bounds cached layers.
+    fn layer_target_size(&self) -> (u32, u32) {
+        let size = self.screen_size * self.device_px_ratio;
+        (size.width.ceil() as u32, size.height.ceil() as u32)
+    }
+
+    /// Renders the layers recorded this frame into the color atlas or
+    /// their pooled textures, then binds the textures of the cached layers
+    /// drawn.
+    fn render_layers(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        profile_span!("vger::render_layers");
+        if !self.scenes[self.cur_scene].layers.is_empty() {
+            let (width, height) = self.layer_target_size();
+            let target = self.textures.acquire(
+                device,
+                width,
+                height,
+                wgpu::TextureFormat::Rgba8Unorm,
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            );
+            let view = self
+                .textures
+                .texture(target)
+                .create_view(&wgpu::TextureViewDescriptor::default());
+            let viewport = self.screen_size * self.device_px_ratio;
+
+            for index in 0..self.scenes[self.cur_scene].layers.len() {
+                let scene = &self.scenes[self.cur_scene];
+                let layer = &scene.layers[index];
+                {
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("vger layer pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: &view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                                store: true,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                    });
+
+                    // The target may be larger than the window.
+                    rpass.set_viewport(0.0, 0.0, viewport.width, viewport.height, 0.0, 1.0);
+                    rpass.set_pipeline(&self.layer_pipeline);
+                    rpass.set_bind_group(0, &scene.bind_groups[CACHED_LAYER_PRIMS], &[]);
+                    rpass.set_bind_group(1, &self.uniform_bind_group, &[]);
+                    rpass.draw(0..4, layer.prims.clone());
+                }
+
+                let (origin, width, height) = match layer.target {
+                    LayerTarget::Atlas(rect) => (
+                        wgpu::Origin3d {
+                            x: rect.x as u32,
+                            y: rect.y as u32,
+                            z: 0,
+                        },
+                        rect.width as u32,
+                        rect.height as u32,
+                    ),
+                    LayerTarget::Pool { width, height, .. } => {
+                        (wgpu::Origin3d::ZERO, width, height)
+                    }
+                };
+                let glyph_cache = self.glyph_cache.lock();
+                let destination = match layer.target {
+                    LayerTarget::Atlas(_) => glyph_cache.color_atlas.texture(),
+                    LayerTarget::Pool { key, width, height } => {
+                        let texture = self.textures.acquire(
+                            device,
+                            width,
+                            height,
+                            wgpu::TextureFormat::Rgba8Unorm,
+                            wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+                        );
+                        self.textures.keep(texture, key);
+                        self.textures.texture(texture)
+                    }
+                };
+                encoder.copy_texture_to_texture(
+                    wgpu::ImageCopyTexture {
+                        texture: self.textures.texture(target),
+                        mip_level: 0,
+                        origin: wgpu::Origin3d::ZERO,
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    wgpu::ImageCopyTexture {
+                        texture: destination,
+                        mip_level: 0,
+                        origin,
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+        }
+
+        let scene = &mut self.scenes[self.cur_scene];
+        for key in &scene.layer_keys {
+            let texture = match self.textures.kept(*key) {
+                Some(texture) => texture,
+                None => continue,
+            };
+            let view = self
+                .textures
+                .texture(texture)
+                .create_view(&wgpu::TextureViewDescriptor::default());
+            let bind_group = Self::uniform_bind_group(
+                device,
+                &self.uniform_bind_group_layout,
+                &self.uniforms,
+                AtlasBindings {
+                    mask: &self.mask_texture_view,
+                    color: &view,
+                    layer: &view,
+                },
+                &self.glyph_sampler,
+                &self.color_glyph_sampler,
+            );
+            scene.layer_bind_groups.insert(*key, bind_group);
+        }
+    }
+
+    /// Copies the external textures drawn this frame into the color atlas.
+    fn copy_external_textures(&self, encoder: &mut wgpu::CommandEncoder) {
+        for (image, rect) in &self.scenes[self.cur_scene].externals {
+            let external = match self.external_textures.get(*image) {
+                Some(external) => external,
+                None => continue,
+            };
+            encoder.copy_texture_to_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &external.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyTexture {
+                    texture: self.glyph_cache.lock().color_atlas.texture(),
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: rect.x as u32,
+                        y: rect.y as u32,
+                        z: 0,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    width: external.width,
+                    height: external.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    /// Converts the video frames drawn this frame into the color atlas.
+    fn convert_videos(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        let videos = &self.scenes[self.cur_scene].videos;
+        if videos.is_empty() {
+            return;
+        }
+
+        let width = videos.iter().map(|(frame, _)| frame.width).max().unwrap();
+        let height = videos.iter().map(|(frame, _)| frame.height).max().unwrap();
+        let target = self.textures.acquire(
+            device,
+            width,
+            height,
+            wgpu::TextureFormat::Rgba8Unorm,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+        let target = self.textures.texture(target);
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let converter = self
+            .video_converter
+            .get_or_insert_with(|| VideoConverter::new(device));
+
+        for (frame, rect) in &self.scenes[self.cur_scene].videos {
+            converter.convert(device, encoder, frame, &view);
+            encoder.copy_texture_to_texture(
+                wgpu::ImageCopyTexture {
+                    texture: target,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyTexture {
+                    texture: self.glyph_cache.lock().color_atlas.texture(),
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: rect.x as u32,
+                        y: rect.y as u32,
+                        z: 0,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    width: frame.width,
+                    height: frame.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    /// Intermediate textures, shared with the host for its own render to
+    /// texture work so textures are reused across both. Textures acquired
+    /// from it are recycled at the end of `encode`.
+    pub fn texture_pool(&mut self) -> &mut TexturePool {
+        &mut self.textures
+    }
+
+    /// Limits memory use. When vger is over `bytes` after a frame's
+    /// `prepare`, buffers of the other frames' scenes, grown by earlier,
+    /// larger frames, are shrunk. They aren't shrunk again until usage has
+    /// fallen to three quarters of the budget, so a frame which needs more
+    /// doesn't trim every frame. Only `trim` shrinks the atlases.
+    pub fn set_memory_budget(&mut self, bytes: Option<usize>) {
+        self.memory_budget = bytes;
+    }
+
+    /// Approximate bytes held by vger's buffers, atlases and scratch
+    /// memory, on the cpu and gpu.
+    pub fn memory_usage(&self) -> usize {
+        self.scenes.iter().map(|s| s.memory_usage()).sum::<usize>()
+            + self.glyph_cache.lock().memory_usage()
+            + self.arena.capacity()
+            + self.textures.memory_usage()
+    }
+
+    /// Releases as much memory as possible, e.g. when the app is
+    /// backgrounded. Cached glyphs and images are dropped and will be
+    /// rasterized again when next drawn, and the atlases are shrunk,
+    /// growing back as they fill. Renderers sharing the glyph cache bind
+    /// the new atlases when they next prepare.
+    pub fn trim(&mut self, device: &wgpu::Device) {
+        self.glyph_cache.lock().shrink_atlases(device);
+        self.bind_atlases(device);
+        self.arena.shrink();
+        self.textures.clear();
+        self.trim_buffers(device);
+    }
+
+    /// Shrinks the buffers of the other frames' scenes when over the
+    /// memory budget. The current scene's buffers hold the frame being
+    /// rendered, so they're left alone.
+    fn check_budget(&mut self, device: &wgpu::Device) {
+        let budget = match self.memory_budget {
+            Some(budget) => budget,
+            None => return,
+        };
+        let usage = self.memory_usage();
+        if usage <= budget / 4 * 3 {
+            self.budget_trimmed = false;
+        } else if usage > budget && !self.budget_trimmed {
+            self.budget_trimmed = true;
+            for i in 0..OVERLAY_SCENE {
+                if i != self.cur_scene {
+                    self.scenes[i].trim(device);
+                }
+            }
+        }
+    }
+
+    fn trim_buffers(&mut self, device: &wgpu::Device) {
+        // The overlay's buffers are kept, since it can be shown again
+        // without being prepared.
+        for scene in &mut self.scenes[..OVERLAY_SCENE] {
+            scene.trim(device);
+        }
+    }
+
+    /// Encode all rendering to a command buffer drawing into `view`,
+    /// clearing it first or not according to `options`.
+    pub fn encode_to_view(
+        &mut self,
+        device: &wgpu::Device,
+        view: &wgpu::TextureView,
+        queue: &wgpu::Queue,
+        options: &RenderOptions,
+    ) {
+        let load = match options.clear {
+            Some(color) => wgpu::LoadOp::Clear(color.into()),
+            None => wgpu::LoadOp::Load,
+        };
+        let desc = wgpu::RenderPassDescriptor {
+            label: Some("vger render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations { load, store: true },
+            })],
+            depth_stencil_attachment: None,
+        };
+        self.encode(device, &desc, queue);
+    }
+
+    /// Copies the pixels under `rect`, in local coordinates under the
+    /// current transform, from a texture vger has rendered into. The
+    /// texture needs `COPY_SRC` usage and an 8 bit RGBA or BGRA format.
+    pub fn capture_region(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target: &wgpu::Texture,
+        rect: LocalRect,
+    ) -> Capture {
+        let q = [rect.min_x(), rect.min_y(), rect.max_x(), rect.max_y()];
+        capture::capture(device, queue, target, self.device_rect(q))
+    }
+
+    /// Copies one pixel, at `(x, y)` in device pixels, from a texture vger
+    /// has rendered into, e.g. for an eyedropper. Requirements on the
+    /// texture are as for `capture_region`.
+    pub fn read_pixel(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target: &wgpu::Texture,
+        x: u32,
+        y: u32,
+    ) -> PixelRead {
+        capture::read_pixel(device, queue, target, x, y)
+    }
+
+    fn add_prim(&mut self, prim: Prim) {
+        self.add_layer_prim(prim, None);
+    }
+
+    /// Adds a prim sampling the cached layer `layer`, if any, in place of
+    /// the color atlas.
+    fn add_layer_prim(&mut self, mut prim: Prim, layer: Option<u64>) {
+        if !self.validate(&prim) {
+            return;
+        }
+        let state = *self.state();
+        if state.access_node.is_some() || state.pick_tag.is_some() {
+            let mut unpadded = prim;
+            unpadded.expand_quad(0.0);
+            let bounds = self.device_rect(unpadded.quad_bounds);
+            if let Some(node) = state.access_node {
+                self.access.add_bounds(node, bounds);
+            }
+            if let Some(tag) = state.pick_tag {
+                self.picks.add(tag, bounds, state.z_index);
+            }
+        }
+        prim.xform = self.add_xform() as u32;
+        let glow = self.scenes[self.cur_scene]
+            .paints
+            .get(prim.paint as usize)
+            .map_or(0.0, |paint| paint.glow_radius());
+        prim.expand_quad(self.aa_pad() + glow);
+        let z_index = self.state().z_index;
+        let run = DrawRun {
+            label: self.debug_label(),
+            blend: self.state().blend_mode,
+            layer,
+        };
+        if self.validation
+            && run.blend != BlendMode::Normal
+            && (self.indirect || self.recording_layer)
+            && !self
+                .validation_errors
+                .contains(&ValidationError::BlendModeIgnored)
+        {
+            self.validation_errors
+                .push(ValidationError::BlendModeIgnored);
+        }
+        let scene = &mut self.scenes[self.cur_scene];
+        let prims = scene.depthed_prims.entry(z_index).or_default();
+        let index = prims.len() as u32;
+        prims.push(prim);
+
+        if run != DrawRun::default() || scene.runs.contains_key(&z_index) {
+            let runs = scene.runs.entry(z_index).or_default();
+            if runs.last().map(|last| last.1) != Some(run) {
+                runs.push((index, run));
+            }
+        }
+    }
+
+    /// Label of the open debug groups in the current scene.
+    fn debug_label(&mut self) -> Option<u32> {
+        if self.debug_groups.is_empty() {
+            return None;
+        }
+        match self.debug_label {
+            Some((scene, label)) if scene == self.cur_scene => Some(label),
+            _ => {
+                let labels = &mut self.scenes[self.cur_scene].debug_labels;
+                let label = labels.len() as u32;
+                labels.push(self.debug_groups.join("/"));
+                self.debug_label = Some((self.cur_scene, label));
+                Some(label)
+            }
+        }
+    }
+
+    /// Labels subsequent drawing until the matching `pop_debug_group`, so
+    /// graphics debuggers such as RenderDoc and Xcode show its draws as a
+    /// group. Nested groups are shown by their path, e.g.
+    /// "sidebar/button". Frames using groups are split into a draw per
+    /// run of prims in the same group, so this is meant for debugging.
+    /// Indirect draws aren't split.
+    pub fn push_debug_group(&mut self, label: &str) {
+        self.debug_groups.push(label.to_string());
+        self.debug_label = None;
+    }
+
+    /// Closes the group opened by the last `push_debug_group`.
+    pub fn pop_debug_group(&mut self) {
+        self.debug_groups.pop();
+        self.debug_label = None;
+    }
+
+    /// Fills a circle.
+    pub fn fill_circle<Pt: Into<LocalPoint>>(
+        &mut self,
+        center: Pt,
+        radius: f32,
+        paint_index: PaintIndex,
+    ) {
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::Circle as u32;
+        let c: LocalPoint = center.into();
+        prim.cvs[0] = c.x;
+        prim.cvs[1] = c.y;
+        prim.radius = radius;
+        prim.paint = paint_index.index as u32;
+        prim.scissor = self.add_scissor() as u32;
+
+        self.add_prim(prim);
+    }
+
+    /// Strokes an arc.
+    pub fn stroke_arc<Pt: Into<LocalPoint>>(
+        &mut self,
+        center: Pt,
+        radius: f32,
+        width: f32,
+        rotation: f32,
+        aperture: f32,
+        paint_index: PaintIndex,
+    ) {
+        let mut prim = arc_prim(center.into(), radius, width, rotation, aperture);
+        prim.paint = paint_index.index as u32;
+        prim.scissor = self.add_scissor() as u32;
+
+        self.add_prim(prim);
+    }
+
+    /// Strokes an arc with round ends, e.g. for progress rings.
+    pub fn stroke_arc_round<Pt: Into<LocalPoint>>(
+        &mut self,
+        center: Pt,
+        radius: f32,
+        width: f32,
+        rotation: f32,
+        aperture: f32,
+        paint_index: PaintIndex,
+    ) {
+        let mut prim = arc_prim(center.into(), radius, width, rotation, aperture);
+        prim.prim_type = PrimType::RoundArc as u32;
+        prim.paint = paint_index.index as u32;
+        prim.scissor = self.add_scissor() as u32;
+
+        self.add_prim(prim);
+    }
+
+    /// Draws a ring progress indicator: the full ring in
+    /// `background_paint` with the gauge's arc over it, with round ends,
+    /// in `value_paint`.
+    pub fn draw_gauge(
+        &mut self,
+        gauge: &Gauge,
+        background_paint: PaintIndex,
+        value_paint: PaintIndex,
+    ) {
+        use std::f32::consts::PI;
+
+        self.stroke_arc(
+            gauge.center,
+            gauge.radius,
+            gauge.thickness,
+            0.0,
+            PI,
+            background_paint,
+        );
+
+        if gauge.sweep != 0.0 {
+            let (rotation, aperture) = arc_rotation_aperture(gauge.start, gauge.sweep);
+            self.stroke_arc_round(
+                gauge.center,
+                gauge.radius,
+                gauge.thickness,
+                rotation,
+                aperture,
+                value_paint,
+            );
+        }
+    }
+
+    /// Fills a pie or donut slice. Edges are computed analytically, so
+    /// they're crisp at any size. Test hover with `Sector::contains`.
+    pub fn fill_sector(&mut self, sector: &Sector, paint_index: PaintIndex) {
+        let inner = sector.inner_radius.max(0.0);
+        let outer = sector.outer_radius.max(inner);
+        let (rotation, aperture) = arc_rotation_aperture(sector.start, sector.sweep);
+
+        // A slice is an arc as wide as the ring.
+        self.stroke_arc(
+            sector.center,
+            (inner + outer) / 2.0,
+            outer - inner,
+            rotation,
+            aperture,
+            paint_index,
+        );
+    }
+
+    /// Fills a rectangle.
+    pub fn fill_rect<Rect: Into<LocalRect>>(
+        &mut self,
+        rect: Rect,
+        radius: f32,
+        paint_index: PaintIndex,
+    ) {
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::Rect as u32;
+        let r: LocalRect = rect.into();
+        let min = r.min();
+        let max = r.max();
+        prim.cvs[0] = min.x;
+        prim.cvs[1] = min.y;
+        prim.cvs[2] = max.x;
+        prim.cvs[3] = max.y;
+        prim.radius = radius;
+        prim.paint = paint_index.index as u32;
+        prim.scissor = self.add_scissor() as u32;
+
+        self.add_prim(prim);
+    }
+
+    /// Strokes a rectangle. Any paint can be used, e.g. a linear gradient
+    /// for a gradient outline.
+    pub fn stroke_rect(
+        &mut self,
+        min: LocalPoint,
+        max: LocalPoint,
+        radius: f32,
+        width: f32,
+        paint_index: PaintIndex,
+    ) {
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::RectStroke as u32;
+        prim.cvs[0] = min.x;
+        prim.cvs[1] = min.y;
+        prim.cvs[2] = max.x;
+        prim.cvs[3] = max.y;
+        prim.radius = radius;
+        prim.width = width;
+        prim.paint = paint_index.index as u32;
+        prim.scissor = self.add_scissor() as u32;
+
+        self.add_prim(prim);
+    }
+
+    /// Fills a shape between a rect and a circle, e.g. one frame of an
+    /// avatar growing into a card.
+    pub fn fill_morph(&mut self, shape: &Morph, paint_index: PaintIndex) {
+        self.fill_rect(shape.rect, shape.radius(), paint_index);
+    }
+
+    /// Strokes a shape between a rect and a circle.
+    pub fn stroke_morph(&mut self, shape: &Morph, width: f32, paint_index: PaintIndex) {
+        let rect = shape.rect;
+        self.stroke_rect(rect.min(), rect.max(), shape.radius(), width, paint_index);
+    }
+
+    /// Strokes the inside of a rectangle with a width and color per side,
+    /// in the order top, right, bottom, left as in CSS. Sides meet along
+    /// miters, drawn as one prim without seams.
+    pub fn stroke_rect_sides<Rect: Into<LocalRect>>(
+        &mut self,
+        rect: Rect,
+        radius: f32,
+        widths: [f32; 4],
+        colors: [Color; 4],
+    ) {
+        // The shader indexes the side's paint from the first, so they
+        // can't be shared.
+        let paint = self.push_paint(Paint::solid_color(colors[0]));
+        for color in &colors[1..] {
+            self.push_paint(Paint::solid_color(*color));
+        }
+        if paint.index + 3 >= self.paint_count {
+            return;
+        }
+
+        let r: LocalRect = rect.into();
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::RectBorder as u32;
+        prim.cvs[0] = r.min_x();
+        prim.cvs[1] = r.min_y();
+        prim.cvs[2] = r.max_x();
+        prim.cvs[3] = r.max_y();
+        prim.radius = radius;
+        prim.start = self.scenes[self.cur_scene].cvs.len() as u32;
+        let [top, right, bottom, left] = widths;
+        self.add_cv([top.max(0.0), right.max(0.0)]);
+        self.add_cv([bottom.max(0.0), left.max(0.0)]);
+        prim.count = 2;
+        prim.paint = paint.index as u32;
+        prim.scissor = self.add_scissor() as u32;
+
+        self.add_prim(prim);
+    }
+
+    /// Strokes a focus ring `width` wide, `offset` outside a rectangle
+    /// with corner `radius`. The ring's corners are concentric with the
+    /// rectangle's, so they round out as the offset grows.
+    pub fn stroke_focus_ring<Rect: Into<LocalRect>>(
+        &mut self,
+        rect: Rect,
+        radius: f32,
+        width: f32,
+        offset: f32,
+        paint_index: PaintIndex,
+    ) {
+        let (ring, ring_radius) = focus_ring(rect.into(), radius, width, offset);
+        self.stroke_rect(ring.min(), ring.max(), ring_radius, width, paint_index);
+    }
+
+    /// Strokes a line segment.
+    pub fn stroke_segment<Pt: Into<LocalPoint>>(
+        &mut self,
+        a: Pt,
+        b: Pt,
+        width: f32,
+        paint_index: PaintIndex,
+    ) {
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::Segment as u32;
+        let ap: LocalPoint = a.into();
+        let bp: LocalPoint = b.into();
+        prim.cvs[0] = ap.x;
+        prim.cvs[1] = ap.y;
+        prim.cvs[2] = bp.x;
+        prim.cvs[3] = bp.y;
+        prim.width = width;
+        prim.paint = paint_index.index as u32;
+        prim.scissor = self.add_scissor() as u32;
+
+        self.add_prim(prim);
+    }
+
+    /// Strokes a polyline.
+    pub fn stroke_polyline<Pt: Into<LocalPoint> + Copy>(
+        &mut self,
+        points: &[Pt],
+        width: f32,
+        paint_index: PaintIndex,
+    ) {
+        let mut local = self.arena.take_points();
+        local.extend(points.iter().map(|p| (*p).into()));
+        // A device pixel along the local x axis.
+        let m = self.current_transform();
+        let x_scale = LocalVector::new(m.m11, m.m12).length() * self.device_px_ratio;
+        let bucket_width = 1.0 / x_scale.max(0.001);
+        if self.decimate_polylines && should_decimate(&local, bucket_width) {
+            decimate_min_max(&mut local, bucket_width);
+        }
+
+        for w in local.windows(2) {
+            self.stroke_segment(w[0], w[1], width, paint_index);
+        }
+        self.arena.put_points(local);
+    }
+
+    /// Strokes a polyline with a gradient along its length. Stops are
+    /// (offset, color) pairs sorted by offset, where 0 is the start of
+    /// the polyline and 1 is the end.
+    pub fn stroke_polyline_gradient<Pt: Into<LocalPoint> + Copy>(
+        &mut self,
+        points: &[Pt],
+        width: f32,
+        stops: &[(f32, Color)],
+    ) {
+        let mut local = self.arena.take_points();
+        local.extend(points.iter().map(|p| (*p).into()));
+        let total: f32 = local.windows(2).map(|w| (w[1] - w[0]).length()).sum();
+        if total <= 0.0 {
+            self.arena.put_points(local);
+            return;
+        }
+
+        let mut s = 0.0;
+        let mut ts = self.arena.take_floats();
+        for w in local.windows(2) {
+            let (a, b) = (w[0], w[1]);
+            let len = (b - a).length();
+            if len <= 0.0 {
+                continue;
+            }
+            let t0 = s / total;
+            let t1 = (s + len) / total;
+
+            // Split at stops so each piece is a two-color gradient.
+            ts.clear();
+            ts.push(t0);
+            ts.extend(stops.iter().map(|(t, _)| *t).filter(|t| *t > t0 && *t < t1));
+            ts.push(t1);
+
+            for piece in ts.windows(2) {
+                let pa = a.lerp(b, (piece[0] - t0) / (t1 - t0));
+                let pb = a.lerp(b, (piece[1] - t0) / (t1 - t0));
+                let paint = self.linear_gradient(
+                    pa,
+                    pb,
+                    stop_color(stops, piece[0]),
+                    stop_color(stops, piece[1]),
+                    0.0,
+                );
+                self.stroke_segment(pa, pb, width, paint);
+            }
+
+            s += len;
+        }
+        self.arena.put_floats(ts);
+        self.arena.put_points(local);
+    }
+
+    /// Strokes a line segment with round ends whose width changes
+    /// linearly from `width_a` to `width_b`.
+    pub fn stroke_tapered_segment<Pt: Into<LocalPoint>>(
+        &mut self,
+        a: Pt,
+        b: Pt,
+        width_a: f32,
+        width_b: f32,
+        paint_index: PaintIndex,
+    ) {
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::TaperedSegment as u32;
+        let ap: LocalPoint = a.into();
+        let bp: LocalPoint = b.into();
+        prim.cvs = [ap.x, ap.y, bp.x, bp.y, 0.5 * width_a, 0.5 * width_b];
+        prim.width = width_a.max(width_b);
+        prim.paint = paint_index.index as u32;
+        prim.scissor = self.add_scissor() as u32;
+
+        self.add_prim(prim);
+    }
+
+    /// Strokes a polyline with a width for each point.
+    pub fn stroke_polyline_var<Pt: Into<LocalPoint> + Copy>(
+        &mut self,
+        points: &[(Pt, f32)],
+        paint_index: PaintIndex,
+    ) {
+        for w in points.windows(2) {
+            self.stroke_tapered_segment(w[0].0, w[1].0, w[0].1, w[1].1, paint_index);
+        }
+    }
+
+    /// Strokes a polyline whose width is given by a profile function of
+    /// the distance along the polyline, from 0 at the start to 1 at the end.
+    pub fn stroke_polyline_profile<Pt: Into<LocalPoint> + Copy>(
+        &mut self,
+        points: &[Pt],
+        profile: impl Fn(f32) -> f32,
+        paint_index: PaintIndex,
+    ) {
+        let mut local = self.arena.take_points();
+        local.extend(points.iter().map(|p| (*p).into()));
+        let total: f32 = local.windows(2).map(|w| (w[1] - w[0]).length()).sum();
+        let width_at = |s: f32| profile(if total > 0.0 { s / total } else { 0.0 });
+
+        let mut s = 0.0;
+        for w in local.windows(2) {
+            let len = (w[1] - w[0]).length();
+            let (width_a, width_b) = (width_at(s), width_at(s + len));
+            self.stroke_tapered_segment(w[0], w[1], width_a, width_b, paint_index);
+            s += len;
+        }
+        self.arena.put_points(local);
+    }
+
+    /// Strokes a quadratic bezier segment.
+    pub fn stroke_bezier<Pt: Into<LocalPoint>>(
+        &mut self,
+        a: Pt,
+        b: Pt,
+        c: Pt,
+        width: f32,
+        paint_index: PaintIndex,
+    ) {
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::Bezier as u32;
+        let ap: LocalPoint = a.into();
+        let bp: LocalPoint = b.into();
+        let cp: LocalPoint = c.into();
+        prim.cvs[0] = ap.x;
+        prim.cvs[1] = ap.y;
+        prim.cvs[2] = bp.x;
+        prim.cvs[3] = bp.y;
+        prim.cvs[4] = cp.x;
+        prim.cvs[5] = cp.y;
+        prim.width = width;
+        prim.paint = paint_index.index as u32;
+        prim.scissor = self.add_scissor() as u32;
+
+        self.add_prim(prim);
+    }
+
+    /// Strokes node editor edges, with arrowheads filled in the same
+    /// paint. Pieces share one scissor, so thousands of edges are cheap to
+    /// record.
+    pub fn stroke_connectors(
+        &mut self,
+        connectors: &[Connector],
+        width: f32,
+        paint_index: PaintIndex,
+    ) {
+        let scissor = self.add_scissor() as u32;
+        let mut prim = Prim::default();
+        prim.width = width;
+        prim.paint = paint_index.index as u32;
+        prim.scissor = scissor;
+
+        for connector in connectors {
+            let (from, to) = (connector.from, connector.to);
+
+            // Both routes enter horizontally. Stop the line at the
+            // arrowhead's base so its cap doesn't poke past the tip.
+            let direction = match connector.route {
+                ConnectorRoute::Orthogonal { .. } if to.x < from.x => LocalVector::new(-1.0, 0.0),
+                _ => LocalVector::new(1.0, 0.0),
+            };
+            let end = to - direction * connector.arrow_size.max(0.0);
+
+            match connector.route {
+                ConnectorRoute::Cubic => {
+                    let (c1, c2) = cubic_controls(from, end, 4.0 * width);
+                    let tolerance = 0.25 / self.device_px_ratio;
+                    for [a, b, c] in cubic_to_quads([from, c1, c2, end], tolerance) {
+                        prim.prim_type = PrimType::Bezier as u32;
+                        prim.cvs = [a.x, a.y, b.x, b.y, c.x, c.y];
+                        self.add_prim(prim);
+                    }
+                }
+                ConnectorRoute::Orthogonal { radius } => {
+                    for piece in round_corners(&orthogonal_route(from, end), radius) {
+                        match piece {
+                            Piece::Segment(a, b) => {
+                                prim.prim_type = PrimType::Segment as u32;
+                                prim.cvs = [a.x, a.y, b.x, b.y, 0.0, 0.0];
+                            }
+                            Piece::Bezier(a, b, c) => {
+                                prim.prim_type = PrimType::Bezier as u32;
+                                prim.cvs = [a.x, a.y, b.x, b.y, c.x, c.y];
+                            }
+                        }
+                        self.add_prim(prim);
+                    }
+                }
+            }
+
+            if connector.arrow_size > 0.0 {
+                let [tip, a, b] = arrowhead(to, direction, connector.arrow_size);
+                self.move_to(tip);
+                self.line_to(a);
+                self.line_to(b);
+                self.line_to(tip);
+                self.fill(paint_index);
+            }
+        }
+    }
+
+    /// Move the pen to a point (path fills only)
+    pub fn move_to<Pt: Into<LocalPoint>>(&mut self, p: Pt) {
+        self.pen = p.into();
+    }
+
+    /// Makes a quadratic curve to a point (path fills only)
+    pub fn quad_to<Pt: Into<LocalPoint>>(&mut self, b: Pt, c: Pt) {
+        let cp: LocalPoint = c.into();
+        self.path_scanner
+            .segments
+            .push(PathSegment::new(self.pen, b.into(), cp));
+        self.pen = cp;
+    }
+
+    /// Makes a line to a point (path fills only)
+    pub fn line_to<Pt: Into<LocalPoint>>(&mut self, b: Pt) {
+        let bp: LocalPoint = b.into();
+        let cp = self.pen.lerp(bp, 0.5);
+        self.quad_to(cp, bp);
+    }
+
+    fn add_cv<Pt: Into<LocalPoint>>(&mut self, p: Pt) {
+        self.scenes[self.cur_scene].cvs.push(p.into())
+    }
+
+    /// Replays drawing recorded in a display list, e.g. on a worker
+    /// thread. State changes stay within the list: unmatched restores are
+    /// ignored and the state is restored afterwards, so lists from
+    /// untrusted clients can be drawn.
+    pub fn draw_display_list(&mut self, list: &DisplayList) {
+        let depth = self.state_stack.len();
+        self.save();
+        for command in list.commands() {
+            match command {
+                DrawCommand::Save => self.save(),
+                DrawCommand::Restore => {
+                    if self.state_stack.len() > depth + 1 {
+                        self.restore();
+                    }
+                }
+                DrawCommand::Translate(offset) => self.translate(*offset),
+                DrawCommand::Scale(scale) => self.scale(*scale),
+                DrawCommand::Rotate(theta) => self.rotate(*theta),
+                DrawCommand::Scissor(rect) => self.scissor(*rect),
+                DrawCommand::FillRect {
+                    rect,
+                    radius,
+                    color,
+                } => {
+                    let paint = self.color_paint(*color);
+                    self.fill_rect(*rect, *radius, paint);
+                }
+                DrawCommand::StrokeRect {
+                    rect,
+                    radius,
+                    width,
+                    color,
+                } => {
+                    let paint = self.color_paint(*color);
+                    self.stroke_rect(rect.min(), rect.max(), *radius, *width, paint);
+                }
+                DrawCommand::FillCircle {
+                    center,
+                    radius,
+                    color,
+                } => {
+                    let paint = self.color_paint(*color);
+                    self.fill_circle(*center, *radius, paint);
+                }
+                DrawCommand::StrokeSegment { a, b, width, color } => {
+                    let paint = self.color_paint(*color);
+                    self.stroke_segment(*a, *b, *width, paint);
+                }
+                DrawCommand::StrokeBezier {
+                    a,
+                    b,
+                    c,
+                    width,
+                    color,
+                } => {
+                    let paint = self.color_paint(*color);
+                    self.stroke_bezier(*a, *b, *c, *width, paint);
+                }
+                DrawCommand::FillPath {
+                    start,
+                    quads,
+                    color,
+                } => {
+                    let paint = self.color_paint(*color);
+                    self.move_to(*start);
+                    for (b, c) in quads {
+                        self.quad_to(*b, *c);
+                    }
+                    self.fill(paint);
+                }
+                DrawCommand::Text {
+                    text,
+                    size,
+                    color,
+                    max_width,
+                } => self.text(text, *size, *color, *max_width),
+            }
+        }
+        self.state_stack.truncate(depth);
+    }
+
+    /// Fills a path.
+    pub fn fill(&mut self, paint_index: PaintIndex) {
+        let scissor = self.add_scissor();
+
+        self.path_scanner.init();
+
+        while self.path_scanner.next() {
+            let mut prim = Prim::default();
+            prim.prim_type = PrimType::PathFill as u32;
+            prim.paint = paint_index.index as u32;
+            prim.scissor = scissor as u32;
+            prim.start = self.scenes[self.cur_scene].cvs.len() as u32;
+
+            let mut x_interval = Interval {
+                a: std::f32::MAX,
+                b: std::f32::MIN,
+            };
+
+            let mut index = self.path_scanner.first;
+            while let Some(a) = index {
+                for i in 0..3 {
+                    let p = self.path_scanner.segments[a].cvs[i];
+                    self.add_cv(p);
+                    x_interval.a = x_interval.a.min(p.x);
+                    x_interval.b = x_interval.b.max(p.x);
+                }
+                prim.count += 1;
+
+                index = self.path_scanner.segments[a].next;
+            }
+
+            prim.quad_bounds[0] = x_interval.a;
+            prim.quad_bounds[1] = self.path_scanner.interval.a;
+            prim.quad_bounds[2] = x_interval.b;
+            prim.quad_bounds[3] = self.path_scanner.interval.b;
+            prim.tex_bounds = prim.quad_bounds;
+
+            self.add_prim(prim);
+        }
+
+        self.path_scanner.segments.clear();
+    }
+
+    /// Fills a rectangle with a checkerboard, e.g. as the background
+    /// behind transparent images.
+    pub fn fill_checkerboard<Rect: Into<LocalRect>>(
+        &mut self,
+        rect: Rect,
+        cell_size: f32,
+        color_a: Color,
+        color_b: Color,
+        space: PatternSpace,
+    ) {
+        let paint_index = self.checkerboard_paint(cell_size, color_a, color_b, space);
+        self.fill_rect(rect, 0.0, paint_index);
+    }
+
+    /// Draws a grid of hairlines over a rectangle, with lines every
+    /// `cell_size` starting at the rectangle's origin. Every
+    /// `major_every`th line uses `major_paint`.
+    pub fn draw_grid<Rect: Into<LocalRect>>(
+        &mut self,
+        rect: Rect,
+        cell_size: f32,
+        major_every: u32,
+        minor_paint: PaintIndex,
+        major_paint: PaintIndex,
+    ) {
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::Grid as u32;
+        let r: LocalRect = rect.into();
+        let min = r.min();
+        let max = r.max();
+        prim.cvs[0] = min.x;
+        prim.cvs[1] = min.y;
+        prim.cvs[2] = max.x;
+        prim.cvs[3] = max.y;
+        prim.cvs[4] = cell_size.max(0.0001);
+        prim.cvs[5] = major_every.max(1) as f32;
+        prim.paint = minor_paint.index as u32;
+        prim.glyph = major_paint.index as u32;
+        prim.scissor = self.add_scissor() as u32;
+
+        self.add_prim(prim);
+    }
+
+    /// Fills many axis-aligned bars, snapped to device pixels.
+    pub fn fill_bars(&mut self, bars: &[BarSpec]) {
+        let xform = self.add_xform() as u32;
+        let scissor = self.add_scissor() as u32;
+        let scale = self.device_px_ratio;
+        let pad = self.aa_pad();
+        let z_index = self.state().z_index;
+
+        let prims = self.scenes[self.cur_scene]
+            .depthed_prims
+            .entry(z_index)
+            .or_default();
+        prims.reserve(bars.len());
+
+        for bar in bars {
+            let (x0, x1) = snap_interval(bar.x, bar.x + bar.width, scale);
+            let (y0, y1) = snap_interval(bar.y0, bar.y1, scale);
+
+            let mut prim = Prim::default();
+            prim.prim_type = PrimType::Rect as u32;
+            prim.cvs[0] = x0;
+            prim.cvs[1] = y0;
+            prim.cvs[2] = x1;
+            prim.cvs[3] = y1;
+            prim.paint = bar.paint.index as u32;
+            if self.validation {
+                if let Err(err) = validate_prim(&prim) {
+                    self.validation_errors.push(err);
+                    continue;
+                }
+            }
+            prim.expand_quad(pad);
+            prim.xform = xform;
+            prim.scissor = scissor;
+            prims.push(prim);
+        }
+    }
+
+    /// Draws a table's row backgrounds and gridlines with its top left at
+    /// `origin`, snapped to device pixels. `row_paints` are cycled, e.g.
+    /// two for alternating rows. Runs of rows with the same paint share a
+    /// rect and each gridline spans the table, so a grid of r rows and c
+    /// columns takes at most 2r + c + 2 prims rather than one per cell.
+    pub fn draw_table<Pt: Into<LocalPoint>>(
+        &mut self,
+        origin: Pt,
+        column_widths: &[f32],
+        row_heights: &[f32],
+        row_paints: &[PaintIndex],
+        border: Option<&TableBorder>,
+    ) {
+        let bars = table_bars(
+            origin.into(),
+            column_widths,
+            row_heights,
+            row_paints,
+            border,
+        );
+        self.fill_bars(&bars);
+    }
+
+    /// Draws a candlestick chart, snapped to device pixels. Candles which
+    /// close at or above their open use `up_paint`.
+    pub fn draw_candlesticks(
+        &mut self,
+        candles: &[Candle],
+        wick_width: f32,
+        up_paint: PaintIndex,
+        down_paint: PaintIndex,
+    ) {
+        let mut bars = Vec::with_capacity(candles.len() * 2);
+        for candle in candles {
+            let paint = if candle.close >= candle.open {
+                up_paint
+            } else {
+                down_paint
+            };
+            let center = candle.x + 0.5 * candle.width;
+            bars.push(BarSpec {
+                x: center - 0.5 * wick_width,
+                width: wick_width,
+                y0: candle.low,
+                y1: candle.high,
+                paint,
+            });
+            bars.push(BarSpec {
+                x: candle.x,
+                width: candle.width,
+                y0: candle.open,
+                y1: candle.close,
+                paint,
+            });
+        }
+        self.fill_bars(&bars);
+    }
+
+    /// Draws markers along a polyline, oriented along its direction.
+    pub fn draw_markers_along<Pt: Into<LocalPoint> + Copy>(
+        &mut self,
+        points: &[Pt],
+        placement: &MarkerPlacement,
+        marker: &MarkerShape,
+        paint_index: PaintIndex,
+    ) {
+        let mut local = self.arena.take_points();
+        local.extend(points.iter().map(|p| (*p).into()));
+        let mut markers = self.arena.take_markers();
+        marker_positions(&local, placement, &mut markers);
+        self.arena.put_points(local);
+        self.draw_markers(&markers, marker, paint_index);
+        self.arena.put_markers(markers);
+    }
+
+    /// Draws markers along quadratic curves from `start`, given as
+    /// (control, end) pairs as with `quad_to`, e.g. curved diagram edges.
+    /// Markers at the ends are oriented along the curves' tangents.
+    pub fn draw_markers_along_curve<Pt: Into<LocalPoint> + Copy>(
+        &mut self,
+        start: Pt,
+        quads: &[(Pt, Pt)],
+        placement: &MarkerPlacement,
+        marker: &MarkerShape,
+        paint_index: PaintIndex,
+    ) {
+        let quads: Vec<(LocalPoint, LocalPoint)> = quads
+            .iter()
+            .map(|(b, c)| ((*b).into(), (*c).into()))
+            .collect();
+        let markers = curve_marker_positions(start.into(), &quads, placement);
+        self.draw_markers(&markers, marker, paint_index);
+    }
+
+    /// Draws `marker` at each position, with +x along the direction.
+    fn draw_markers(
+        &mut self,
+        markers: &[(LocalPoint, LocalVector)],
+        marker: &MarkerShape,
+        paint_index: PaintIndex,
+    ) {
+        for &(p, dir) in markers {
+            // Marker space to local space.
+            let normal = LocalVector::new(-dir.y, dir.x);
+            let to_local = |q: LocalPoint| p + dir * q.x + normal * q.y;
+
+            match marker {
+                MarkerShape::Dot { radius } => self.fill_circle(p, *radius, paint_index),
+                MarkerShape::Arrow { length, width } => {
+                    self.move_to(p);
+                    self.line_to(to_local(LocalPoint::new(-length, 0.5 * width)));
+                    self.line_to(to_local(LocalPoint::new(-length, -0.5 * width)));
+                    self.line_to(p);
+                    self.fill(paint_index);
+                }
+                MarkerShape::Path(cvs) => {
+                    if let Some(first) = cvs.first() {
+                        self.move_to(to_local(*first));
+                        for pair in cvs[1..].chunks_exact(2) {
+                            self.quad_to(to_local(pair[0]), to_local(pair[1]));
+                        }
+                        self.fill(paint_index);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Local offset moving a baseline through `origin` onto a device pixel
+    /// row, or zero without baseline snapping.
+    fn baseline_snap(&self, origin: LocalPoint) -> LocalVector {
+        if !self.snap_baselines {
+            return LocalVector::zero();
+        }
+        let transform = self.state().transform;
+        let y = transform.transform_point(origin).y;
+        let dy = snap(y, self.device_px_ratio) - y;
+        transform.inverse().map_or(LocalVector::zero(), |inverse| {
+            inverse.transform_vector(euclid::vec2(0.0, dy))
+        })
+    }
+
+    fn setup_layout(&mut self, text: &str, size: u32, max_width: Option<f32>) {
+        profile_span!("vger::shape_text");
+        let scale = self.device_px_ratio;
+        let source = text;
+
+        let options = self.state().text_layout;
+        let max_width = max_width.map(|w| w * scale);
+        let scaled_size = self.font_px(size);
+
+        let line_height = match options.line_height {
+            LineHeight::Multiple(m) => m,
+            LineHeight::Absolute(height) => {
+                let font_height = self
+                    .glyph_cache
+                    .lock()
+                    .font
+                    .horizontal_line_metrics(scaled_size)
+                    .map_or(scaled_size, |m| m.new_line_size);
+                if font_height > 0.0 {
+                    height * scale / font_height
+                } else {
+                    1.0
+                }
+            }
+        };
+
+        self.layout.reset(&LayoutSettings {
+            max_width,
+            line_height,
+            horizontal_align: match options.align {
+                TextAlign::Left | TextAlign::Justify => HorizontalAlign::Left,
+                TextAlign::Center => HorizontalAlign::Center,
+                TextAlign::Right => HorizontalAlign::Right,
+            },
+            ..LayoutSettings::default()
+        });
+
+        let placeholder = self
+            .glyph_cache
+            .lock()
+            .font
+            .metrics(OBJECT_REPLACEMENT, scaled_size);
+        let objects = &self.inline_objects;
+
+        // Lines of different widths, and objects wider than their
+        // placeholders, are wrapped before layout.
+        let wrapped = match max_width {
+            Some(width) if options.is_indented() || self.hyphenates() || !objects.is_empty() => {
+                let object_at: Vec<usize> = text
+                    .match_indices(OBJECT_REPLACEMENT)
+                    .map(|(i, _)| i)
+                    .collect();
+                let glyph_cache = self.glyph_cache.lock();
+                wrap::wrap(
+                    text,
+                    |first| width - options.indent(first) * scale,
+                    |i, c| {
+                        let advance = glyph_cache.font.metrics(c, scaled_size).advance_width;
+                        match object_at
+                            .binary_search(&i)
+                            .ok()
+                            .and_then(|k| objects.get(k))
+                        {
+                            Some(object) => advance.max(object.width * scale),
+                            None => advance,
+                        }
+                    },
+                    |word| self.hyphen_breaks(word),
+                )
+            }
+            _ => None,
+        };
+        let (text, soft_breaks) = match &wrapped {
+            Some(w) => (w.text.as_str(), w.soft_breaks.as_slice()),
+            None => (text, &[][..]),
+        };
+
+        self.layout.append(
+            &[&self.glyph_cache.lock().font],
+            &TextStyle::new(text, scaled_size, 0),
+        );
+
+        self.chars.clear();
+        self.chars.extend(text.chars());
+        self.glyphs.clear();
+        self.glyphs.extend_from_slice(self.layout.glyphs());
+        self.shape_lines(source, scaled_size, max_width);
+        self.join_emoji_sequences(source, scaled_size, max_width);
+        self.baselines.clear();
+        self.object_rects.clear();
+        if let Some(lines) = self.layout.lines() {
+            let chars = &self.chars;
+            let objects = &self.inline_objects;
+            let mut next_object = 0;
+            let mut shift = 0.0;
+            let mut first = true;
+            for (i, line) in lines.iter().enumerate() {
+                let range = line.glyph_start..line.glyph_end.min(chars.len());
+                let paragraph_end = range
+                    .clone()
+                    .any(|i| chars[i] == '\n' && !soft_breaks.contains(&i));
+                let indent = options.indent(first) * scale;
+
+                // Objects on the line, with their glyph indices.
+                let line_objects: Vec<(usize, InlineObject)> = range
+                    .clone()
+                    .filter(|j| chars[*j] == OBJECT_REPLACEMENT)
+                    .zip(objects.iter().skip(next_object).copied())
+                    .collect();
+                next_object += line_objects.len();
+                if !line_objects.is_empty() {
+                    let widths: Vec<f32> = line_objects
+                        .iter()
+                        .map(|(_, object)| object.width * scale)
+                        .collect();
+                    let (offsets, extra) =
+                        object_offsets(&chars[range.clone()], &widths, placeholder.advance_width);
+                    let align = match (options.align, max_width) {
+                        (TextAlign::Center, Some(_)) => -extra / 2.0,
+                        (TextAlign::Right, Some(_)) => -extra,
+                        _ => 0.0,
+                    };
+                    for (glyph, offset) in self.glyphs[range.clone()].iter_mut().zip(offsets) {
+                        glyph.x += offset + align;
+                    }
+
+                    // Tall objects push the line down.
+                    let ascent = line_objects
+                        .iter()
+                        .map(|(_, object)| object.ascent() * scale)
+                        .fold(0.0, f32::max);
+                    shift += (ascent - line.max_ascent).max(0.0);
+                }
+
+                // Lines ending paragraphs aren't stretched.
+                if let (TextAlign::Justify, Some(width)) = (options.align, max_width) {
+                    if !paragraph_end && i + 1 < lines.len() {
+                        let rights: Vec<f32> = self.glyphs[range.clone()]
+                            .iter()
+                            .map(|glyph| glyph.x + glyph.width as f32)
+                            .collect();
+                        let offsets =
+                            justify_offsets(&chars[range.clone()], &rights, width - indent);
+                        for (glyph, offset) in self.glyphs[range.clone()].iter_mut().zip(offsets) {
+                            glyph.x += offset;
+                        }
+                    }
+                }
+
+                // Layout y runs up, so later paragraphs move down.
+                let offset = options.indent_offset(indent);
+                for glyph in &mut self.glyphs[range] {
+                    glyph.x += offset;
+                    glyph.y -= shift;
+                }
+                let baseline = line.baseline_y - shift;
+                self.baselines.push(baseline);
+
+                for (j, object) in &line_objects {
+                    let x = self.glyphs[*j].x - placeholder.xmin as f32;
+                    self.object_rects.push(LocalRect::new(
+                        [x / scale, baseline / scale + object.baseline_shift].into(),
+                        [object.width, object.height].into(),
+                    ));
+                }
+                let descent = line_objects
+                    .iter()
+                    .map(|(_, object)| object.descent() * scale)
+                    .fold(0.0, f32::max);
+                shift += (descent + line.min_descent).max(0.0);
+                if paragraph_end {
+                    shift += options.paragraph_spacing * scale;
+                }
+                first = paragraph_end;
+            }
+        }
+    }
+
+    /// Pixel size of the built-in font for text of `size`, after
+    /// `size_adjust`.
+    fn font_px(&self, size: u32) -> f32 {
+        let x_height = self.glyph_cache.lock().x_height();
+        adjusted_size(
+            size as f32 * self.device_px_ratio,
+            self.state().text_layout.size_adjust,
+            Some(x_height),
+        )
+    }
+
+    /// Shapes each laid out line with `font_features`, replacing the
+    /// glyphs fontdue picked by character. Lines keep their breaks, but
+    /// centered and right aligned lines are placed again.
+    fn shape_lines(&mut self, source: &str, scaled_size: f32, max_width: Option<f32>) {
+        self.glyph_ids.clear();
+        if self.font_features.is_empty() {
+            return;
+        }
+        let chars = &self.chars;
+        let lines: Vec<std::ops::Range<usize>> = self.layout.lines().map_or(vec![], |lines| {
+            lines
+                .iter()
+                .map(|line| line.glyph_start..line.glyph_end.min(chars.len()))
+                .collect()
+        });
+        let offsets = source_offsets(source, chars);
+        self.glyph_ids = vec![None; chars.len()];
+
+        let align = self.state().text_layout.align;
+        let glyph_cache = self.glyph_cache.lock();
+        let font = &glyph_cache.font;
+        let font_ref = swash::FontRef::from_index(glyphs::FONT, 0).unwrap();
+        let font_features = &self.font_features;
+        for range in lines.into_iter().filter(|range| !range.is_empty()) {
+            let last = range.end - 1;
+            let metrics = |j: usize| font.metrics(chars[j], scaled_size);
+            let start = self.glyphs[range.start].x - metrics(range.start).xmin as f32;
+            let end = self.glyphs[last].x - metrics(last).xmin as f32 + metrics(last).advance_width;
+
+            let mut pen = start;
+            for (run, active) in feature_runs(&offsets[range.clone()], font_features) {
+                let run = run.start + range.start..run.end + range.start;
+                let run_text: String = chars[run.clone()].iter().collect();
+                let char_at = |byte: u32| run_text[..byte as usize].chars().count();
+
+                let features = active.iter().map(|i| {
+                    let feature = &font_features[*i];
+                    (swash::tag_from_bytes(&feature.tag), feature.value)
+                });
+                let mut shaper = self
+                    .shape_context
+                    .builder(font_ref)
+                    .size(scaled_size)
+                    .features(features)
+                    .build();
+                shaper.add_str(&run_text);
+
+                let mut x = 0.0;
+                let mut clusters = vec![];
+                shaper.shape_with(|cluster| {
+                    let glyphs = cluster
+                        .glyphs
+                        .iter()
+                        .map(|glyph| {
+                            let shaped = (glyph.id, x + glyph.x, glyph.y);
+                            x += glyph.advance;
+                            shaped
+                        })
+                        .collect();
+                    clusters.push((
+                        char_at(cluster.source.start)..char_at(cluster.source.end),
+                        glyphs,
+                    ));
+                });
+
+                let mut last_x = pen;
+                for (k, shaped) in cluster_glyphs(run.len(), &clusters).into_iter().enumerate() {
+                    let j = run.start + k;
+                    let old = metrics(j);
+                    let glyph = &mut self.glyphs[j];
+                    match shaped {
+                        Some((id, gx, gy)) => {
+                            let new = font.metrics_indexed(id, scaled_size);
+                            glyph.x = pen + gx + new.xmin as f32;
+                            glyph.y += gy + (new.ymin - old.ymin) as f32;
+                            glyph.width = new.width;
+                            glyph.height = new.height;
+                            last_x = glyph.x;
+                            self.glyph_ids[j] = Some(id);
+                        }
+                        None => {
+                            glyph.x = last_x;
+                            glyph.width = 0;
+                            glyph.height = 0;
+                        }
+                    }
+                }
+                pen += x;
+            }
+
+            // Shaping changes the line's width.
+            let extra = pen - end;
+            let offset = match (align, max_width) {
+                (TextAlign::Center, Some(_)) => -extra / 2.0,
+                (TextAlign::Right, Some(_)) => -extra,
+                _ => 0.0,
+            };
+            for glyph in &mut self.glyphs[range] {
+                glyph.x += offset;
+            }
+        }
+    }
+
+    /// Collapses emoji sequences the font lacks, such as 👩‍👩‍👧 or 👍🏽, into
+    /// their first glyph, which draws the whole sequence from an emoji
+    /// font, moving the rest of the line back.
+    fn join_emoji_sequences(&mut self, source: &str, scaled_size: f32, max_width: Option<f32>) {
+        self.emoji_sequences.clear();
+        if source.is_ascii() {
+            return;
+        }
+        let chars = &self.chars;
+        let lines: Vec<std::ops::Range<usize>> = self.layout.lines().map_or(vec![], |lines| {
+            lines
+                .iter()
+                .map(|line| line.glyph_start..line.glyph_end.min(chars.len()))
+                .collect()
+        });
+        let sources = source_chars(source, chars);
+        let mut removed = vec![0.0; lines.len()];
+
+        let glyph_cache = self.glyph_cache.lock();
+        let font = &glyph_cache.font;
+        let mut next_char = 0;
+        for range in grapheme_ranges(source) {
+            let first = next_char;
+            let grapheme = &source[range];
+            next_char += grapheme.chars().count();
+            if next_char - first < 2 || !emoji::is_emoji(grapheme) {
+                continue;
+            }
+            let (start, end) = (sources[first], sources[next_char - 1] + 1);
+            let line = match lines.iter().position(|line| line.contains(&start)) {
+                Some(line) if end <= lines[line].end => line,
+                _ => continue,
+            };
+            if chars[start].is_control() || font.lookup_glyph_index(chars[start]) != 0 {
+                continue;
+            }
+
+            let advance: f32 = chars[start + 1..end]
+                .iter()
+                .map(|c| font.metrics(*c, scaled_size).advance_width)
+                .sum();
+            let x = self.glyphs[start].x;
+            for glyph in &mut self.glyphs[start + 1..end] {
+                glyph.x = x;
+                glyph.width = 0;
+                glyph.height = 0;
+            }
+            for glyph in &mut self.glyphs[end..lines[line].end] {
+                glyph.x -= advance;
+            }
+            removed[line] += advance;
+            self.emoji_sequences.push(start..end);
+        }
+
+        let align = self.state().text_layout.align;
+        for (range, removed) in lines.into_iter().zip(removed) {
+            let offset = match (align, max_width) {
+                (TextAlign::Center, Some(_)) => removed / 2.0,
+                (TextAlign::Right, Some(_)) => removed,
+                _ => 0.0,
+            };
+            for glyph in &mut self.glyphs[range] {
+                glyph.x += offset;
+            }
+        }
+    }
+
+    #[cfg(feature = "hyphenate")]
+    fn hyphenates(&self) -> bool {
+        self.hyphenator.is_some()
+    }
+
+    #[cfg(not(feature = "hyphenate"))]
+    fn hyphenates(&self) -> bool {
+        false
+    }
+
+    /// Where `word` may be hyphenated, as byte indices.
+    #[cfg(feature = "hyphenate")]
+    fn hyphen_breaks(&self, word: &str) -> Vec<usize> {
+        use hyphenation::Hyphenator;
+        self.hyphenator
+            .as_ref()
+            .map_or(vec![], |dictionary| dictionary.hyphenate(word).breaks)
+    }
+
+    #[cfg(not(feature = "hyphenate"))]
+    fn hyphen_breaks(&self, _word: &str) -> Vec<usize> {
+        vec![]
+    }
+
+    /// Draws a glyph with its origin at `(x, y)`. `image` rasterizes it at
+    /// `size`, and its placement is in local units. It's only called when
+    /// the glyph isn't cached. See `render_scaled_glyph` for text which is
+    /// sharp on high density displays.
+    pub fn render_glyph<'a>(
+        &mut self,
+        x: f32,
         y: f32,
         font_id: cosmic_text::fontdb::ID,
         glyph_id: u16,
@@ -601,20 +3426,160 @@ impl Vger {
         subpx: (SubpixelBin, SubpixelBin),
         image: impl FnOnce() -> SwashImage,
         paint_index: PaintIndex,
+    ) {
+        let glyph = GlyphPlacement {
+            x,
+            y,
+            font_id,
+            glyph_id,
+            size,
+            subpx,
+        };
+        self.render_glyph_at_scale(glyph, 1.0, image, paint_index)
+    }
+
+    /// Like `render_glyph`, but `image` rasterizes the glyph at `size`
+    /// times `device_px_ratio`, and its placement is in device pixels.
+    /// Glyphs are cached per scale, so moving to a display with another
+    /// scale rasterizes them again.
+    pub fn render_scaled_glyph(
+        &mut self,
+        x: f32,
+        y: f32,
+        font_id: cosmic_text::fontdb::ID,
+        glyph_id: u16,
+        size: u32,
+        subpx: (SubpixelBin, SubpixelBin),
+        image: impl FnOnce() -> SwashImage,
+        paint_index: PaintIndex,
+    ) {
+        let glyph = GlyphPlacement {
+            x,
+            y,
+            font_id,
+            glyph_id,
+            size,
+            subpx,
+        };
+        self.render_glyph_at_scale(glyph, self.device_px_ratio, image, paint_index)
+    }
+
+    fn render_glyph_at_scale(
+        &mut self,
+        glyph: GlyphPlacement,
+        scale: f32,
+        image: impl FnOnce() -> SwashImage,
+        paint_index: PaintIndex,
+    ) {
+        let GlyphPlacement {
+            x,
+            y,
+            font_id,
+            glyph_id,
+            size,
+            subpx,
+        } = glyph;
+        let binning = self.subpixel_binning;
+        let subpx = (binning.quantize(subpx.0), binning.quantize(subpx.1));
+        let info = self
+            .glyph_cache
+            .lock()
+            .get_glyph_mask(font_id, glyph_id, size, scale, subpx, image);
+        if let Some(rect) = info.rect {
+            let snap = self.baseline_snap([x, y].into());
+            let mut prim = Prim::default();
+            prim.prim_type = if info.colored {
+                PrimType::ColorGlyph
+            } else {
+                PrimType::Glyph
+            } as u32;
+
+            // Placement is in pixels at `scale`.
+            let x = x + info.left as f32 / scale;
+            let y = y - info.top as f32 / scale;
+            prim.quad_bounds = [
+                x,
+                y,
+                x + rect.width as f32 / scale,
+                y + rect.height as f32 / scale,
+            ];
+
+            prim.tex_bounds = [
+                rect.x as f32,
+                rect.y as f32,
+                (rect.x + rect.width) as f32,
+                (rect.y + rect.height) as f32,
+            ];
+            prim.paint = paint_index.index as u32;
+            prim.scissor = self.add_scissor() as u32;
+            prim.offset_quad(snap);
+
+            self.add_prim(prim);
+        }
+    }
+
+    pub fn render_svg(
+        &mut self,
+        x: f32,
+        y: f32,
+        key: &SvgKey,
+        width: u32,
+        height: u32,
+        image: impl FnOnce() -> Vec<u8>,
+        paint_index: Option<PaintIndex>,
+    ) {
+        let info = self
+            .glyph_cache
+            .lock()
+            .get_svg_mask(key, width, height, image);
+        if let Some(rect) = info.rect {
+            let mut prim = Prim::default();
+            prim.prim_type = if paint_index.is_some() {
+                PrimType::OverrideColorSvg
+            } else {
+                PrimType::ColorGlyph
+            } as u32;
+
+            let x = x + info.left as f32;
+            let y = y - info.top as f32;
+            prim.quad_bounds = [x, y, x + rect.width as f32, y + rect.height as f32];
+
+            prim.tex_bounds = [
+                rect.x as f32,
+                rect.y as f32,
+                (rect.x + rect.width) as f32,
+                (rect.y + rect.height) as f32,
+            ];
+            let paint_index =
+                paint_index.unwrap_or_else(|| self.color_paint(Color::new(1.0, 1.0, 1.0, 1.0)));
+            prim.paint = paint_index.index as u32;
+            prim.scissor = self.add_scissor() as u32;
+
+            self.add_prim(prim);
+        }
+    }
+
+    /// Renders an RGBA image, uploading it to the color atlas on first use.
+    pub fn render_image(
+        &mut self,
+        x: f32,
+        y: f32,
+        key: &ImageKey,
+        width: u32,
+        height: u32,
+        image: impl FnOnce() -> Vec<u8>,
     ) {
         let info = self
             .glyph_cache
-            .get_glyph_mask(font_id, glyph_id, size, subpx, image);
+            .lock()
+            .get_image_mask(key, width, height, image);
         if let Some(rect) = info.rect {
-            let mut prim = Prim::default();
-            prim.prim_type = if info.colored {
-                PrimType::ColorGlyph
-            } else {
-                PrimType::Glyph
-            } as u32;
+            let paint = self.color_paint(Color::new(1.0, 1.0, 1.0, 1.0));
 
-            let x = x + info.left as f32;
-            let y = y - info.top as f32;
+            let mut prim = Prim::default();
+            prim.prim_type = PrimType::ColorGlyph as u32;
+            prim.flags = self.state().image_sampling.flags();
+            prim.radius = self.state().image_corner_radius;
             prim.quad_bounds = [x, y, x + rect.width as f32, y + rect.height as f32];
 
             prim.tex_bounds = [
@@ -623,97 +3588,930 @@ impl Vger {
                 (rect.x + rect.width) as f32,
                 (rect.y + rect.height) as f32,
             ];
-            prim.paint = paint_index.index as u32;
+            prim.paint = paint.index as u32;
+            prim.scissor = self.add_scissor() as u32;
+
+            self.add_prim(prim);
+        }
+    }
+
+    /// Renders an RGBA image scaled to fill a rectangle, with an opacity.
+    pub fn render_image_rect<Rect: Into<LocalRect>>(
+        &mut self,
+        rect: Rect,
+        key: &ImageKey,
+        width: u32,
+        height: u32,
+        image: impl FnOnce() -> Vec<u8>,
+        alpha: f32,
+    ) {
+        let info = self
+            .glyph_cache
+            .lock()
+            .get_image_mask(key, width, height, image);
+        if let Some(atlas_rect) = info.rect {
+            let r: LocalRect = rect.into();
+            let paint = self.color_paint(Color::new(1.0, 1.0, 1.0, alpha));
+
+            let mut prim = Prim::default();
+            prim.prim_type = PrimType::ColorGlyph as u32;
+            prim.flags = self.state().image_sampling.flags();
+            prim.radius = self.state().image_corner_radius;
+            prim.quad_bounds = [r.min_x(), r.min_y(), r.max_x(), r.max_y()];
+            prim.tex_bounds = [
+                atlas_rect.x as f32,
+                atlas_rect.y as f32,
+                (atlas_rect.x + atlas_rect.width) as f32,
+                (atlas_rect.y + atlas_rect.height) as f32,
+            ];
+            prim.paint = paint.index as u32;
             prim.scissor = self.add_scissor() as u32;
 
-            self.render(prim);
+            self.add_prim(prim);
+        }
+    }
+
+    /// Draws an RGBA image as a border around `rect`, sliced and stretched
+    /// as in CSS `border-image`, uploading it to the color atlas on first
+    /// use.
+    pub fn render_border_image<Rect: Into<LocalRect>>(
+        &mut self,
+        rect: Rect,
+        key: &ImageKey,
+        width: u32,
+        height: u32,
+        image: impl FnOnce() -> Vec<u8>,
+        border: &BorderImage,
+    ) {
+        let info = self
+            .glyph_cache
+            .lock()
+            .get_image_mask(key, width, height, image);
+        if let Some(atlas_rect) = info.rect {
+            let paint = self.color_paint(Color::WHITE);
+            let scissor = self.add_scissor() as u32;
+            let sampling = self.state().image_sampling.flags();
+            let (x, y) = (atlas_rect.x as f32, atlas_rect.y as f32);
+
+            for (dest, src) in nine_slice(rect.into(), width as f32, height as f32, border) {
+                let mut prim = Prim::default();
+                prim.prim_type = PrimType::ColorGlyph as u32;
+                prim.flags = sampling;
+                prim.quad_bounds = dest;
+                prim.tex_bounds = [x + src[0], y + src[1], x + src[2], y + src[3]];
+                prim.paint = paint.index as u32;
+                prim.scissor = scissor;
+
+                self.add_prim(prim);
+            }
+        }
+    }
+
+    /// Draws what `draw` draws at the origin, from pixels rendered into a
+    /// texture from the texture pool the first time and reused while `key`
+    /// and `size` are unchanged, so a static panel costs one quad per
+    /// frame. `key` should be a hash of everything the content depends on.
+    ///
+    /// The layer is rendered at the device pixel ratio without the current
+    /// transform, so it's resampled if scaled or rotated. It's rendered
+    /// again if the pool drops its texture, e.g. after going unused for a
+    /// while or over the pool's budget. Layers larger than the window,
+    /// nested in another cached layer, or in the overlay are drawn
+    /// directly. Accessibility nodes aren't recorded for cached content.
+    pub fn cached_layer<Size: Into<LocalSize>>(
+        &mut self,
+        key: u64,
+        size: Size,
+        draw: impl FnOnce(&mut Vger),
+    ) {
+        let size: LocalSize = size.into();
+        let scale = self.device_px_ratio;
+        let width = (size.width * scale).ceil().max(1.0) as u32;
+        let height = (size.height * scale).ceil().max(1.0) as u32;
+
+        let (max_width, max_height) = self.layer_target_size();
+        if width > max_width
+            || height > max_height
+            || self.recording_layer
+            || self.cur_scene == OVERLAY_SCENE
+        {
+            draw(self);
+            return;
+        }
+
+        // Kept from an earlier frame, or already recorded this frame.
+        let target = LayerTarget::Pool { key, width, height };
+        let recorded = self.scenes[self.cur_scene].layers.iter().any(|layer| {
+            matches!(layer.target, LayerTarget::Pool { key: k, width: w, height: h }
+                if (k, w, h) == (key, width, height))
+        });
+        let cached = self.layer_sizes.get(&key) == Some(&(width, height))
+            && self.textures.kept(key).is_some();
+        if !recorded && !cached {
+            self.textures.release(key);
+            self.layer_sizes.insert(key, (width, height));
+            self.record_layer(target, draw);
+        }
+
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::Layer as u32;
+        prim.quad_bounds = [0.0, 0.0, width as f32 / scale, height as f32 / scale];
+        prim.tex_bounds = [0.0, 0.0, width as f32, height as f32];
+        prim.scissor = self.add_scissor() as u32;
+
+        self.scenes[self.cur_scene].layer_keys.push(key);
+        self.add_layer_prim(prim, Some(key));
+    }
+
+    /// Draws text at the origin, as `text` does, over a rounded rect of
+    /// its blurred, tinted backdrop, e.g. for map labels and captions.
+    /// `backdrop` draws what's behind the label, in the current local
+    /// coordinates, and is rendered into the color atlas each frame under
+    /// `key`.
+    ///
+    /// Each scrim costs a layer pass every frame, and its blur samples the
+    /// backdrop 49 times per pixel, so use them for a few small labels
+    /// rather than large panels.
+    pub fn draw_label_scrim(
+        &mut self,
+        key: u64,
+        text: &str,
+        style: &LabelScrim,
+        backdrop: impl FnOnce(&mut Vger),
+    ) {
+        let bounds = self.text_bounds(text, style.text_size, style.max_width);
+        if !bounds.is_empty() {
+            let rect = bounds.inflate(style.padding, style.padding);
+            self.draw_backdrop(key, rect, style.radius, style.blur, backdrop);
+            let tint = self.color_paint(style.tint);
+            self.fill_rect(rect, style.radius, tint);
+        }
+        self.text(text, style.text_size, style.text_color, style.max_width);
+    }
+
+    /// Draws `rect` of what `backdrop` draws, blurred and masked to a
+    /// rounded rect.
+    fn draw_backdrop(
+        &mut self,
+        key: u64,
+        rect: LocalRect,
+        radius: f32,
+        blur: f32,
+        backdrop: impl FnOnce(&mut Vger),
+    ) {
+        let scale = self.device_px_ratio;
+        let width = (rect.width() * scale).ceil().max(1.0) as u32;
+        let height = (rect.height() * scale).ceil().max(1.0) as u32;
+        let (max_width, max_height) = self.layer_target_size();
+        if width > max_width || height > max_height {
+            return;
+        }
+
+        let key = LayerKey::Backdrop(key);
+        let cached = self.glyph_cache.lock().get_layer(key, width, height);
+        let atlas_rect = match cached {
+            Some(atlas_rect) => atlas_rect,
+            None => match self.glyph_cache.lock().add_layer(key, width, height) {
+                Some(atlas_rect) => atlas_rect,
+                None => return,
+            },
+        };
+        self.record_layer(LayerTarget::Atlas(atlas_rect), |vger| {
+            vger.translate(-rect.origin.to_vector());
+            backdrop(vger);
+        });
+
+        // The layer is sampled in atlas pixels, so the blur and mask are
+        // too.
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::Layer as u32;
+        prim.quad_bounds = [
+            rect.min_x(),
+            rect.min_y(),
+            rect.min_x() + width as f32 / scale,
+            rect.min_y() + height as f32 / scale,
+        ];
+        prim.tex_bounds = [
+            atlas_rect.x as f32,
+            atlas_rect.y as f32,
+            (atlas_rect.x + atlas_rect.width) as f32,
+            (atlas_rect.y + atlas_rect.height) as f32,
+        ];
+        prim.width = blur.max(0.0) * scale;
+        prim.radius = radius.max(0.0) * scale;
+        prim.scissor = self.add_scissor() as u32;
+
+        self.add_prim(prim);
+    }
+
+    /// Records a layer's prims apart from the frame's, with a fresh
+    /// state.
+    fn record_layer(&mut self, target: LayerTarget, draw: impl FnOnce(&mut Vger)) {
+        let frame_prims = std::mem::take(&mut self.scenes[self.cur_scene].depthed_prims);
+        let frame_runs = std::mem::take(&mut self.scenes[self.cur_scene].runs);
+        let depth = self.state_stack.len();
+        self.state_stack.push(State::new());
+        let recording = std::mem::replace(&mut self.recording_layer, true);
+
+        draw(self);
+
+        self.recording_layer = recording;
+        self.state_stack.truncate(depth);
+        let scene = &mut self.scenes[self.cur_scene];
+        let layer_prims = std::mem::replace(&mut scene.depthed_prims, frame_prims);
+        scene.runs = frame_runs;
+        scene.add_layer(target, layer_prims);
+    }
+
+    /// Registers a texture created outside vger so it can be drawn with
+    /// `render_external`, e.g. a Wayland client's buffer imported from a
+    /// dmabuf with `wgpu::Device::create_texture_from_hal`. `desc` is the
+    /// texture's descriptor.
+    ///
+    /// Each frame the texture is drawn, it's copied into the color atlas on
+    /// the gpu, so it's never read back to the cpu. It must be
+    /// `Rgba8Unorm`, with premultiplied alpha as Wayland buffers have, and
+    /// `COPY_SRC` usage. Returns `None` otherwise.
+    pub fn register_external_texture(
+        &mut self,
+        texture: wgpu::Texture,
+        desc: &wgpu::TextureDescriptor,
+    ) -> Option<ExternalImage> {
+        self.external_textures.register(texture, desc)
+    }
+
+    /// Stops drawing an external texture and hands it back, e.g. when the
+    /// client releases its buffer. Its copy in the color atlas is freed.
+    pub fn unregister_external_texture(&mut self, image: ExternalImage) -> Option<wgpu::Texture> {
+        self.glyph_cache
+            .lock()
+            .remove_layer(LayerKey::External(image.key()));
+        self.external_textures.remove(image)
+    }
+
+    /// Draws an external texture's current contents scaled to fill `rect`.
+    pub fn render_external<Rect: Into<LocalRect>>(&mut self, image: ExternalImage, rect: Rect) {
+        let (width, height) = match self.external_textures.get(image) {
+            Some(external) => (external.width, external.height),
+            None => return,
+        };
+
+        let key = LayerKey::External(image.key());
+        let cached = self.glyph_cache.lock().get_layer(key, width, height);
+        let atlas_rect = match cached {
+            Some(atlas_rect) => atlas_rect,
+            None => match self.glyph_cache.lock().add_layer(key, width, height) {
+                Some(atlas_rect) => atlas_rect,
+                None => return,
+            },
+        };
+
+        // Copy once per frame however many times it's drawn.
+        let externals = &mut self.scenes[self.cur_scene].externals;
+        if !externals.iter().any(|(i, _)| *i == image) {
+            externals.push((image, atlas_rect));
+        }
+
+        let r: LocalRect = rect.into();
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::Layer as u32;
+        prim.quad_bounds = [r.min_x(), r.min_y(), r.max_x(), r.max_y()];
+        prim.tex_bounds = [
+            atlas_rect.x as f32,
+            atlas_rect.y as f32,
+            (atlas_rect.x + atlas_rect.width) as f32,
+            (atlas_rect.y + atlas_rect.height) as f32,
+        ];
+
+        // Layers take their corner radius in texels.
+        let texels = (width as f32 / r.width()).min(height as f32 / r.height());
+        prim.radius = self.state().image_corner_radius * texels;
+        prim.scissor = self.add_scissor() as u32;
+
+        self.add_prim(prim);
+    }
+
+    /// Draws a video frame scaled to fill `rect`, converting it from YUV on
+    /// the gpu. `stream` identifies the video, so its frames reuse the same
+    /// atlas space. Controls drawn afterwards go on top.
+    pub fn render_video<Rect: Into<LocalRect>>(
+        &mut self,
+        stream: u64,
+        frame: VideoFrame,
+        rect: Rect,
+    ) {
+        let key = LayerKey::Video(stream);
+        let (width, height) = (frame.width, frame.height);
+        let cached = self.glyph_cache.lock().get_layer(key, width, height);
+        let atlas_rect = match cached {
+            Some(atlas_rect) => atlas_rect,
+            None => match self.glyph_cache.lock().add_layer(key, width, height) {
+                Some(atlas_rect) => atlas_rect,
+                None => return,
+            },
+        };
+        self.scenes[self.cur_scene].videos.push((frame, atlas_rect));
+
+        let r: LocalRect = rect.into();
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::Layer as u32;
+        prim.quad_bounds = [r.min_x(), r.min_y(), r.max_x(), r.max_y()];
+        prim.tex_bounds = [
+            atlas_rect.x as f32,
+            atlas_rect.y as f32,
+            (atlas_rect.x + atlas_rect.width) as f32,
+            (atlas_rect.y + atlas_rect.height) as f32,
+        ];
+        prim.scissor = self.add_scissor() as u32;
+
+        self.add_prim(prim);
+    }
+
+    /// Sets the fonts `text` draws characters the built-in font lacks
+    /// from, tried in order. By default they're the system's color emoji
+    /// fonts from `emoji::system_emoji_font_paths`.
+    pub fn set_emoji_fonts(&mut self, fonts: Vec<EmojiFont>) {
+        self.glyph_cache.lock().set_emoji_fonts(fonts);
+    }
+
+    /// Sets a function called when the atlases fill up and cached glyphs
+    /// and images are evicted, e.g. to log a warning. Atlases are clamped
+    /// to the device's largest texture, so they can't grow instead. See
+    /// `SharedGlyphCache::with_atlas_size`.
+    pub fn set_atlas_full_callback(&mut self, callback: Option<AtlasFullCallback>) {
+        self.glyph_cache.lock().set_atlas_full_callback(callback);
+    }
+
+    /// Removes an image from the cache, e.g. when it won't be drawn again,
+    /// freeing its atlas space. Don't evict an image drawn in the frame
+    /// being recorded, since its space may be reused.
+    pub fn evict_image(&mut self, key: &ImageKey) {
+        self.glyph_cache.lock().remove_image(key);
+    }
+
+    /// Renders text.
+    pub fn text(&mut self, text: &str, size: u32, color: Color, max_width: Option<f32>) {
+        self.text_with_shadows(text, size, color, max_width, &[]);
+    }
+
+    /// Renders text over shadows. As in CSS, the first shadow is on top.
+    pub fn text_with_shadows(
+        &mut self,
+        text: &str,
+        size: u32,
+        color: Color,
+        max_width: Option<f32>,
+        shadows: &[TextShadow],
+    ) {
+        self.setup_layout(text, size, max_width);
+        self.draw_glyphs(size, color, shadows);
+        self.add_access_text(text);
+    }
+
+    /// Draws the glyphs of the current layout.
+    fn draw_glyphs(&mut self, size: u32, color: Color, shadows: &[TextShadow]) {
+        let scale = self.device_px_ratio;
+        let scaled_size = self.font_px(size);
+        let size_adjust = self.state().text_layout.size_adjust;
+
+        let shadow_paints: Vec<PaintIndex> = shadows
+            .iter()
+            .map(|shadow| self.color_paint(shadow.color))
+            .collect();
+        let paint = self.color_paint(color);
+        let scissor = self.add_scissor() as u32;
+        let outlined = self.outlined(scaled_size);
+
+        // Each line snaps by itself, as line heights needn't be whole
+        // pixels.
+        let mut snaps = self.arena.take_vectors();
+        snaps.resize(self.glyphs.len(), self.baseline_snap(LocalPoint::zero()));
+        if let Some(lines) = self.layout.lines() {
+            for (line, baseline) in lines.iter().zip(&self.baselines) {
+                let snap = self.baseline_snap([0.0, baseline / scale].into());
+                let end = line.glyph_end.min(snaps.len());
+                for s in snaps.get_mut(line.glyph_start..end).into_iter().flatten() {
+                    *s = snap;
+                }
+            }
+        }
+
+        let mut prims = self.arena.take_prims();
+        let mut shadow_prims = self.arena.take_prims();
+        // Glyph ids and origins, in device pixels, of glyphs drawn as paths.
+        let mut outlines = self.arena.take_outlines();
+        for (i, glyph) in self.glyphs.iter().enumerate() {
+            let c = self.chars[i];
+            if c == OBJECT_REPLACEMENT {
+                continue;
+            }
+            let snap = snaps[i];
+
+            // Emoji sequences draw as one glyph, at their first character.
+            let sequence = self.emoji_sequences.iter().find(|seq| seq.contains(&i));
+            if sequence.map_or(false, |seq| seq.start != i) {
+                continue;
+            }
+
+            if outlined {
+                let glyph_cache = self.glyph_cache.lock();
+                let id = match self.glyph_ids.get(i) {
+                    Some(Some(id)) => *id,
+                    Some(None) => continue,
+                    None => glyph_cache.font.lookup_glyph_index(c),
+                };
+                if id != 0 {
+                    let origin =
+                        outline_origin(&glyph_cache.font, id, glyph.x, glyph.y, scaled_size);
+                    outlines.push((id, origin + snap * scale));
+                    continue;
+                }
+            }
+
+            // println!("glyph {:?}", c);
+            let info = match self.glyph_ids.get(i) {
+                Some(Some(id)) => self.glyph_cache.lock().get_glyph_indexed(*id, scaled_size),
+                Some(None) => continue,
+                None => self.glyph_cache.lock().get_glyph(c, scaled_size),
+            };
+
+            // Emoji the font lacks fall back to an emoji font.
+            if !c.is_control() && self.glyph_cache.lock().font.lookup_glyph_index(c) == 0 {
+                let text: String = match sequence {
+                    Some(seq) => self.chars[seq.clone()].iter().collect(),
+                    None => c.into(),
+                };
+                let mut glyph_cache = self.glyph_cache.lock();
+                let emoji_size = adjusted_size(
+                    size as f32 * scale,
+                    size_adjust,
+                    glyph_cache.emoji_x_height(c),
+                );
+                let emoji = if emoji::is_emoji(&text) {
+                    glyph_cache.get_emoji_sequence(&text, emoji_size)
+                } else {
+                    None
+                };
+                drop(glyph_cache);
+                if let Some((emoji, rect)) = emoji.and_then(|e| Some((e, e.rect?))) {
+                    let mut prim = emoji_prim(glyph, info.metrics.ymin, &emoji, rect, scale);
+                    prim.scissor = scissor;
+                    prim.paint = paint.index as u32;
+                    prim.offset_quad(snap);
+                    prims.push(prim);
+                    continue;
+                }
+            }
+
+            if let Some(rect) = info.rect {
+                let mut prim = Prim::default();
+                prim.prim_type = PrimType::Glyph as u32;
+                prim.scissor = scissor;
+                assert!(glyph.width == rect.width as usize);
+                assert!(glyph.height == rect.height as usize);
+
+                prim.quad_bounds = [
+                    glyph.x / scale,
+                    glyph.y / scale,
+                    (glyph.x + glyph.width as f32) / scale,
+                    (glyph.y + glyph.height as f32) / scale,
+                ];
+                // println!("quad_bounds: {:?}", prim.quad_bounds);
+
+                prim.tex_bounds = [
+                    rect.x as f32,
+                    (rect.y + rect.height) as f32,
+                    (rect.x + rect.width) as f32,
+                    rect.y as f32,
+                ];
+                prim.paint = paint.index as u32;
+                prim.offset_quad(snap);
+                // println!("tex_bounds: {:?}", prim.tex_bounds);
+
+                for (shadow, shadow_paint) in shadows.iter().zip(&shadow_paints) {
+                    shadow_prims.push(glyph_shadow(
+                        &prim,
+                        rect,
+                        shadow,
+                        shadow_paint.index as u32,
+                        scale,
+                    ));
+                }
+
+                prims.push(prim);
+            }
+        }
+
+        // Draw shadows from the last, so the first ends up on top.
+        let n = shadows.len();
+        if !outlines.is_empty() {
+            for (shadow, shadow_paint) in shadows.iter().zip(&shadow_paints).rev() {
+                self.fill_outlines(&outlines, scaled_size, shadow.offset, *shadow_paint);
+            }
+        }
+        for i in (0..n).rev() {
+            for prim in shadow_prims.iter().skip(i).step_by(n) {
+                self.add_prim(*prim);
+            }
+        }
+        self.arena.put_prims(shadow_prims);
+
+        if !outlines.is_empty() {
+            self.fill_outlines(&outlines, scaled_size, LocalVector::zero(), paint);
+        }
+        for prim in prims.drain(..) {
+            self.add_prim(prim);
+        }
+        self.arena.put_prims(prims);
+        self.arena.put_outlines(outlines);
+        self.arena.put_vectors(snaps);
+    }
+
+    /// Fills glyphs of the built-in font from their outlines, given their
+    /// ids and origins in device pixels, at `px` device pixels, moved by
+    /// `offset` in local space.
+    fn fill_outlines(
+        &mut self,
+        glyphs: &[(u16, LocalPoint)],
+        px: f32,
+        offset: LocalVector,
+        paint: PaintIndex,
+    ) {
+        let scale = self.device_px_ratio;
+        for (id, origin) in glyphs {
+            let outline = match self.glyph_cache.lock().get_outline(*id) {
+                Some(outline) => outline,
+                None => continue,
+            };
+            let at = |p: LocalPoint| (*origin + p.to_vector() * px) / scale + offset;
+            for contour in &outline.contours {
+                self.move_to(at(contour.start));
+                for (b, c) in &contour.quads {
+                    self.quad_to(at(*b), at(*c));
+                }
+            }
+        }
+        self.fill(paint);
+    }
+
+    /// Renders text with `ruby` annotations above their bases, at half
+    /// the text's size. Ruby wider than its base overhangs neighboring
+    /// kana by up to one ruby character on each side, and otherwise
+    /// widens the base, moving the rest of the line right. Lines with ruby
+    /// move down to make room for it. Only horizontal text is supported.
+    pub fn text_with_ruby(
+        &mut self,
+        text: &str,
+        size: u32,
+        color: Color,
+        max_width: Option<f32>,
+        ruby: &[Ruby],
+    ) {
+        self.setup_layout(text, size, max_width);
+
+        let scale = self.device_px_ratio;
+        let scaled_size = self.font_px(size);
+        let ruby_size = (size / 2).max(1);
+        let ruby_scaled = self.font_px(ruby_size);
+        let (ruby_height, ruby_descent) = self
+            .glyph_cache
+            .lock()
+            .font
+            .horizontal_line_metrics(ruby_scaled)
+            .map_or((ruby_scaled, 0.0), |m| (m.new_line_size, m.descent));
+
+        let sources = source_chars(text, &self.chars);
+        let lines: Vec<(std::ops::Range<usize>, f32)> =
+            self.layout.lines().map_or(vec![], |lines| {
+                lines
+                    .iter()
+                    .map(|line| (line.glyph_start..line.glyph_end, line.max_ascent))
+                    .collect()
+            });
+
+        let mut annotations = ruby.iter().collect::<Vec<_>>();
+        annotations.sort_by_key(|r| r.base.start);
+
+        // Ruby text with its pen start, baseline and letter spacing.
+        let mut placed = vec![];
+        let mut shift = 0.0;
+        for (l, (range, max_ascent)) in lines.iter().enumerate() {
+            let range = range.start..range.end.min(self.chars.len());
+            let bases: Vec<(&Ruby, usize, usize)> = annotations
+                .iter()
+                .filter(|r| {
+                    r.base.start < r.base.end
+                        && text.is_char_boundary(r.base.start)
+                        && text.is_char_boundary(r.base.end)
+                })
+                .filter_map(|r| {
+                    let first = sources[text[..r.base.start].chars().count()];
+                    if !range.contains(&first) {
+                        return None;
+                    }
+                    let last = sources[text[..r.base.end].chars().count() - 1];
+                    Some((*r, first, last.min(range.end - 1)))
+                })
+                .collect();
+            if !bases.is_empty() {
+                shift += ruby_height;
+            }
+            for glyph in &mut self.glyphs[range.clone()] {
+                glyph.y -= shift;
+            }
+            self.baselines[l] -= shift;
+
+            let glyph_cache = self.glyph_cache.lock();
+            let font = &glyph_cache.font;
+            for (r, first, last) in bases {
+                let chars = &self.chars;
+                let glyphs = &self.glyphs;
+                let pen = |j: usize| glyphs[j].x - font.metrics(chars[j], scaled_size).xmin as f32;
+                let start = pen(first);
+                let end = pen(last) + font.metrics(chars[last], scaled_size).advance_width;
+                let width: f32 = r
+                    .text
+                    .chars()
+                    .map(|c| font.metrics(c, ruby_scaled).advance_width)
+                    .sum();
+                let overhang = |j: Option<usize>| match j {
+                    Some(j) if range.contains(&j) && can_overhang(chars[j]) => ruby_scaled,
+                    _ => 0.0,
+                };
+                let p = place_ruby(
+                    start,
+                    end,
+                    width,
+                    r.text.chars().count(),
+                    (overhang(first.checked_sub(1)), overhang(Some(last + 1))),
+                );
+                for glyph in &mut self.glyphs[first..=last] {
+                    glyph.x += p.base_shift;
+                }
+                for glyph in &mut self.glyphs[last + 1..range.end] {
+                    glyph.x += p.widen;
+                }
+                let baseline = self.baselines[l] + max_ascent - ruby_descent;
+                placed.push((r.text.as_str(), p.start, baseline, p.spacing));
+            }
+        }
+
+        self.draw_glyphs(size, color, &[]);
+        self.add_access_text(text);
+
+        for (ruby_text, start, baseline, spacing) in placed {
+            self.save();
+            self.set_text_align(TextAlign::Left);
+            self.set_indents(0.0, 0.0);
+            self.setup_layout(ruby_text, ruby_size, None);
+            let top = self.baselines.first().copied().unwrap_or(0.0);
+            for (k, glyph) in self.glyphs.iter_mut().enumerate() {
+                glyph.x += k as f32 * spacing;
+            }
+            self.translate([start / scale, (baseline - top) / scale]);
+            self.draw_glyphs(ruby_size, color, &[]);
+            self.restore();
+        }
+    }
+
+    /// Renders text with `objects` in place of its `OBJECT_REPLACEMENT`
+    /// characters, in order, and returns where the objects go, in local
+    /// coordinates, for the caller to draw icons, images or math there.
+    /// Lines grow to fit tall objects.
+    pub fn text_with_objects(
+        &mut self,
+        text: &str,
+        size: u32,
+        color: Color,
+        max_width: Option<f32>,
+        objects: &[InlineObject],
+    ) -> Vec<LocalRect> {
+        self.inline_objects.extend_from_slice(objects);
+        self.text(text, size, color, max_width);
+        self.inline_objects.clear();
+        std::mem::take(&mut self.object_rects)
+    }
+
+    /// Renders text with indicators for spaces, tabs and line breaks, as
+    /// editors show, drawn with prims rather than glyphs from the font.
+    pub fn text_with_whitespace(
+        &mut self,
+        text: &str,
+        size: u32,
+        color: Color,
+        max_width: Option<f32>,
+        marks: &WhitespaceMarks,
+    ) {
+        // Leaves the layout set up for the marks.
+        self.text(text, size, color, max_width);
+
+        let s = 1.0 / self.device_px_ratio;
+        let chars = &self.chars;
+        let glyphs = &self.glyphs;
+        let mut parts = vec![];
+        if let Some(lines) = self.layout.lines() {
+            for (line, baseline) in lines.iter().zip(&self.baselines) {
+                let line_glyphs: Vec<(char, f32)> = (line.glyph_start..line.glyph_end)
+                    .filter_map(|i| Some((*chars.get(i)?, glyphs[i].x * s)))
+                    .collect();
+                parts.extend(line_marks(&line_glyphs, baseline * s, size as f32, marks));
+            }
+        }
+
+        let paint = self.color_paint(marks.color);
+        let width = 0.04 * size as f32;
+        for part in parts {
+            match part {
+                Mark::Dot { center, radius } => self.fill_circle(center, radius, paint),
+                Mark::Line { a, b } => self.stroke_segment(a, b, width, paint),
+            }
         }
     }
 
-    pub fn render_svg(
+    /// Renders a line of text like `text`, reusing its layout from earlier
+    /// frames when the text, size, width and device pixel ratio are
+    /// unchanged. Returns the line's bounds. Meant for editors drawing
+    /// many lines, most of which don't change between frames.
+    pub fn text_line(
         &mut self,
-        x: f32,
-        y: f32,
-        hash: &[u8],
-        width: u32,
-        height: u32,
-        image: impl FnOnce() -> Vec<u8>,
-        paint_index: Option<PaintIndex>,
-    ) {
-        let info = self.glyph_cache.get_svg_mask(hash, width, height, image);
-        if let Some(rect) = info.rect {
-            let mut prim = Prim::default();
-            prim.prim_type = if paint_index.is_some() {
-                PrimType::OverrideColorSvg
-            } else {
-                PrimType::ColorGlyph
-            } as u32;
+        text: &str,
+        size: u32,
+        color: Color,
+        max_width: Option<f32>,
+    ) -> LocalRect {
+        let scale = self.device_px_ratio;
+        let key = LineKey::new(
+            text,
+            size,
+            max_width,
+            &self.text_layout(),
+            &self.font_features,
+            scale,
+        );
+        if self.line_cache.get(&key, text).is_none() {
+            let run = self.layout_line(text, size, max_width);
+            self.line_cache.insert(key, text, run);
+        }
 
-            let x = x + info.left as f32;
-            let y = y - info.top as f32;
-            prim.quad_bounds = [x, y, x + rect.width as f32, y + rect.height as f32];
+        let scaled_size = self.font_px(size);
+        let base = self.glyph_prim(color);
+        let snap = self.baseline_snap(LocalPoint::zero());
 
-            prim.tex_bounds = [
-                rect.x as f32,
-                rect.y as f32,
-                (rect.x + rect.width) as f32,
-                (rect.y + rect.height) as f32,
-            ];
-            if let Some(paint_index) = paint_index {
-                prim.paint = paint_index.index as u32;
-            }
-            prim.scissor = self.add_scissor() as u32;
+        let mut prims = self.arena.take_prims();
+        let mut outlines = self.arena.take_outlines();
+        let run = self.line_cache.peek(&key, text).unwrap();
+        let bounds = run.bounds;
+        let style = LineStyle {
+            scaled_size,
+            scale,
+            base: &base,
+        };
+        let outlined = self.outlined(scaled_size);
+        line_prims(
+            &mut self.glyph_cache.lock(),
+            run,
+            &style,
+            snap,
+            &mut LineOutput {
+                prims: &mut prims,
+                outlines: outlined.then(|| &mut outlines),
+            },
+        );
 
-            self.render(prim);
+        if !outlines.is_empty() {
+            let paint = PaintIndex {
+                index: base.paint as usize,
+            };
+            self.fill_outlines(&outlines, scaled_size, LocalVector::zero(), paint);
         }
+        for prim in prims.drain(..) {
+            self.add_prim(prim);
+        }
+        self.arena.put_prims(prims);
+        self.arena.put_outlines(outlines);
+        self.add_access_text(text);
+        bounds
     }
 
-    /// Renders text.
-    pub fn text(&mut self, text: &str, size: u32, color: Color, max_width: Option<f32>) {
-        self.setup_layout(text, size, max_width);
-
+    /// Draws many single line labels, each with its origin at a point,
+    /// like `text_line` under a translation. The state, paint and glyph
+    /// cache are looked up once for the batch, so data grids drawing
+    /// thousands of labels a frame spend less time per label.
+    pub fn draw_texts(&mut self, labels: &[(LocalPoint, &str)], size: u32, color: Color) {
         let scale = self.device_px_ratio;
-        let scaled_size = size as f32 * scale;
+        let layout = self.text_layout();
 
-        let paint = self.color_paint(color);
-        let scissor = self.add_scissor() as u32;
+        // Lines the cache lacks are laid out once, drawn, and cached
+        // after, so a label can't lose its line to a later one with the
+        // same hash.
+        let mut lines: Vec<(LineKey, Option<LineRun>)> = Vec::with_capacity(labels.len());
+        for (_, text) in labels {
+            let key = LineKey::new(text, size, None, &layout, &self.font_features, scale);
+            let run = match self.line_cache.get(&key, text) {
+                Some(_) => None,
+                None => Some(self.layout_line(text, size, None)),
+            };
+            lines.push((key, run));
+        }
 
-        let mut prims = vec![];
-        for (i, glyph) in self.layout.glyphs().iter().enumerate() {
-            let c = text.chars().nth(i).unwrap();
-            // println!("glyph {:?}", c);
-            let info = self.glyph_cache.get_glyph(c, scaled_size);
+        let scaled_size = self.font_px(size);
+        let base = self.glyph_prim(color);
 
-            if let Some(rect) = info.rect {
-                let mut prim = Prim::default();
-                prim.prim_type = PrimType::Glyph as u32;
-                prim.scissor = scissor;
-                assert!(glyph.width == rect.width as usize);
-                assert!(glyph.height == rect.height as usize);
+        let mut prims = self.arena.take_prims();
+        let mut outlines = self.arena.take_outlines();
+        let outlined = self.outlined(scaled_size);
+        {
+            let mut glyph_cache = self.glyph_cache.lock();
+            let style = LineStyle {
+                scaled_size,
+                scale,
+                base: &base,
+            };
+            let mut out = LineOutput {
+                prims: &mut prims,
+                outlines: outlined.then(|| &mut outlines),
+            };
+            for ((origin, text), (key, run)) in labels.iter().zip(&lines) {
+                let run = match run.as_ref().or_else(|| self.line_cache.peek(key, text)) {
+                    Some(run) => run,
+                    None => continue,
+                };
+                let offset = origin.to_vector() + self.baseline_snap(*origin);
+                line_prims(&mut glyph_cache, run, &style, offset, &mut out);
+            }
+        }
+        for ((_, text), (key, run)) in labels.iter().zip(lines) {
+            if let Some(run) = run {
+                self.line_cache.insert(key, text, run);
+            }
+        }
 
-                prim.quad_bounds = [
-                    glyph.x / scale,
-                    glyph.y / scale,
-                    (glyph.x + glyph.width as f32) / scale,
-                    (glyph.y + glyph.height as f32) / scale,
-                ];
-                // println!("quad_bounds: {:?}", prim.quad_bounds);
+        if !outlines.is_empty() {
+            let paint = PaintIndex {
+                index: base.paint as usize,
+            };
+            self.fill_outlines(&outlines, scaled_size, LocalVector::zero(), paint);
+        }
+        for prim in prims.drain(..) {
+            self.add_prim(prim);
+        }
+        self.arena.put_prims(prims);
+        self.arena.put_outlines(outlines);
+        for (_, text) in labels {
+            self.add_access_text(text);
+        }
+    }
 
-                prim.tex_bounds = [
-                    rect.x as f32,
-                    (rect.y + rect.height) as f32,
-                    (rect.x + rect.width) as f32,
-                    rect.y as f32,
-                ];
-                prim.paint = paint.index as u32;
-                // println!("tex_bounds: {:?}", prim.tex_bounds);
+    /// Whether glyphs at `px` device pixels are drawn from their outlines.
+    /// See `set_outline_threshold`.
+    fn outlined(&self, px: f32) -> bool {
+        self.outline_threshold
+            .map_or(false, |threshold| px >= threshold)
+    }
 
-                prims.push(prim);
-            }
-        }
+    /// A glyph prim with the paint for `color` and the current scissor.
+    fn glyph_prim(&mut self, color: Color) -> Prim {
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::Glyph as u32;
+        prim.paint = self.color_paint(color).index as u32;
+        prim.scissor = self.add_scissor() as u32;
+        prim
+    }
+
+    /// Shapes a line for the line cache.
+    fn layout_line(&mut self, text: &str, size: u32, max_width: Option<f32>) -> LineRun {
+        self.setup_layout(text, size, max_width);
+        let s = 1.0 / self.device_px_ratio;
+
+        let mut bounds: Option<LocalRect> = None;
+        let glyph_ids = &self.glyph_ids;
+        let glyphs = self
+            .glyphs
+            .iter()
+            .zip(self.chars.iter().copied())
+            .enumerate()
+            .filter(|(i, _)| glyph_ids.get(*i) != Some(&None))
+            .map(|(i, (glyph, c))| {
+                let rect = LocalRect::new(
+                    [glyph.x, glyph.y].into(),
+                    [glyph.width as f32, glyph.height as f32].into(),
+                )
+                .scale(s, s);
+                bounds = Some(bounds.map_or(rect, |b| b.union(&rect)));
+                LineGlyph {
+                    c,
+                    glyph_id: glyph_ids.get(i).copied().flatten(),
+                    x: glyph.x,
+                    y: glyph.y,
+                    width: glyph.width,
+                    height: glyph.height,
+                }
+            })
+            .collect();
 
-        for prim in prims {
-            self.render(prim);
+        LineRun {
+            glyphs,
+            bounds: bounds.unwrap_or_else(LocalRect::zero),
         }
     }
 
@@ -726,7 +4524,7 @@ impl Vger {
 
         let scale = self.device_px_ratio;
 
-        for glyph in self.layout.glyphs() {
+        for glyph in &self.glyphs {
             min = min.min([glyph.x / scale, glyph.y / scale].into());
             max = max.max(
                 [
@@ -754,7 +4552,7 @@ impl Vger {
 
         let s = 1.0 / self.device_px_ratio;
 
-        for glyph in self.layout.glyphs() {
+        for glyph in &self.glyphs {
             rects.push(
                 LocalRect::new(
                     [glyph.x, glyph.y].into(),
@@ -767,6 +4565,46 @@ impl Vger {
         rects
     }
 
+    /// Grapheme clusters of laid out text, for hit-testing which treats
+    /// each as a unit, such as a letter with accents or the emoji
+    /// sequence 👩‍👩‍👧.
+    pub fn glyph_clusters(
+        &mut self,
+        text: &str,
+        size: u32,
+        max_width: Option<f32>,
+    ) -> Vec<GlyphCluster> {
+        self.setup_layout(text, size, max_width);
+        let s = 1.0 / self.device_px_ratio;
+
+        let sources = source_chars(text, &self.chars);
+        let mut next_char = 0;
+        grapheme_ranges(text)
+            .into_iter()
+            .filter_map(|text_range| {
+                let first = next_char;
+                next_char += text[text_range.clone()].chars().count();
+                let end = (sources[next_char - 1] + 1).min(self.glyphs.len());
+                let glyphs = sources[first].min(end)..end;
+                let bounds = self.glyphs[glyphs.clone()]
+                    .iter()
+                    .map(|glyph| {
+                        LocalRect::new(
+                            [glyph.x, glyph.y].into(),
+                            [glyph.width as f32, glyph.height as f32].into(),
+                        )
+                        .scale(s, s)
+                    })
+                    .reduce(|a, b| a.union(&b))?;
+                Some(GlyphCluster {
+                    text_range,
+                    glyphs,
+                    bounds,
+                })
+            })
+            .collect()
+    }
+
     pub fn line_metrics(
         &mut self,
         text: &str,
@@ -779,7 +4617,7 @@ impl Vger {
         let mut rects = vec![];
         rects.reserve(text.len());
 
-        let glyphs = self.layout.glyphs();
+        let glyphs = &self.glyphs;
 
         if let Some(lines) = self.layout.lines() {
             for line in lines {
@@ -803,12 +4641,19 @@ impl Vger {
         rects
     }
 
+    /// Index of the current transform in the transform buffer, shared
+    /// with earlier drawing under the same transform.
     fn add_xform(&mut self) -> usize {
+        let m = self.state().transform;
+        let key = [m.m11, m.m12, m.m21, m.m22, m.m31, m.m32].map(f32::to_bits);
+        let scene = &mut self.scenes[self.cur_scene];
+        scene.xform_requests += 1;
+        if let Some(index) = scene.xform_lookup.get(&key) {
+            return *index;
+        }
         if self.xform_count < MAX_PRIMS {
-            let m = *self.tx_stack.last().unwrap();
-            self.scenes[self.cur_scene]
-                .xforms
-                .push(m.to_3d().to_array());
+            scene.xforms.push(m.to_3d().to_array());
+            scene.xform_lookup.insert(key, self.xform_count);
             let n = self.xform_count;
             self.xform_count += 1;
             return n;
@@ -816,9 +4661,207 @@ impl Vger {
         0
     }
 
+    /// Transforms requested and stored for the frame being recorded.
+    /// Drawing under a transform already used this frame, e.g. after a
+    /// `restore`, reuses its entry.
+    pub fn transform_stats(&self) -> TransformStats {
+        let scene = &self.scenes[self.cur_scene];
+        TransformStats {
+            requested: scene.xform_requests,
+            stored: scene.xforms.len(),
+        }
+    }
+
+    /// Local distance covering at least one device pixel in every
+    /// direction under the current transform, to leave room for
+    /// antialiasing around shapes.
+    fn aa_pad(&self) -> f32 {
+        let m = self.current_transform();
+
+        // Smallest singular value of the linear part.
+        let a = m.m11 * m.m11 + m.m12 * m.m12 + m.m21 * m.m21 + m.m22 * m.m22;
+        let det = m.determinant();
+        let s_min = (0.5 * (a - (a * a - 4.0 * det * det).max(0.0).sqrt()))
+            .max(0.0)
+            .sqrt();
+
+        1.0 / (s_min * self.device_px_ratio).max(0.001)
+    }
+
+    /// Device pixel bounds of the quads recorded so far, by z index and
+    /// then in drawing order.
+    pub fn device_bounds(&self) -> Vec<ScreenRect> {
+        let scene = &self.scenes[self.cur_scene];
+        let mut bounds = vec![];
+        for prims in scene.depthed_prims.values() {
+            for prim in prims {
+                let m = scene.xforms[prim.xform as usize];
+                let q = prim.quad_bounds;
+                let corners = [[q[0], q[1]], [q[2], q[1]], [q[0], q[3]], [q[2], q[3]]];
+                let points = corners.iter().map(|p| {
+                    ScreenPoint::new(
+                        (m[0] * p[0] + m[4] * p[1] + m[12]) * self.device_px_ratio,
+                        (m[1] * p[0] + m[5] * p[1] + m[13]) * self.device_px_ratio,
+                    )
+                });
+                bounds.push(ScreenRect::from_points(points));
+            }
+        }
+        bounds
+    }
+
+    /// Device pixel bounds of a local space quad under the current
+    /// transform.
+    fn device_rect(&self, q: [f32; 4]) -> ScreenRect {
+        let m = self.current_transform();
+        let corners = [[q[0], q[1]], [q[2], q[1]], [q[0], q[3]], [q[2], q[3]]];
+        ScreenRect::from_points(corners.iter().map(|p| {
+            let w = m.transform_point(LocalPoint::new(p[0], p[1]));
+            ScreenPoint::new(w.x * self.device_px_ratio, w.y * self.device_px_ratio)
+        }))
+    }
+
+    /// Tags subsequent drawing with an accessibility node, until the
+    /// next `restore` or a call with `None`. Bounds and text drawn for
+    /// each node are reported by `access_nodes`.
+    pub fn set_access_node(&mut self, node: Option<AccessNode>) {
+        if let Some(state) = self.state_stack.last_mut() {
+            state.access_node = node;
+        }
+    }
+
+    /// Adds text to the current accessibility node, for text drawn
+    /// glyph by glyph with `render_glyph`. `text` does this itself.
+    pub fn add_access_text(&mut self, text: &str) {
+        if let Some(node) = self.state().access_node {
+            self.access.add_text(node, text);
+        }
+    }
+
+    /// Accessibility nodes drawn since `begin`, in the order they were
+    /// first drawn.
+    pub fn access_nodes(&self) -> &[AccessInfo] {
+        self.access.infos()
+    }
+
+    /// Sets how subsequently drawn images are filtered, until the next
+    /// `restore`.
+    pub fn set_image_sampling(&mut self, sampling: ImageSampling) {
+        if let Some(state) = self.state_stack.last_mut() {
+            state.image_sampling = sampling;
+        }
+    }
+
+    /// Sets how subsequent drawing combines with what's under it, until
+    /// the next `restore`. Changing modes splits the frame into more draw
+    /// calls, so group additive drawing together where possible. Cached
+    /// layers and indirect draws are always blended normally, which
+    /// validation reports as `ValidationError::BlendModeIgnored`.
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        if let Some(state) = self.state_stack.last_mut() {
+            state.blend_mode = mode;
+        }
+    }
+
+    /// Rounds the corners of subsequently drawn images and external
+    /// textures to `radius` in local units, until the next `restore`. The
+    /// image is masked as it's sampled, so avatars and thumbnails need no
+    /// clip layer. Border images aren't rounded.
+    pub fn set_image_corner_radius(&mut self, radius: f32) {
+        if let Some(state) = self.state_stack.last_mut() {
+            state.image_corner_radius = radius.max(0.0);
+        }
+    }
+
+    /// Hyphenates words that don't fit when wrapping text to a `max_width`,
+    /// using a dictionary such as
+    /// `Standard::from_embedded(Language::EnglishUS)`, or stops with
+    /// `None`. Glyph positions then include the inserted hyphens.
+    #[cfg(feature = "hyphenate")]
+    pub fn set_hyphenation(&mut self, dictionary: Option<hyphenation::Standard>) {
+        self.hyphenator = dictionary;
+    }
+
+    /// Sets the OpenType features applied to subsequent text, such as
+    /// `FontFeature::on(b"tnum")` for tabular figures, until they're set
+    /// again. Text is shaped when any are set.
+    pub fn set_font_features(&mut self, features: &[FontFeature]) {
+        self.font_features = features.to_vec();
+    }
+
+    /// Sets how subsequent text is aligned within its `max_width`, until
+    /// the next `restore`.
+    pub fn set_text_align(&mut self, align: TextAlign) {
+        if let Some(state) = self.state_stack.last_mut() {
+            state.text_layout.align = align;
+        }
+    }
+
+    /// Sets the distance between lines of subsequent text, until the next
+    /// `restore`.
+    pub fn set_line_height(&mut self, line_height: LineHeight) {
+        if let Some(state) = self.state_stack.last_mut() {
+            state.text_layout.line_height = line_height;
+        }
+    }
+
+    /// Sets the space added after each line break in subsequent text,
+    /// until the next `restore`.
+    pub fn set_paragraph_spacing(&mut self, spacing: f32) {
+        if let Some(state) = self.state_stack.last_mut() {
+            state.text_layout.paragraph_spacing = spacing;
+        }
+    }
+
+    /// Scales fonts so their x-heights are `adjust` times the size of
+    /// subsequent text, as CSS `font-size-adjust`, until the next
+    /// `restore`. Emoji fonts which don't record an x-height keep their
+    /// size.
+    pub fn set_font_size_adjust(&mut self, adjust: Option<f32>) {
+        if let Some(state) = self.state_stack.last_mut() {
+            state.text_layout.size_adjust = adjust;
+        }
+    }
+
+    /// Sets the indents of first lines and of the other lines of each
+    /// paragraph in subsequent text, until the next `restore`. A hanging
+    /// indent lines wrapped lines of a bullet up after the bullet.
+    pub fn set_indents(&mut self, first_line: f32, hanging: f32) {
+        if let Some(state) = self.state_stack.last_mut() {
+            state.text_layout.first_line_indent = first_line;
+            state.text_layout.hanging_indent = hanging;
+        }
+    }
+
+    /// Layout options for text drawn now, e.g. to look up a line drawn
+    /// with `text_line` in the line cache.
+    pub fn text_layout(&self) -> TextLayout {
+        self.state().text_layout
+    }
+
+    /// Tags subsequent drawing for picking, until the next `restore` or a
+    /// call with `None`. Untagged drawing isn't indexed.
+    pub fn set_pick_tag(&mut self, tag: Option<PickTag>) {
+        if let Some(state) = self.state_stack.last_mut() {
+            state.pick_tag = tag;
+        }
+    }
+
+    /// Tags of drawing since `begin` whose bounds contain a point in
+    /// device pixels, topmost first.
+    pub fn query_point(&mut self, p: ScreenPoint) -> Vec<PickTag> {
+        self.picks.query_point(p)
+    }
+
+    /// Tags of drawing since `begin` whose bounds overlap a rect in
+    /// device pixels, topmost first.
+    pub fn query_rect(&mut self, rect: ScreenRect) -> Vec<PickTag> {
+        self.picks.query_rect(rect)
+    }
+
     fn add_scissor(&mut self) -> usize {
         if self.scissor_count < MAX_PRIMS {
-            let scissor = *self.scissor_stack.last().unwrap();
+            let scissor = self.state().scissor;
             self.scenes[self.cur_scene].scissors.push(scissor);
             let n = self.scissor_count;
             self.scissor_count += 1;
@@ -829,36 +4872,59 @@ impl Vger {
 
     /// Translates the coordinate system.
     pub fn translate<Vec: Into<LocalVector>>(&mut self, offset: Vec) {
-        if let Some(m) = self.tx_stack.last_mut() {
-            *m = (*m).pre_translate(offset.into());
+        if let Some(state) = self.state_stack.last_mut() {
+            state.transform = state.transform.pre_translate(offset.into());
         }
     }
 
     /// Scales the coordinate system.
     pub fn scale<Vec: Into<LocalVector>>(&mut self, scale: Vec) {
-        if let Some(m) = self.tx_stack.last_mut() {
+        if let Some(state) = self.state_stack.last_mut() {
             let s: LocalVector = scale.into();
-            *m = (*m).pre_scale(s.x, s.y);
+            state.transform = state.transform.pre_scale(s.x, s.y);
         }
     }
 
     /// Rotates the coordinate system.
     pub fn rotate(&mut self, theta: f32) {
-        if let Some(m) = self.tx_stack.last_mut() {
-            *m = m.pre_rotate(euclid::Angle::<f32>::radians(theta));
+        if let Some(state) = self.state_stack.last_mut() {
+            state.transform = state
+                .transform
+                .pre_rotate(euclid::Angle::<f32>::radians(theta));
         }
     }
 
+    /// Sets the camera used by `translate_world`.
+    pub fn set_camera(&mut self, camera: Camera64) {
+        self.camera = camera;
+    }
+
+    /// Gets the camera used by `translate_world`.
+    pub fn camera(&self) -> Camera64 {
+        self.camera
+    }
+
+    /// Moves the origin to a world position given in f64 and scales by the
+    /// camera zoom. The offset from the camera is computed in f64 first, so
+    /// large world coordinates don't lose precision.
+    pub fn translate_world(&mut self, position: WorldPoint64) {
+        let offset = self.camera.relative(position);
+        let zoom = self.camera.zoom as f32;
+        self.translate(offset);
+        self.scale(LocalVector::new(zoom, zoom));
+    }
+
     /// Gets the current transform.
     pub fn current_transform(&self) -> LocalToWorld {
-        *self.tx_stack.last().unwrap()
+        self.state().transform
     }
 
     /// Sets the current scissor rect.
     pub fn scissor(&mut self, rect: LocalRect) {
-        if let Some(m) = self.scissor_stack.last_mut() {
+        if let Some(state) = self.state_stack.last_mut() {
+            let m = &mut state.scissor;
             *m = Scissor::new();
-            if let Some(xform) = self.tx_stack.last().unwrap().inverse() {
+            if let Some(xform) = state.transform.inverse() {
                 m.xform = xform;
                 m.origin = rect.origin.to_array();
                 m.size = rect.size.to_array();
@@ -867,17 +4933,40 @@ impl Vger {
     }
 
     pub fn set_z_index(&mut self, z_index: i32) {
-        self.cur_z_index = z_index;
+        if let Some(state) = self.state_stack.last_mut() {
+            state.z_index = z_index;
+        }
     }
 
     /// Resets the current scissor rect.
     pub fn reset_scissor(&mut self) {
-        if let Some(m) = self.scissor_stack.last_mut() {
-            *m = Scissor::new();
+        if let Some(state) = self.state_stack.last_mut() {
+            state.scissor = Scissor::new();
         }
     }
 
+    /// Adds a paint, or finds an identical one recorded this frame, so
+    /// drawing in a loop with the same paint doesn't fill the buffer.
     fn add_paint(&mut self, paint: Paint) -> PaintIndex {
+        let key = paint.key();
+        if let Some(index) = self.scenes[self.cur_scene].paint_lookup.get(&key) {
+            return PaintIndex { index: *index };
+        }
+        let count = self.paint_count;
+        let paint_index = self.push_paint(paint);
+        if self.paint_count > count {
+            self.scenes[self.cur_scene]
+                .paint_lookup
+                .insert(key, paint_index.index);
+        }
+        paint_index
+    }
+
+    /// Adds a paint in a new slot.
+    fn push_paint(&mut self, paint: Paint) -> PaintIndex {
+        if self.validation && !paint.is_finite() {
+            self.validation_errors.push(ValidationError::NonFinitePaint);
+        }
         if self.paint_count < MAX_PRIMS {
             self.scenes[self.cur_scene].paints.push(paint);
             self.paint_count += 1;
@@ -893,6 +4982,205 @@ impl Vger {
         self.add_paint(Paint::solid_color(color))
     }
 
+    /// Paint which refers to a theme variable. The variable is looked up
+    /// when encoding, so the theme can change without re-recording.
+    pub fn paint_var(&mut self, name: &str) -> PaintIndex {
+        let paint = self.theme.resolve(name);
+        let count = self.paint_count;
+        let paint_index = self.push_paint(paint);
+        if self.paint_count > count {
+            self.scenes[self.cur_scene]
+                .paint_vars
+                .push((paint_index.index, name.into()));
+        }
+        paint_index
+    }
+
+    /// Hatch paint: lines at `angle` radians, `spacing` apart and `width`
+    /// wide, drawn over `background`.
+    pub fn hatch_paint(
+        &mut self,
+        angle: f32,
+        spacing: f32,
+        width: f32,
+        color: Color,
+        background: Color,
+        space: PatternSpace,
+    ) -> PaintIndex {
+        self.add_paint(Paint::hatch(
+            angle, spacing, width, color, background, false, space,
+        ))
+    }
+
+    /// Cross-hatch paint: like `hatch_paint` with a second set of lines
+    /// perpendicular to the first.
+    pub fn cross_hatch_paint(
+        &mut self,
+        angle: f32,
+        spacing: f32,
+        width: f32,
+        color: Color,
+        background: Color,
+        space: PatternSpace,
+    ) -> PaintIndex {
+        self.add_paint(Paint::hatch(
+            angle, spacing, width, color, background, true, space,
+        ))
+    }
+
+    /// Checkerboard paint: squares `cell_size` wide alternating between
+    /// `color_a` and `color_b`.
+    pub fn checkerboard_paint(
+        &mut self,
+        cell_size: f32,
+        color_a: Color,
+        color_b: Color,
+        space: PatternSpace,
+    ) -> PaintIndex {
+        self.add_paint(Paint::checkerboard(cell_size, color_a, color_b, space))
+    }
+
+    /// `paint` with a glow of `color` around shapes drawn with it, fading
+    /// out over `radius` from their edges. The glow is computed from each
+    /// shape's distance field, so it needs no offscreen pass. Text and
+    /// images don't glow.
+    ///
+    /// This is a new paint, so drawing which already used `paint`, or an
+    /// identical paint, is unchanged.
+    pub fn glow_paint(&mut self, paint: PaintIndex, color: Color, radius: f32) -> PaintIndex {
+        self.derive_paint(paint, |p| p.with_glow(color, radius))
+    }
+
+    /// `paint` continuing past the ends of its linear or conical gradient
+    /// as `spread` says: `Repeat` for stripes or a barber pole from one
+    /// short gradient, `Reflect` to mirror it. Like `glow_paint`, this is a
+    /// new paint.
+    pub fn spread_paint(&mut self, paint: PaintIndex, spread: SpreadMode) -> PaintIndex {
+        self.derive_paint(paint, |p| p.with_spread(spread))
+    }
+
+    /// Adds a paint made from one of this frame's. A paint variable's
+    /// current value is used.
+    fn derive_paint(&mut self, paint: PaintIndex, f: impl FnOnce(Paint) -> Paint) -> PaintIndex {
+        match self.scenes[self.cur_scene].paints.get(paint.index) {
+            Some(old) => {
+                let new = f(*old);
+                self.add_paint(new)
+            }
+            None => paint,
+        }
+    }
+
+    /// Procedural noise paint, mixing from `color_a` to `color_b` as the
+    /// noise goes from zero to one. Use a translucent `color_b` over a
+    /// transparent `color_a` to overlay grain on other drawing.
+    pub fn noise_paint(&mut self, noise: &Noise, color_a: Color, color_b: Color) -> PaintIndex {
+        self.add_paint(Paint::noise(noise, color_a, color_b))
+    }
+
+    /// Sets the time in seconds that animated paints such as
+    /// `shimmer_paint` are evaluated at, from the next `begin`. Only the
+    /// uniforms change, so paints needn't be recreated each frame.
+    pub fn set_time(&mut self, seconds: f32) {
+        self.time = seconds;
+    }
+
+    /// Loading skeleton shimmer: a soft highlight band sweeping over
+    /// `base` from `start` to `end`, then repeating. `band_width` is a
+    /// fraction of the distance from start to end, and `speed` is in
+    /// sweeps per second.
+    pub fn shimmer_paint<Pt: Into<LocalPoint>>(
+        &mut self,
+        start: Pt,
+        end: Pt,
+        base: Color,
+        highlight: Color,
+        band_width: f32,
+        speed: f32,
+    ) -> PaintIndex {
+        self.add_paint(Paint::shimmer(
+            start.into(),
+            end.into(),
+            base,
+            highlight,
+            band_width,
+            speed,
+        ))
+    }
+
+    /// Paint coloring shapes by `value` through `colormap`, with `range`
+    /// spanning the colormap, e.g. for heatmap cells and scatter plot
+    /// dots. The lookup happens in the shader, from a table uploaded to
+    /// the color atlas on first use. Values are rounded to the table's
+    /// entries, so values drawn with the same color share a paint.
+    pub fn heatmap_paint(&mut self, value: f32, range: [f32; 2], colormap: Colormap) -> PaintIndex {
+        let t = (value - range[0]) / (range[1] - range[0]);
+        let t = if t.is_finite() {
+            Colormap::quantize(t)
+        } else {
+            0.0
+        };
+        let lut = self.colormap_lut(&colormap);
+        self.add_paint(Paint::heatmap(
+            t,
+            lut,
+            colormap.color(0.0),
+            colormap.color(1.0),
+        ))
+    }
+
+    /// Paint running through `colormap` from `start` to `end`, e.g. for a
+    /// colorbar next to a heatmap. See `heatmap_paint`.
+    pub fn heatmap_gradient<Pt: Into<LocalPoint>>(
+        &mut self,
+        start: Pt,
+        end: Pt,
+        colormap: Colormap,
+    ) -> PaintIndex {
+        let lut = self.colormap_lut(&colormap);
+        self.add_paint(Paint::heatmap_gradient(
+            start.into(),
+            end.into(),
+            lut,
+            colormap.color(0.0),
+            colormap.color(1.0),
+        ))
+    }
+
+    /// Where a colormap's lookup table is in the color atlas, uploading it
+    /// if needed. `None` if the atlas is full.
+    fn colormap_lut(&mut self, colormap: &Colormap) -> Option<[f32; 2]> {
+        let info = self.glyph_cache.lock().get_image_mask(
+            &colormap.key(),
+            crate::colormap::LUT_SIZE,
+            1,
+            || colormap.lut(),
+        );
+        info.rect.map(|rect| [rect.x as f32, rect.y as f32])
+    }
+
+    /// Two point conical gradient paint, from the circle at `start` to the
+    /// one at `end`, as with Canvas `createRadialGradient`. See
+    /// `Paint::conical_gradient`.
+    pub fn conical_gradient<Pt: Into<LocalPoint>>(
+        &mut self,
+        start: Pt,
+        start_radius: f32,
+        end: Pt,
+        end_radius: f32,
+        inner_color: Color,
+        outer_color: Color,
+    ) -> PaintIndex {
+        self.add_paint(Paint::conical_gradient(
+            start.into(),
+            start_radius,
+            end.into(),
+            end_radius,
+            inner_color,
+            outer_color,
+        ))
+    }
+
     /// Linear gradient paint.
     pub fn linear_gradient<Pt: Into<LocalPoint>>(
         &mut self,
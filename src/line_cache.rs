@@ -0,0 +1,317 @@
+//! Glyph layouts of single lines kept between frames, for drawing large
+//! buffers with `Vger::text_line` without shaping unchanged lines again.
+
+use std::collections::HashMap;
+
+use crate::defs::*;
+use crate::features::{features_hash, FontFeature};
+use crate::hash::fnv1a;
+use crate::TextLayout;
+
+/// A laid out glyph, in device pixels relative to the line origin.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LineGlyph {
+    pub c: char,
+
+    /// The glyph picked by shaping, when font features are set.
+    pub glyph_id: Option<u16>,
+    pub x: f32,
+    pub y: f32,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// The glyphs of a line, as laid out at one size, width, layout, set of
+/// font features and scale.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineRun {
+    pub glyphs: Vec<LineGlyph>,
+
+    /// Bounds of the glyphs in local coordinates.
+    pub bounds: LocalRect,
+}
+
+/// Lookups since the cache was created or its stats were reset.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LineCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct LineKey {
+    hash: u64,
+    size: u32,
+    max_width: Option<u32>,
+    layout: [u32; 6],
+    features: u64,
+    scale: u32,
+}
+
+impl LineKey {
+    pub(crate) fn new(
+        text: &str,
+        size: u32,
+        max_width: Option<f32>,
+        layout: &TextLayout,
+        features: &[FontFeature],
+        scale: f32,
+    ) -> Self {
+        Self {
+            hash: fnv1a(text.as_bytes()),
+            size,
+            max_width: max_width.map(f32::to_bits),
+            layout: layout.key(),
+            features: features_hash(features),
+            scale: scale.to_bits(),
+        }
+    }
+}
+
+struct CachedLine {
+    /// Compared on lookup, so a hash collision is a miss rather than the
+    /// wrong glyphs.
+    text: String,
+    run: LineRun,
+    last_used: u64,
+}
+
+/// Maps line text, size, wrap width, layout options, font features and
+/// device pixel ratio to laid out glyphs. Lines not drawn for `max_age` frames are
+/// dropped, and editors can drop lines they know changed with
+/// `invalidate`.
+pub struct LineLayoutCache {
+    lines: HashMap<LineKey, CachedLine>,
+    frame: u64,
+    max_age: Option<u64>,
+    stats: LineCacheStats,
+}
+
+impl Default for LineLayoutCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineLayoutCache {
+    /// Frames a line is kept without being drawn, by default.
+    pub const DEFAULT_MAX_AGE: u64 = 120;
+
+    pub fn new() -> Self {
+        Self {
+            lines: HashMap::new(),
+            frame: 0,
+            max_age: Some(Self::DEFAULT_MAX_AGE),
+            stats: LineCacheStats::default(),
+        }
+    }
+
+    /// Number of cached lines.
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Frames a line is kept without being drawn. `None` keeps lines
+    /// until they're invalidated.
+    pub fn set_max_age(&mut self, frames: Option<u64>) {
+        self.max_age = frames;
+    }
+
+    /// Drops layouts of `text` at every size and width, e.g. after an
+    /// edit or a change to the text's attributes.
+    pub fn invalidate(&mut self, text: &str) {
+        let hash = fnv1a(text.as_bytes());
+        self.lines.retain(|key, _| key.hash != hash);
+    }
+
+    /// Drops the lines `keep` returns false for.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.lines.retain(|_, line| keep(&line.text));
+    }
+
+    /// Drops every line, e.g. after changing fonts.
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    /// The cached layout of a line drawn with `Vger::text_line`, e.g. to
+    /// place a caret, without counting as a use.
+    pub fn layout(
+        &self,
+        text: &str,
+        size: u32,
+        max_width: Option<f32>,
+        layout: &TextLayout,
+        features: &[FontFeature],
+        device_px_ratio: f32,
+    ) -> Option<&LineRun> {
+        let key = LineKey::new(text, size, max_width, layout, features, device_px_ratio);
+        self.lines
+            .get(&key)
+            .filter(|line| line.text == text)
+            .map(|line| &line.run)
+    }
+
+    pub fn stats(&self) -> LineCacheStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = LineCacheStats::default();
+    }
+
+    /// The cached layout of `text` for `key`, counting the lookup.
+    pub(crate) fn get(&mut self, key: &LineKey, text: &str) -> Option<&LineRun> {
+        let frame = self.frame;
+        match self.lines.get_mut(key) {
+            Some(line) if line.text == text => {
+                self.stats.hits += 1;
+                line.last_used = frame;
+                Some(&line.run)
+            }
+            _ => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// The layout of `text` for `key` without counting a lookup, e.g. just
+    /// after inserting it.
+    pub(crate) fn peek(&self, key: &LineKey, text: &str) -> Option<&LineRun> {
+        self.lines
+            .get(key)
+            .filter(|line| line.text == text)
+            .map(|line| &line.run)
+    }
+
+    pub(crate) fn insert(&mut self, key: LineKey, text: &str, run: LineRun) {
+        self.lines.insert(
+            key,
+            CachedLine {
+                text: text.to_string(),
+                run,
+                last_used: self.frame,
+            },
+        );
+    }
+
+    /// Starts a frame, dropping lines that have aged out.
+    pub(crate) fn next_frame(&mut self) {
+        self.frame += 1;
+        if let Some(max_age) = self.max_age {
+            let frame = self.frame;
+            self.lines
+                .retain(|_, line| frame - line.last_used <= max_age);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn run(c: char) -> LineRun {
+        LineRun {
+            glyphs: vec![LineGlyph {
+                c,
+                glyph_id: None,
+                x: 0.0,
+                y: 0.0,
+                width: 8,
+                height: 10,
+            }],
+            bounds: euclid::rect(0.0, 0.0, 8.0, 10.0),
+        }
+    }
+
+    #[test]
+    fn test_line_cache() {
+        let mut cache = LineLayoutCache::new();
+        let key = LineKey::new("fn main() {", 14, None, &TextLayout::default(), &[], 2.0);
+        assert!(cache.get(&key, "fn main() {").is_none());
+        cache.insert(key, "fn main() {", run('f'));
+        assert_eq!(cache.get(&key, "fn main() {"), Some(&run('f')));
+        assert_eq!(
+            cache.layout("fn main() {", 14, None, &TextLayout::default(), &[], 2.0),
+            Some(&run('f'))
+        );
+
+        // Size, width, layout, features and scale are part of the key.
+        let right = TextLayout {
+            align: crate::TextAlign::Right,
+            ..TextLayout::default()
+        };
+        assert_ne!(
+            key,
+            LineKey::new("fn main() {", 16, None, &TextLayout::default(), &[], 2.0)
+        );
+        assert_ne!(
+            key,
+            LineKey::new(
+                "fn main() {",
+                14,
+                Some(100.0),
+                &TextLayout::default(),
+                &[],
+                2.0
+            )
+        );
+        assert_ne!(
+            key,
+            LineKey::new("fn main() {", 14, None, &TextLayout::default(), &[], 1.0)
+        );
+        assert_ne!(key, LineKey::new("fn main() {", 14, None, &right, &[], 2.0));
+        assert_ne!(
+            key,
+            LineKey::new(
+                "fn main() {",
+                14,
+                None,
+                &TextLayout::default(),
+                &[FontFeature::off(b"liga")],
+                2.0
+            )
+        );
+
+        // Text that doesn't match the entry misses.
+        assert!(cache.get(&key, "fn main() {}").is_none());
+        assert!(cache.peek(&key, "fn main() {}").is_none());
+        assert_eq!(cache.peek(&key, "fn main() {"), Some(&run('f')));
+        assert_eq!(cache.stats(), LineCacheStats { hits: 1, misses: 2 });
+
+        let other = LineKey::new("}", 14, None, &TextLayout::default(), &[], 2.0);
+        cache.insert(other, "}", run('}'));
+        cache.invalidate("fn main() {");
+        assert_eq!(cache.len(), 1);
+        cache.retain(|text| text != "}");
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_line_cache_age() {
+        let mut cache = LineLayoutCache::new();
+        cache.set_max_age(Some(2));
+        let a = LineKey::new("a", 14, None, &TextLayout::default(), &[], 1.0);
+        let b = LineKey::new("b", 14, None, &TextLayout::default(), &[], 1.0);
+        cache.insert(a, "a", run('a'));
+        cache.insert(b, "b", run('b'));
+
+        for _ in 0..3 {
+            cache.next_frame();
+            assert!(cache.get(&a, "a").is_some());
+        }
+        assert_eq!(cache.len(), 1);
+
+        cache.set_max_age(None);
+        for _ in 0..10 {
+            cache.next_frame();
+        }
+        assert_eq!(cache.len(), 1);
+    }
+}
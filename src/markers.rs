@@ -0,0 +1,320 @@
+use crate::defs::*;
+
+/// Shape drawn at each marker position. Shapes are defined in marker
+/// space, where the origin is the marker position and +x points along
+/// the path.
+#[derive(Clone, Debug)]
+pub enum MarkerShape {
+    /// Filled circle.
+    Dot { radius: f32 },
+
+    /// Filled arrowhead with its tip at the marker position.
+    Arrow { length: f32, width: f32 },
+
+    /// Filled quadratic path: a start point followed by (control, end) pairs,
+    /// as with `move_to` and `quad_to`.
+    Path(Vec<LocalPoint>),
+}
+
+/// Where markers are placed along a polyline.
+#[derive(Clone, Debug)]
+pub enum MarkerPlacement {
+    Start,
+    End,
+    StartAndEnd,
+
+    /// At every vertex, oriented along the bisector of adjacent segments.
+    Vertices,
+
+    /// Every `spacing` units of length, starting at the first point.
+    Spacing(f32),
+
+    /// At the given distances along the polyline.
+    Positions(Vec<f32>),
+}
+
+fn direction(a: LocalPoint, b: LocalPoint) -> Option<LocalVector> {
+    let d = b - a;
+    let len = d.length();
+    if len > 0.0 {
+        Some(d / len)
+    } else {
+        None
+    }
+}
+
+/// Position and direction at a distance along a polyline. Distances
+/// are clamped to the polyline.
+fn at_distance(points: &[LocalPoint], distance: f32) -> (LocalPoint, LocalVector) {
+    let mut last_dir = LocalVector::new(1.0, 0.0);
+    let mut s = 0.0;
+    for w in points.windows(2) {
+        let len = (w[1] - w[0]).length();
+        if let Some(dir) = direction(w[0], w[1]) {
+            last_dir = dir;
+            if distance <= s + len {
+                let t = ((distance - s) / len).max(0.0);
+                return (w[0].lerp(w[1], t), dir);
+            }
+        }
+        s += len;
+    }
+    (*points.last().unwrap(), last_dir)
+}
+
+/// Segments each quadratic curve is flattened into to place markers.
+const CURVE_SEGMENTS: usize = 16;
+
+/// Bisector of the directions into and out of a vertex.
+fn bisect(incoming: Option<LocalVector>, outgoing: Option<LocalVector>) -> Option<LocalVector> {
+    match (incoming, outgoing) {
+        (Some(a), Some(b)) => {
+            let bisector = a + b;
+            let len = bisector.length();
+            Some(if len > 0.0 { bisector / len } else { a })
+        }
+        (a, b) => a.or(b),
+    }
+}
+
+/// Computes marker positions and unit directions along a polyline,
+/// appending them to `markers`.
+pub fn marker_positions(
+    points: &[LocalPoint],
+    placement: &MarkerPlacement,
+    markers: &mut Vec<(LocalPoint, LocalVector)>,
+) {
+    let n = points.len();
+    if n == 0 {
+        return;
+    }
+
+    let total: f32 = points.windows(2).map(|w| (w[1] - w[0]).length()).sum();
+    let start = at_distance(points, 0.0);
+    let end = at_distance(points, total);
+
+    match placement {
+        MarkerPlacement::Start => markers.push(start),
+        MarkerPlacement::End => markers.push(end),
+        MarkerPlacement::StartAndEnd => markers.extend([start, end]),
+        MarkerPlacement::Vertices => {
+            markers.push(start);
+            let mut previous = start.1;
+            for i in 1..n - 1 {
+                let incoming = direction(points[i - 1], points[i]);
+                let outgoing = direction(points[i], points[i + 1]);
+                let dir = bisect(incoming, outgoing).unwrap_or(previous);
+                markers.push((points[i], dir));
+                previous = dir;
+            }
+            if n > 1 {
+                markers.push(end);
+            }
+        }
+        MarkerPlacement::Spacing(spacing) => {
+            if *spacing <= 0.0 {
+                markers.push(start);
+                return;
+            }
+            let mut d = 0.0;
+            while d <= total {
+                markers.push(at_distance(points, d));
+                d += spacing;
+            }
+        }
+        MarkerPlacement::Positions(distances) => {
+            markers.extend(distances.iter().map(|d| at_distance(points, *d)))
+        }
+    }
+}
+
+/// Point at `t` on the quadratic curve from `a` to `c` with control `b`.
+fn quad_point(a: LocalPoint, b: LocalPoint, c: LocalPoint, t: f32) -> LocalPoint {
+    a.lerp(b, t).lerp(b.lerp(c, t), t)
+}
+
+/// Directions of a quadratic curve at its ends, the tangents at t = 0
+/// and t = 1. A control point on an end falls back to the chord.
+fn quad_tangents(
+    a: LocalPoint,
+    b: LocalPoint,
+    c: LocalPoint,
+) -> (Option<LocalVector>, Option<LocalVector>) {
+    (
+        direction(a, b).or_else(|| direction(a, c)),
+        direction(b, c).or_else(|| direction(a, c)),
+    )
+}
+
+/// Like `marker_positions`, along quadratic curves from `start`, given as
+/// (control, end) pairs as with `quad_to`. Markers at the ends and
+/// vertices are oriented along the curves' tangents there, rather than
+/// the chords of the flattened curves.
+pub fn curve_marker_positions(
+    start: LocalPoint,
+    quads: &[(LocalPoint, LocalPoint)],
+    placement: &MarkerPlacement,
+) -> Vec<(LocalPoint, LocalVector)> {
+    // Vertices and tangents at the start of each curve and the end of
+    // the last.
+    let mut vertices = vec![(start, None, None)];
+    let mut points = vec![start];
+    let mut pen = start;
+    for (b, c) in quads {
+        let (out, into) = quad_tangents(pen, *b, *c);
+        vertices.last_mut().unwrap().2 = out;
+        vertices.push((*c, into, None));
+        points.extend(
+            (1..=CURVE_SEGMENTS).map(|i| quad_point(pen, *b, *c, i as f32 / CURVE_SEGMENTS as f32)),
+        );
+        pen = *c;
+    }
+
+    // Directions at the ends are those of the first and last curves
+    // which go anywhere.
+    let first = vertices.iter().find_map(|v| v.2);
+    let last = vertices.iter().rev().find_map(|v| v.1);
+    let total: f32 = points.windows(2).map(|w| (w[1] - w[0]).length()).sum();
+    let orient = |(p, dir): (LocalPoint, LocalVector), distance: f32| {
+        if distance <= 0.0 {
+            (p, first.unwrap_or(dir))
+        } else if distance >= total {
+            (p, last.unwrap_or(dir))
+        } else {
+            (p, dir)
+        }
+    };
+
+    match placement {
+        MarkerPlacement::Start => vec![orient(at_distance(&points, 0.0), 0.0)],
+        MarkerPlacement::End => vec![orient(at_distance(&points, total), total)],
+        MarkerPlacement::StartAndEnd => vec![
+            orient(at_distance(&points, 0.0), 0.0),
+            orient(at_distance(&points, total), total),
+        ],
+        MarkerPlacement::Vertices => {
+            let mut markers: Vec<(LocalPoint, LocalVector)> = vec![];
+            for (i, (p, into, out)) in vertices.iter().enumerate() {
+                let dir = if i == 0 {
+                    first
+                } else if i == vertices.len() - 1 {
+                    last
+                } else {
+                    bisect(*into, *out)
+                };
+                let previous = markers.last().map_or(LocalVector::new(1.0, 0.0), |m| m.1);
+                markers.push((*p, dir.unwrap_or(previous)));
+            }
+            markers
+        }
+        MarkerPlacement::Spacing(spacing) => {
+            let mut markers = vec![];
+            marker_positions(&points, placement, &mut markers);
+            markers
+                .into_iter()
+                .enumerate()
+                .map(|(i, m)| orient(m, i as f32 * spacing.max(0.0)))
+                .collect()
+        }
+        MarkerPlacement::Positions(distances) => distances
+            .iter()
+            .map(|d| orient(at_distance(&points, *d), *d))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn positions(
+        points: &[LocalPoint],
+        placement: &MarkerPlacement,
+    ) -> Vec<(LocalPoint, LocalVector)> {
+        let mut markers = vec![];
+        marker_positions(points, placement, &mut markers);
+        markers
+    }
+
+    fn path() -> Vec<LocalPoint> {
+        vec![
+            LocalPoint::new(0.0, 0.0),
+            LocalPoint::new(10.0, 0.0),
+            LocalPoint::new(10.0, 10.0),
+        ]
+    }
+
+    #[test]
+    fn test_start_end() {
+        let m = positions(&path(), &MarkerPlacement::StartAndEnd);
+        assert_eq!(m.len(), 2);
+        assert_eq!(
+            m[0],
+            (LocalPoint::new(0.0, 0.0), LocalVector::new(1.0, 0.0))
+        );
+        assert_eq!(
+            m[1],
+            (LocalPoint::new(10.0, 10.0), LocalVector::new(0.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn test_vertices() {
+        let m = positions(&path(), &MarkerPlacement::Vertices);
+        assert_eq!(m.len(), 3);
+        let s = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((m[1].1 - LocalVector::new(s, s)).length() < 1e-6);
+    }
+
+    #[test]
+    fn test_spacing() {
+        let m = positions(&path(), &MarkerPlacement::Spacing(5.0));
+        assert_eq!(m.len(), 5);
+        assert_eq!(m[1].0, LocalPoint::new(5.0, 0.0));
+        assert_eq!(m[3].0, LocalPoint::new(10.0, 5.0));
+
+        let m = positions(&path(), &MarkerPlacement::Positions(vec![15.0, 100.0]));
+        assert_eq!(m[0].0, LocalPoint::new(10.0, 5.0));
+        assert_eq!(m[1].0, LocalPoint::new(10.0, 10.0));
+    }
+
+    #[test]
+    fn test_curve_tangents() {
+        // Leaves the start heading up, and bends round to arrive heading
+        // left, so the chords point elsewhere.
+        let start = LocalPoint::new(0.0, 0.0);
+        let quads = [(LocalPoint::new(0.0, -10.0), LocalPoint::new(-10.0, -10.0))];
+        let m = curve_marker_positions(start, &quads, &MarkerPlacement::StartAndEnd);
+        assert_eq!(m[0], (start, LocalVector::new(0.0, -1.0)));
+        assert!((m[1].0 - LocalPoint::new(-10.0, -10.0)).length() < 1e-4);
+        assert_eq!(m[1].1, LocalVector::new(-1.0, 0.0));
+
+        let m = curve_marker_positions(start, &quads, &MarkerPlacement::Positions(vec![-1.0]));
+        assert_eq!(m[0].1, LocalVector::new(0.0, -1.0));
+
+        // A control point on the start falls back to the chord.
+        let quads = [(start, LocalPoint::new(10.0, 0.0))];
+        let m = curve_marker_positions(start, &quads, &MarkerPlacement::Start);
+        assert_eq!(m[0].1, LocalVector::new(1.0, 0.0));
+    }
+
+    #[test]
+    fn test_curve_vertices() {
+        let start = LocalPoint::new(0.0, 0.0);
+        let quads = [
+            (LocalPoint::new(5.0, 0.0), LocalPoint::new(10.0, 0.0)),
+            (LocalPoint::new(10.0, 5.0), LocalPoint::new(10.0, 10.0)),
+        ];
+        let m = curve_marker_positions(start, &quads, &MarkerPlacement::Vertices);
+        assert_eq!(m.len(), 3);
+        assert_eq!(m[1].0, LocalPoint::new(10.0, 0.0));
+        let s = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((m[1].1 - LocalVector::new(s, s)).length() < 1e-6);
+        assert_eq!(m[2].1, LocalVector::new(0.0, 1.0));
+
+        let m = curve_marker_positions(start, &quads, &MarkerPlacement::Spacing(6.0));
+        assert_eq!(m.len(), 4);
+        assert_eq!(m[0].1, LocalVector::new(1.0, 0.0));
+    }
+}
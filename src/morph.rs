@@ -0,0 +1,98 @@
+//! Shapes morphing between rects, rounded rects and circles, drawn with
+//! `Vger::fill_morph`.
+
+use crate::defs::*;
+
+/// A rounded rect whose roundness is relative to its size, so shapes
+/// interpolate smoothly from a circle to a card without cross-fading two
+/// prims.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Morph {
+    pub rect: LocalRect,
+
+    /// Corner radius as a fraction of the largest that fits: zero for
+    /// square corners, one for a circle when `rect` is square.
+    pub roundness: f32,
+}
+
+impl Morph {
+    pub fn rect(rect: LocalRect) -> Self {
+        Self {
+            rect,
+            roundness: 0.0,
+        }
+    }
+
+    /// A rect with corners of `radius`, limited to what fits.
+    pub fn rounded_rect(rect: LocalRect, radius: f32) -> Self {
+        let max = max_radius(rect);
+        let roundness = if max > 0.0 {
+            (radius / max).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        Self { rect, roundness }
+    }
+
+    pub fn circle(center: LocalPoint, radius: f32) -> Self {
+        Self {
+            rect: LocalRect::new(
+                center - LocalVector::new(radius, radius),
+                LocalSize::new(2.0 * radius, 2.0 * radius),
+            ),
+            roundness: 1.0,
+        }
+    }
+
+    /// The shape `t` of the way to `other`.
+    pub fn lerp(&self, other: &Morph, t: f32) -> Morph {
+        Morph {
+            rect: LocalRect::from_points([
+                self.rect.min().lerp(other.rect.min(), t),
+                self.rect.max().lerp(other.rect.max(), t),
+            ]),
+            roundness: self.roundness * (1.0 - t) + other.roundness * t,
+        }
+    }
+
+    /// Corner radius in local coordinates.
+    pub fn radius(&self) -> f32 {
+        self.roundness.clamp(0.0, 1.0) * max_radius(self.rect)
+    }
+}
+
+fn max_radius(rect: LocalRect) -> f32 {
+    0.5 * rect.width().min(rect.height()).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_morph() {
+        let circle = Morph::circle(LocalPoint::new(50.0, 50.0), 20.0);
+        assert_eq!(circle.rect, euclid::rect(30.0, 30.0, 40.0, 40.0));
+        assert_eq!(circle.radius(), 20.0);
+
+        let card = Morph::rounded_rect(euclid::rect(0.0, 0.0, 200.0, 100.0), 10.0);
+        assert_eq!(card.roundness, 0.2);
+        assert_eq!(card.radius(), 10.0);
+
+        assert_eq!(circle.lerp(&card, 0.0), circle);
+        assert_eq!(circle.lerp(&card, 1.0), card);
+
+        // Halfway the radius always fits.
+        let mid = circle.lerp(&card, 0.5);
+        assert_eq!(mid.rect, euclid::rect(15.0, 15.0, 120.0, 70.0));
+        assert!((mid.radius() - 0.6 * 35.0).abs() < 1e-4);
+
+        // Radii too large for the rect are limited.
+        assert_eq!(
+            Morph::rounded_rect(euclid::rect(0.0, 0.0, 10.0, 10.0), 8.0).radius(),
+            5.0
+        );
+        assert_eq!(Morph::rect(card.rect).radius(), 0.0);
+    }
+}
@@ -0,0 +1,269 @@
+//! Localized number formatting, e.g. for chart axis labels, without a
+//! dependency on ICU. Covers digit grouping, decimal separators and
+//! native digits for common locales. Numbers are formatted before
+//! shaping, so they're drawn like any other text.
+//! Enabled with the `numbers` feature.
+
+/// The digits numbers are written with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Digits {
+    /// 0123456789
+    Latin,
+
+    /// ٠١٢٣٤٥٦٧٨٩, as in Arabic.
+    ArabicIndic,
+
+    /// ۰۱۲۳۴۵۶۷۸۹, as in Persian and Urdu.
+    EasternArabicIndic,
+
+    /// ०१२३४५६७८९
+    Devanagari,
+
+    /// ০১২৩৪৫৬৭৮৯
+    Bengali,
+}
+
+impl Digits {
+    /// The digit for 0. The rest follow in order.
+    fn zero(self) -> char {
+        match self {
+            Digits::Latin => '0',
+            Digits::ArabicIndic => '\u{0660}',
+            Digits::EasternArabicIndic => '\u{06F0}',
+            Digits::Devanagari => '\u{0966}',
+            Digits::Bengali => '\u{09E6}',
+        }
+    }
+
+    /// Replaces ASCII digits in `text`.
+    pub fn localize(self, text: &str) -> String {
+        let zero = self.zero() as u32;
+        text.chars()
+            .map(|c| match c.to_digit(10) {
+                Some(d) if c.is_ascii_digit() => std::char::from_u32(zero + d).unwrap_or(c),
+                _ => c,
+            })
+            .collect()
+    }
+}
+
+/// How digits before the decimal separator are grouped.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Grouping {
+    /// Groups of three: 1,234,567.
+    Thousands,
+
+    /// Three, then twos, as in India: 12,34,567.
+    Indian,
+}
+
+/// Separators, grouping and digits for formatting numbers in a locale.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub decimal: char,
+
+    /// `None` doesn't group digits.
+    pub group: Option<char>,
+    pub grouping: Grouping,
+    pub digits: Digits,
+    pub minus: char,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            decimal: '.',
+            group: Some(','),
+            grouping: Grouping::Thousands,
+            digits: Digits::Latin,
+            minus: '-',
+        }
+    }
+}
+
+impl NumberFormat {
+    /// Format for a BCP 47 language tag such as `de`, `fr-CA` or `hi-IN`.
+    /// Unknown languages get the English format.
+    pub fn for_locale(tag: &str) -> Self {
+        let mut parts = tag.split(|c| c == '-' || c == '_');
+        let language = parts.next().unwrap_or("").to_ascii_lowercase();
+        let region = parts
+            .find(|part| part.len() == 2)
+            .map(|part| part.to_ascii_uppercase());
+        let region = region.as_deref();
+
+        let base = Self::default();
+        match (language.as_str(), region) {
+            ("de", Some("CH")) => Self {
+                group: Some('\u{2019}'),
+                ..base
+            },
+            ("de" | "es" | "it" | "nl" | "pt" | "id" | "tr" | "da" | "el", _) => Self {
+                decimal: ',',
+                group: Some('.'),
+                ..base
+            },
+            ("fr" | "nb" | "sv" | "fi" | "cs" | "pl" | "ru" | "uk", _) => Self {
+                decimal: ',',
+                group: Some('\u{202F}'),
+                minus: '\u{2212}',
+                ..base
+            },
+            ("ar", _) => Self {
+                decimal: '\u{066B}',
+                group: Some('\u{066C}'),
+                digits: Digits::ArabicIndic,
+                ..base
+            },
+            ("fa" | "ur", _) => Self {
+                decimal: '\u{066B}',
+                group: Some('\u{066C}'),
+                digits: Digits::EasternArabicIndic,
+                minus: '\u{2212}',
+                ..base
+            },
+            ("mr" | "ne", _) => Self {
+                grouping: Grouping::Indian,
+                digits: Digits::Devanagari,
+                ..base
+            },
+            ("bn", _) => Self {
+                grouping: Grouping::Indian,
+                digits: Digits::Bengali,
+                ..base
+            },
+            ("hi" | "ta" | "te" | "gu" | "kn" | "ml", _) | ("en", Some("IN")) => Self {
+                grouping: Grouping::Indian,
+                ..base
+            },
+            _ => base,
+        }
+    }
+
+    /// Formats `value` with `decimals` digits after the decimal separator.
+    pub fn format(&self, value: f64, decimals: usize) -> String {
+        if value.is_nan() {
+            return "NaN".to_string();
+        }
+        if value.is_infinite() {
+            let sign = if value < 0.0 { Some(self.minus) } else { None };
+            return sign.into_iter().chain(Some('∞')).collect();
+        }
+
+        let text = format!("{:.*}", decimals, value.abs());
+        let (int, frac) = match text.split_once('.') {
+            Some((int, frac)) => (int, Some(frac)),
+            None => (text.as_str(), None),
+        };
+
+        let mut out = String::new();
+        // Values which round to zero don't get a sign.
+        if value < 0.0 && text.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            out.push(self.minus);
+        }
+        out.push_str(&self.digits.localize(&self.group_digits(int)));
+        if let Some(frac) = frac {
+            out.push(self.decimal);
+            out.push_str(&self.digits.localize(frac));
+        }
+        out
+    }
+
+    /// Formats a whole number. Unlike `format`, every digit is exact,
+    /// even past 2^53.
+    pub fn format_int(&self, value: i64) -> String {
+        let mut out = String::new();
+        if value < 0 {
+            out.push(self.minus);
+        }
+        let int = value.unsigned_abs().to_string();
+        out.push_str(&self.digits.localize(&self.group_digits(&int)));
+        out
+    }
+
+    /// Inserts group separators into a run of ASCII digits.
+    fn group_digits(&self, int: &str) -> String {
+        let separator = match self.group {
+            Some(separator) => separator,
+            None => return int.to_string(),
+        };
+
+        // Group sizes from the right.
+        let sizes = std::iter::once(3).chain(std::iter::repeat(match self.grouping {
+            Grouping::Thousands => 3,
+            Grouping::Indian => 2,
+        }));
+        let mut groups = vec![];
+        let mut end = int.len();
+        for size in sizes {
+            if end == 0 {
+                break;
+            }
+            let start = end.saturating_sub(size);
+            groups.push(&int[start..end]);
+            end = start;
+        }
+        groups.reverse();
+        groups.join(separator.encode_utf8(&mut [0; 4]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_grouping() {
+        let en = NumberFormat::default();
+        assert_eq!(en.format(1234567.891, 2), "1,234,567.89");
+        assert_eq!(en.format(999.0, 0), "999");
+        assert_eq!(en.format(-1000.0, 1), "-1,000.0");
+        assert_eq!(en.format_int(0), "0");
+
+        let india = NumberFormat::for_locale("en-IN");
+        assert_eq!(india.format_int(1234567), "12,34,567");
+        assert_eq!(india.format_int(12345), "12,345");
+
+        let none = NumberFormat { group: None, ..en };
+        assert_eq!(none.format_int(1234567), "1234567");
+
+        // Whole numbers don't go through f64.
+        assert_eq!(en.format_int(9007199254740993), "9,007,199,254,740,993");
+        assert_eq!(en.format_int(i64::MIN), "-9,223,372,036,854,775,808");
+    }
+
+    #[test]
+    fn test_locales() {
+        assert_eq!(NumberFormat::for_locale("de").format(1234.5, 1), "1.234,5");
+        assert_eq!(
+            NumberFormat::for_locale("fr-FR").format(-1234.5, 1),
+            "\u{2212}1\u{202F}234,5"
+        );
+        assert_eq!(
+            NumberFormat::for_locale("de_CH").format_int(1234),
+            "1\u{2019}234"
+        );
+        assert_eq!(NumberFormat::for_locale("ar").format(1234.5, 1), "١٬٢٣٤٫٥");
+        assert_eq!(NumberFormat::for_locale("fa").format_int(1402), "۱٬۴۰۲");
+        assert_eq!(
+            NumberFormat::for_locale("mr").format_int(123456),
+            "१,२३,४५६"
+        );
+        assert_eq!(NumberFormat::for_locale("xx"), NumberFormat::default());
+    }
+
+    #[test]
+    fn test_special_values() {
+        let en = NumberFormat::default();
+        assert_eq!(en.format(-0.001, 2), "0.00");
+        assert_eq!(en.format(f64::NAN, 2), "NaN");
+        assert_eq!(en.format(f64::NEG_INFINITY, 2), "-∞");
+    }
+
+    #[test]
+    fn test_localize_digits() {
+        assert_eq!(Digits::Devanagari.localize("v1.2"), "v१.२");
+        assert_eq!(Digits::Latin.localize("42"), "42");
+    }
+}
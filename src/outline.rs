@@ -0,0 +1,153 @@
+//! Glyph outlines, for drawing text too large for the atlas as filled
+//! paths. See `Vger::set_outline_threshold`.
+
+use swash::scale::ScaleContext;
+use swash::zeno::Verb;
+use swash::FontRef;
+
+use crate::defs::*;
+
+/// A closed curve of an outline, made of quadratic segments.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Contour {
+    pub start: LocalPoint,
+
+    /// Control and end points.
+    pub quads: Vec<(LocalPoint, LocalPoint)>,
+}
+
+/// A glyph's contours at a size of one pixel, with y up from the
+/// baseline, so one outline serves every size.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct GlyphOutline {
+    pub contours: Vec<Contour>,
+}
+
+impl GlyphOutline {
+    fn pen(&self) -> LocalPoint {
+        self.contours.last().map_or(LocalPoint::zero(), |contour| {
+            contour.quads.last().map_or(contour.start, |q| q.1)
+        })
+    }
+
+    pub fn move_to(&mut self, p: LocalPoint) {
+        self.close();
+        self.contours.push(Contour {
+            start: p,
+            quads: vec![],
+        });
+    }
+
+    pub fn line_to(&mut self, p: LocalPoint) {
+        let pen = self.pen();
+        self.quad_to(pen.lerp(p, 0.5), p);
+    }
+
+    pub fn quad_to(&mut self, b: LocalPoint, c: LocalPoint) {
+        if let Some(contour) = self.contours.last_mut() {
+            contour.quads.push((b, c));
+        }
+    }
+
+    /// Approximates a cubic, as in CFF fonts, by a quadratic for each
+    /// half.
+    pub fn cubic_to(&mut self, b: LocalPoint, c: LocalPoint, d: LocalPoint) {
+        let a = self.pen();
+        let ab = a.lerp(b, 0.5);
+        let bc = b.lerp(c, 0.5);
+        let cd = c.lerp(d, 0.5);
+        let abc = ab.lerp(bc, 0.5);
+        let bcd = bc.lerp(cd, 0.5);
+        let mid = abc.lerp(bcd, 0.5);
+        for (p0, p1, p2, p3) in [(a, ab, abc, mid), (mid, bcd, cd, d)] {
+            let control =
+                ((p1.to_vector() + p2.to_vector()) * 3.0 - p0.to_vector() - p3.to_vector()) / 4.0;
+            self.quad_to(control.to_point(), p3);
+        }
+    }
+
+    /// Joins the current contour back to its start.
+    pub fn close(&mut self) {
+        if let Some(contour) = self.contours.last() {
+            let start = contour.start;
+            if self.pen() != start {
+                self.line_to(start);
+            }
+        }
+    }
+}
+
+/// The outline of glyph `id` of `font`, or `None` for bitmap glyphs.
+pub(crate) fn glyph_outline(
+    context: &mut ScaleContext,
+    font: FontRef,
+    id: u16,
+) -> Option<GlyphOutline> {
+    let mut scaler = context.builder(font).size(1.0).hint(false).build();
+    let outline = scaler.scale_outline(id)?;
+
+    let mut glyph = GlyphOutline::default();
+    let mut points = outline.points().iter().map(|p| LocalPoint::new(p.x, p.y));
+    let mut next = || points.next().unwrap_or_else(LocalPoint::zero);
+    for verb in outline.verbs() {
+        match verb {
+            Verb::MoveTo => glyph.move_to(next()),
+            Verb::LineTo => glyph.line_to(next()),
+            Verb::QuadTo => {
+                let b = next();
+                glyph.quad_to(b, next());
+            }
+            Verb::CurveTo => {
+                let (b, c) = (next(), next());
+                glyph.cubic_to(b, c, next());
+            }
+            Verb::Close => glyph.close(),
+        }
+    }
+    glyph.close();
+    Some(glyph)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_contours() {
+        let mut outline = GlyphOutline::default();
+        outline.move_to(LocalPoint::new(0.0, 0.0));
+        outline.line_to(LocalPoint::new(1.0, 0.0));
+        outline.line_to(LocalPoint::new(1.0, 1.0));
+        outline.move_to(LocalPoint::new(2.0, 0.0));
+        outline.quad_to(LocalPoint::new(3.0, 1.0), LocalPoint::new(2.0, 0.0));
+        outline.close();
+
+        assert_eq!(outline.contours.len(), 2);
+
+        // The first contour was closed by a line back to its start.
+        let first = &outline.contours[0];
+        assert_eq!(first.quads.len(), 3);
+        assert_eq!(first.quads[2], (LocalPoint::new(0.5, 0.5), first.start));
+
+        // The second already ended at its start.
+        assert_eq!(outline.contours[1].quads.len(), 1);
+    }
+
+    #[test]
+    fn test_cubic() {
+        let mut outline = GlyphOutline::default();
+        outline.move_to(LocalPoint::new(0.0, 0.0));
+
+        // A cubic which is exactly a quadratic with control (1, 2).
+        let b = LocalPoint::new(2.0 / 3.0, 4.0 / 3.0);
+        let c = LocalPoint::new(4.0 / 3.0, 4.0 / 3.0);
+        outline.cubic_to(b, c, LocalPoint::new(2.0, 0.0));
+
+        let quads = &outline.contours[0].quads;
+        assert_eq!(quads.len(), 2);
+        assert!((quads[0].1 - LocalPoint::new(1.0, 1.0)).length() < 1e-5);
+        assert!((quads[0].0 - LocalPoint::new(0.5, 1.0)).length() < 1e-5);
+        assert_eq!(quads[1].1, LocalPoint::new(2.0, 0.0));
+    }
+}
@@ -0,0 +1,138 @@
+// Half size prims and paints with RGBA8 colors, appended to shader.wgsl
+// for Vger::set_packed_prims. Prims are loaded with load_prim instead of
+// from the prims buffer, which holds those that couldn't be packed, and
+// paints likewise with unpack_paint. Matches PackedPrim in prim.rs and
+// PackedPaint in paint.rs.
+
+struct PackedPrim {
+
+    /// The quad, which is also the texture space bounds.
+    quad_bounds_min: vec2<f32>,
+    quad_bounds_max: vec2<f32>,
+
+    /// Transform index then prim type, in 16 bits each.
+    xform_type: u32,
+
+    /// Paint index then scissor index, in 16 bits each.
+    paint_scissor: u32,
+
+    /// Width and radius as f16s.
+    width_radius: u32,
+
+    /// Control vertices as pairs of f16s. Points are relative to the
+    /// center of the quad, and unused control vertices are zero.
+    cv0: u32,
+    cv1: u32,
+    cv2: u32,
+
+    glyph: u32,
+
+    /// Index in the prims buffer of a prim which couldn't be packed, or
+    /// all ones.
+    full: u32,
+};
+
+struct PackedPrims {
+    prims: array<PackedPrim>,
+};
+
+@group(0)
+@binding(5)
+var<storage> packed_prims: PackedPrims;
+
+/// Whether control vertex i is a point rather than e.g. a sine and cosine.
+/// Unused control vertices are packed as zero, so are never read.
+fn cv_is_point(prim_type: u32, i: u32) -> bool {
+    if (prim_type == 1u || prim_type == 17u) { // vgerArc, vgerRoundArc
+        return i == 0u;
+    }
+    if (prim_type == 12u || prim_type == 13u) { // vgerTaperedSegment, vgerGrid
+        return i < 2u;
+    }
+    return true;
+}
+
+fn unpack_cv(cv: u32, prim_type: u32, i: u32, center: vec2<f32>) -> vec2<f32> {
+    let v = unpack2x16float(cv);
+    if (cv_is_point(prim_type, i)) {
+        return v + center;
+    }
+    return v;
+}
+
+fn load_prim(index: u32) -> Prim {
+    let packed = packed_prims.prims[index];
+    if (packed.full != 0xffffffffu) {
+        return prims.prims[packed.full];
+    }
+
+    var prim: Prim;
+    prim.quad_bounds_min = packed.quad_bounds_min;
+    prim.quad_bounds_max = packed.quad_bounds_max;
+    prim.tex_bounds_min = packed.quad_bounds_min;
+    prim.tex_bounds_max = packed.quad_bounds_max;
+    prim.xform = packed.xform_type & 0xffffu;
+    prim.prim_type = packed.xform_type >> 16u;
+    prim.paint = packed.paint_scissor & 0xffffu;
+    prim.scissor = packed.paint_scissor >> 16u;
+
+    let width_radius = unpack2x16float(packed.width_radius);
+    prim.width = width_radius.x;
+    prim.radius = width_radius.y;
+
+    let center = 0.5 * (packed.quad_bounds_min + packed.quad_bounds_max);
+    prim.cv0 = unpack_cv(packed.cv0, prim.prim_type, 0u, center);
+    prim.cv1 = unpack_cv(packed.cv1, prim.prim_type, 1u, center);
+    prim.cv2 = unpack_cv(packed.cv2, prim.prim_type, 2u, center);
+    prim.glyph = packed.glyph;
+    return prim;
+}
+
+struct PackedPaint {
+    params: vec4<f32>,
+    xform: PackedMat3x2,
+    glow: f32,
+    image: i32,
+
+    /// Colors as RGBA8.
+    inner_color: u32,
+    outer_color: u32,
+
+    paint_type: u32,
+    space: u32,
+    glow_color: u32,
+    glow_radius: f32,
+
+    /// Index in the paints buffer of a paint which couldn't be packed, or
+    /// all ones.
+    full: u32,
+    pad: u32,
+};
+
+struct PackedPaints {
+    paints: array<PackedPaint>,
+};
+
+@group(0)
+@binding(6)
+var<storage> packed_paints: PackedPaints;
+
+fn unpack_paint(index: u32) -> Paint {
+    let packed = packed_paints.paints[index];
+    if (packed.full != 0xffffffffu) {
+        return paints.paints[packed.full];
+    }
+
+    var paint: Paint;
+    paint.xform = packed.xform;
+    paint.glow = packed.glow;
+    paint.image = packed.image;
+    paint.inner_color = unpack4x8unorm(packed.inner_color);
+    paint.outer_color = unpack4x8unorm(packed.outer_color);
+    paint.params = packed.params;
+    paint.paint_type = packed.paint_type;
+    paint.space = packed.space;
+    paint.glow_color = packed.glow_color;
+    paint.glow_radius = packed.glow_radius;
+    return paint;
+}
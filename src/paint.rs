@@ -1,6 +1,124 @@
 use crate::color::*;
 use crate::defs::*;
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum PaintType {
+    /// Solid colors and linear gradients.
+    Gradient,
+
+    /// Parallel lines.
+    Hatch,
+
+    /// Two perpendicular sets of parallel lines.
+    CrossHatch,
+
+    /// Alternating square cells of two colors.
+    Checkerboard,
+
+    /// Highlight band moving over a base color with time.
+    Shimmer,
+
+    /// Smoothly interpolated random values on a grid.
+    ValueNoise,
+
+    /// Gradient (Perlin) noise.
+    GradientNoise,
+
+    /// Random value per cell, uninterpolated.
+    Grain,
+
+    /// Gradient between two circles, as in Canvas
+    /// `createRadialGradient`.
+    ConicalGradient,
+
+    /// Value looked up in a colormap.
+    Heatmap,
+}
+
+/// Kind of procedural noise.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NoiseKind {
+    /// Blobby noise interpolated between random values.
+    Value,
+
+    /// Smoother gradient (Perlin) noise, with fewer grid artifacts than
+    /// value noise.
+    Gradient,
+
+    /// Film grain: an independent random value per cell. With a scale of
+    /// one device pixel, good for dithering gradients to hide banding.
+    Grain,
+}
+
+/// Procedural noise for `Vger::noise_paint`, evaluated in the shader.
+#[derive(Copy, Clone, Debug)]
+pub struct Noise {
+    pub kind: NoiseKind,
+
+    /// Size of a noise cell, in local units or device pixels according
+    /// to `space`.
+    pub scale: f32,
+
+    /// Contrast, from 0 for flat to 1 for the full range.
+    pub intensity: f32,
+
+    /// Different seeds give unrelated noise.
+    pub seed: u32,
+
+    /// Cells after which the noise repeats, for seamless tiles. Zero
+    /// never repeats.
+    pub period: u32,
+
+    pub space: PatternSpace,
+}
+
+/// Coordinate space in which a pattern paint is evaluated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PatternSpace {
+    /// Pattern follows the shape's local coordinates.
+    Local,
+
+    /// Pattern is fixed to device pixels.
+    Device,
+}
+
+/// How a gradient continues past its ends.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpreadMode {
+    /// Extends the end colors.
+    Pad,
+
+    /// Starts over at each end, for stripes.
+    Repeat,
+
+    /// Runs back and forth, mirrored at each end.
+    Reflect,
+}
+
+impl Default for SpreadMode {
+    fn default() -> Self {
+        SpreadMode::Pad
+    }
+}
+
+impl SpreadMode {
+    /// Maps a gradient position to 0..1. Mirrors `spread` in the shader.
+    pub(crate) fn apply(self, t: f32) -> f32 {
+        match self {
+            SpreadMode::Pad => t.clamp(0.0, 1.0),
+            SpreadMode::Repeat => t - t.floor(),
+            SpreadMode::Reflect => {
+                let u = t * 0.5;
+                1.0 - ((u - u.floor()) * 2.0 - 1.0).abs()
+            }
+        }
+    }
+}
+
+/// Bitwise copy of a `Paint`.
+pub type PaintKey = [u32; 24];
+
 #[derive(Clone, Copy)]
 #[repr(C)]
 pub struct Paint {
@@ -11,19 +129,72 @@ pub struct Paint {
 
     inner_color: Color, // vec4<f32>
     outer_color: Color, // vec4<f32>
+
+    /// Parameters depending on the paint type.
+    params: [f32; 4], // vec4<f32>
+
+    paint_type: u32,
+
+    /// PatternSpace for pattern paints, SpreadMode for gradients.
+    space: u32,
+
+    /// Color of the glow around shapes, packed as RGBA8.
+    glow_color: u32,
+
+    /// Distance the glow fades out over. No glow if zero.
+    glow_radius: f32,
 }
 
 impl Paint {
     #[allow(dead_code)]
     pub fn apply(&self, p: WorldPoint) -> Color {
         let local_point = self.xform.transform_point(p);
-        let d = local_point
-            .clamp(LocalPoint::zero(), LocalPoint::new(1.0, 1.0))
-            .x;
+        if self.paint_type == PaintType::ConicalGradient as u32 {
+            let [dx, dy, r0, r1] = self.params;
+            let d = local_point.to_vector();
+            return match conical_t(d, LocalVector::new(dx, dy), r0, r1) {
+                Some(t) => self
+                    .inner_color
+                    .mix(self.outer_color, self.spread().apply(t)),
+                None => Color::new(0.0, 0.0, 0.0, 0.0),
+            };
+        }
+        let d = self.spread().apply(local_point.x);
 
         self.inner_color.mix(self.outer_color, d)
     }
 
+    fn spread(&self) -> SpreadMode {
+        match self.space {
+            1 => SpreadMode::Repeat,
+            2 => SpreadMode::Reflect,
+            _ => SpreadMode::Pad,
+        }
+    }
+
+    /// Sets how a linear or conical gradient continues past its ends,
+    /// e.g. repeating for a barber pole. Other paints are unchanged.
+    pub fn with_spread(mut self, spread: SpreadMode) -> Self {
+        let gradient = self.paint_type == PaintType::Gradient as u32
+            || self.paint_type == PaintType::ConicalGradient as u32;
+        if gradient {
+            self.space = spread as u32;
+        }
+        self
+    }
+
+    /// Whether all colors, parameters and the transform are finite.
+    pub fn is_finite(&self) -> bool {
+        let c = |c: &Color| [c.r, c.g, c.b, c.a];
+        self.xform
+            .to_array()
+            .iter()
+            .chain(c(&self.inner_color).iter())
+            .chain(c(&self.outer_color).iter())
+            .chain(self.params.iter())
+            .all(|v| v.is_finite())
+    }
+
     pub fn solid_color(color: Color) -> Self {
         Self {
             xform: WorldToLocal::identity(),
@@ -31,6 +202,11 @@ impl Paint {
             outer_color: color,
             image: -1,
             glow: 0.0,
+            params: [0.0; 4],
+            paint_type: PaintType::Gradient as u32,
+            space: PatternSpace::Local as u32,
+            glow_color: 0,
+            glow_radius: 0.0,
         }
     }
 
@@ -57,8 +233,287 @@ impl Paint {
             outer_color,
             image: -1,
             glow,
+            params: [0.0; 4],
+            paint_type: PaintType::Gradient as u32,
+            space: PatternSpace::Local as u32,
+            glow_color: 0,
+            glow_radius: 0.0,
         }
     }
+
+    /// Gradient from the circle at `start` with radius `start_radius` to
+    /// the one at `end`, like Canvas `createRadialGradient`. Colors are
+    /// extended past the circles, and points on no circle in between are
+    /// transparent. A simple radial gradient starts with a zero radius
+    /// circle at the center of the end circle.
+    pub fn conical_gradient(
+        start: LocalPoint,
+        start_radius: f32,
+        end: LocalPoint,
+        end_radius: f32,
+        inner_color: Color,
+        outer_color: Color,
+    ) -> Self {
+        let mut paint = Self::solid_color(inner_color);
+        paint.outer_color = outer_color;
+        paint.xform = WorldToLocal::translation(-start.x, -start.y);
+        let d = end - start;
+        paint.params = [d.x, d.y, start_radius.max(0.0), end_radius.max(0.0)];
+        paint.paint_type = PaintType::ConicalGradient as u32;
+        paint
+    }
+
+    /// Highlight band `band_width` wide, as a fraction of the distance from
+    /// `start` to `end`, sweeping between them `speed` times a second.
+    pub fn shimmer(
+        start: LocalPoint,
+        end: LocalPoint,
+        base: Color,
+        highlight: Color,
+        band_width: f32,
+        speed: f32,
+    ) -> Self {
+        let mut paint = Self::linear_gradient(start, end, base, highlight, 0.0);
+        paint.params = [band_width.max(0.0001), speed, 0.0, 0.0];
+        paint.paint_type = PaintType::Shimmer as u32;
+        paint
+    }
+
+    /// Noise mixing between two colors.
+    pub fn noise(noise: &Noise, color_a: Color, color_b: Color) -> Self {
+        let mut paint = Self::solid_color(color_a);
+        paint.outer_color = color_b;
+        paint.params = [
+            noise.scale.max(0.0001),
+            noise.intensity.clamp(0.0, 1.0),
+            // Exact in an f32 up to 2^24.
+            (noise.seed & 0xffffff) as f32,
+            noise.period as f32,
+        ];
+        paint.paint_type = match noise.kind {
+            NoiseKind::Value => PaintType::ValueNoise,
+            NoiseKind::Gradient => PaintType::GradientNoise,
+            NoiseKind::Grain => PaintType::Grain,
+        } as u32;
+        paint.space = noise.space as u32;
+        paint
+    }
+
+    /// Colormap lookup of a value constant over the shape, e.g. a heatmap
+    /// cell or scatter plot dot. `t` is the value mapped to 0..1 over the
+    /// colormap, and `lut` the position of the colormap's lookup table in
+    /// the color atlas. `low` and `high` are the colormap's ends, blended
+    /// between by `apply`, and in the shader when there's no `lut`.
+    pub fn heatmap(t: f32, lut: Option<[f32; 2]>, low: Color, high: Color) -> Self {
+        let mut paint = Self::solid_color(low);
+        paint.outer_color = high;
+        paint.xform = WorldToLocal::new(0.0, 0.0, 0.0, 0.0, t, 0.0);
+        paint.set_lut(lut);
+        paint
+    }
+
+    /// Colormap lookup from `start` to `end`, e.g. for a colorbar. See
+    /// `heatmap`.
+    pub fn heatmap_gradient(
+        start: LocalPoint,
+        end: LocalPoint,
+        lut: Option<[f32; 2]>,
+        low: Color,
+        high: Color,
+    ) -> Self {
+        let mut paint = Self::linear_gradient(start, end, low, high, 0.0);
+        paint.set_lut(lut);
+        paint
+    }
+
+    fn set_lut(&mut self, lut: Option<[f32; 2]>) {
+        if let Some([x, y]) = lut {
+            self.params = [x, y, 0.0, 0.0];
+            self.paint_type = PaintType::Heatmap as u32;
+        }
+    }
+
+    /// Adds a glow of `color` around shapes drawn with the paint, fading
+    /// out over `radius` from the shape's edge.
+    pub fn with_glow(mut self, color: Color, radius: f32) -> Self {
+        self.glow_color = pack_unorm(color);
+        self.glow_radius = radius.max(0.0);
+        self
+    }
+
+    pub fn glow_radius(&self) -> f32 {
+        self.glow_radius
+    }
+
+    /// Bits of the paint, for finding identical paints.
+    pub fn key(&self) -> PaintKey {
+        unsafe { std::mem::transmute::<Paint, PaintKey>(*self) }
+    }
+
+    /// Lines at `angle` radians, `spacing` apart (center to center) and
+    /// `width` wide, over a background color.
+    pub fn hatch(
+        angle: f32,
+        spacing: f32,
+        width: f32,
+        color: Color,
+        background: Color,
+        cross: bool,
+        space: PatternSpace,
+    ) -> Self {
+        let mut paint = Self::solid_color(color);
+        paint.outer_color = background;
+        paint.params = [angle.cos(), angle.sin(), spacing.max(0.0001), width];
+        paint.paint_type = if cross {
+            PaintType::CrossHatch
+        } else {
+            PaintType::Hatch
+        } as u32;
+        paint.space = space as u32;
+        paint
+    }
+
+    /// Squares `cell_size` wide alternating between two colors, with
+    /// `color_a` in the cell at the origin.
+    pub fn checkerboard(
+        cell_size: f32,
+        color_a: Color,
+        color_b: Color,
+        space: PatternSpace,
+    ) -> Self {
+        let mut paint = Self::solid_color(color_a);
+        paint.outer_color = color_b;
+        paint.params = [cell_size.max(0.0001), 0.0, 0.0, 0.0];
+        paint.paint_type = PaintType::Checkerboard as u32;
+        paint.space = space as u32;
+        paint
+    }
+}
+
+/// Marks a packed paint as encoded fully.
+const NOT_REDIRECTED: u32 = u32::MAX;
+
+/// `Paint` with RGBA8 colors, for `Vger::set_packed_prims`. Matches
+/// `PackedPaint` in the packed shader.
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct PackedPaint {
+    params: [f32; 4],
+    xform: [f32; 6],
+    glow: f32,
+    image: i32,
+    inner_color: u32,
+    outer_color: u32,
+    paint_type: u32,
+    space: u32,
+    glow_color: u32,
+    glow_radius: f32,
+
+    /// Index of the paint in the full precision buffer, if it couldn't be
+    /// packed.
+    full: u32,
+
+    pad: u32,
+}
+
+impl PackedPaint {
+    /// Entry referring to a paint in the full precision buffer.
+    pub fn redirect(index: usize) -> Self {
+        Self {
+            full: index as u32,
+            ..Default::default()
+        }
+    }
+}
+
+impl Paint {
+    /// Packs the paint, unless a color is outside 0 to 1, which RGBA8
+    /// can't hold.
+    pub fn pack(&self) -> Option<PackedPaint> {
+        let in_range = |c: &Color| [c.r, c.g, c.b, c.a].iter().all(|v| (0.0..=1.0).contains(v));
+        if !in_range(&self.inner_color) || !in_range(&self.outer_color) {
+            return None;
+        }
+        Some(PackedPaint {
+            params: self.params,
+            xform: self.xform.to_array(),
+            glow: self.glow,
+            image: self.image,
+            inner_color: pack_unorm(self.inner_color),
+            outer_color: pack_unorm(self.outer_color),
+            paint_type: self.paint_type,
+            space: self.space,
+            glow_color: self.glow_color,
+            glow_radius: self.glow_radius,
+            full: NOT_REDIRECTED,
+            pad: 0,
+        })
+    }
+}
+
+/// Packs a color as `unpack4x8unorm` in the shader expects, red in the
+/// low byte.
+fn pack_unorm(color: Color) -> u32 {
+    let c = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u32;
+    c(color.r) | (c(color.g) << 8) | (c(color.b) << 16) | (c(color.a) << 24)
+}
+
+/// Gradient position of the point `p` from the start center, for circles
+/// `d` apart with radii `r0` and `r1`: the largest t for which the circle
+/// interpolated to t passes through p with a radius that isn't negative.
+/// Mirrors `conical_t` in the shader.
+fn conical_t(p: LocalVector, d: LocalVector, r0: f32, r1: f32) -> Option<f32> {
+    let dr = r1 - r0;
+    let a = d.dot(d) - dr * dr;
+    let b = p.dot(d) + r0 * dr;
+    let c = p.dot(p) - r0 * r0;
+    let radius_ok = |t: f32| r0 + t * dr >= 0.0;
+
+    // Circles growing as fast as they move give a linear equation.
+    if a.abs() < 1e-6 {
+        let t = c / (2.0 * b);
+        return if b != 0.0 && radius_ok(t) {
+            Some(t)
+        } else {
+            None
+        };
+    }
+
+    let disc = b * b - a * c;
+    if disc < 0.0 {
+        return None;
+    }
+    let s = disc.sqrt();
+    let (t0, t1) = ((b + s) / a, (b - s) / a);
+    let (hi, lo) = if t0 > t1 { (t0, t1) } else { (t1, t0) };
+    [hi, lo].iter().copied().find(|t| radius_ok(*t))
+}
+
+/// Evaluates a multi-stop gradient. Stops are (offset, color) pairs
+/// sorted by offset. Outside the stops, the end colors are extended.
+pub fn stop_color(stops: &[(f32, Color)], t: f32) -> Color {
+    let (first, last) = match (stops.first(), stops.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Color::new(0.0, 0.0, 0.0, 0.0),
+    };
+
+    if t <= first.0 {
+        return first.1;
+    }
+
+    for w in stops.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        if t <= b.0 {
+            let s = if b.0 > a.0 {
+                (t - a.0) / (b.0 - a.0)
+            } else {
+                1.0
+            };
+            return a.1.mix(b.1, s);
+        }
+    }
+
+    last.1
 }
 
 #[cfg(test)]
@@ -68,7 +523,15 @@ mod tests {
 
     #[test]
     fn test_paint_size() {
-        assert_eq!(std::mem::size_of::<Paint>(), 64);
+        assert_eq!(std::mem::size_of::<Paint>(), 96);
+    }
+
+    #[test]
+    fn test_key() {
+        let a = Paint::solid_color(Color::CYAN);
+        assert_eq!(a.key(), Paint::solid_color(Color::CYAN).key());
+        assert_ne!(a.key(), Paint::solid_color(Color::MAGENTA).key());
+        assert_ne!(a.key(), a.with_glow(Color::CYAN, 4.0).key());
     }
 
     #[test]
@@ -116,4 +579,171 @@ mod tests {
             assert_eq!(paint.apply(WorldPoint::new(3.0, 0.0)), Color::gray(1.0));
         }
     }
+
+    #[test]
+    fn test_conical_gradient() {
+        // Concentric, so a plain radial gradient.
+        let paint = Paint::conical_gradient(
+            LocalPoint::new(10.0, 10.0),
+            0.0,
+            LocalPoint::new(10.0, 10.0),
+            10.0,
+            Color::gray(0.0),
+            Color::gray(1.0),
+        );
+        assert_eq!(paint.apply(WorldPoint::new(10.0, 10.0)), Color::gray(0.0));
+        assert_eq!(paint.apply(WorldPoint::new(15.0, 10.0)), Color::gray(0.5));
+        assert_eq!(paint.apply(WorldPoint::new(10.0, 30.0)), Color::gray(1.0));
+
+        // Focal point inside the end circle.
+        let paint = Paint::conical_gradient(
+            LocalPoint::new(4.0, 0.0),
+            0.0,
+            LocalPoint::new(0.0, 0.0),
+            8.0,
+            Color::gray(0.0),
+            Color::gray(1.0),
+        );
+        assert_eq!(paint.apply(WorldPoint::new(4.0, 0.0)), Color::gray(0.0));
+        assert_eq!(paint.apply(WorldPoint::new(-8.0, 0.0)), Color::gray(1.0));
+        assert_eq!(paint.apply(WorldPoint::new(8.0, 0.0)), Color::gray(1.0));
+
+        // Circles moving apart leave a cone, outside of which nothing is
+        // drawn.
+        let paint = Paint::conical_gradient(
+            LocalPoint::new(0.0, 0.0),
+            1.0,
+            LocalPoint::new(10.0, 0.0),
+            2.0,
+            Color::gray(0.0),
+            Color::gray(1.0),
+        );
+        assert_eq!(paint.apply(WorldPoint::new(5.0, 10.0)).a, 0.0);
+
+        // Of the two circles through a point, the later one wins.
+        let t = conical_t(
+            LocalVector::new(5.0, 0.0),
+            LocalVector::new(10.0, 0.0),
+            1.0,
+            2.0,
+        );
+        assert!((t.unwrap() - 2.0 / 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let low = Color::gray(0.0);
+        let high = Color::gray(1.0);
+        let lut = Some([16.0, 32.0]);
+
+        // The value is the same everywhere.
+        let paint = Paint::heatmap(0.25, lut, low, high);
+        assert_eq!(paint.paint_type, PaintType::Heatmap as u32);
+        assert_eq!(paint.params, [16.0, 32.0, 0.0, 0.0]);
+        assert_eq!(paint.apply(WorldPoint::new(0.0, 0.0)), Color::gray(0.25));
+        assert_eq!(paint.apply(WorldPoint::new(90.0, -7.0)), Color::gray(0.25));
+
+        let paint = Paint::heatmap_gradient(
+            LocalPoint::new(0.0, 0.0),
+            LocalPoint::new(0.0, 10.0),
+            lut,
+            low,
+            high,
+        );
+        assert!((paint.apply(WorldPoint::new(3.0, 5.0)).r - 0.5).abs() < 1e-5);
+
+        // Without a lookup table it's a gradient between the ends.
+        let paint = Paint::heatmap(0.5, None, low, high);
+        assert_eq!(paint.paint_type, PaintType::Gradient as u32);
+    }
+
+    #[test]
+    fn test_spread() {
+        let paint = Paint::linear_gradient(
+            LocalPoint::new(0.0, 0.0),
+            LocalPoint::new(1.0, 0.0),
+            Color::gray(0.0),
+            Color::gray(1.0),
+            0.0,
+        );
+        let at = |paint: &Paint, x: f32| paint.apply(WorldPoint::new(x, 0.0));
+        assert_eq!(at(&paint, 1.25), Color::gray(1.0));
+
+        let repeat = paint.with_spread(SpreadMode::Repeat);
+        assert_eq!(at(&repeat, 1.25), Color::gray(0.25));
+        assert_eq!(at(&repeat, -0.25), Color::gray(0.75));
+
+        let reflect = paint.with_spread(SpreadMode::Reflect);
+        assert_eq!(at(&reflect, 1.25), Color::gray(0.75));
+        assert_eq!(at(&reflect, 2.25), Color::gray(0.25));
+        assert_eq!(at(&reflect, -0.25), Color::gray(0.25));
+
+        // Patterns keep their space.
+        let hatch = Paint::hatch(
+            0.0,
+            4.0,
+            1.0,
+            Color::WHITE,
+            Color::gray(0.0),
+            false,
+            PatternSpace::Device,
+        );
+        assert_eq!(hatch.with_spread(SpreadMode::Pad).space, 1);
+    }
+
+    #[test]
+    fn test_glow() {
+        let paint = Paint::solid_color(Color::WHITE).with_glow(Color::CYAN, -1.0);
+        assert_eq!(paint.glow_radius(), 0.0);
+
+        assert_eq!(pack_unorm(Color::new(1.0, 0.0, 0.0, 1.0)), 0xff0000ff);
+        assert_eq!(pack_unorm(Color::new(0.0, 1.0, 2.0, 0.0)), 0x00ffff00);
+    }
+
+    #[test]
+    fn test_pack() {
+        let paint = Paint::solid_color(Color::new(1.0, 0.5, 0.0, 1.0));
+        let packed = paint.pack().unwrap();
+        assert_eq!(packed.inner_color, 0xff0080ff);
+        assert_eq!(packed.full, NOT_REDIRECTED);
+        assert_eq!(packed.xform, [1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+        // Colors RGBA8 can't hold stay full precision.
+        assert!(Paint::solid_color(Color::new(2.0, 0.0, 0.0, 1.0))
+            .pack()
+            .is_none());
+        assert_eq!(std::mem::size_of::<PackedPaint>(), 80);
+    }
+
+    #[test]
+    fn test_noise() {
+        let noise = Noise {
+            kind: NoiseKind::Grain,
+            scale: 0.0,
+            intensity: 2.0,
+            seed: 0x1234567,
+            period: 16,
+            space: PatternSpace::Device,
+        };
+        let paint = Paint::noise(&noise, Color::gray(0.0), Color::gray(1.0));
+        assert_eq!(paint.paint_type, PaintType::Grain as u32);
+        assert_eq!(paint.params, [0.0001, 1.0, 0x234567 as f32, 16.0]);
+        assert_eq!(paint.space, PatternSpace::Device as u32);
+    }
+
+    #[test]
+    fn test_stop_color() {
+        let stops = [
+            (0.0, Color::gray(0.0)),
+            (0.5, Color::gray(1.0)),
+            (1.0, Color::gray(0.0)),
+        ];
+
+        assert_eq!(stop_color(&stops, -1.0), Color::gray(0.0));
+        assert_eq!(stop_color(&stops, 0.25), Color::gray(0.5));
+        assert_eq!(stop_color(&stops, 0.5), Color::gray(1.0));
+        assert_eq!(stop_color(&stops, 0.75), Color::gray(0.5));
+        assert_eq!(stop_color(&stops, 2.0), Color::gray(0.0));
+        assert_eq!(stop_color(&[], 0.5).a, 0.0);
+    }
 }
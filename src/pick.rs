@@ -0,0 +1,220 @@
+//! Spatial index of tagged drawing, for picking and tooltips.
+
+use std::ops::Range;
+
+use crate::defs::*;
+
+/// User tag for drawing recorded by `Vger::set_pick_tag`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PickTag(pub u64);
+
+/// Children per node of the tree.
+const NODE_SIZE: usize = 16;
+
+#[derive(Copy, Clone, Debug)]
+struct Entry {
+    bounds: ScreenRect,
+    tag: PickTag,
+
+    /// Z index then draw order, so the topmost entry sorts last.
+    order: (i32, usize),
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    bounds: ScreenRect,
+
+    /// Entries for leaves, otherwise nodes of the level below.
+    children: Range<usize>,
+}
+
+/// R-tree of device space bounds, bulk loaded on the first query after
+/// drawing.
+#[derive(Default)]
+pub(crate) struct PickIndex {
+    entries: Vec<Entry>,
+    nodes: Vec<Node>,
+
+    /// Ranges of `nodes` in each level, leaves first.
+    levels: Vec<Range<usize>>,
+    built: bool,
+}
+
+impl PickIndex {
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.nodes.clear();
+        self.levels.clear();
+        self.built = false;
+    }
+
+    pub fn add(&mut self, tag: PickTag, bounds: ScreenRect, z_index: i32) {
+        let order = (z_index, self.entries.len());
+        self.entries.push(Entry { bounds, tag, order });
+        self.built = false;
+    }
+
+    /// Tags drawn over `p`, topmost first.
+    pub fn query_point(&mut self, p: ScreenPoint) -> Vec<PickTag> {
+        self.query(|r| r.min_x() <= p.x && p.x <= r.max_x() && r.min_y() <= p.y && p.y <= r.max_y())
+    }
+
+    /// Tags drawn overlapping `rect`, topmost first.
+    pub fn query_rect(&mut self, rect: ScreenRect) -> Vec<PickTag> {
+        self.query(|r| {
+            r.min_x() <= rect.max_x()
+                && rect.min_x() <= r.max_x()
+                && r.min_y() <= rect.max_y()
+                && rect.min_y() <= r.max_y()
+        })
+    }
+
+    fn query(&mut self, hit: impl Fn(&ScreenRect) -> bool) -> Vec<PickTag> {
+        self.build();
+
+        let mut found: Vec<&Entry> = vec![];
+        if let Some(root) = self.levels.last() {
+            let mut stack = vec![(self.levels.len() - 1, root.start)];
+            while let Some((level, node)) = stack.pop() {
+                let node = &self.nodes[node];
+                if !hit(&node.bounds) {
+                    continue;
+                }
+                if level == 0 {
+                    found.extend(
+                        self.entries[node.children.clone()]
+                            .iter()
+                            .filter(|e| hit(&e.bounds)),
+                    );
+                } else {
+                    stack.extend(node.children.clone().map(|child| (level - 1, child)));
+                }
+            }
+        }
+
+        found.sort_by(|a, b| b.order.cmp(&a.order));
+        let mut tags: Vec<PickTag> = vec![];
+        for entry in found {
+            if !tags.contains(&entry.tag) {
+                tags.push(entry.tag);
+            }
+        }
+        tags
+    }
+
+    /// Sort-tile-recursive bulk load.
+    fn build(&mut self) {
+        if self.built {
+            return;
+        }
+        self.nodes.clear();
+        self.levels.clear();
+        self.built = true;
+        if self.entries.is_empty() {
+            return;
+        }
+
+        tile(&mut self.entries, |e| e.bounds);
+        let leaves = group(self.entries.len(), |range| {
+            union(self.entries[range].iter().map(|e| e.bounds))
+        });
+        self.push_level(leaves);
+
+        while self.levels.last().unwrap().len() > 1 {
+            let level = self.levels.last().unwrap().clone();
+            tile(&mut self.nodes[level.clone()], |n| n.bounds);
+            let nodes = &self.nodes;
+            let parents = group(level.len(), |range| {
+                let range = range.start + level.start..range.end + level.start;
+                union(nodes[range].iter().map(|n| n.bounds))
+            })
+            .into_iter()
+            .map(|mut node| {
+                node.children = node.children.start + level.start..node.children.end + level.start;
+                node
+            })
+            .collect();
+            self.push_level(parents);
+        }
+    }
+
+    fn push_level(&mut self, nodes: Vec<Node>) {
+        let start = self.nodes.len();
+        self.nodes.extend(nodes);
+        self.levels.push(start..self.nodes.len());
+    }
+}
+
+/// Orders items into vertical strips, sorted by y within each, so
+/// consecutive runs of `NODE_SIZE` are spatially close.
+fn tile<T>(items: &mut [T], bounds: impl Fn(&T) -> ScreenRect) {
+    let center = |item: &T| bounds(item).center();
+    items.sort_by(|a, b| center(a).x.total_cmp(&center(b).x));
+
+    let pages = (items.len() + NODE_SIZE - 1) / NODE_SIZE;
+    let strips = (pages as f32).sqrt().ceil() as usize;
+    let strip_len = NODE_SIZE * ((pages + strips - 1) / strips);
+    for strip in items.chunks_mut(strip_len) {
+        strip.sort_by(|a, b| center(a).y.total_cmp(&center(b).y));
+    }
+}
+
+/// Nodes for consecutive runs of `NODE_SIZE` of `n` items.
+fn group(n: usize, bounds: impl Fn(Range<usize>) -> ScreenRect) -> Vec<Node> {
+    (0..n)
+        .step_by(NODE_SIZE)
+        .map(|start| {
+            let children = start..(start + NODE_SIZE).min(n);
+            Node {
+                bounds: bounds(children.clone()),
+                children,
+            }
+        })
+        .collect()
+}
+
+fn union(mut rects: impl Iterator<Item = ScreenRect>) -> ScreenRect {
+    let first = rects.next().unwrap_or_else(ScreenRect::zero);
+    rects.fold(first, |a, b| {
+        ScreenRect::from_points([a.min(), a.max(), b.min(), b.max()])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn rect(x: f32, y: f32, w: f32, h: f32) -> ScreenRect {
+        ScreenRect::new(ScreenPoint::new(x, y), [w, h].into())
+    }
+
+    #[test]
+    fn test_query() {
+        let mut index = PickIndex::default();
+        for i in 0..1000 {
+            let (x, y) = ((i % 40) as f32 * 10.0, (i / 40) as f32 * 10.0);
+            index.add(PickTag(i), rect(x, y, 8.0, 8.0), 0);
+        }
+
+        assert_eq!(
+            index.query_point(ScreenPoint::new(54.0, 32.0)),
+            [PickTag(125)]
+        );
+        assert!(index.query_point(ScreenPoint::new(59.0, 32.0)).is_empty());
+        assert_eq!(index.query_rect(rect(15.0, 15.0, 10.0, 10.0)).len(), 4);
+
+        // Later draws and higher z indices come first, and tags are
+        // reported once.
+        index.add(PickTag(2000), rect(50.0, 30.0, 20.0, 20.0), 1);
+        index.add(PickTag(1000), rect(50.0, 30.0, 20.0, 20.0), 0);
+        index.add(PickTag(1000), rect(52.0, 30.0, 20.0, 20.0), 0);
+        assert_eq!(
+            index.query_point(ScreenPoint::new(54.0, 32.0)),
+            [PickTag(2000), PickTag(1000), PickTag(125)]
+        );
+
+        index.clear();
+        assert!(index.query_rect(rect(0.0, 0.0, 400.0, 400.0)).is_empty());
+    }
+}
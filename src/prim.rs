@@ -1,3 +1,5 @@
+use crate::defs::LocalVector;
+
 #[derive(Copy, Clone)]
 #[allow(dead_code)]
 pub enum PrimType {
@@ -36,6 +38,77 @@ pub enum PrimType {
 
     /// Svg with override color
     OverrideColorSvg,
+
+    /// Line segment with round ends of different widths.
+    TaperedSegment,
+
+    /// Grid of hairlines with every nth line using a second paint.
+    Grid,
+
+    /// Cached layer, premultiplied, in the color atlas.
+    Layer,
+
+    /// Blurred glyph coverage, for text shadows.
+    GlyphShadow,
+
+    /// Rectangle border with a width and paint per side.
+    RectBorder,
+
+    /// Stroked arc with round ends.
+    RoundArc,
+}
+
+impl PrimType {
+    /// Every prim type, in the order of their values.
+    const ALL: [PrimType; 18] = [
+        PrimType::Circle,
+        PrimType::Arc,
+        PrimType::Rect,
+        PrimType::RectStroke,
+        PrimType::Bezier,
+        PrimType::Segment,
+        PrimType::Curve,
+        PrimType::Wire,
+        PrimType::Glyph,
+        PrimType::ColorGlyph,
+        PrimType::PathFill,
+        PrimType::OverrideColorSvg,
+        PrimType::TaperedSegment,
+        PrimType::Grid,
+        PrimType::Layer,
+        PrimType::GlyphShadow,
+        PrimType::RectBorder,
+        PrimType::RoundArc,
+    ];
+
+    /// The prim type with the value `prim_type`, as stored in `Prim`.
+    pub fn from_u32(prim_type: u32) -> Option<PrimType> {
+        Self::ALL.get(prim_type as usize).copied()
+    }
+
+    /// Name of the prim type, for debug labels.
+    pub fn name(self) -> &'static str {
+        match self {
+            PrimType::Circle => "Circle",
+            PrimType::Arc => "Arc",
+            PrimType::Rect => "Rect",
+            PrimType::RectStroke => "RectStroke",
+            PrimType::Bezier => "Bezier",
+            PrimType::Segment => "Segment",
+            PrimType::Curve => "Curve",
+            PrimType::Wire => "Wire",
+            PrimType::Glyph => "Glyph",
+            PrimType::ColorGlyph => "ColorGlyph",
+            PrimType::PathFill => "PathFill",
+            PrimType::OverrideColorSvg => "OverrideColorSvg",
+            PrimType::TaperedSegment => "TaperedSegment",
+            PrimType::Grid => "Grid",
+            PrimType::Layer => "Layer",
+            PrimType::GlyphShadow => "GlyphShadow",
+            PrimType::RectBorder => "RectBorder",
+            PrimType::RoundArc => "RoundArc",
+        }
+    }
 }
 
 #[derive(Copy, Clone, Default)]
@@ -77,13 +150,406 @@ pub struct Prim {
     /// Index of scissor.
     pub scissor: u32,
 
-    pad: u32,
+    /// Options for the prim type, e.g. `IMAGE_BICUBIC`.
+    pub flags: u32,
+}
+
+/// `Prim::flags` of images filtered with Catmull-Rom bicubic sampling.
+/// Matches `imageBicubic` in the shader.
+pub const IMAGE_BICUBIC: u32 = 1;
+
+/// `Prim::flags` of images averaged over each pixel's footprint. Matches
+/// `imageFootprint` in the shader.
+pub const IMAGE_FOOTPRINT: u32 = 2;
+
+impl Prim {
+    /// Moves the quad, e.g. to snap a glyph to the pixel grid.
+    pub fn offset_quad(&mut self, offset: LocalVector) {
+        self.quad_bounds[0] += offset.x;
+        self.quad_bounds[1] += offset.y;
+        self.quad_bounds[2] += offset.x;
+        self.quad_bounds[3] += offset.y;
+    }
+
+    /// Sets the quad to conservatively cover the shape, plus `pad` on each
+    /// side for antialiasing. Bounds are in local space, so they stay
+    /// conservative under any transform. Textured prims keep their quad,
+    /// since it maps to the atlas.
+    pub fn expand_quad(&mut self, pad: f32) {
+        const CIRCLE: u32 = PrimType::Circle as u32;
+        const ARC: u32 = PrimType::Arc as u32;
+        const ROUND_ARC: u32 = PrimType::RoundArc as u32;
+        const RECT: u32 = PrimType::Rect as u32;
+        const RECT_STROKE: u32 = PrimType::RectStroke as u32;
+        const BEZIER: u32 = PrimType::Bezier as u32;
+        const SEGMENT: u32 = PrimType::Segment as u32;
+        const CURVE: u32 = PrimType::Curve as u32;
+        const WIRE: u32 = PrimType::Wire as u32;
+        const TAPERED_SEGMENT: u32 = PrimType::TaperedSegment as u32;
+        const GRID: u32 = PrimType::Grid as u32;
+        const RECT_BORDER: u32 = PrimType::RectBorder as u32;
+
+        let c = self.cvs;
+        let hull = |n: usize, r: f32| {
+            let mut b = [f32::MAX, f32::MAX, f32::MIN, f32::MIN];
+            for i in 0..n {
+                b[0] = b[0].min(c[2 * i] - r);
+                b[1] = b[1].min(c[2 * i + 1] - r);
+                b[2] = b[2].max(c[2 * i] + r);
+                b[3] = b[3].max(c[2 * i + 1] + r);
+            }
+            b
+        };
+
+        let b = match self.prim_type {
+            CIRCLE => hull(1, self.radius),
+            ARC | ROUND_ARC => hull(1, self.radius + self.width / 2.0),
+            RECT | GRID | RECT_BORDER => hull(2, 0.0),
+            RECT_STROKE => hull(2, self.width / 2.0),
+            BEZIER => hull(3, self.width / 2.0),
+            SEGMENT | WIRE => hull(2, self.width / 2.0),
+            TAPERED_SEGMENT => hull(2, c[4].max(c[5])),
+            CURVE => self.quad_bounds,
+
+            // Path fills are split into bands which mustn't overlap.
+            _ => return,
+        };
+
+        self.quad_bounds = [b[0] - pad, b[1] - pad, b[2] + pad, b[3] + pad];
+        self.tex_bounds = self.quad_bounds;
+    }
+}
+
+/// Largest error, in local units, that packing may introduce.
+pub const PACK_TOLERANCE: f32 = 1.0 / 64.0;
+
+/// Marks a packed prim as encoded fully.
+const NOT_REDIRECTED: u32 = u32::MAX;
+
+/// Half size encoding of a `Prim`, for `Vger::set_packed_prims`. Matches
+/// `PackedPrim` in the packed shader.
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct PackedPrim {
+    /// The quad, in full precision, which is also the texture space
+    /// bounds.
+    quad_bounds: [f32; 4],
+
+    /// Transform index then prim type, in 16 bits each.
+    xform_type: u32,
+
+    /// Paint index then scissor index, in 16 bits each.
+    paint_scissor: u32,
+
+    /// Width and radius as f16s.
+    width_radius: u32,
+
+    /// Control vertices as pairs of f16s. Points are relative to the
+    /// center of the quad, and unused control vertices are zero.
+    cvs: [u32; 3],
+
+    glyph: u32,
+
+    /// Index of the prim in the full precision buffer, if it couldn't be
+    /// packed.
+    full: u32,
+}
+
+impl PackedPrim {
+    /// Entry referring to a prim in the full precision buffer.
+    pub fn redirect(index: usize) -> Self {
+        Self {
+            full: index as u32,
+            ..Default::default()
+        }
+    }
+}
+
+/// What a control vertex of a packable prim holds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum CvKind {
+    /// A point, packed relative to the quad's center.
+    Point,
+
+    /// Other values, e.g. a sine and cosine, packed as they are.
+    Value,
+
+    /// Nothing, packed as zero.
+    Unused,
+}
+
+impl Prim {
+    /// What control vertex `i` holds. Points match `cv_is_point` in the
+    /// packed shader.
+    fn cv_kind(&self, i: usize) -> CvKind {
+        const CIRCLE: u32 = PrimType::Circle as u32;
+        const ARC: u32 = PrimType::Arc as u32;
+        const ROUND_ARC: u32 = PrimType::RoundArc as u32;
+        const RECT: u32 = PrimType::Rect as u32;
+        const RECT_STROKE: u32 = PrimType::RectStroke as u32;
+        const BEZIER: u32 = PrimType::Bezier as u32;
+        const SEGMENT: u32 = PrimType::Segment as u32;
+        const WIRE: u32 = PrimType::Wire as u32;
+        const TAPERED_SEGMENT: u32 = PrimType::TaperedSegment as u32;
+        const GRID: u32 = PrimType::Grid as u32;
+
+        match (self.prim_type, i) {
+            (CIRCLE | ARC | ROUND_ARC, 0) => CvKind::Point,
+            (ARC | ROUND_ARC, _) => CvKind::Value,
+            (RECT | RECT_STROKE | SEGMENT | WIRE, 0 | 1) => CvKind::Point,
+            (BEZIER, _) => CvKind::Point,
+            (TAPERED_SEGMENT | GRID, 0 | 1) => CvKind::Point,
+            (TAPERED_SEGMENT | GRID, _) => CvKind::Value,
+            _ => CvKind::Unused,
+        }
+    }
+
+    /// Packs the prim, unless it uses the cv buffer or textures, or would
+    /// move by more than `PACK_TOLERANCE`.
+    pub fn pack(&self) -> Option<PackedPrim> {
+        const CIRCLE: u32 = PrimType::Circle as u32;
+        const ARC: u32 = PrimType::Arc as u32;
+        const RECT: u32 = PrimType::Rect as u32;
+        const RECT_STROKE: u32 = PrimType::RectStroke as u32;
+        const BEZIER: u32 = PrimType::Bezier as u32;
+        const SEGMENT: u32 = PrimType::Segment as u32;
+        const WIRE: u32 = PrimType::Wire as u32;
+        const TAPERED_SEGMENT: u32 = PrimType::TaperedSegment as u32;
+        const GRID: u32 = PrimType::Grid as u32;
+        const ROUND_ARC: u32 = PrimType::RoundArc as u32;
+
+        match self.prim_type {
+            CIRCLE | ARC | RECT | RECT_STROKE | BEZIER | SEGMENT | WIRE | TAPERED_SEGMENT
+            | GRID | ROUND_ARC => (),
+            _ => return None,
+        }
+        if self.tex_bounds != self.quad_bounds || self.start != 0 || self.count != 0 {
+            return None;
+        }
+        let indices = [self.xform, self.prim_type, self.paint, self.scissor];
+        if indices.iter().any(|i| *i > 0xFFFF) {
+            return None;
+        }
+
+        let q = self.quad_bounds;
+        let center = [(q[0] + q[2]) / 2.0, (q[1] + q[3]) / 2.0];
+        let mut cvs = [0; 3];
+        for (i, cv) in cvs.iter_mut().enumerate() {
+            let origin = match self.cv_kind(i) {
+                CvKind::Point => center,
+                CvKind::Value => [0.0, 0.0],
+                CvKind::Unused => continue,
+            };
+            *cv = pack_f16_pair(self.cvs[2 * i] - origin[0], self.cvs[2 * i + 1] - origin[1])?;
+        }
+
+        Some(PackedPrim {
+            quad_bounds: q,
+            xform_type: self.xform | (self.prim_type << 16),
+            paint_scissor: self.paint | (self.scissor << 16),
+            width_radius: pack_f16_pair(self.width, self.radius)?,
+            cvs,
+            glyph: self.glyph,
+            full: NOT_REDIRECTED,
+        })
+    }
+}
+
+/// Two values as f16s in the low and high halves, as `unpack2x16float`
+/// expects, if they round trip within `PACK_TOLERANCE`.
+fn pack_f16_pair(a: f32, b: f32) -> Option<u32> {
+    let (ha, hb) = (f32_to_f16(a), f32_to_f16(b));
+    let close = |x: f32, h: u16| (f16_to_f32(h) - x).abs() <= PACK_TOLERANCE;
+    if close(a, ha) && close(b, hb) {
+        Some(ha as u32 | ((hb as u32) << 16))
+    } else {
+        None
+    }
 }
 
+/// Converts to IEEE half precision, rounding to nearest even.
+fn f32_to_f16(x: f32) -> u16 {
+    let bits = x.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7F_FFFF;
+
+    if exp == 0xFF {
+        // Infinity or NaN.
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7C00 | nan;
+    }
+
+    let e = exp - 127 + 15;
+    if e >= 0x1F {
+        return sign | 0x7C00;
+    }
+    if e <= 0 {
+        // Subnormal, or too small for a half.
+        if e < -10 {
+            return sign;
+        }
+        let m = mantissa | 0x80_0000;
+        let shift = (14 - e) as u32;
+        let half = 1 << (shift - 1);
+        let rest = m & ((1 << shift) - 1);
+        let mut h = m >> shift;
+        if rest > half || (rest == half && h & 1 == 1) {
+            h += 1;
+        }
+        return sign | h as u16;
+    }
+
+    let mut h = ((e as u32) << 10) | (mantissa >> 13);
+    let rest = mantissa & 0x1FFF;
+    if rest > 0x1000 || (rest == 0x1000 && h & 1 == 1) {
+        // Carries into the exponent, up to infinity, as it should.
+        h += 1;
+    }
+    sign | h as u16
+}
+
+fn f16_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((h >> 10) & 0x1F) as i32;
+    let mantissa = h & 0x3FF;
+    match exp {
+        0 => sign * mantissa as f32 * 2f32.powi(-24),
+        0x1F if mantissa == 0 => sign * f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => sign * (1.0 + mantissa as f32 / 1024.0) * 2f32.powi(exp - 15),
+    }
+}
+
+#[cfg(test)]
 mod tests {
 
+    use super::*;
+
+    #[test]
+    fn test_expand_quad() {
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::Segment as u32;
+        prim.cvs = [0.0, 0.0, 10.0, 5.0, 0.0, 0.0];
+        prim.width = 4.0;
+        prim.expand_quad(1.0);
+        assert_eq!(prim.quad_bounds, [-3.0, -3.0, 13.0, 8.0]);
+        assert_eq!(prim.tex_bounds, prim.quad_bounds);
+
+        let mut glyph = Prim::default();
+        glyph.prim_type = PrimType::Glyph as u32;
+        glyph.quad_bounds = [1.0, 2.0, 3.0, 4.0];
+        glyph.expand_quad(1.0);
+        assert_eq!(glyph.quad_bounds, [1.0, 2.0, 3.0, 4.0]);
+        glyph.offset_quad(LocalVector::new(0.5, -1.0));
+        assert_eq!(glyph.quad_bounds, [1.5, 1.0, 3.5, 3.0]);
+    }
+
     #[test]
     fn test_size() {
         assert_eq!(std::mem::size_of::<super::Prim>(), 96);
+        assert_eq!(std::mem::size_of::<super::PackedPrim>(), 48);
+    }
+
+    #[test]
+    fn test_prim_type_names() {
+        for (i, prim_type) in PrimType::ALL.iter().enumerate() {
+            assert_eq!(*prim_type as usize, i);
+        }
+        assert_eq!(PrimType::ALL.len(), PrimType::RoundArc as usize + 1);
+        assert_eq!(PrimType::Glyph.name(), "Glyph");
+        assert_eq!(
+            PrimType::from_u32(PrimType::Layer as u32).map(PrimType::name),
+            Some("Layer")
+        );
+        assert!(PrimType::from_u32(18).is_none());
+    }
+
+    #[test]
+    fn test_f16() {
+        for x in [0.0, 1.0, -2.5, 0.1, 65504.0, 1e-3, 3.14159] {
+            let h = f32_to_f16(x);
+            assert!((f16_to_f32(h) - x).abs() <= x.abs() / 1024.0, "{}", x);
+        }
+        assert_eq!(f32_to_f16(1.0), 0x3C00);
+        assert_eq!(f32_to_f16(-2.0), 0xC000);
+        assert_eq!(f32_to_f16(65520.0), 0x7C00);
+        assert_eq!(f32_to_f16(1.0 + 1.0 / 2048.0), 0x3C00);
+        assert_eq!(f16_to_f32(f32_to_f16(1e-5)), 168.0 * 2f32.powi(-24));
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+    }
+
+    #[test]
+    fn test_pack() {
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::Circle as u32;
+        prim.cvs = [1000.0, 1000.0, 0.0, 0.0, 0.0, 0.0];
+        prim.radius = 10.0;
+        prim.expand_quad(1.0);
+
+        // Points are relative to the quad, so precision doesn't depend on
+        // where the prim is.
+        let packed = prim.pack().unwrap();
+        assert_eq!(packed.cvs[0], 0);
+        assert_eq!(packed.full, NOT_REDIRECTED);
+
+        // Large prims lose too much.
+        prim.radius = 5000.5;
+        prim.expand_quad(1.0);
+        assert!(prim.pack().is_none());
+
+        let mut glyph = Prim::default();
+        glyph.prim_type = PrimType::Glyph as u32;
+        assert!(glyph.pack().is_none());
+    }
+
+    #[test]
+    fn test_pack_off_origin() {
+        let unpack = |cv: u32, origin: [f32; 2]| {
+            [
+                f16_to_f32(cv as u16) + origin[0],
+                f16_to_f32((cv >> 16) as u16) + origin[1],
+            ]
+        };
+        let close = |a: [f32; 2], b: [f32; 2]| {
+            (a[0] - b[0]).abs() <= PACK_TOLERANCE && (a[1] - b[1]).abs() <= PACK_TOLERANCE
+        };
+
+        // Far from the origin, with fractional centers, unused control
+        // vertices don't stop a prim packing.
+        let mut circle = Prim::default();
+        circle.prim_type = PrimType::Circle as u32;
+        circle.cvs = [1000.3, 2000.7, 0.0, 0.0, 0.0, 0.0];
+        circle.radius = 10.25;
+        circle.expand_quad(1.0);
+        let packed = circle.pack().unwrap();
+        let q = packed.quad_bounds;
+        let center = [(q[0] + q[2]) / 2.0, (q[1] + q[3]) / 2.0];
+        assert!(close(unpack(packed.cvs[0], center), [1000.3, 2000.7]));
+        assert_eq!(packed.cvs[1..], [0, 0]);
+
+        // Arcs keep their angles as they are.
+        let mut arc = Prim::default();
+        arc.prim_type = PrimType::Arc as u32;
+        arc.cvs = [-3000.5, 1500.25, 0.6, 0.8, 0.8, 0.6];
+        arc.radius = 20.0;
+        arc.width = 2.0;
+        arc.expand_quad(1.0);
+        let packed = arc.pack().unwrap();
+        let q = packed.quad_bounds;
+        let center = [(q[0] + q[2]) / 2.0, (q[1] + q[3]) / 2.0];
+        assert!(close(unpack(packed.cvs[0], center), [-3000.5, 1500.25]));
+        assert!(close(unpack(packed.cvs[1], [0.0, 0.0]), [0.6, 0.8]));
+
+        let mut segment = Prim::default();
+        segment.prim_type = PrimType::Segment as u32;
+        segment.cvs = [5000.125, 5000.5, 5010.75, 4990.25, 0.0, 0.0];
+        segment.width = 1.5;
+        segment.expand_quad(1.0);
+        let packed = segment.pack().unwrap();
+        let q = packed.quad_bounds;
+        let center = [(q[0] + q[2]) / 2.0, (q[1] + q[3]) / 2.0];
+        assert!(close(unpack(packed.cvs[1], center), [5010.75, 4990.25]));
+        assert_eq!(packed.cvs[2], 0);
     }
 }
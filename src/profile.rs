@@ -0,0 +1,12 @@
+//! Spans for profilers, emitted through `tracing` with the `profile`
+//! feature. Hook up a subscriber such as `tracing-chrome` or
+//! `tracing-tracy` to see them.
+
+/// Enters a span named `$name` until the end of the enclosing scope. Does
+/// nothing without the `profile` feature.
+macro_rules! profile_span {
+    ($name:literal) => {
+        #[cfg(feature = "profile")]
+        let _span = tracing::info_span!($name).entered();
+    };
+}
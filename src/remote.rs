@@ -0,0 +1,145 @@
+//! Compositing scenes streamed from other processes.
+//!
+//! Clients, which only need `vger-core`, connect over TCP and send display
+//! lists with `vger_core::wire::write_frame`, one frame per update. The
+//! server keeps each client's latest scene and draws them in connection
+//! order.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+
+pub use vger_core::wire::{read_frame, write_frame};
+
+use crate::{DisplayList, Vger};
+
+/// Identifies a connection, in the order clients connected.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ClientId(pub u64);
+
+enum Message {
+    Frame(ClientId, DisplayList),
+    Closed(ClientId),
+}
+
+/// Receives scenes from clients on background threads, for the render
+/// thread to draw.
+pub struct SceneServer {
+    addr: SocketAddr,
+    receiver: mpsc::Receiver<Message>,
+    scenes: BTreeMap<ClientId, DisplayList>,
+}
+
+impl SceneServer {
+    /// Listens on `addr`, reading each client on its own thread. A client
+    /// sending a malformed frame is disconnected.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for (id, stream) in listener.incoming().enumerate() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let sender = sender.clone();
+                thread::spawn(move || read_client(ClientId(id as u64), stream, sender));
+            }
+        });
+
+        Ok(Self {
+            addr,
+            receiver,
+            scenes: BTreeMap::new(),
+        })
+    }
+
+    /// The address clients connect to, e.g. when bound to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Takes frames received since the last call, keeping each client's
+    /// latest and dropping clients that disconnected. Returns whether any
+    /// scene changed.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        for message in self.receiver.try_iter() {
+            match message {
+                Message::Frame(id, list) => {
+                    self.scenes.insert(id, list);
+                }
+                Message::Closed(id) => {
+                    self.scenes.remove(&id);
+                }
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    /// Connected clients that have sent a scene.
+    pub fn clients(&self) -> impl Iterator<Item = ClientId> + '_ {
+        self.scenes.keys().copied()
+    }
+
+    /// The latest scene from a client.
+    pub fn scene(&self, id: ClientId) -> Option<&DisplayList> {
+        self.scenes.get(&id)
+    }
+
+    /// Draws every client's latest scene, first connected at the bottom.
+    pub fn draw(&self, vger: &mut Vger) {
+        for list in self.scenes.values() {
+            vger.draw_display_list(list);
+        }
+    }
+}
+
+fn read_client(id: ClientId, mut stream: TcpStream, sender: mpsc::Sender<Message>) {
+    while let Ok(Some(list)) = read_frame(&mut stream) {
+        if sender.send(Message::Frame(id, list)).is_err() {
+            return;
+        }
+    }
+    let _ = sender.send(Message::Closed(id));
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::Color;
+    use std::time::{Duration, Instant};
+
+    fn wait(server: &mut SceneServer, done: impl Fn(&SceneServer) -> bool) {
+        let start = Instant::now();
+        while !done(server) {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            server.poll();
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_server() {
+        let mut server = SceneServer::bind("127.0.0.1:0").unwrap();
+
+        let mut list = DisplayList::new();
+        list.fill_circle([10.0, 10.0], 5.0, Color::WHITE);
+
+        let mut client = TcpStream::connect(server.local_addr()).unwrap();
+        write_frame(&mut client, &DisplayList::new()).unwrap();
+        write_frame(&mut client, &list).unwrap();
+        wait(&mut server, |s| s.scene(ClientId(0)) == Some(&list));
+
+        // Malformed frames disconnect the client and drop its scene.
+        use std::io::Write;
+        client.write_all(&[1, 0, 0, 0, 0xff]).unwrap();
+        wait(&mut server, |s| s.clients().next().is_none());
+    }
+}
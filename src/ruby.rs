@@ -0,0 +1,144 @@
+//! Ruby annotations, such as furigana, drawn above base text by
+//! `Vger::text_with_ruby`.
+
+use std::ops::Range;
+
+/// An annotation of part of a text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ruby {
+    /// Byte range of the annotated base text.
+    pub base: Range<usize>,
+
+    /// The annotation, e.g. the reading of the base in kana.
+    pub text: String,
+}
+
+impl Ruby {
+    pub fn new(base: Range<usize>, text: &str) -> Self {
+        Self {
+            base,
+            text: text.to_string(),
+        }
+    }
+}
+
+/// Whether ruby may overhang `c` next to its base: kana and ideographic
+/// punctuation, but not kanji, which would read as part of the base.
+pub(crate) fn can_overhang(c: char) -> bool {
+    matches!(c, '\u{3000}'..='\u{30FF}') && c != '\u{3005}'
+}
+
+/// Where ruby goes over its base, in the base's coordinates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct RubyPlacement {
+    /// Pen position of the first ruby character.
+    pub start: f32,
+
+    /// Space added between ruby characters.
+    pub spacing: f32,
+
+    /// How far the base moves right to center it under ruby that doesn't
+    /// fit.
+    pub base_shift: f32,
+
+    /// How far text after the base moves right.
+    pub widen: f32,
+}
+
+/// Places `len` ruby characters `width` wide over the base between `start`
+/// and `end`. Ruby narrower than its base is spread over it 1:2:1, with
+/// half gaps at the ends. Wider ruby overhangs the neighbors of the base
+/// by up to `overhang`, before and after, and widens the base by the
+/// rest.
+pub(crate) fn place_ruby(
+    start: f32,
+    end: f32,
+    width: f32,
+    len: usize,
+    overhang: (f32, f32),
+) -> RubyPlacement {
+    let base = end - start;
+    if width <= base {
+        let spacing = if len > 0 {
+            (base - width) / len as f32
+        } else {
+            0.0
+        };
+        return RubyPlacement {
+            start: start + spacing / 2.0,
+            spacing,
+            base_shift: 0.0,
+            widen: 0.0,
+        };
+    }
+
+    let excess = width - base;
+    let before = overhang.0.min(excess / 2.0);
+    let after = overhang.1.min(excess / 2.0);
+    let widen = excess - before - after;
+    RubyPlacement {
+        start: start - before,
+        spacing: 0.0,
+        base_shift: widen / 2.0,
+        widen,
+    }
+}
+
+/// Index in `laid_out` of each character of `text`, which `laid_out`
+/// has with line breaks and hyphens inserted where it wrapped.
+pub(crate) fn source_chars(text: &str, laid_out: &[char]) -> Vec<usize> {
+    let mut indices = Vec::with_capacity(laid_out.len());
+    let mut j = 0;
+    for c in text.chars() {
+        while j < laid_out.len() && laid_out[j] != c {
+            j += 1;
+        }
+        indices.push(j);
+        j += 1;
+    }
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_place_ruby() {
+        // Two ruby characters spread over a wider base.
+        let p = place_ruby(0.0, 40.0, 20.0, 2, (10.0, 10.0));
+        assert_eq!(p.start, 5.0);
+        assert_eq!(p.spacing, 10.0);
+        assert_eq!(p.widen, 0.0);
+
+        // Overhanging both neighbors.
+        let p = place_ruby(0.0, 20.0, 30.0, 3, (10.0, 10.0));
+        assert_eq!(p.start, -5.0);
+        assert_eq!(p.widen, 0.0);
+
+        // Nothing to overhang before the base.
+        let p = place_ruby(0.0, 20.0, 30.0, 3, (0.0, 10.0));
+        assert_eq!(p.start, 0.0);
+        assert_eq!(p.widen, 5.0);
+        assert_eq!(p.base_shift, 2.5);
+    }
+
+    #[test]
+    fn test_can_overhang() {
+        assert!(can_overhang('の'));
+        assert!(can_overhang('カ'));
+        assert!(can_overhang('、'));
+        assert!(!can_overhang('漢'));
+        assert!(!can_overhang('々'));
+        assert!(!can_overhang('a'));
+    }
+
+    #[test]
+    fn test_source_chars() {
+        let laid_out: Vec<char> = "hy-\nphen".chars().collect();
+        assert_eq!(source_chars("hyphen", &laid_out), [0, 1, 4, 5, 6, 7]);
+        let laid_out: Vec<char> = "ab".chars().collect();
+        assert_eq!(source_chars("ab", &laid_out), [0, 1]);
+    }
+}
@@ -1,24 +1,172 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::*;
 
 pub const MAX_LAYERS: usize = 4;
 
+/// Prim buffer holding the contents of cached layers.
+pub const CACHED_LAYER_PRIMS: usize = 1;
+
+/// Where a layer's pixels go.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum LayerTarget {
+    /// A region of the color atlas, for backdrops.
+    Atlas(rect_packer::Rect),
+
+    /// A pooled texture kept under a cached layer's key.
+    Pool { key: u64, width: u32, height: u32 },
+}
+
+/// A layer to render before drawing the frame.
+pub(crate) struct LayerDraw {
+    pub target: LayerTarget,
+
+    /// Instances in the cached layer prim buffer.
+    pub prims: std::ops::Range<u32>,
+}
+
+/// What a frame's draws are split on: debug groups, blend modes and the
+/// cached layers drawn.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct DrawRun {
+    /// Debug group, as an index in `Scene::debug_labels`.
+    pub label: Option<u32>,
+    pub blend: BlendMode,
+    /// Key of the cached layer sampled, as a key in
+    /// `Scene::layer_bind_groups`.
+    pub layer: Option<u64>,
+}
+
+/// Scene holding the overlay, apart from the triple buffered frames.
+pub const OVERLAY_SCENE: usize = 3;
+
 type Mat4x4 = [f32; 16];
 
 pub(crate) struct Scene {
-    pub depthed_prims: HashMap<i32, Vec<Prim>>,
+    /// Prims by z index, ordered so drawing doesn't depend on hashing.
+    pub depthed_prims: BTreeMap<i32, Vec<Prim>>,
     pub prims: [GPUVec<Prim>; MAX_LAYERS],
     pub cvs: GPUVec<LocalPoint>,
     pub xforms: GPUVec<Mat4x4>,
     pub paints: GPUVec<Paint>,
     pub scissors: GPUVec<Scissor>,
+    /// Paints which refer to theme variables, by paint index.
+    pub paint_vars: Vec<(usize, String)>,
+    /// Indices of paints recorded this frame, so identical paints share a
+    /// slot. Indices are handed out in drawing order.
+    pub paint_lookup: BTreeMap<PaintKey, usize>,
+    /// Indices of transforms by their bits, so drawing under the same
+    /// transform shares an entry.
+    pub xform_lookup: BTreeMap<[u32; 6], usize>,
+    /// Transforms looked up this frame, for `TransformStats`.
+    pub xform_requests: usize,
+    /// Layers recorded this frame.
+    pub layers: Vec<LayerDraw>,
+    /// Keys of the cached layers drawn this frame.
+    pub layer_keys: Vec<u64>,
+    /// Bind groups sampling each cached layer's texture in place of the
+    /// color atlas, by key.
+    pub layer_bind_groups: HashMap<u64, wgpu::BindGroup>,
+    /// External textures drawn this frame, and where they go in the color
+    /// atlas.
+    pub externals: Vec<(ExternalImage, rect_packer::Rect)>,
+    /// Video frames drawn this frame, and where they go in the color atlas.
+    pub videos: Vec<(VideoFrame, rect_packer::Rect)>,
     //pub bind_group_layout: wgpu::BindGroupLayout,
     pub bind_groups: [wgpu::BindGroup; MAX_LAYERS],
+    /// Frame prims when there are few enough, instead of `prims[0]`.
+    pub small_prims: GPUVec<Prim>,
+    pub small_bind_group: wgpu::BindGroup,
+    /// Whether the frame's prims are in `small_prims`.
+    pub small: bool,
+    /// Frame prims at half size, when packing. Those which can't be packed
+    /// are in `prims[0]`.
+    pub packed_prims: GPUVec<PackedPrim>,
+    /// Paints with RGBA8 colors, when packing. Those which can't be
+    /// packed are in `full_paints`.
+    pub packed_paints: GPUVec<PackedPaint>,
+    pub full_paints: GPUVec<Paint>,
+    pub packed_bind_group: wgpu::BindGroup,
+    /// Whether the frame's prims are in `packed_prims`.
+    pub packed: bool,
+    /// Arguments for drawing the frame's prims with `draw_indirect`.
+    pub draw_args: wgpu::Buffer,
+    /// Debug group and blend mode changes by z index, as the index of the
+    /// first prim in the run.
+    pub runs: BTreeMap<i32, Vec<(u32, DrawRun)>>,
+    /// Debug group paths recorded this frame.
+    pub debug_labels: Vec<String>,
+    /// Instance ranges of the frame's prims and their runs, when debug
+    /// groups or blend modes were used.
+    pub draws: Vec<(std::ops::Range<u32>, DrawRun)>,
+    /// Types of the frame's prims, when drawing them one at a time.
+    pub debug_prim_types: Vec<u32>,
+    /// Times the scene was cleared, for pruning `depthed_prims`.
+    clears: u64,
 }
 
 pub const MAX_PRIMS: usize = 65536;
 
+/// Clears between dropping z indices which went unused, so the prims of
+/// z indices used every frame keep their capacity, while animated or
+/// one-off z indices don't pile up.
+const PRUNE_INTERVAL: u64 = 16;
+
+/// Frames with at most this many prims read them from a uniform buffer,
+/// which is faster than a storage buffer on many mobile GPUs. 12KB, under
+/// the 16KB uniform binding limit of downlevel devices.
+pub const SMALL_SCENE_PRIMS: usize = 128;
+
+/// `source` with `from`, which must appear exactly once, replaced by `to`,
+/// so shader variants fail loudly when `shader.wgsl` changes under them.
+fn replace_once(source: &str, from: &str, to: &str) -> String {
+    let count = source.matches(from).count();
+    assert_eq!(
+        count, 1,
+        "expected one {:?} in the shader, found {}",
+        from, count
+    );
+    source.replacen(from, to, 1)
+}
+
+/// `shader.wgsl` reading prims from a uniform buffer of
+/// `SMALL_SCENE_PRIMS`.
+pub fn small_scene_shader() -> String {
+    let shader = replace_once(
+        include_str!("shader.wgsl"),
+        "prims: array<Prim>,",
+        &format!("prims: array<Prim, {}>,", SMALL_SCENE_PRIMS),
+    );
+    replace_once(
+        &shader,
+        "var<storage> prims: Prims;",
+        "var<uniform> prims: Prims;",
+    )
+}
+
+/// `shader.wgsl` loading prims from the packed buffer. See
+/// `Vger::set_packed_prims`.
+pub fn packed_scene_shader() -> String {
+    let shader = replace_once(
+        include_str!("shader.wgsl"),
+        "prims.prims[instance]",
+        "load_prim(instance)",
+    );
+    let shader = replace_once(
+        &shader,
+        "prims.prims[in.prim_index]",
+        "load_prim(in.prim_index)",
+    );
+    replace_once(
+        &shader,
+        "return paints.paints[index];",
+        "return unpack_paint(index);",
+    ) + include_str!("packed.wgsl")
+}
+
+/// Smallest buffer capacity `trim` shrinks to.
+const MIN_CAPACITY: usize = 1024;
+
 impl Scene {
     pub fn new(device: &wgpu::Device) -> Self {
         let prims = [
@@ -33,6 +181,8 @@ impl Scene {
         let paints = GPUVec::new(device, MAX_PRIMS, "Paint Buffer");
         let scissors = GPUVec::new(device, MAX_PRIMS, "scissor Buffer");
 
+        let small_prims = GPUVec::new_uniform_array(device, SMALL_SCENE_PRIMS, "Small Prim Buffer");
+
         //let bind_group_layout = Self::bind_group_layout(device);
 
         let bind_groups = [
@@ -41,16 +191,62 @@ impl Scene {
             Scene::bind_group(device, &prims[2], &cvs, &xforms, &paints, &scissors),
             Scene::bind_group(device, &prims[3], &cvs, &xforms, &paints, &scissors),
         ];
+        let draw_args = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Draw Args Buffer"),
+            size: 16,
+            usage: wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let small_bind_group =
+            Scene::small_bind_group(device, &small_prims, &cvs, &xforms, &paints, &scissors);
+        let packed_prims = GPUVec::new(device, MIN_CAPACITY, "Packed Prim Buffer");
+        let packed_paints = GPUVec::new(device, MIN_CAPACITY, "Packed Paint Buffer");
+        let full_paints = GPUVec::new(device, MIN_CAPACITY, "Full Paint Buffer");
+        let packed_bind_group = Scene::packed_bind_group(
+            device,
+            &prims[0],
+            &cvs,
+            &xforms,
+            &full_paints,
+            &scissors,
+            &packed_prims,
+            &packed_paints,
+        );
 
         Self {
-            depthed_prims: HashMap::new(),
+            depthed_prims: BTreeMap::new(),
             prims,
             cvs,
             xforms,
             paints,
             scissors,
+            paint_vars: vec![],
+            paint_lookup: BTreeMap::new(),
+            xform_lookup: BTreeMap::new(),
+            xform_requests: 0,
+            layers: vec![],
+            layer_keys: vec![],
+            layer_bind_groups: HashMap::new(),
+            externals: vec![],
+            videos: vec![],
             //bind_group_layout,
             bind_groups,
+            small_prims,
+            small_bind_group,
+            small: false,
+            packed_prims,
+            packed_paints,
+            full_paints,
+            packed_bind_group,
+            packed: false,
+            draw_args,
+            runs: BTreeMap::new(),
+            debug_labels: vec![],
+            draws: vec![],
+            debug_prim_types: vec![],
+            clears: 0,
         }
     }
 
@@ -67,6 +263,83 @@ impl Scene {
         })
     }
 
+    /// Layout for `small_scene_shader`.
+    pub fn small_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                GPUVec::<Prim>::uniform_bind_group_layout_entry(0),
+                GPUVec::<LocalPoint>::bind_group_layout_entry(1),
+                GPUVec::<Mat4x4>::bind_group_layout_entry(2),
+                GPUVec::<Paint>::bind_group_layout_entry(3),
+                GPUVec::<Scissor>::bind_group_layout_entry(4),
+            ],
+            label: Some("small_bind_group_layout"),
+        })
+    }
+
+    fn small_bind_group(
+        device: &wgpu::Device,
+        prims: &GPUVec<Prim>,
+        cvs: &GPUVec<LocalPoint>,
+        xforms: &GPUVec<Mat4x4>,
+        paints: &GPUVec<Paint>,
+        scissors: &GPUVec<Scissor>,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &Self::small_bind_group_layout(device),
+            entries: &[
+                prims.bind_group_entry(0),
+                cvs.bind_group_entry(1),
+                xforms.bind_group_entry(2),
+                paints.bind_group_entry(3),
+                scissors.bind_group_entry(4),
+            ],
+            label: Some("vger small bind group"),
+        })
+    }
+
+    /// Layout for `packed_scene_shader`, which also binds the packed prims
+    /// and paints.
+    pub fn packed_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                GPUVec::<Prim>::bind_group_layout_entry(0),
+                GPUVec::<LocalPoint>::bind_group_layout_entry(1),
+                GPUVec::<Mat4x4>::bind_group_layout_entry(2),
+                GPUVec::<Paint>::bind_group_layout_entry(3),
+                GPUVec::<Scissor>::bind_group_layout_entry(4),
+                GPUVec::<PackedPrim>::bind_group_layout_entry(5),
+                GPUVec::<PackedPaint>::bind_group_layout_entry(6),
+            ],
+            label: Some("packed_bind_group_layout"),
+        })
+    }
+
+    fn packed_bind_group(
+        device: &wgpu::Device,
+        prims: &GPUVec<Prim>,
+        cvs: &GPUVec<LocalPoint>,
+        xforms: &GPUVec<Mat4x4>,
+        paints: &GPUVec<Paint>,
+        scissors: &GPUVec<Scissor>,
+        packed_prims: &GPUVec<PackedPrim>,
+        packed_paints: &GPUVec<PackedPaint>,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &Self::packed_bind_group_layout(device),
+            entries: &[
+                prims.bind_group_entry(0),
+                cvs.bind_group_entry(1),
+                xforms.bind_group_entry(2),
+                paints.bind_group_entry(3),
+                scissors.bind_group_entry(4),
+                packed_prims.bind_group_entry(5),
+                packed_paints.bind_group_entry(6),
+            ],
+            label: Some("vger packed bind group"),
+        })
+    }
+
     fn bind_group(
         device: &wgpu::Device,
         prims: &GPUVec<Prim>,
@@ -90,31 +363,303 @@ impl Scene {
         })
     }
 
-    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
-        let mut keys: Vec<i32> = self.depthed_prims.keys().copied().collect();
-        keys.sort();
-        for z_index in keys {
-            let mut prims = self.depthed_prims.remove(&z_index).unwrap();
-            self.prims[0].append(&mut prims);
+    /// Writes the frame's buffers, packing prims to half size with `pack`,
+    /// and noting prim types for `split` draws.
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, pack: bool, split: bool) {
+        // Per z index vectors are emptied rather than removed so their
+        // capacity carries over to the next frame.
+        let count: usize = self.depthed_prims.values().map(|prims| prims.len()).sum();
+        self.update_draws();
+        self.debug_prim_types.clear();
+        if split {
+            for prims in self.depthed_prims.values() {
+                self.debug_prim_types
+                    .extend(prims.iter().map(|prim| prim.prim_type));
+            }
+        }
+        self.small = count <= SMALL_SCENE_PRIMS;
+        self.packed = pack && !self.small;
+        if self.packed {
+            // Prims which can't be packed are referred to in the full
+            // size buffer.
+            for prims in self.depthed_prims.values_mut() {
+                for prim in prims.drain(..) {
+                    match prim.pack() {
+                        Some(packed) => self.packed_prims.push(packed),
+                        None => {
+                            let index = self.prims[0].len();
+                            self.packed_prims.push(PackedPrim::redirect(index));
+                            self.prims[0].push(prim);
+                        }
+                    }
+                }
+            }
+        } else {
+            let frame_prims = if self.small {
+                &mut self.small_prims
+            } else {
+                &mut self.prims[0]
+            };
+            for prims in self.depthed_prims.values_mut() {
+                frame_prims.append(prims);
+            }
+        }
+
+        let mut realloc = false;
+        if self.small {
+            self.small_prims.update(device, queue);
+        } else {
+            realloc |= self.prims[0].update(device, queue);
+        }
+        if self.packed {
+            realloc |= self.packed_prims.update(device, queue);
+            realloc |= self.update_packed_paints(device, queue);
+        }
+        for prims in &mut self.prims[1..] {
+            realloc |= prims.update(device, queue);
+        }
+
+        // Vertex count, instance count, first vertex and first instance.
+        let args = [4, count as u32, 0, 0];
+        let mut bytes = [0u8; 16];
+        for (chunk, arg) in bytes.chunks_exact_mut(4).zip(args) {
+            chunk.copy_from_slice(&arg.to_le_bytes());
+        }
+        queue.write_buffer(&self.draw_args, 0, &bytes);
+        realloc |= self.cvs.update(device, queue);
+        realloc |= self.xforms.update(device, queue);
+        // Packed frames only read full paints through `full_paints`, but
+        // layers still read them all.
+        let layer_prims = self.prims[1..].iter().any(|prims| prims.len() > 0);
+        if !self.packed || layer_prims {
+            realloc |= self.paints.update(device, queue);
+        }
+        realloc |= self.scissors.update(device, queue);
+
+        if realloc {
+            self.update_bind_groups(device);
+        }
+    }
+
+    /// Packs the frame's paints, putting those which can't be packed in
+    /// `full_paints`. Returns whether a buffer was reallocated.
+    fn update_packed_paints(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> bool {
+        self.packed_paints.clear();
+        self.full_paints.clear();
+        for i in 0..self.paints.len() {
+            let paint = self.paints[i];
+            match paint.pack() {
+                Some(packed) => self.packed_paints.push(packed),
+                None => {
+                    let index = self.full_paints.len();
+                    self.packed_paints.push(PackedPaint::redirect(index));
+                    self.full_paints.push(paint);
+                }
+            }
+        }
+        self.packed_paints.update(device, queue) | self.full_paints.update(device, queue)
+    }
+
+    /// Splits the frame's prims, in the order they're uploaded, into
+    /// ranges with the same debug group and blend mode.
+    fn update_draws(&mut self) {
+        self.draws.clear();
+        if self.runs.is_empty() {
+            return;
         }
 
+        let draws = &mut self.draws;
+        let mut push = |range: std::ops::Range<u32>, run| {
+            if !range.is_empty() {
+                draws.push((range, run));
+            }
+        };
+        let mut offset = 0;
+        for (z_index, prims) in &self.depthed_prims {
+            let (mut start, mut run) = (0, DrawRun::default());
+            for &(run_start, next) in self.runs.get(z_index).into_iter().flatten() {
+                push(offset + start..offset + run_start, run);
+                start = run_start;
+                run = next;
+            }
+            let len = prims.len() as u32;
+            push(offset + start..offset + len, run);
+            offset += len;
+        }
+        self.runs.clear();
+    }
+
+    /// Queues the prims recorded for a layer, by z index, to be rendered
+    /// into `target`.
+    pub fn add_layer(&mut self, target: LayerTarget, mut depthed: BTreeMap<i32, Vec<Prim>>) {
+        let prims = &mut self.prims[CACHED_LAYER_PRIMS];
+        let start = prims.len() as u32;
+
+        for layer_prims in depthed.values_mut() {
+            prims.append(layer_prims);
+        }
+
+        self.layers.push(LayerDraw {
+            target,
+            prims: start..prims.len() as u32,
+        });
+    }
+
+    fn update_bind_groups(&mut self, device: &wgpu::Device) {
+        self.packed_bind_group = Scene::packed_bind_group(
+            device,
+            &self.prims[0],
+            &self.cvs,
+            &self.xforms,
+            &self.full_paints,
+            &self.scissors,
+            &self.packed_prims,
+            &self.packed_paints,
+        );
+        self.small_bind_group = Scene::small_bind_group(
+            device,
+            &self.small_prims,
+            &self.cvs,
+            &self.xforms,
+            &self.paints,
+            &self.scissors,
+        );
+        for i in 0..MAX_LAYERS {
+            self.bind_groups[i] = Scene::bind_group(
+                device,
+                &self.prims[i],
+                &self.cvs,
+                &self.xforms,
+                &self.paints,
+                &self.scissors,
+            );
+        }
+    }
+
+    /// Releases memory beyond what the current contents need.
+    pub fn trim(&mut self, device: &wgpu::Device) {
+        self.depthed_prims.retain(|_, prims| !prims.is_empty());
+        for prims in self.depthed_prims.values_mut() {
+            prims.shrink_to_fit();
+        }
+        self.paint_vars.shrink_to_fit();
+
+        let mut realloc = false;
         for i in 0..4 {
-            self.prims[i].update(device, queue);
+            realloc |= self.prims[i].shrink(device, MIN_CAPACITY);
+        }
+        realloc |= self.packed_prims.shrink(device, MIN_CAPACITY);
+        realloc |= self.packed_paints.shrink(device, MIN_CAPACITY);
+        realloc |= self.full_paints.shrink(device, MIN_CAPACITY);
+        realloc |= self.cvs.shrink(device, MIN_CAPACITY);
+        realloc |= self.xforms.shrink(device, MIN_CAPACITY);
+        realloc |= self.paints.shrink(device, MIN_CAPACITY);
+        realloc |= self.scissors.shrink(device, MIN_CAPACITY);
+
+        if realloc {
+            self.update_bind_groups(device);
         }
-        self.cvs.update(device, queue);
-        self.xforms.update(device, queue);
-        self.paints.update(device, queue);
-        self.scissors.update(device, queue);
+    }
+
+    /// Approximate bytes held by the scene.
+    pub fn memory_usage(&self) -> usize {
+        let depthed: usize = self
+            .depthed_prims
+            .values()
+            .map(|prims| prims.capacity() * std::mem::size_of::<Prim>())
+            .sum();
+        depthed
+            + self.prims.iter().map(|p| p.memory_usage()).sum::<usize>()
+            + self.small_prims.memory_usage()
+            + self.packed_prims.memory_usage()
+            + self.packed_paints.memory_usage()
+            + self.full_paints.memory_usage()
+            + self.cvs.memory_usage()
+            + self.xforms.memory_usage()
+            + self.paints.memory_usage()
+            + self.scissors.memory_usage()
     }
 
     pub fn clear(&mut self) {
-        self.depthed_prims.clear();
+        self.clears += 1;
+        if self.clears % PRUNE_INTERVAL == 0 {
+            // Z indices left empty by the last clear weren't drawn since.
+            self.depthed_prims.retain(|_, prims| !prims.is_empty());
+        }
+        for prims in self.depthed_prims.values_mut() {
+            prims.clear();
+        }
         for i in 0..4 {
             self.prims[i].clear();
         }
+        self.small_prims.clear();
+        self.packed_prims.clear();
+        self.packed_paints.clear();
+        self.full_paints.clear();
         self.cvs.clear();
         self.xforms.clear();
         self.paints.clear();
         self.scissors.clear();
+        self.paint_vars.clear();
+        self.paint_lookup.clear();
+        self.xform_lookup.clear();
+        self.xform_requests = 0;
+        self.layers.clear();
+        self.layer_keys.clear();
+        self.layer_bind_groups.clear();
+        self.externals.clear();
+        self.videos.clear();
+        self.runs.clear();
+        self.debug_labels.clear();
+        self.draws.clear();
+        self.debug_prim_types.clear();
+    }
+
+    /// Drops prims already drawn by `Vger::flush`, keeping the paints,
+    /// transforms and scissors that later drawing refers to.
+    pub fn clear_prims(&mut self) {
+        for prims in &mut self.prims {
+            prims.clear();
+        }
+        self.small_prims.clear();
+        self.packed_prims.clear();
+        self.layers.clear();
+        self.layer_keys.clear();
+        self.layer_bind_groups.clear();
+        self.externals.clear();
+        self.videos.clear();
+        self.draws.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_small_scene_shader() {
+        let shader = small_scene_shader();
+        assert!(shader.contains("var<uniform> prims: Prims;"));
+        assert!(shader.contains(&format!("array<Prim, {}>", SMALL_SCENE_PRIMS)));
+        assert!(!shader.contains("var<storage> prims"));
+    }
+
+    #[test]
+    fn test_packed_scene_shader() {
+        let shader = packed_scene_shader();
+        assert!(shader.contains("load_prim(instance)"));
+        assert!(shader.contains("load_prim(in.prim_index)"));
+        assert!(!shader.contains("prims.prims[instance]"));
+        assert!(shader.contains("var<storage> packed_prims"));
+        assert!(shader.contains("return unpack_paint(index);"));
+        assert!(shader.contains("var<storage> packed_paints"));
+    }
+
+    #[test]
+    #[should_panic(expected = "expected one")]
+    fn test_replace_once() {
+        assert_eq!(replace_once("a b", "b", "c"), "a c");
+        replace_once("a b b", "b", "c");
     }
 }
@@ -36,6 +36,30 @@ const vgerPathFill = 10;
 /// Svg with override color
 const overrideColorSvg = 11;
 
+/// Line segment with round ends of different widths.
+const vgerTaperedSegment = 12;
+
+/// Grid of hairlines with every nth line using a second paint.
+const vgerGrid = 13;
+
+/// Cached layer, premultiplied, in the color atlas.
+const vgerLayer = 14;
+
+/// Blurred glyph coverage, for text shadows.
+const vgerGlyphShadow = 15;
+
+/// Rectangle border with a width and paint per side.
+const vgerRectBorder = 16;
+
+/// Stroked arc with round ends.
+const vgerRoundArc = 17;
+
+/// Prim flags of images filtered with Catmull-Rom bicubic sampling, and
+/// averaged over each pixel's footprint. Match IMAGE_BICUBIC and
+/// IMAGE_FOOTPRINT in prim.rs.
+const imageBicubic = 1u;
+const imageFootprint = 2u;
+
 struct Prim {
 
     /// Min and max coordinates of the quad we're rendering.
@@ -78,8 +102,8 @@ struct Prim {
     /// Index of scissor rectangle.
     scissor: u32,
 
-    /// Alignment padding.
-    pad: u32,
+    /// Options for the prim type, e.g. imageBicubic.
+    flags: u32,
 
 };
 
@@ -186,6 +210,39 @@ fn sdBezier(pos: vec2<f32>, A: vec2<f32>, B: vec2<f32>, C: vec2<f32> ) -> f32
     return sqrt( res );
 }
 
+fn cro(a: vec2<f32>, b: vec2<f32>) -> f32 {
+    return a.x*b.y - a.y*b.x;
+}
+
+// Segment with round ends of radius ra at a and rb at b.
+fn sdUnevenCapsule(p: vec2<f32>, pa: vec2<f32>, pb: vec2<f32>, ra: f32, rb: f32) -> f32
+{
+    let pp = p - pa;
+    let ba = pb - pa;
+    let h = dot(ba, ba);
+    let r = ra - rb;
+
+    // One end contains the other.
+    if (r*r >= h) {
+        return min(length(pp) - ra, length(pp - ba) - rb);
+    }
+
+    var q = vec2<f32>(dot(pp, vec2<f32>(ba.y, -ba.x)), dot(pp, ba)) / h;
+    q.x = abs(q.x);
+
+    let c = vec2<f32>(sqrt(h - r*r), r);
+    let k = cro(c, q);
+    let m = dot(c, q);
+    let n = dot(q, q);
+
+    if (k < 0.0) {
+        return sqrt(h*n) - ra;
+    } else if (k > c.x) {
+        return sqrt(h*(n + 1.0 - 2.0*q.y)) - rb;
+    }
+    return m - ra;
+}
+
 fn sdSubtract(d1: f32, d2: f32) -> f32
 {
     return max(-d1, d2);
@@ -205,6 +262,18 @@ fn sdArc2(p: vec2<f32>, sca: vec2<f32>, scb: vec2<f32>, radius: f32, width: f32)
                      abs(sdCircle(pp, radius)) - width);
 }
 
+/// Arc with round ends. See https://iquilezles.org/articles/distfunctions2d/
+fn sdRoundArc(p: vec2<f32>, sca: vec2<f32>, scb: vec2<f32>, radius: f32, width: f32) -> f32
+{
+    // Rotate point so the arc is centered on the y axis.
+    let pp = p * mat2x2<f32>(sca,vec2<f32>(-sca.y,sca.x));
+    let q = vec2<f32>(abs(pp.x), pp.y);
+    if (scb.y*q.x > scb.x*q.y) {
+        return length(q - scb*radius) - width;
+    }
+    return abs(length(q) - radius) - width;
+}
+
 // From https://www.shadertoy.com/view/4sySDK
 
 fn inv(M: mat2x2<f32>) -> mat2x2<f32> {
@@ -308,7 +377,7 @@ fn sdPrimBounds(prim: Prim) -> BBox {
             b.min = prim.cv0 - prim.radius;
             b.max = prim.cv0 + prim.radius;
         }
-        case 1u: { // vgerArc
+        case 1u, 17u: { // vgerArc, vgerRoundArc
             b.min = prim.cv0 - prim.radius;
             b.max = prim.cv0 + prim.radius;
         }
@@ -358,11 +427,63 @@ fn sdPrimBounds(prim: Prim) -> BBox {
             b.min = prim.cv0;
             b.max = prim.cv1;
         }
+        case 12u: { // vgerTaperedSegment
+            let r = max(prim.cv2.x, prim.cv2.y);
+            b.min = min(prim.cv0, prim.cv1) - r;
+            b.max = max(prim.cv0, prim.cv1) + r;
+        }
+        case 13u: { // vgerGrid
+            b.min = prim.cv0;
+            b.max = prim.cv1;
+        }
+        case 14u: { // vgerLayer
+            b.min = prim.cv0;
+            b.max = prim.cv1;
+        }
+        case 16u: { // vgerRectBorder
+            b.min = prim.cv0;
+            b.max = prim.cv1;
+        }
         default: {}
     }
     return b;
 }
 
+/// Border widths, top, right, bottom and left, stored in the cv buffer.
+fn borderWidths(prim: Prim) -> vec4<f32> {
+    let a = cvs.cvs[prim.start];
+    let b = cvs.cvs[prim.start + 1u];
+    return vec4<f32>(a, b);
+}
+
+/// Distance to the area between a rounded rect and the rect inset by the
+/// border widths. The inner corner radius shrinks by the widest side.
+fn sdRectBorder(prim: Prim, p: vec2<f32>) -> f32 {
+    let w = borderWidths(prim);
+    let outer = sdBox(p - 0.5*(prim.cv0 + prim.cv1), 0.5*(prim.cv1 - prim.cv0), prim.radius);
+
+    let inner_min = prim.cv0 + w.wx;
+    let inner_max = prim.cv1 - w.yz;
+    let inner_r = max(prim.radius - max(max(w.x, w.y), max(w.z, w.w)), 0.0);
+    let inner = sdBox(p - 0.5*(inner_min + inner_max), max(0.5*(inner_max - inner_min), vec2<f32>(0.0)), inner_r);
+
+    return max(outer, -inner);
+}
+
+/// Side of a border p is on: 0 top, 1 right, 2 bottom, 3 left. Sides meet
+/// along miters from the outer to the inner corners.
+fn borderSide(prim: Prim, p: vec2<f32>) -> u32 {
+    let w = max(borderWidths(prim), vec4<f32>(1e-6));
+    let d = vec4<f32>(p.y - prim.cv0.y, prim.cv1.x - p.x, prim.cv1.y - p.y, p.x - prim.cv0.x) / w;
+
+    var side = 0u;
+    var best = d.x;
+    if (d.y < best) { side = 1u; best = d.y; }
+    if (d.z < best) { side = 2u; best = d.z; }
+    if (d.w < best) { side = 3u; }
+    return side;
+}
+
 fn lineTest(p: vec2<f32>, A: vec2<f32>, B: vec2<f32>) -> bool {
 
     let cs = i32(A.y < p.y) * 2 + i32(B.y < p.y);
@@ -488,6 +609,20 @@ fn sdPrim(prim: Prim, p: vec2<f32>, filterWidth: f32) -> f32 {
             let size = prim.cv1 - prim.cv0;
             d = sdBox(p - center, 0.5*size, prim.radius);
         }
+        case 12u: { // vgerTaperedSegment
+            d = sdUnevenCapsule(p, prim.cv0, prim.cv1, prim.cv2.x, prim.cv2.y);
+        }
+        case 13u: { // vgerGrid
+            let center = 0.5*(prim.cv1 + prim.cv0);
+            let size = prim.cv1 - prim.cv0;
+            d = sdBox(p - center, 0.5*size, 0.0);
+        }
+        case 16u: { // vgerRectBorder
+            d = sdRectBorder(prim, p);
+        }
+        case 17u: { // vgerRoundArc
+            d = sdRoundArc(p - prim.cv0, prim.cv1, prim.cv2, prim.radius, prim.width/2.0);
+        }
         default: { }
     }
     return d;
@@ -514,6 +649,16 @@ struct VertexOutput {
 
 struct Uniforms {
     size: vec2<f32>,
+
+    /// Seconds, for animated paints.
+    time: f32,
+
+    /// Nonzero when the color atlas is sampled through an sRGB view, so
+    /// samples are linear.
+    srgb_atlas: f32,
+
+    /// Sizes of the mask and color atlases.
+    atlas_size: vec2<f32>,
 };
 
 @group(1)
@@ -580,6 +725,11 @@ struct Paint {              // align  size
     image: i32,             // 4      4
     inner_color: vec4<f32>, // 16     16
     outer_color: vec4<f32>, // 16     16
+    params: vec4<f32>,      // 16     16
+    paint_type: u32,        // 4      4
+    space: u32,             // 4      4
+    glow_color: u32,        // 4      4
+    glow_radius: f32,       // 4      4
 };
 
 struct Paints {
@@ -590,11 +740,197 @@ struct Paints {
 @binding(3)
 var<storage> paints: Paints;
 
-fn apply(paint: Paint, p: vec2<f32>) -> vec4<f32> {
-    let local_point = unpack_mat3x2(paint.xform) * vec3<f32>(p, 1.0);
-    let d = clamp(local_point, vec2<f32>(0.0), vec2<f32>(1.0)).x;
+/// Paint at index. Packed scenes unpack it instead.
+fn load_paint(index: u32) -> Paint {
+    return paints.paints[index];
+}
 
-    return mix(paint.inner_color, paint.outer_color, d);
+/// Distance to the nearest of a set of parallel lines.
+fn hatch_distance(p: vec2<f32>, dir: vec2<f32>, spacing: f32) -> f32 {
+    let u = dot(p, vec2<f32>(-dir.y, dir.x)) / spacing;
+    return abs(fract(u + 0.5) - 0.5) * spacing;
+}
+
+/// Distance to the nearest line of a grid with the given cell size.
+fn grid_distance(p: vec2<f32>, cell: f32) -> f32 {
+    let q = abs(fract(p / cell + 0.5) - 0.5) * cell;
+    return min(q.x, q.y);
+}
+
+/// Random value in [0, 1] for a lattice cell, wrapped to period cells if
+/// it's nonzero.
+fn cell_random(c: vec2<i32>, seed: u32, period: i32) -> f32 {
+    var q = c;
+    if (period > 0) {
+        q = ((c % period) + period) % period;
+    }
+    var h = bitcast<u32>(q.x) * 1664525u + bitcast<u32>(q.y) * 1013904223u + seed * 2654435769u;
+    h = h ^ (h >> 16u);
+    h = h * 2246822519u;
+    h = h ^ (h >> 13u);
+    h = h * 3266489917u;
+    h = h ^ (h >> 16u);
+    return f32(h) / 4294967295.0;
+}
+
+fn value_noise(q: vec2<f32>, seed: u32, period: i32) -> f32 {
+    let i = vec2<i32>(floor(q));
+    let f = fract(q);
+    let u = f * f * (3.0 - 2.0 * f);
+    let a = cell_random(i, seed, period);
+    let b = cell_random(i + vec2<i32>(1, 0), seed, period);
+    let c = cell_random(i + vec2<i32>(0, 1), seed, period);
+    let d = cell_random(i + vec2<i32>(1, 1), seed, period);
+    return mix(mix(a, b, u.x), mix(c, d, u.x), u.y);
+}
+
+/// Contribution of the random gradient at a lattice corner.
+fn corner_gradient(i: vec2<i32>, f: vec2<f32>, seed: u32, period: i32) -> f32 {
+    let angle = 6.2831853 * cell_random(i, seed, period);
+    return dot(vec2<f32>(cos(angle), sin(angle)), f);
+}
+
+fn gradient_noise(q: vec2<f32>, seed: u32, period: i32) -> f32 {
+    let i = vec2<i32>(floor(q));
+    let f = fract(q);
+    let u = f * f * (3.0 - 2.0 * f);
+    let a = corner_gradient(i, f, seed, period);
+    let b = corner_gradient(i + vec2<i32>(1, 0), f - vec2<f32>(1.0, 0.0), seed, period);
+    let c = corner_gradient(i + vec2<i32>(0, 1), f - vec2<f32>(0.0, 1.0), seed, period);
+    let d = corner_gradient(i + vec2<i32>(1, 1), f - vec2<f32>(1.0, 1.0), seed, period);
+
+    // Scale from about [-0.7, 0.7].
+    return clamp(mix(mix(a, b, u.x), mix(c, d, u.x), u.y) / 1.4 + 0.5, 0.0, 1.0);
+}
+
+/// Position on a two point conical gradient of q, relative to the start
+/// center, for circles d apart with radii r0 and r1, and whether q is on
+/// a circle with a radius that isn't negative. Mirrors `conical_t` in
+/// paint.rs.
+fn conical_t(q: vec2<f32>, d: vec2<f32>, r0: f32, r1: f32) -> vec2<f32> {
+    let dr = r1 - r0;
+    let a = dot(d, d) - dr * dr;
+    let b = dot(q, d) + r0 * dr;
+    let c = dot(q, q) - r0 * r0;
+
+    if (abs(a) < 1e-6) {
+        let t = c / (2.0 * b);
+        return vec2<f32>(t, select(0.0, 1.0, b != 0.0 && r0 + t * dr >= 0.0));
+    }
+
+    let disc = b * b - a * c;
+    if (disc < 0.0) {
+        return vec2<f32>(0.0, 0.0);
+    }
+    let s = sqrt(disc);
+    let hi = max((b + s) / a, (b - s) / a);
+    let lo = min((b + s) / a, (b - s) / a);
+    if (r0 + hi * dr >= 0.0) {
+        return vec2<f32>(hi, 1.0);
+    }
+    return vec2<f32>(lo, select(0.0, 1.0, r0 + lo * dr >= 0.0));
+}
+
+/// Maps a gradient position to [0, 1] for a spread mode: pad, repeat or
+/// reflect. Mirrors `SpreadMode::apply`.
+fn spread(t: f32, mode: u32) -> f32 {
+    if (mode == 1u) {
+        return fract(t);
+    }
+    if (mode == 2u) {
+        return 1.0 - abs(fract(t * 0.5) * 2.0 - 1.0);
+    }
+    return clamp(t, 0.0, 1.0);
+}
+
+/// Samples the colormap lookup table at lut in the color atlas, at t
+/// from 0 to 1. Tables are LUT_SIZE (256) texels wide.
+fn colormap(lut: vec2<f32>, t: f32) -> vec4<f32> {
+    let st = lut + vec2<f32>(0.5 + t * 255.0, 0.5);
+    return from_color_atlas(textureSampleLevel(color_atlas, color_samp, st/uniforms.atlas_size.y, 0.0));
+}
+
+/// Evaluates a paint at local point p and device point device_p.
+/// fw is the filter width in local space.
+fn apply(paint: Paint, p: vec2<f32>, device_p: vec2<f32>, fw: f32) -> vec4<f32> {
+    var color: vec4<f32>;
+    switch (paint.paint_type) {
+        case 1u, 2u: { // hatch, cross hatch
+            var q = p;
+            var w = fw;
+            if (paint.space == 1u) {
+                q = device_p;
+                w = 1.0;
+            }
+            let dir = paint.params.xy;
+            var d = hatch_distance(q, dir, paint.params.z);
+            if (paint.paint_type == 2u) {
+                d = min(d, hatch_distance(q, rot90(dir), paint.params.z));
+            }
+            let coverage = 1.0 - smoothstep(-w/2.0, w/2.0, d - paint.params.w/2.0);
+            color = mix(paint.outer_color, paint.inner_color, coverage);
+        }
+        case 3u: { // checkerboard
+            var q = p;
+            var w = fw;
+            if (paint.space == 1u) {
+                q = device_p;
+                w = 1.0;
+            }
+
+            // Signed distance to the nearest cell edge, positive in
+            // cells of the second color.
+            let cell = paint.params.x;
+            let f = (fract(q / (2.0 * cell)) - 0.5) * 2.0 * cell;
+            let e = cell / 2.0 - abs(abs(f) - cell / 2.0);
+            let d = -min(e.x, e.y) * sign(f.x * f.y);
+            color = mix(paint.inner_color, paint.outer_color, smoothstep(-w/2.0, w/2.0, d));
+        }
+        case 4u: { // shimmer
+            // The band starts and ends just outside the gradient axis so
+            // it slides in and out.
+            let u = (unpack_mat3x2(paint.xform) * vec3<f32>(p, 1.0)).x;
+            let w = paint.params.x;
+            let center = mix(-w, 1.0 + w, fract(uniforms.time * paint.params.y));
+            let t = 1.0 - smoothstep(0.0, w, abs(u - center));
+            color = mix(paint.inner_color, paint.outer_color, t);
+        }
+        case 5u, 6u, 7u: { // value noise, gradient noise, grain
+            var q = p;
+            if (paint.space == 1u) {
+                q = device_p;
+            }
+            q = q / paint.params.x;
+            let seed = u32(paint.params.z);
+            let period = i32(paint.params.w);
+
+            var n: f32;
+            if (paint.paint_type == 5u) {
+                n = value_noise(q, seed, period);
+            } else if (paint.paint_type == 6u) {
+                n = gradient_noise(q, seed, period);
+            } else {
+                n = cell_random(vec2<i32>(floor(q)), seed, period);
+            }
+            n = 0.5 + (n - 0.5) * paint.params.y;
+            color = mix(paint.inner_color, paint.outer_color, n);
+        }
+        case 8u: { // two point conical gradient
+            let q = unpack_mat3x2(paint.xform) * vec3<f32>(p, 1.0);
+            let t = conical_t(q, paint.params.xy, paint.params.z, paint.params.w);
+            color = mix(paint.inner_color, paint.outer_color, spread(t.x, paint.space)) * t.y;
+        }
+        case 9u: { // heatmap
+            let local_point = unpack_mat3x2(paint.xform) * vec3<f32>(p, 1.0);
+            color = colormap(paint.params.xy, clamp(local_point.x, 0.0, 1.0));
+        }
+        default: {
+            let local_point = unpack_mat3x2(paint.xform) * vec3<f32>(p, 1.0);
+            let d = spread(local_point.x, paint.space);
+            color = mix(paint.inner_color, paint.outer_color, d);
+        }
+    }
+    return color;
 }
 
 struct Scissor {
@@ -639,6 +975,13 @@ var samp : sampler;
 @binding(4)
 var color_samp : sampler;
 
+/// The color atlas, or a cached layer's texture, viewed without sRGB
+/// decoding, for layer prims, which hold colors as they were rendered or
+/// copied.
+@group(1)
+@binding(5)
+var layer_texture: texture_2d<f32>;
+
 // sRGB to linear conversion for one channel.
 fn toLinear(s: f32) -> f32
 {
@@ -648,21 +991,213 @@ fn toLinear(s: f32) -> f32
     return pow((s + 0.055)/1.055, 2.4);
 }
 
-@fragment
-fn fs_main(
-    in: VertexOutput,
-) -> @location(0) vec4<f32> {
+fn toSrgb(l: f32) -> f32
+{
+    if l < 0.0031308 {
+        return l * 12.92;
+    }
+    return 1.055 * pow(l, 1.0/2.4) - 0.055;
+}
+
+/// A color atlas sample back in sRGB, which shading works in. With an
+/// sRGB view the hardware filtered in linear space.
+fn from_color_atlas(c: vec4<f32>) -> vec4<f32> {
+    if (uniforms.srgb_atlas == 0.0) {
+        return c;
+    }
+    return vec4<f32>(toSrgb(c.r), toSrgb(c.g), toSrgb(c.b), c.a);
+}
+
+/// Atlas coordinate t inset half a texel into the region between a and
+/// b, so linear filtering of scaled quads doesn't reach neighbouring
+/// entries in the atlas.
+fn inset_atlas_coord(t: vec2<f32>, a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let center = 0.5 * (a + b);
+    let lo = min(min(a, b) + 0.5, center);
+    let hi = max(max(a, b) - 0.5, center);
+    return clamp(t, lo, hi);
+}
+
+/// Glyph coverage at t, zero outside the glyph's atlas rect, since the
+/// rest of the atlas holds other glyphs.
+fn glyph_coverage(t: vec2<f32>, lo: vec2<f32>, hi: vec2<f32>) -> f32 {
+    if (any(t < lo) || any(t > hi)) {
+        return 0.0;
+    }
+    let st = inset_atlas_coord(t, lo, hi);
+    return textureSampleLevel(glyph_atlas, samp, st/uniforms.atlas_size.x, 0.0).r;
+}
+
+/// Glyph coverage blurred by a gaussian with standard deviation r/2, in
+/// texels. The glyph's atlas rect is in cv0 and cv1.
+fn blurred_glyph(prim: Prim, t: vec2<f32>) -> f32 {
+    let r = prim.radius;
+    if (r <= 0.0) {
+        return glyph_coverage(t, prim.cv0, prim.cv1);
+    }
+
+    let step = r / 3.0;
+    var sum = 0.0;
+    var total = 0.0;
+    for (var i: i32 = -3; i <= 3; i = i+1) {
+        for (var j: i32 = -3; j <= 3; j = j+1) {
+            let o = vec2<f32>(f32(i), f32(j)) * step;
+            let w = exp(-2.0 * dot(o, o) / (r * r));
+            sum = sum + w * glyph_coverage(t + o, prim.cv0, prim.cv1);
+            total = total + w;
+        }
+    }
+    return sum / total;
+}
+
+/// Size of the texture a layer prim samples: the color atlas for
+/// backdrops and external textures, or the cached layer's own texture.
+fn layer_size() -> vec2<f32> {
+    return vec2<f32>(textureDimensions(layer_texture));
+}
+
+/// Region of a layer blurred by a gaussian with standard deviation
+/// prim.width/2, in atlas pixels, clamped to the region.
+fn blurred_layer(prim: Prim, t: vec2<f32>) -> vec4<f32> {
+    let r = prim.width;
+    let step = r / 3.0;
+    var sum = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    var total = 0.0;
+    for (var i: i32 = -3; i <= 3; i = i+1) {
+        for (var j: i32 = -3; j <= 3; j = j+1) {
+            let o = vec2<f32>(f32(i), f32(j)) * step;
+            let w = exp(-2.0 * dot(o, o) / (r * r));
+            let c = inset_atlas_coord(t + o, prim.tex_bounds_min, prim.tex_bounds_max);
+            sum = sum + w * textureSampleLevel(layer_texture, color_samp, c/layer_size(), 0.0);
+            total = total + w;
+        }
+    }
+    return sum / total;
+}
+
+/// Coverage of a layer's rounded rect mask of radius prim.radius, in
+/// atlas pixels, for scrim backdrops and rounded external textures.
+fn layer_mask(prim: Prim, t: vec2<f32>, fw: f32) -> f32 {
+    let center = 0.5 * (prim.tex_bounds_min + prim.tex_bounds_max);
+    let size = prim.tex_bounds_max - prim.tex_bounds_min;
+    let r = min(prim.radius, 0.5 * min(size.x, size.y));
+    let d = sdBox(t - center, 0.5 * size, r);
+    return 1.0 - smoothstep(-fw/2.0, fw/2.0, d);
+}
+
+/// Coverage of an image's rounded corners. The radius is in local units,
+/// and t in atlas space, so t is mapped to the quad first.
+fn image_mask(prim: Prim, t: vec2<f32>, fw: f32) -> f32 {
+    if (prim.radius <= 0.0) {
+        return 1.0;
+    }
+    let size = prim.quad_bounds_max - prim.quad_bounds_min;
+    let scale = size / (prim.tex_bounds_max - prim.tex_bounds_min);
+    let p = (t - prim.tex_bounds_min) * scale;
+    let r = min(prim.radius, 0.5 * min(size.x, size.y));
+    let d = sdBox(p - 0.5 * size, 0.5 * size, r);
+    let w = fw * max(scale.x, scale.y);
+    return 1.0 - smoothstep(-w/2.0, w/2.0, d);
+}
+
+/// Catmull-Rom weights for the four texels around a sample f of the way
+/// between the middle two.
+fn catmull_rom_weights(f: f32) -> vec4<f32> {
+    let f2 = f * f;
+    let f3 = f2 * f;
+    return vec4<f32>(
+        -0.5 * f3 + f2 - 0.5 * f,
+        1.5 * f3 - 2.5 * f2 + 1.0,
+        -1.5 * f3 + 2.0 * f2 + 0.5 * f,
+        0.5 * f3 - 0.5 * f2
+    );
+}
+
+/// Image color at t with bicubic filtering, clamped to the prim's region
+/// of the color atlas.
+fn sample_bicubic(prim: Prim, t: vec2<f32>) -> vec4<f32> {
+    let a = prim.tex_bounds_min;
+    let b = prim.tex_bounds_max;
+    let p = inset_atlas_coord(t, a, b) - 0.5;
+    let base = floor(p);
+    let wx = catmull_rom_weights(p.x - base.x);
+    let wy = catmull_rom_weights(p.y - base.y);
+
+    var sum = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    for (var j: i32 = 0; j < 4; j = j+1) {
+        for (var i: i32 = 0; i < 4; i = i+1) {
+            let c = inset_atlas_coord(base + vec2<f32>(f32(i) - 0.5, f32(j) - 0.5), a, b);
+            let texel = textureSampleLevel(color_atlas, color_samp, c/uniforms.atlas_size.y, 0.0);
+            sum = sum + wx[i] * wy[j] * texel;
+        }
+    }
+    return from_color_atlas(clamp(sum, vec4<f32>(0.0), vec4<f32>(1.0)));
+}
+
+/// Image color averaged over a pixel's footprint, given by the change in
+/// t across the pixel, clamped to the prim's region of the color atlas.
+fn sample_footprint(prim: Prim, t: vec2<f32>, dtdx: vec2<f32>, dtdy: vec2<f32>) -> vec4<f32> {
+    let nx = i32(clamp(ceil(length(dtdx)), 1.0, 4.0));
+    let ny = i32(clamp(ceil(length(dtdy)), 1.0, 4.0));
+
+    var sum = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    for (var j: i32 = 0; j < ny; j = j+1) {
+        for (var i: i32 = 0; i < nx; i = i+1) {
+            let u = (f32(i) + 0.5) / f32(nx) - 0.5;
+            let v = (f32(j) + 0.5) / f32(ny) - 0.5;
+            let c = inset_atlas_coord(t + u * dtdx + v * dtdy, prim.tex_bounds_min, prim.tex_bounds_max);
+            sum = sum + textureSampleLevel(color_atlas, color_samp, c/uniforms.atlas_size.y, 0.0);
+        }
+    }
+    return from_color_atlas(sum / f32(nx * ny));
+}
+
+/// Composites a shape's color over its paint's glow, which fades out
+/// quadratically with distance d outside the shape.
+fn glow_under(fill: vec4<f32>, paint: Paint, d: f32) -> vec4<f32> {
+    let g = unpack4x8unorm(paint.glow_color);
+    let t = clamp(1.0 - d / paint.glow_radius, 0.0, 1.0);
+    let glow_a = g.a * t * t;
+
+    let a = fill.a + glow_a * (1.0 - fill.a);
+    if (a <= 0.0) {
+        return vec4<f32>(fill.rgb, 0.0);
+    }
+    let rgb = (fill.rgb * fill.a + g.rgb * glow_a * (1.0 - fill.a)) / a;
+    return vec4<f32>(rgb, a);
+}
+
+/// Color of a fragment, not premultiplied.
+fn shade(in: VertexOutput) -> vec4<f32> {
 
     let fw = length(fwidth(in.t));
     let prim = prims.prims[in.prim_index];
-    let paint = paints.paints[prim.paint];
+    let paint = load_paint(prim.paint);
     let scissor = scissors.scissors[prim.scissor];
 
+    // Evaluate the sdf before branching so its screen-space derivatives
+    // are taken in uniform control flow. aa is the change in distance
+    // across one pixel, which keeps edges one pixel soft under any
+    // transform, including non-uniform scale. It's at most the filter
+    // width, since derivatives spike where distance is discontinuous,
+    // e.g. where a path fill's bands meet, which would leave seams.
+    let d = sdPrim(prim, in.t, fw);
+    var aa = clamp(length(vec2<f32>(dpdx(d), dpdy(d))), 1e-6, fw);
+    if (prim.prim_type == 10u) { // vgerPathFill
+        aa = fw;
+    }
+
     // Look up glyph alpha (if not a glyph, still have to because of wgsl).
     // let a = textureSample(glyph_atlas, samp, (in.t+0.5)/1024.0).r;
     // let mask = textureLoad(glyph_atlas, vec2<i32>(in.t), 0);
-    let mask = textureSample(glyph_atlas, samp, in.t/4096.0);
-    let color_mask = textureSample(color_atlas, color_samp, in.t/4096.0);
+    // Change in t across the pixel, for images sampled over their
+    // footprint.
+    let dtdx = dpdx(in.t);
+    let dtdy = dpdy(in.t);
+
+    let st = inset_atlas_coord(in.t, prim.tex_bounds_min, prim.tex_bounds_max);
+    let mask = textureSample(glyph_atlas, samp, st/uniforms.atlas_size.x);
+    let color_mask = from_color_atlas(textureSample(color_atlas, color_samp, st/uniforms.atlas_size.y));
 
     let s = scissor_mask(scissor, in.p);
 
@@ -684,13 +1219,31 @@ fn fs_main(
         return s * color;
     }
     
+    if(prim.prim_type == 15u) { // vgerGlyphShadow
+        let a = blurred_glyph(prim, in.t);
+        if (a <= 0.0) {
+            discard;
+        }
+
+        let c = paint.inner_color;
+        return s * vec4<f32>(c.rgb, c.a * a);
+    }
+
     if(prim.prim_type == 9u) { // vgerColorGlyph
 
         let c = paint.inner_color;
 
+        // Images may ask for better filtering than the bilinear sample.
+        var image = color_mask;
+        if ((prim.flags & imageBicubic) != 0u) {
+            image = sample_bicubic(prim, in.t);
+        } else if ((prim.flags & imageFootprint) != 0u) {
+            image = sample_footprint(prim, in.t, dtdx, dtdy);
+        }
+
         // XXX: using toLinear is a bit of a guess. Gets us closer
         // to matching the glyph atlas in the output.
-        var color = vec4<f32>(color_mask.rgb, c.a * color_mask.a);
+        var color = vec4<f32>(image.rgb, c.a * image.a * image_mask(prim, in.t, fw));
 
         //if(glow) {
         //    color.a *= paint.glow;
@@ -714,8 +1267,100 @@ fn fs_main(
         return s * color;
     }
 
-    let d = sdPrim(prim, in.t, fw);
-    let color = apply(paint, in.t);
+    if(prim.prim_type == 14u) { // vgerLayer
+
+        // Backdrops are blurred and masked to a rounded rect.
+        var layer = textureSampleLevel(layer_texture, color_samp, st/layer_size(), 0.0);
+        if (prim.width > 0.0) {
+            layer = blurred_layer(prim, in.t);
+        }
+
+        // Undo the premultiplication from rendering the layer.
+        let a = layer.a;
+        if (a <= 0.0) {
+            discard;
+        }
+
+        var color = vec4<f32>(layer.rgb / a, a);
+
+        // Only scrim backdrops and rounded external textures have a
+        // radius, so other layers skip the mask.
+        if (prim.radius > 0.0) {
+            color.a = color.a * layer_mask(prim, in.t, fw);
+        }
+
+        return s * color;
+    }
+
+    if(prim.prim_type == 16u) { // vgerRectBorder
+
+        // Paints for each side are consecutive.
+        let side_paint = load_paint(prim.paint + borderSide(prim, in.t));
+        let color = apply(side_paint, in.t, in.position.xy, fw);
+
+        return s * mix(vec4<f32>(color.rgb,0.0), color, 1.0-smoothstep(-aa/2.0,aa/2.0,d) );
+    }
+
+    if(prim.prim_type == 13u) { // vgerGrid
+
+        // Lines are hairlines, one filter width wide. The major paint
+        // index is stored in the glyph slot.
+        let q = in.t - prim.cv0;
+        let minor = 1.0 - smoothstep(-fw/2.0, fw/2.0, grid_distance(q, prim.cv2.x) - fw/2.0);
+        let major = 1.0 - smoothstep(-fw/2.0, fw/2.0, grid_distance(q, prim.cv2.x * prim.cv2.y) - fw/2.0);
+
+        let minor_color = apply(paint, in.t, in.position.xy, fw);
+        let major_color = apply(load_paint(prim.glyph), in.t, in.position.xy, fw);
+
+        var color = mix(vec4<f32>(minor_color.rgb, 0.0), minor_color, minor);
+        color = mix(color, major_color, major);
+
+        color.a = color.a * (1.0 - smoothstep(-aa/2.0, aa/2.0, d));
+
+        return s * color;
+    }
+
+    let color = apply(paint, in.t, in.position.xy, fw);
+    let fill = mix(vec4<f32>(color.rgb,0.0), color, 1.0-smoothstep(-aa/2.0,aa/2.0,d) );
+
+    if (paint.glow_radius > 0.0) {
+        return s * glow_under(fill, paint, d);
+    }
 
-    return s * mix(vec4<f32>(color.rgb,0.0), color, 1.0-smoothstep(-fw/2.0,fw/2.0,d) );
+    return s * fill;
+}
+
+@fragment
+fn fs_main(
+    in: VertexOutput,
+) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+/// Coverage only, for rendering masks into single channel targets.
+@fragment
+fn fs_mask(
+    in: VertexOutput,
+) -> @location(0) vec4<f32> {
+    return vec4<f32>(shade(in).a);
+}
+
+/// Colors premultiplied by alpha, for transparent windows.
+@fragment
+fn fs_premultiplied(
+    in: VertexOutput,
+) -> @location(0) vec4<f32> {
+    let c = shade(in);
+    return vec4<f32>(c.rgb * c.a, c.a);
+}
+
+/// Colors converted from sRGB to linear and premultiplied, for linear
+/// float targets.
+@fragment
+fn fs_linear(
+    in: VertexOutput,
+) -> @location(0) vec4<f32> {
+    let c = shade(in);
+    let rgb = vec3<f32>(toLinear(c.r), toLinear(c.g), toLinear(c.b));
+    return vec4<f32>(rgb * c.a, c.a);
 }
@@ -0,0 +1,203 @@
+//! Shelf packing, which fills an atlas in rows. Glyphs of a size are all
+//! about as tall, so rows of them waste little space, and a row can be
+//! evicted on its own.
+
+use rect_packer::Rect;
+
+/// A row of regions, filled left to right.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Shelf {
+    y: i32,
+    height: i32,
+
+    /// Where the next region goes.
+    x: i32,
+
+    /// Area of the regions in the shelf, each with padding on two sides.
+    area: i32,
+
+    /// Regions in the shelf.
+    count: usize,
+}
+
+/// Packs regions into shelves as tall as the first region put in them.
+/// A region goes in the shelf which fits it with the least height to
+/// spare, so short glyphs don't take space in tall shelves.
+#[derive(Clone, Debug)]
+pub struct ShelfPacker {
+    size: i32,
+    padding: i32,
+    shelves: Vec<Shelf>,
+}
+
+impl ShelfPacker {
+    /// Smallest fraction of a shelf's height a region must fill to go in
+    /// it, rather than start a new shelf.
+    const MIN_FILL: f32 = 0.7;
+
+    /// Packer for an atlas `size` pixels square, leaving `padding` pixels
+    /// between regions and around the edge.
+    pub fn new(size: u32, padding: u32) -> Self {
+        Self {
+            size: size as i32,
+            padding: padding as i32,
+            shelves: vec![],
+        }
+    }
+
+    pub fn pack(&mut self, width: i32, height: i32) -> Option<Rect> {
+        let (padding, size) = (self.padding, self.size);
+        if width + 2 * padding > size {
+            return None;
+        }
+
+        let fits = |shelf: &Shelf| {
+            height <= shelf.height
+                && height as f32 >= shelf.height as f32 * Self::MIN_FILL
+                && shelf.x + width + padding <= size
+        };
+        let best = (0..self.shelves.len())
+            .filter(|i| fits(&self.shelves[*i]))
+            .min_by_key(|i| self.shelves[*i].height);
+
+        let index = match best {
+            Some(index) => index,
+            None => {
+                let y = self
+                    .shelves
+                    .last()
+                    .map_or(padding, |s| s.y + s.height + padding);
+                if y + height + padding > size {
+                    return None;
+                }
+                self.shelves.push(Shelf {
+                    y,
+                    height,
+                    x: padding,
+                    area: 0,
+                    count: 0,
+                });
+                self.shelves.len() - 1
+            }
+        };
+
+        let shelf = &mut self.shelves[index];
+        let rect = Rect::new(shelf.x, shelf.y, width, height);
+        shelf.x += width + padding;
+        shelf.area += (width + padding) * (height + padding);
+        shelf.count += 1;
+        Some(rect)
+    }
+
+    /// Frees a region. Its space is reused once every region in its shelf
+    /// is freed. Returns false if no shelf has it.
+    pub fn free(&mut self, rect: Rect) -> bool {
+        let padding = self.padding;
+        let shelf = match self
+            .shelves
+            .iter_mut()
+            .find(|s| rect.y == s.y && s.count > 0)
+        {
+            Some(shelf) => shelf,
+            None => return false,
+        };
+        shelf.area -= (rect.width + padding) * (rect.height + padding);
+        shelf.count -= 1;
+        if shelf.count == 0 {
+            shelf.x = padding;
+            shelf.area = 0;
+        }
+        true
+    }
+
+    /// Frees the shelf containing row `y`, returning its bounds and the
+    /// area its regions used. Regions cached in the bounds should be
+    /// dropped, since new ones will overwrite them.
+    pub fn evict_shelf(&mut self, y: i32) -> Option<(Rect, i32)> {
+        let shelf = self
+            .shelves
+            .iter_mut()
+            .find(|s| y >= s.y && y < s.y + s.height)?;
+        let bounds = Rect::new(0, shelf.y, self.size, shelf.height);
+        let area = shelf.area;
+        shelf.x = self.padding;
+        shelf.area = 0;
+        shelf.count = 0;
+        Some((bounds, area))
+    }
+
+    /// Frees every shelf.
+    pub fn clear(&mut self) {
+        self.shelves.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_shelves() {
+        let mut packer = ShelfPacker::new(64, 1);
+        let a = packer.pack(10, 10).unwrap();
+        let b = packer.pack(10, 9).unwrap();
+        assert_eq!((a.x, a.y), (1, 1));
+        assert_eq!((b.x, b.y), (12, 1));
+
+        // Too short for the first shelf, so it starts another.
+        let c = packer.pack(10, 4).unwrap();
+        assert_eq!((c.x, c.y), (1, 12));
+
+        // Fills the best fitting shelf.
+        let d = packer.pack(10, 4).unwrap();
+        assert_eq!((d.x, d.y), (12, 12));
+        let e = packer.pack(10, 10).unwrap();
+        assert_eq!((e.x, e.y), (23, 1));
+
+        assert!(packer.pack(63, 1).is_none());
+        assert!(packer.pack(10, 60).is_none());
+    }
+
+    #[test]
+    fn test_full_shelf() {
+        let mut packer = ShelfPacker::new(32, 0);
+        for _ in 0..4 {
+            assert_eq!(packer.pack(8, 8).unwrap().y, 0);
+        }
+        assert_eq!(packer.pack(8, 8).unwrap().y, 8);
+    }
+
+    #[test]
+    fn test_evict_shelf() {
+        let mut packer = ShelfPacker::new(64, 0);
+        packer.pack(10, 10).unwrap();
+        packer.pack(10, 10).unwrap();
+        packer.pack(10, 4).unwrap();
+
+        let (bounds, area) = packer.evict_shelf(5).unwrap();
+        assert_eq!((bounds.y, bounds.height), (0, 10));
+        assert_eq!(area, 200);
+
+        // Space in the shelf is reused, other shelves are untouched.
+        assert_eq!(packer.pack(10, 10).unwrap().x, 0);
+        assert_eq!(packer.pack(10, 4).unwrap().x, 10);
+        assert!(packer.evict_shelf(40).is_none());
+    }
+
+    #[test]
+    fn test_free() {
+        let mut packer = ShelfPacker::new(64, 0);
+        let a = packer.pack(10, 10).unwrap();
+        let b = packer.pack(10, 10).unwrap();
+
+        assert!(packer.free(a));
+        assert_eq!(packer.pack(10, 10).unwrap().x, 20);
+
+        // The shelf is reused once it's empty.
+        assert!(packer.free(b));
+        assert!(packer.free(Rect::new(20, 0, 10, 10)));
+        assert_eq!(packer.pack(10, 10).unwrap().x, 0);
+        assert!(!packer.free(Rect::new(0, 30, 10, 10)));
+    }
+}
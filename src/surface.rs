@@ -0,0 +1,148 @@
+//! Surfaces which come and go with the app lifecycle, as on Android and iOS.
+
+/// Owns a window's `wgpu::Surface` and its configuration.
+///
+/// On Android the native window is destroyed when the app is suspended and
+/// a new one is created on resume, and on iOS a backgrounded app's surface
+/// can be lost. Call `suspend` and `resume` from the corresponding window
+/// events and `acquire` once per frame.
+///
+/// The format chosen on the first `resume` is kept afterwards when the
+/// surface allows, since a `Vger` is created for one format.
+pub struct SurfaceState {
+    surface: Option<wgpu::Surface>,
+    config: wgpu::SurfaceConfiguration,
+    transparent: bool,
+    configured: bool,
+}
+
+impl SurfaceState {
+    /// `format` is the preferred format. With `transparent`, an alpha mode
+    /// letting the OS blend the window with what's behind it is chosen.
+    pub fn new(format: wgpu::TextureFormat, transparent: bool) -> Self {
+        Self {
+            surface: None,
+            config: wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                format,
+                width: 1,
+                height: 1,
+                present_mode: wgpu::PresentMode::Fifo,
+                alpha_mode: wgpu::CompositeAlphaMode::Auto,
+                view_formats: vec![],
+            },
+            transparent,
+            configured: false,
+        }
+    }
+
+    /// Takes a newly created surface and configures it for `width` by
+    /// `height` physical pixels.
+    pub fn resume(
+        &mut self,
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        surface: wgpu::Surface,
+        width: u32,
+        height: u32,
+    ) {
+        let caps = surface.get_capabilities(adapter);
+        if !caps.formats.contains(&self.config.format) {
+            if let Some(format) = caps.formats.first() {
+                self.config.format = *format;
+            }
+        }
+        self.config.alpha_mode = choose_alpha_mode(&caps.alpha_modes, self.transparent);
+        self.surface = Some(surface);
+        self.configured = true;
+        self.resize(device, width, height);
+    }
+
+    /// Drops the surface, which must not outlive the native window.
+    pub fn suspend(&mut self) {
+        self.surface = None;
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.surface.is_none()
+    }
+
+    /// Reconfigures for a new size in physical pixels.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.config.width = width.max(1);
+        self.config.height = height.max(1);
+        if let Some(surface) = &self.surface {
+            surface.configure(device, &self.config);
+        }
+    }
+
+    /// Format of the surface's textures, once resumed.
+    pub fn format(&self) -> Option<wgpu::TextureFormat> {
+        if self.configured {
+            Some(self.config.format)
+        } else {
+            None
+        }
+    }
+
+    pub fn alpha_mode(&self) -> wgpu::CompositeAlphaMode {
+        self.config.alpha_mode
+    }
+
+    /// Texture for the next frame, or `None` while suspended or when no
+    /// frame is available. A lost or outdated surface is reconfigured and
+    /// tried again.
+    pub fn acquire(&mut self, device: &wgpu::Device) -> Option<wgpu::SurfaceTexture> {
+        let surface = self.surface.as_ref()?;
+        match surface.get_current_texture() {
+            Ok(frame) => Some(frame),
+            Err(wgpu::SurfaceError::Lost) | Err(wgpu::SurfaceError::Outdated) => {
+                surface.configure(device, &self.config);
+                surface.get_current_texture().ok()
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+/// Picks a composite alpha mode from those a surface supports. Transparent
+/// windows prefer premultiplied alpha.
+pub fn choose_alpha_mode(
+    supported: &[wgpu::CompositeAlphaMode],
+    transparent: bool,
+) -> wgpu::CompositeAlphaMode {
+    use wgpu::CompositeAlphaMode::*;
+    let preferred: &[wgpu::CompositeAlphaMode] = if transparent {
+        &[PreMultiplied, PostMultiplied, Inherit]
+    } else {
+        &[Opaque]
+    };
+    preferred
+        .iter()
+        .find(|mode| supported.contains(mode))
+        .or_else(|| supported.first())
+        .copied()
+        .unwrap_or(Auto)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use wgpu::CompositeAlphaMode::*;
+
+    #[test]
+    fn test_choose_alpha_mode() {
+        assert_eq!(
+            choose_alpha_mode(&[Opaque, PreMultiplied], true),
+            PreMultiplied
+        );
+        assert_eq!(
+            choose_alpha_mode(&[Opaque, PostMultiplied], true),
+            PostMultiplied
+        );
+        assert_eq!(choose_alpha_mode(&[Opaque, PreMultiplied], false), Opaque);
+        assert_eq!(choose_alpha_mode(&[Inherit], false), Inherit);
+        assert_eq!(choose_alpha_mode(&[], true), Auto);
+    }
+}
@@ -0,0 +1,154 @@
+//! Data grids drawn with `Vger::draw_table`.
+
+use crate::chart::BarSpec;
+use crate::defs::*;
+use crate::PaintIndex;
+
+/// Gridlines of a table.
+#[derive(Copy, Clone, Debug)]
+pub struct TableBorder {
+    pub width: f32,
+    pub paint: PaintIndex,
+
+    /// Whether to draw the lines around the outside as well as between
+    /// cells.
+    pub outer: bool,
+}
+
+/// Bars for a table with its top left at `origin`: backgrounds for each
+/// run of rows with the same paint from `row_paints`, which are cycled,
+/// then one line across the whole table for each row and column edge.
+pub(crate) fn table_bars(
+    origin: LocalPoint,
+    column_widths: &[f32],
+    row_heights: &[f32],
+    row_paints: &[PaintIndex],
+    border: Option<&TableBorder>,
+) -> Vec<BarSpec> {
+    let edges = |start: f32, sizes: &[f32]| {
+        let mut edges = Vec::with_capacity(sizes.len() + 1);
+        edges.push(start);
+        for size in sizes {
+            edges.push(edges[edges.len() - 1] + size.max(0.0));
+        }
+        edges
+    };
+    let xs = edges(origin.x, column_widths);
+    let ys = edges(origin.y, row_heights);
+    let (x0, x1) = (xs[0], xs[xs.len() - 1]);
+    let (y0, y1) = (ys[0], ys[ys.len() - 1]);
+
+    let mut bars = vec![];
+    if !row_paints.is_empty() && x1 > x0 {
+        let mut start = 0;
+        for row in 1..=row_heights.len() {
+            let paint = row_paints[start % row_paints.len()];
+            let end = row == row_heights.len() || row_paints[row % row_paints.len()] != paint;
+            if end {
+                if ys[row] > ys[start] {
+                    bars.push(BarSpec {
+                        x: x0,
+                        width: x1 - x0,
+                        y0: ys[start],
+                        y1: ys[row],
+                        paint,
+                    });
+                }
+                start = row;
+            }
+        }
+    }
+
+    if let Some(border) = border {
+        let half = border.width / 2.0;
+        let lines = |edges: &[f32]| {
+            let n = edges.len();
+            // No cells along this axis, so nothing to separate.
+            if n < 2 {
+                return vec![];
+            }
+            let inner = if border.outer { 0..n } else { 1..n - 1 };
+            edges[inner].to_vec()
+        };
+        for x in lines(&xs) {
+            bars.push(BarSpec {
+                x: x - half,
+                width: border.width,
+                y0: y0 - half,
+                y1: y1 + half,
+                paint: border.paint,
+            });
+        }
+        for y in lines(&ys) {
+            bars.push(BarSpec {
+                x: x0 - half,
+                width: x1 - x0 + border.width,
+                y0: y - half,
+                y1: y + half,
+                paint: border.paint,
+            });
+        }
+    }
+    bars
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn paint(index: usize) -> PaintIndex {
+        PaintIndex { index }
+    }
+
+    #[test]
+    fn test_table_bars() {
+        let border = TableBorder {
+            width: 1.0,
+            paint: paint(9),
+            outer: true,
+        };
+        let bars = table_bars(
+            LocalPoint::new(10.0, 20.0),
+            &[50.0, 100.0],
+            &[10.0; 4],
+            &[paint(0), paint(1)],
+            Some(&border),
+        );
+
+        // A background per row, then 3 column and 5 row lines.
+        assert_eq!(bars.len(), 4 + 3 + 5);
+        assert_eq!((bars[1].y0, bars[1].y1), (30.0, 40.0));
+        assert_eq!(bars[1].paint.index, 1);
+        assert_eq!((bars[5].x, bars[5].width), (59.5, 1.0));
+        assert_eq!((bars[5].y0, bars[5].y1), (19.5, 60.5));
+        assert_eq!((bars[11].x, bars[11].width), (9.5, 151.0));
+
+        // Rows with the same paint share a background, and inner lines
+        // skip the outside.
+        let inner = TableBorder {
+            outer: false,
+            ..border
+        };
+        let bars = table_bars(
+            LocalPoint::zero(),
+            &[50.0, 100.0],
+            &[10.0; 4],
+            &[paint(0)],
+            Some(&inner),
+        );
+        assert_eq!(bars.len(), 1 + 1 + 3);
+        assert_eq!((bars[0].y0, bars[0].y1), (0.0, 40.0));
+
+        assert!(table_bars(LocalPoint::zero(), &[], &[], &[paint(0)], None).is_empty());
+
+        // Empty tables draw no lines, and a single cell has no inner ones.
+        assert!(table_bars(LocalPoint::zero(), &[], &[], &[], Some(&inner)).is_empty());
+        assert!(table_bars(LocalPoint::zero(), &[], &[], &[], Some(&border)).is_empty());
+        assert!(table_bars(LocalPoint::zero(), &[50.0], &[], &[], Some(&inner)).is_empty());
+        let bars = table_bars(LocalPoint::zero(), &[50.0], &[10.0], &[], Some(&inner));
+        assert!(bars.is_empty());
+        let bars = table_bars(LocalPoint::zero(), &[50.0], &[10.0], &[], Some(&border));
+        assert_eq!(bars.len(), 2 + 2);
+    }
+}
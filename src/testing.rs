@@ -0,0 +1,298 @@
+//! Golden-image regression testing, for vger itself and for code built on it.
+//!
+//! Enabled with the `testing` feature.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::{Color, RenderOptions, Vger};
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Renders a closure into an RGBA image `width` by `height` device pixels.
+pub fn render_rgba(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    width: u32,
+    height: u32,
+    device_px_ratio: f32,
+    draw: impl FnOnce(&mut Vger),
+) -> Vec<u8> {
+    let mut vger = Vger::new(device, FORMAT);
+    vger.begin(
+        width as f32 / device_px_ratio,
+        height as f32 / device_px_ratio,
+        device_px_ratio,
+    );
+    draw(&mut vger);
+
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        label: Some("golden_texture"),
+        view_formats: &[FORMAT],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let options = RenderOptions {
+        clear: Some(Color::gray(0.0)),
+    };
+    vger.encode_to_view(device, &view, queue, &options);
+
+    // Rows must be aligned for the copy, so strip the padding afterwards.
+    let row = width * 4;
+    let padded_row = (row + wgpu::COPY_BYTES_PER_ROW_ALIGNMENT - 1)
+        / wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("golden_buffer"),
+        size: (padded_row * height) as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(padded_row),
+                rows_per_image: None,
+            },
+        },
+        size,
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |r| sender.send(r).unwrap());
+    device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .unwrap()
+        .expect("failed to map golden buffer");
+
+    let mut pixels = Vec::with_capacity((row * height) as usize);
+    {
+        let data = slice.get_mapped_range();
+        for y in 0..height {
+            let start = (y * padded_row) as usize;
+            pixels.extend_from_slice(&data[start..start + row as usize]);
+        }
+    }
+    buffer.unmap();
+
+    pixels
+}
+
+/// Perceptual difference between two colors, from 0 to 1, using the
+/// YIQ color space as in pixelmatch.
+pub fn color_delta(a: &[u8], b: &[u8]) -> f32 {
+    // Blend with white by alpha first.
+    let blend = |p: &[u8]| {
+        let alpha = p[3] as f32 / 255.0;
+        let c = |v: u8| 255.0 + (v as f32 - 255.0) * alpha;
+        (c(p[0]), c(p[1]), c(p[2]))
+    };
+    let (r1, g1, b1) = blend(a);
+    let (r2, g2, b2) = blend(b);
+
+    let y = |r: f32, g: f32, b: f32| r * 0.29889531 + g * 0.58662247 + b * 0.11448223;
+    let i = |r: f32, g: f32, b: f32| r * 0.59597799 - g * 0.2741761 - b * 0.32180189;
+    let q = |r: f32, g: f32, b: f32| r * 0.21147017 - g * 0.52261711 + b * 0.31114694;
+
+    let dy = y(r1, g1, b1) - y(r2, g2, b2);
+    let di = i(r1, g1, b1) - i(r2, g2, b2);
+    let dq = q(r1, g1, b1) - q(r2, g2, b2);
+
+    ((0.5053 * dy * dy + 0.299 * di * di + 0.1957 * dq * dq) / 35215.0).sqrt()
+}
+
+/// Result of comparing two images.
+pub struct ImageDiff {
+    /// Number of pixels whose difference exceeded the threshold.
+    pub bad_pixels: usize,
+
+    /// Largest difference found, from 0 to 1.
+    pub max_delta: f32,
+
+    /// RGBA image with differing pixels in red over a faded copy of the
+    /// expected image.
+    pub image: Vec<u8>,
+}
+
+/// Compares two RGBA images of the same size. Pixels differing by more than
+/// `threshold` (0 to 1) count as bad.
+pub fn compare(expected: &[u8], actual: &[u8], threshold: f32) -> ImageDiff {
+    assert_eq!(expected.len(), actual.len());
+
+    let mut diff = ImageDiff {
+        bad_pixels: 0,
+        max_delta: 0.0,
+        image: Vec::with_capacity(expected.len()),
+    };
+
+    for (e, a) in expected.chunks(4).zip(actual.chunks(4)) {
+        let delta = color_delta(e, a);
+        diff.max_delta = diff.max_delta.max(delta);
+        if delta > threshold {
+            diff.bad_pixels += 1;
+            diff.image.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let gray =
+                (255.0 - 0.1 * (255.0 - (e[0] as f32 + e[1] as f32 + e[2] as f32) / 3.0)) as u8;
+            diff.image.extend_from_slice(&[gray, gray, gray, 255]);
+        }
+    }
+
+    diff
+}
+
+fn read_png(path: &Path) -> Result<(u32, u32, Vec<u8>), String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut reader = png::Decoder::new(file)
+        .read_info()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+        return Err(format!("{}: expected 8-bit RGBA", path.display()));
+    }
+    buf.truncate(info.buffer_size());
+    Ok((info.width, info.height, buf))
+}
+
+fn write_png(path: &Path, width: u32, height: u32, data: &[u8]) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(data))
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Renders closures and compares them against reference PNGs.
+///
+/// References live in `dir` as `<name>.png`. On a mismatch, the rendered
+/// image and a diff image are written next to the reference as
+/// `<name>.actual.png` and `<name>.diff.png`. Missing references are
+/// created, and with `VGER_UPDATE_GOLDEN` set all references are
+/// rewritten.
+pub struct GoldenTest {
+    pub dir: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub device_px_ratio: f32,
+
+    /// Per-pixel perceptual threshold, from 0 to 1.
+    pub threshold: f32,
+
+    /// Number of pixels allowed to exceed the threshold, to absorb
+    /// antialiasing differences between GPUs.
+    pub max_bad_pixels: usize,
+}
+
+impl GoldenTest {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            width: 512,
+            height: 512,
+            device_px_ratio: 1.0,
+            threshold: 0.1,
+            max_bad_pixels: 0,
+        }
+    }
+
+    /// Renders `draw` and compares it with the reference called `name`.
+    pub fn check(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        name: &str,
+        draw: impl FnOnce(&mut Vger),
+    ) -> Result<(), String> {
+        let actual = render_rgba(
+            device,
+            queue,
+            self.width,
+            self.height,
+            self.device_px_ratio,
+            draw,
+        );
+
+        let reference = self.dir.join(format!("{}.png", name));
+        if std::env::var_os("VGER_UPDATE_GOLDEN").is_some() || !reference.exists() {
+            std::fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+            return write_png(&reference, self.width, self.height, &actual);
+        }
+
+        let (width, height, expected) = read_png(&reference)?;
+        if (width, height) != (self.width, self.height) {
+            return Err(format!(
+                "{}: reference is {}x{}, rendered {}x{}",
+                name, width, height, self.width, self.height
+            ));
+        }
+
+        let diff = compare(&expected, &actual, self.threshold);
+        if diff.bad_pixels > self.max_bad_pixels {
+            let actual_path = self.dir.join(format!("{}.actual.png", name));
+            let diff_path = self.dir.join(format!("{}.diff.png", name));
+            write_png(&actual_path, width, height, &actual)?;
+            write_png(&diff_path, width, height, &diff.image)?;
+            return Err(format!(
+                "{}: {} pixels differ (max delta {:.3}), see {}",
+                name,
+                diff.bad_pixels,
+                diff.max_delta,
+                diff_path.display()
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let a = vec![0, 0, 0, 255, 255, 255, 255, 255];
+        let mut b = a.clone();
+        assert_eq!(compare(&a, &b, 0.1).bad_pixels, 0);
+
+        b[0] = 3;
+        let diff = compare(&a, &b, 0.1);
+        assert_eq!(diff.bad_pixels, 0);
+        assert!(diff.max_delta > 0.0);
+
+        b[4] = 0;
+        let diff = compare(&a, &b, 0.1);
+        assert_eq!(diff.bad_pixels, 1);
+        assert_eq!(&diff.image[4..8], &[255, 0, 0, 255]);
+
+        assert!(color_delta(&[0, 0, 0, 255], &[255, 255, 255, 255]) > 0.9);
+    }
+}
@@ -0,0 +1,255 @@
+//! Reuse of intermediate textures, e.g. for rendering cached layers.
+
+/// Identifies a texture acquired from a `TexturePool` until the next
+/// `recycle`, or while it's kept.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PoolTexture(usize);
+
+struct Entry {
+    texture: wgpu::Texture,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+    bytes: usize,
+    in_use: bool,
+    last_used: u64,
+    /// Key the texture is kept under, if its contents are reused.
+    kept: Option<u64>,
+}
+
+/// Render targets shared by everything which needs a temporary texture.
+///
+/// Sizes are rounded up to buckets so textures can be reused as content
+/// changes size. Textures acquired during a frame are returned to the pool
+/// by `recycle`, and those left unused are dropped, least recently used
+/// first, to keep the pool within its budget. Textures can also be kept
+/// under a key so their contents are reused over frames, until they go
+/// unused for a while or the pool needs the room.
+pub struct TexturePool {
+    entries: Vec<Option<Entry>>,
+    frame: u64,
+    budget: usize,
+}
+
+/// Default budget, enough for a couple of 4K targets.
+pub const DEFAULT_BUDGET: usize = 128 << 20;
+
+/// Bucket size, in pixels.
+const BUCKET: u32 = 256;
+
+/// Frames a free texture is kept for, even within the budget.
+const MAX_IDLE_FRAMES: u64 = 60;
+
+impl TexturePool {
+    /// Creates a pool keeping at most `budget` bytes of textures which
+    /// aren't in use.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            entries: vec![],
+            frame: 0,
+            budget,
+        }
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+    }
+
+    /// A texture at least `width` by `height` which nothing else uses until
+    /// the next `recycle`.
+    pub fn acquire(
+        &mut self,
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+    ) -> PoolTexture {
+        let max = device.limits().max_texture_dimension_2d;
+        let width = bucket(width).min(max).max(width);
+        let height = bucket(height).min(max).max(height);
+
+        let free = self.entries.iter().position(|entry| match entry {
+            Some(e) => {
+                !e.in_use
+                    && e.kept.is_none()
+                    && (e.width, e.height) == (width, height)
+                    && e.format == format
+                    && e.usage.contains(usage)
+            }
+            None => false,
+        });
+
+        let index = match free {
+            Some(index) => index,
+            None => {
+                let texture = device.create_texture(&wgpu::TextureDescriptor {
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage,
+                    label: Some("pooled texture"),
+                    view_formats: &view_formats(format),
+                });
+                let bytes = (width * height) as usize * format.describe().block_size as usize;
+                let entry = Some(Entry {
+                    texture,
+                    width,
+                    height,
+                    format,
+                    usage,
+                    bytes,
+                    in_use: false,
+                    last_used: 0,
+                    kept: None,
+                });
+                match self.entries.iter().position(|e| e.is_none()) {
+                    Some(index) => {
+                        self.entries[index] = entry;
+                        index
+                    }
+                    None => {
+                        self.entries.push(entry);
+                        self.entries.len() - 1
+                    }
+                }
+            }
+        };
+
+        let entry = self.entries[index].as_mut().unwrap();
+        entry.in_use = true;
+        entry.last_used = self.frame;
+        PoolTexture(index)
+    }
+
+    /// The texture for a handle from `acquire`.
+    pub fn texture(&self, handle: PoolTexture) -> &wgpu::Texture {
+        &self.entries[handle.0].as_ref().unwrap().texture
+    }
+
+    /// Keeps an acquired texture's contents under `key`, replacing any
+    /// texture kept under it before, so it isn't handed out again.
+    pub fn keep(&mut self, handle: PoolTexture, key: u64) {
+        self.release(key);
+        self.entries[handle.0].as_mut().unwrap().kept = Some(key);
+    }
+
+    /// The texture kept under `key`, if it hasn't been dropped, marked as
+    /// used this frame.
+    pub fn kept(&mut self, key: u64) -> Option<PoolTexture> {
+        let frame = self.frame;
+        let index = self
+            .entries
+            .iter()
+            .position(|e| e.as_ref().map_or(false, |e| e.kept == Some(key)))?;
+        let entry = self.entries[index].as_mut().unwrap();
+        entry.in_use = true;
+        entry.last_used = frame;
+        Some(PoolTexture(index))
+    }
+
+    /// Whether a texture is kept under `key`.
+    pub fn is_kept(&self, key: u64) -> bool {
+        self.entries.iter().flatten().any(|e| e.kept == Some(key))
+    }
+
+    /// Returns the texture kept under `key` to the pool.
+    pub fn release(&mut self, key: u64) {
+        for entry in self.entries.iter_mut().flatten() {
+            if entry.kept == Some(key) {
+                entry.kept = None;
+            }
+        }
+    }
+
+    /// Returns every acquired texture to the pool, drops those idle for a
+    /// while, kept or not, then drops the least recently used while over
+    /// budget. Call once per frame, after the frame's commands are
+    /// submitted.
+    pub fn recycle(&mut self) {
+        self.frame += 1;
+        for entry in self.entries.iter_mut().flatten() {
+            entry.in_use = false;
+        }
+
+        let frame = self.frame;
+        for entry in &mut self.entries {
+            if entry
+                .as_ref()
+                .map_or(false, |e| frame - e.last_used > MAX_IDLE_FRAMES)
+            {
+                *entry = None;
+            }
+        }
+
+        while self.memory_usage() > self.budget {
+            let oldest = self
+                .entries
+                .iter()
+                .enumerate()
+                .filter_map(|(i, e)| e.as_ref().map(|e| (i, e.last_used)))
+                .min_by_key(|(_, last_used)| *last_used);
+            match oldest {
+                Some((index, _)) => self.entries[index] = None,
+                None => break,
+            }
+        }
+    }
+
+    /// Drops all textures. Handles from `acquire` become invalid.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Number of textures held.
+    pub fn len(&self) -> usize {
+        self.entries.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes held by the pool's textures.
+    pub fn memory_usage(&self) -> usize {
+        self.entries.iter().flatten().map(|e| e.bytes).sum()
+    }
+}
+
+/// Formats a pooled texture can be viewed as. Color textures can also be
+/// viewed as sRGB, like the color atlas.
+fn view_formats(format: wgpu::TextureFormat) -> Vec<wgpu::TextureFormat> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm => vec![format, wgpu::TextureFormat::Rgba8UnormSrgb],
+        wgpu::TextureFormat::Bgra8Unorm => vec![format, wgpu::TextureFormat::Bgra8UnormSrgb],
+        _ => vec![format],
+    }
+}
+
+/// Rounds a dimension up to a multiple of `BUCKET`, so nearby sizes
+/// share textures.
+fn bucket(size: u32) -> u32 {
+    let n = (size + BUCKET - 1) / BUCKET;
+    n.max(1) * BUCKET
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_bucket() {
+        assert_eq!(bucket(0), 256);
+        assert_eq!(bucket(256), 256);
+        assert_eq!(bucket(257), 512);
+        assert_eq!(bucket(2160), 2304);
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use crate::color::Color;
+use crate::defs::*;
+use crate::paint::Paint;
+
+/// Named paints. Paints created with `Vger::paint_var` are looked up
+/// here each time the scene is encoded, so switching themes doesn't
+/// require re-recording the scene.
+#[derive(Clone, Default)]
+pub struct Theme {
+    vars: HashMap<String, Paint>,
+}
+
+impl Theme {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets a variable to a solid color.
+    pub fn set_color(&mut self, name: &str, color: Color) {
+        self.vars.insert(name.into(), Paint::solid_color(color));
+    }
+
+    /// Sets a variable to a linear gradient.
+    pub fn set_linear_gradient<Pt: Into<LocalPoint>>(
+        &mut self,
+        name: &str,
+        start: Pt,
+        end: Pt,
+        inner_color: Color,
+        outer_color: Color,
+    ) {
+        self.vars.insert(
+            name.into(),
+            Paint::linear_gradient(start.into(), end.into(), inner_color, outer_color, 0.0),
+        );
+    }
+
+    /// Removes a variable.
+    pub fn remove(&mut self, name: &str) {
+        self.vars.remove(name);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.vars.contains_key(name)
+    }
+
+    /// Looks up a variable. Unknown variables are transparent.
+    pub(crate) fn resolve(&self, name: &str) -> Paint {
+        match self.vars.get(name) {
+            Some(paint) => *paint,
+            None => Paint::solid_color(Color::new(0.0, 0.0, 0.0, 0.0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let mut theme = Theme::new();
+        theme.set_color("accent", Color::CYAN);
+
+        let p = WorldPoint::new(0.0, 0.0);
+        assert_eq!(theme.resolve("accent").apply(p), Color::CYAN);
+        assert_eq!(theme.resolve("missing").apply(p).a, 0.0);
+
+        theme.remove("accent");
+        assert!(!theme.contains("accent"));
+    }
+}
@@ -0,0 +1,276 @@
+//! Slippy-map style raster tile rendering.
+
+use std::collections::HashMap;
+
+use crate::camera::Camera64;
+use crate::chart::snap;
+use crate::defs::*;
+use crate::hash::ImageKey;
+use crate::Vger;
+
+/// Tile address: zoom level and column/row at that level.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TileKey {
+    pub z: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TileKey {
+    pub fn new(z: u32, x: u32, y: u32) -> Self {
+        Self { z, x, y }
+    }
+
+    /// Tile at the previous zoom level which covers this one.
+    pub fn parent(&self) -> Option<TileKey> {
+        if self.z == 0 {
+            None
+        } else {
+            Some(TileKey::new(self.z - 1, self.x / 2, self.y / 2))
+        }
+    }
+
+    fn image_key(&self) -> ImageKey {
+        let mut bytes = b"tile".to_vec();
+        for v in [self.z, self.x, self.y].iter() {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        ImageKey::from_hash(&bytes)
+    }
+}
+
+struct Tile {
+    data: Vec<u8>,
+    last_used: u64,
+
+    /// Time the tile was first drawn, for fading in.
+    shown_at: Option<f64>,
+}
+
+/// Draws raster tiles keyed by (z, x, y).
+///
+/// World coordinates are in pixels at zoom level 0, where the whole map is
+/// one tile. A `Camera64` with zoom 2^z shows level z at its native size.
+/// Tiles fade in over their nearest loaded ancestor, and the least recently
+/// drawn tiles are evicted once more than `capacity` are registered.
+pub struct TileRenderer {
+    /// Width and height of each tile in pixels.
+    pub tile_size: u32,
+
+    /// Maximum number of tiles kept.
+    pub capacity: usize,
+
+    /// Seconds over which a newly shown tile fades in.
+    pub fade_duration: f64,
+
+    /// Highest zoom level tiles are requested at.
+    pub max_zoom: u32,
+
+    tiles: HashMap<TileKey, Tile>,
+    frame: u64,
+}
+
+impl TileRenderer {
+    pub fn new(tile_size: u32, capacity: usize) -> Self {
+        Self {
+            tile_size,
+            capacity,
+            fade_duration: 0.25,
+            max_zoom: 19,
+            tiles: HashMap::new(),
+            frame: 0,
+        }
+    }
+
+    /// Registers a tile's RGBA pixels, `tile_size` square.
+    pub fn insert(&mut self, key: TileKey, rgba: Vec<u8>) {
+        self.tiles.insert(
+            key,
+            Tile {
+                data: rgba,
+                last_used: self.frame,
+                shown_at: None,
+            },
+        );
+    }
+
+    pub fn contains(&self, key: &TileKey) -> bool {
+        self.tiles.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Zoom level whose tiles best match the camera.
+    pub fn zoom_level(&self, camera: &Camera64) -> u32 {
+        (camera.zoom.log2().round().max(0.0) as u32).min(self.max_zoom)
+    }
+
+    /// Tiles at the camera's zoom level overlapping a viewport of the given
+    /// size in local units.
+    pub fn visible_tiles(&self, camera: &Camera64, size: LocalSize) -> Vec<TileKey> {
+        let z = self.zoom_level(camera);
+        let n = 1u64 << z;
+        let world_tile = self.tile_size as f64 / n as f64;
+
+        let min = camera.to_world(LocalPoint::zero());
+        let max = camera.to_world(LocalPoint::new(size.width, size.height));
+
+        let range = |lo: f64, hi: f64| {
+            let a = (lo / world_tile).floor().max(0.0) as u64;
+            let b = ((hi / world_tile).ceil().max(0.0) as u64).min(n);
+            a..b
+        };
+
+        let mut keys = vec![];
+        for y in range(min.y, max.y) {
+            for x in range(min.x, max.x) {
+                keys.push(TileKey::new(z, x as u32, y as u32));
+            }
+        }
+        keys
+    }
+
+    /// Draws the visible tiles. `time` is in seconds and drives fading, so
+    /// keep drawing every frame while tiles are fading in.
+    pub fn draw(&mut self, vger: &mut Vger, camera: &Camera64, size: LocalSize, time: f64) {
+        self.frame += 1;
+
+        // Ancestors are drawn first, under tiles which are missing or
+        // still fading in.
+        let mut fallbacks = vec![];
+        let mut draws = vec![];
+        for key in self.visible_tiles(camera, size) {
+            let alpha = match self.tiles.get_mut(&key) {
+                Some(tile) => {
+                    let shown_at = *tile.shown_at.get_or_insert(time);
+                    if self.fade_duration > 0.0 {
+                        ((time - shown_at) / self.fade_duration).clamp(0.0, 1.0) as f32
+                    } else {
+                        1.0
+                    }
+                }
+                None => 0.0,
+            };
+
+            if alpha < 1.0 {
+                let mut parent = key.parent();
+                while let Some(p) = parent {
+                    if self.tiles.contains_key(&p) {
+                        if !fallbacks.contains(&p) {
+                            fallbacks.push(p);
+                        }
+                        break;
+                    }
+                    parent = p.parent();
+                }
+            }
+
+            if alpha > 0.0 {
+                draws.push((key, alpha));
+            }
+        }
+
+        fallbacks.sort_by_key(|k| k.z);
+        let draws = fallbacks
+            .into_iter()
+            .map(|k| (k, 1.0))
+            .chain(draws.into_iter());
+
+        let scale = vger.device_px_ratio();
+        for (key, alpha) in draws {
+            let tile = self.tiles.get_mut(&key).unwrap();
+            tile.last_used = self.frame;
+
+            // Snap both edges so adjacent tiles share them exactly.
+            let world_tile = self.tile_size as f64 / (1u64 << key.z) as f64;
+            let min = camera.relative(WorldPoint64::new(
+                key.x as f64 * world_tile,
+                key.y as f64 * world_tile,
+            ));
+            let max = camera.relative(WorldPoint64::new(
+                (key.x + 1) as f64 * world_tile,
+                (key.y + 1) as f64 * world_tile,
+            ));
+            let min = LocalPoint::new(snap(min.x, scale), snap(min.y, scale));
+            let max = LocalPoint::new(snap(max.x, scale), snap(max.y, scale));
+
+            let data = &tile.data;
+            vger.render_image_rect(
+                LocalRect::new(min, (max - min).to_size()),
+                &key.image_key(),
+                self.tile_size,
+                self.tile_size,
+                || data.clone(),
+                alpha,
+            );
+        }
+
+        for key in self.evict() {
+            vger.evict_image(&key.image_key());
+        }
+    }
+
+    /// Removes the least recently drawn tiles beyond capacity.
+    fn evict(&mut self) -> Vec<TileKey> {
+        if self.tiles.len() <= self.capacity {
+            return vec![];
+        }
+
+        let mut by_age: Vec<(u64, TileKey)> =
+            self.tiles.iter().map(|(k, t)| (t.last_used, *k)).collect();
+        // Ties go by key so eviction doesn't depend on hashing.
+        by_age.sort_by_key(|(last_used, key)| (*last_used, key.z, key.x, key.y));
+
+        let count = self.tiles.len() - self.capacity;
+        let evicted: Vec<TileKey> = by_age.iter().take(count).map(|(_, k)| *k).collect();
+        for key in &evicted {
+            self.tiles.remove(key);
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parent() {
+        assert_eq!(TileKey::new(3, 5, 2).parent(), Some(TileKey::new(2, 2, 1)));
+        assert_eq!(TileKey::new(0, 0, 0).parent(), None);
+    }
+
+    #[test]
+    fn test_visible_tiles() {
+        let tiles = TileRenderer::new(256, 16);
+
+        // Level 1 at native size: the map is 512 pixels across.
+        let camera = Camera64::new(WorldPoint64::new(64.0, 0.0), 2.0);
+        let visible = tiles.visible_tiles(&camera, LocalSize::new(300.0, 100.0));
+        assert_eq!(visible, vec![TileKey::new(1, 0, 0), TileKey::new(1, 1, 0)]);
+
+        let camera = Camera64::new(WorldPoint64::new(0.0, 0.0), 1.0);
+        let visible = tiles.visible_tiles(&camera, LocalSize::new(1000.0, 1000.0));
+        assert_eq!(visible, vec![TileKey::new(0, 0, 0)]);
+    }
+
+    #[test]
+    fn test_evict() {
+        let mut tiles = TileRenderer::new(1, 2);
+        tiles.insert(TileKey::new(0, 0, 0), vec![0; 4]);
+        tiles.frame += 1;
+        tiles.insert(TileKey::new(1, 0, 0), vec![0; 4]);
+        tiles.insert(TileKey::new(1, 1, 0), vec![0; 4]);
+
+        assert_eq!(tiles.evict(), vec![TileKey::new(0, 0, 0)]);
+        assert_eq!(tiles.len(), 2);
+        assert!(tiles.evict().is_empty());
+    }
+}
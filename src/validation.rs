@@ -0,0 +1,170 @@
+use std::fmt;
+
+use crate::prim::{Prim, PrimType};
+
+/// Problem found by validation mode. See `Vger::set_validation`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationError {
+    /// A draw call had a NaN or infinite coordinate or size.
+    NonFinite { prim: &'static str },
+
+    /// A draw call had a negative width, radius or rectangle size.
+    NegativeSize { prim: &'static str },
+
+    /// A corner radius was larger than half the rectangle.
+    RadiusOutOfRange { prim: &'static str, radius: f32 },
+
+    /// A paint had a NaN or infinite color or transform.
+    NonFinitePaint,
+
+    /// `restore` was called without a matching `save`.
+    UnmatchedRestore,
+
+    /// The frame was encoded with `depth` saves not restored.
+    UnmatchedSave { depth: usize },
+
+    /// The glyph cache was cleared while the frame was drawn, e.g. by a
+    /// renderer sharing it, so its glyphs, images and layers may be
+    /// wrong. Its layers aren't rendered.
+    StaleAtlas,
+
+    /// Something was drawn additively in a cached layer or with indirect
+    /// draws, which only blend normally.
+    BlendModeIgnored,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::NonFinite { prim } => write!(f, "{}: non-finite value", prim),
+            ValidationError::NegativeSize { prim } => write!(f, "{}: negative size", prim),
+            ValidationError::RadiusOutOfRange { prim, radius } => {
+                write!(f, "{}: radius {} out of range", prim, radius)
+            }
+            ValidationError::NonFinitePaint => write!(f, "paint: non-finite value"),
+            ValidationError::UnmatchedRestore => write!(f, "restore without save"),
+            ValidationError::UnmatchedSave { depth } => {
+                write!(f, "{} saves not restored", depth)
+            }
+            ValidationError::StaleAtlas => write!(f, "glyph cache cleared during the frame"),
+            ValidationError::BlendModeIgnored => {
+                write!(f, "blend mode ignored in cached layer or indirect draw")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+fn prim_name(prim_type: u32) -> &'static str {
+    match PrimType::from_u32(prim_type) {
+        Some(PrimType::Circle) => "circle",
+        Some(PrimType::Arc) => "arc",
+        Some(PrimType::Rect) => "rect",
+        Some(PrimType::RectStroke) => "rect stroke",
+        Some(PrimType::Bezier) => "bezier",
+        Some(PrimType::Segment) => "segment",
+        Some(PrimType::Curve) => "curve",
+        Some(PrimType::Wire) => "wire",
+        Some(PrimType::Glyph) => "glyph",
+        Some(PrimType::ColorGlyph) => "color glyph",
+        Some(PrimType::PathFill) => "path fill",
+        Some(PrimType::OverrideColorSvg) => "svg",
+        Some(PrimType::TaperedSegment) => "tapered segment",
+        Some(PrimType::Grid) => "grid",
+        Some(PrimType::Layer) => "layer",
+        Some(PrimType::GlyphShadow) => "glyph shadow",
+        Some(PrimType::RectBorder) => "rect border",
+        Some(PrimType::RoundArc) => "round arc",
+        None => "prim",
+    }
+}
+
+/// Relative slack in the corner radius check, since radii of exactly half
+/// the rect, e.g. `Morph` circles, round to slightly more than half the
+/// size computed from the rect's corners.
+const RADIUS_TOLERANCE: f32 = 1e-3;
+
+/// Checks a prim before it's added to the scene.
+pub(crate) fn validate_prim(prim: &Prim) -> Result<(), ValidationError> {
+    let name = prim_name(prim.prim_type);
+
+    let sizes = [prim.width, prim.radius];
+    let mut values = prim
+        .cvs
+        .iter()
+        .chain(prim.quad_bounds.iter())
+        .chain(sizes.iter());
+    if values.any(|v| !v.is_finite()) {
+        return Err(ValidationError::NonFinite { prim: name });
+    }
+
+    if prim.width < 0.0 || prim.radius < 0.0 {
+        return Err(ValidationError::NegativeSize { prim: name });
+    }
+
+    let rect = PrimType::Rect as u32;
+    let rect_stroke = PrimType::RectStroke as u32;
+    if prim.prim_type == rect || prim.prim_type == rect_stroke {
+        let size = [prim.cvs[2] - prim.cvs[0], prim.cvs[3] - prim.cvs[1]];
+        if size[0] < 0.0 || size[1] < 0.0 {
+            return Err(ValidationError::NegativeSize { prim: name });
+        }
+        if prim.radius > 0.5 * size[0].min(size[1]) * (1.0 + RADIUS_TOLERANCE) {
+            return Err(ValidationError::RadiusOutOfRange {
+                prim: name,
+                radius: prim.radius,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_validate_prim() {
+        let mut prim = Prim::default();
+        prim.prim_type = PrimType::Rect as u32;
+        prim.cvs = [0.0, 0.0, 10.0, 20.0, 0.0, 0.0];
+        prim.radius = 5.0;
+        assert_eq!(validate_prim(&prim), Ok(()));
+
+        prim.radius = 6.0;
+        assert_eq!(
+            validate_prim(&prim),
+            Err(ValidationError::RadiusOutOfRange {
+                prim: "rect",
+                radius: 6.0
+            })
+        );
+
+        // A circle's radius is half its size, give or take rounding.
+        let (center, radius) = (3.3f32, 0.3f32);
+        let min = center - radius;
+        let max = min + 2.0 * radius;
+        prim.cvs = [min, min, max, max, 0.0, 0.0];
+        prim.radius = radius;
+        assert!(0.5 * (max - min) < radius);
+        assert_eq!(validate_prim(&prim), Ok(()));
+
+        prim.radius = 0.0;
+        prim.cvs[0] = 0.0;
+        prim.cvs[2] = -1.0;
+        assert_eq!(
+            validate_prim(&prim),
+            Err(ValidationError::NegativeSize { prim: "rect" })
+        );
+
+        prim.prim_type = PrimType::Circle as u32;
+        prim.cvs[0] = f32::NAN;
+        assert_eq!(
+            validate_prim(&prim),
+            Err(ValidationError::NonFinite { prim: "circle" })
+        );
+    }
+}
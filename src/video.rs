@@ -0,0 +1,266 @@
+//! Drawing video frames decoded to YUV planes.
+
+use wgpu::util::DeviceExt;
+
+/// How luma and chroma map to RGB.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum YuvMatrix {
+    /// Standard definition video.
+    Bt601,
+
+    /// HD video.
+    Bt709,
+}
+
+/// Planes of a decoded frame, as textures the host has uploaded to.
+pub enum VideoPlanes {
+    /// Luma in an `R8Unorm` texture and interleaved chroma in an
+    /// `Rg8Unorm` texture of half the size.
+    Nv12 {
+        y: wgpu::TextureView,
+        uv: wgpu::TextureView,
+    },
+
+    /// Luma and each chroma channel in separate `R8Unorm` textures.
+    I420 {
+        y: wgpu::TextureView,
+        u: wgpu::TextureView,
+        v: wgpu::TextureView,
+    },
+}
+
+/// A video frame for `Vger::render_video`.
+pub struct VideoFrame {
+    pub planes: VideoPlanes,
+
+    /// Size of the luma plane in pixels.
+    pub width: u32,
+    pub height: u32,
+
+    pub matrix: YuvMatrix,
+
+    /// Whether values use the full 0-255 range rather than the 16-235
+    /// of broadcast video.
+    pub full_range: bool,
+}
+
+/// Uniforms for `video.wgsl`: conversion coefficients, range, then flags.
+fn video_params(matrix: YuvMatrix, full_range: bool, nv12: bool) -> [f32; 12] {
+    let c = match matrix {
+        YuvMatrix::Bt601 => [1.402, 0.344136, 0.714136, 1.772],
+        YuvMatrix::Bt709 => [1.5748, 0.187324, 0.468124, 1.8556],
+    };
+    let r = if full_range {
+        [0.0, 1.0, 0.5, 1.0]
+    } else {
+        [16.0 / 255.0, 255.0 / 219.0, 128.0 / 255.0, 255.0 / 224.0]
+    };
+    let nv12 = if nv12 { 1.0 } else { 0.0 };
+
+    [
+        c[0], c[1], c[2], c[3], r[0], r[1], r[2], r[3], nv12, 0.0, 0.0, 0.0,
+    ]
+}
+
+/// Converts frames to RGBA on the gpu.
+pub(crate) struct VideoConverter {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+impl VideoConverter {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("video"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+                "video.wgsl"
+            ))),
+        });
+
+        let plane = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                plane(0),
+                plane(1),
+                plane(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("video_bind_group_layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("video"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("video"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_video",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_video",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("video"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+        }
+    }
+
+    /// Converts `frame` into the top left of `target`.
+    pub fn convert(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        frame: &VideoFrame,
+        target: &wgpu::TextureView,
+    ) {
+        let nv12 = matches!(frame.planes, VideoPlanes::Nv12 { .. });
+        let params = video_params(frame.matrix, frame.full_range, nv12);
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("video params"),
+            contents: unsafe { std::slice::from_raw_parts(params.as_ptr() as *const u8, 48) },
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let (y, u, v) = match &frame.planes {
+            VideoPlanes::Nv12 { y, uv } => (y, uv, uv),
+            VideoPlanes::I420 { y, u, v } => (y, u, v),
+        };
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(y),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(u),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(v),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+            label: Some("video bind group"),
+        });
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("video"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_viewport(0.0, 0.0, frame.width as f32, frame.height as f32, 0.0, 1.0);
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    // The coefficients reproduce the standard white and primaries.
+    fn to_rgb(params: &[f32; 12], y: f32, cb: f32, cr: f32) -> [f32; 3] {
+        let y = (y - params[4]) * params[5];
+        let (cb, cr) = ((cb - params[6]) * params[7], (cr - params[6]) * params[7]);
+        [
+            y + params[0] * cr,
+            y - params[1] * cb - params[2] * cr,
+            y + params[3] * cb,
+        ]
+    }
+
+    #[test]
+    fn test_conversion() {
+        let white = to_rgb(
+            &video_params(YuvMatrix::Bt709, false, true),
+            235.0 / 255.0,
+            0.5 + 0.5 / 255.0,
+            0.5 + 0.5 / 255.0,
+        );
+        for c in white {
+            assert!((c - 1.0).abs() < 0.01);
+        }
+
+        // BT.601 full range red.
+        let red = to_rgb(
+            &video_params(YuvMatrix::Bt601, true, false),
+            0.299,
+            0.5 - 0.168736,
+            1.0,
+        );
+        assert!((red[0] - 1.0).abs() < 0.01);
+        assert!(red[1].abs() < 0.01);
+        assert!(red[2].abs() < 0.01);
+    }
+}
@@ -0,0 +1,70 @@
+struct VideoParams {
+    /// Cr to red, Cb to green, Cr to green and Cb to blue.
+    coeffs: vec4<f32>,
+
+    /// Luma offset and scale, then chroma offset and scale.
+    range: vec4<f32>,
+
+    /// x is 1 when chroma is interleaved in one plane (NV12).
+    flags: vec4<f32>,
+};
+
+@group(0)
+@binding(0)
+var y_plane: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var u_plane: texture_2d<f32>;
+
+@group(0)
+@binding(2)
+var v_plane: texture_2d<f32>;
+
+@group(0)
+@binding(3)
+var plane_samp: sampler;
+
+@group(0)
+@binding(4)
+var<uniform> params: VideoParams;
+
+struct VideoOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// One triangle covering the target.
+@vertex
+fn vs_video(@builtin(vertex_index) vid: u32) -> VideoOutput {
+    var out: VideoOutput;
+    let uv = vec2<f32>(f32((vid << 1u) & 2u), f32(vid & 2u));
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// Output is opaque, so premultiplied as cached layers are.
+@fragment
+fn fs_video(in: VideoOutput) -> @location(0) vec4<f32> {
+    let luma = textureSample(y_plane, plane_samp, in.uv).r;
+    let u = textureSample(u_plane, plane_samp, in.uv);
+    let v = textureSample(v_plane, plane_samp, in.uv);
+
+    var chroma = vec2<f32>(u.r, v.r);
+    if (params.flags.x > 0.5) {
+        chroma = u.rg;
+    }
+
+    let y = (luma - params.range.x) * params.range.y;
+    let c = (chroma - params.range.z) * params.range.w;
+    let cb = c.x;
+    let cr = c.y;
+
+    let rgb = vec3<f32>(
+        y + params.coeffs.x * cr,
+        y - params.coeffs.y * cb - params.coeffs.z * cr,
+        y + params.coeffs.w * cb
+    );
+    return vec4<f32>(clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0);
+}
@@ -0,0 +1,144 @@
+//! Whitespace indicators drawn by `Vger::text_with_whitespace`, built from
+//! prims so the font doesn't need the glyphs.
+
+use crate::color::Color;
+use crate::defs::*;
+
+/// Which whitespace `Vger::text_with_whitespace` marks, and in what
+/// color.
+#[derive(Copy, Clone, Debug)]
+pub struct WhitespaceMarks {
+    pub color: Color,
+
+    /// Middle dots for spaces.
+    pub spaces: bool,
+
+    /// Arrows across tabs.
+    pub tabs: bool,
+
+    /// Pilcrows at line breaks.
+    pub line_ends: bool,
+}
+
+impl Default for WhitespaceMarks {
+    fn default() -> Self {
+        Self {
+            color: Color::new(0.5, 0.5, 0.5, 0.6),
+            spaces: true,
+            tabs: true,
+            line_ends: true,
+        }
+    }
+}
+
+/// Part of a whitespace indicator.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Mark {
+    Dot { center: LocalPoint, radius: f32 },
+    Line { a: LocalPoint, b: LocalPoint },
+}
+
+/// Marks for a laid out line of `glyphs`, each a character and its x
+/// position, with the baseline at `baseline`. Coordinates are local, with
+/// y up, and `size` is the font size.
+pub(crate) fn line_marks(
+    glyphs: &[(char, f32)],
+    baseline: f32,
+    size: f32,
+    marks: &WhitespaceMarks,
+) -> Vec<Mark> {
+    let mid = baseline + 0.3 * size;
+    let mut out = vec![];
+
+    for (i, (c, x)) in glyphs.iter().enumerate() {
+        let x = *x;
+        let end = glyphs.get(i + 1).map_or(x + 0.3 * size, |next| next.1);
+        match c {
+            ' ' if marks.spaces => out.push(Mark::Dot {
+                center: LocalPoint::new((x + end) / 2.0, mid),
+                radius: 0.06 * size,
+            }),
+            '\t' if marks.tabs => {
+                let a = LocalPoint::new(x + 0.1 * size, mid);
+                let b = LocalPoint::new((end - 0.1 * size).max(x + 0.4 * size), mid);
+                let head = 0.15 * size;
+                out.push(Mark::Line { a, b });
+                out.push(Mark::Line {
+                    a: b + LocalVector::new(-head, head),
+                    b,
+                });
+                out.push(Mark::Line {
+                    a: b + LocalVector::new(-head, -head),
+                    b,
+                });
+            }
+            '\n' if marks.line_ends => {
+                let top = baseline + 0.7 * size;
+                let (left, right) = (x + 0.35 * size, x + 0.5 * size);
+                out.push(Mark::Dot {
+                    center: LocalPoint::new(x + 0.25 * size, baseline + 0.55 * size),
+                    radius: 0.15 * size,
+                });
+                out.push(Mark::Line {
+                    a: LocalPoint::new(left, baseline),
+                    b: LocalPoint::new(left, top),
+                });
+                out.push(Mark::Line {
+                    a: LocalPoint::new(right, baseline),
+                    b: LocalPoint::new(right, top),
+                });
+                out.push(Mark::Line {
+                    a: LocalPoint::new(x + 0.25 * size, top),
+                    b: LocalPoint::new(right, top),
+                });
+            }
+            _ => (),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_line_marks() {
+        let glyphs = [
+            ('a', 0.0),
+            (' ', 10.0),
+            ('\t', 14.0),
+            ('b', 40.0),
+            ('\n', 50.0),
+        ];
+        let marks = WhitespaceMarks::default();
+        let out = line_marks(&glyphs, 100.0, 20.0, &marks);
+
+        // A dot, an arrow and a pilcrow.
+        assert_eq!(out.len(), 1 + 3 + 4);
+        assert_eq!(
+            out[0],
+            Mark::Dot {
+                center: LocalPoint::new(12.0, 106.0),
+                radius: 0.06 * 20.0,
+            }
+        );
+        assert_eq!(
+            out[1],
+            Mark::Line {
+                a: LocalPoint::new(16.0, 106.0),
+                b: LocalPoint::new(38.0, 106.0),
+            }
+        );
+
+        let tabs_only = WhitespaceMarks {
+            spaces: false,
+            line_ends: false,
+            ..marks
+        };
+        assert_eq!(line_marks(&glyphs, 100.0, 20.0, &tabs_only).len(), 3);
+        assert!(line_marks(&[('a', 0.0)], 0.0, 20.0, &marks).is_empty());
+    }
+}
@@ -0,0 +1,169 @@
+//! Wrapping done before layout, for lines of different widths, such as
+//! indented first lines, and for hyphenation, enabled by the `hyphenate`
+//! feature. See `Vger::set_hyphenation`.
+
+/// Text with line breaks inserted where it wraps.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Wrapped {
+    pub text: String,
+
+    /// Indices of the inserted line breaks among the text's characters,
+    /// which don't end paragraphs.
+    pub soft_breaks: Vec<usize>,
+}
+
+/// Breaks lines of `text` like wrapping does, where `line_width` is the
+/// width of a line, given whether it starts a paragraph. Words that don't
+/// fit are split at the points `breaks` returns, byte indices into the
+/// word, ending the line with a hyphen. `advance` is the width of a
+/// character, given its byte index in `text`, or where a hyphen would be
+/// inserted. Returns `None` when no line is broken.
+pub(crate) fn wrap(
+    text: &str,
+    line_width: impl Fn(bool) -> f32,
+    advance: impl Fn(usize, char) -> f32,
+    breaks: impl Fn(&str) -> Vec<usize>,
+) -> Option<Wrapped> {
+    // Words are slices of `text`.
+    let offset = |s: &str| s.as_ptr() as usize - text.as_ptr() as usize;
+    let width = |s: &str| {
+        s.char_indices()
+            .map(|(i, c)| advance(offset(s) + i, c))
+            .sum::<f32>()
+    };
+
+    let mut out = String::with_capacity(text.len() + 16);
+    let mut count = 0;
+    let mut soft_breaks = vec![];
+    let push = |out: &mut String, count: &mut usize, s: &str| {
+        out.push_str(s);
+        *count += s.chars().count();
+    };
+
+    for (i, paragraph) in text.split('\n').enumerate() {
+        if i > 0 {
+            push(&mut out, &mut count, "\n");
+        }
+        let mut x = 0.0;
+        let mut max_width = line_width(true);
+        for (j, word) in paragraph.split(' ').enumerate() {
+            if j > 0 {
+                push(&mut out, &mut count, " ");
+                x += advance(offset(word) - 1, ' ');
+            }
+            let mut points = None;
+            let mut start = 0;
+            loop {
+                let rest = &word[start..];
+                let w = width(rest);
+                if x + w <= max_width || rest.is_empty() {
+                    push(&mut out, &mut count, rest);
+                    x += w;
+                    break;
+                }
+                let fit = points
+                    .get_or_insert_with(|| breaks(word))
+                    .iter()
+                    .copied()
+                    .filter(|b| *b > start && *b < word.len() && word.is_char_boundary(*b))
+                    .filter(|b| {
+                        let hyphen = advance(offset(word) + b, '-');
+                        x + width(&word[start..*b]) + hyphen <= max_width
+                    })
+                    .max();
+                match fit {
+                    Some(b) => {
+                        push(&mut out, &mut count, &word[start..b]);
+                        push(&mut out, &mut count, "-");
+                        start = b;
+                    }
+                    None if x > 0.0 => (),
+                    None => {
+                        // Too long even for a line of its own.
+                        push(&mut out, &mut count, rest);
+                        x += w;
+                        break;
+                    }
+                }
+                soft_breaks.push(count);
+                push(&mut out, &mut count, "\n");
+                x = 0.0;
+                max_width = line_width(false);
+            }
+        }
+    }
+
+    if soft_breaks.is_empty() {
+        None
+    } else {
+        Some(Wrapped {
+            text: out,
+            soft_breaks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn breaks(word: &str) -> Vec<usize> {
+        match word {
+            "hyphenation" => vec![2, 6],
+            _ => vec![],
+        }
+    }
+
+    fn width(w: f32) -> impl Fn(bool) -> f32 {
+        move |_| w
+    }
+
+    #[test]
+    fn test_hyphenate() {
+        // Unit width characters.
+        let advance = |_, _| 1.0;
+        assert_eq!(wrap("a hyphenation", width(20.0), advance, breaks), None);
+
+        let out = wrap("a hyphenation", width(10.0), advance, breaks).unwrap();
+        assert_eq!(out.text, "a hyphen-\nation");
+        assert_eq!(out.soft_breaks, [9]);
+
+        // Words which don't fit move to the next line first.
+        let out = wrap("abcdefg hyphenation", width(8.0), advance, breaks).unwrap();
+        assert_eq!(out.text, "abcdefg \nhyphen-\nation");
+        assert_eq!(out.soft_breaks, [8, 16]);
+
+        // Hard breaks stay.
+        let out = wrap("a\nhyphenation", width(5.0), advance, breaks).unwrap();
+        assert_eq!(out.text, "a\nhy-\nphen-\nation");
+        assert_eq!(out.soft_breaks, [5, 11]);
+    }
+
+    #[test]
+    fn test_indent() {
+        let advance = |_, _| 1.0;
+        let no_breaks = |_: &str| vec![];
+
+        // First lines indented by 5.
+        let indented = |first| if first { 7.0 } else { 12.0 };
+        let out = wrap("aa bb cc dd\nee ff", indented, advance, no_breaks).unwrap();
+        assert_eq!(out.text, "aa bb \ncc dd\nee ff");
+        assert_eq!(out.soft_breaks, [6]);
+
+        // Hanging indent.
+        let hanging = |first| if first { 10.0 } else { 8.0 };
+        let out = wrap("aa bb cc dd ee", hanging, advance, no_breaks).unwrap();
+        assert_eq!(out.text, "aa bb cc \ndd ee");
+        assert_eq!(wrap("aa bb", hanging, advance, no_breaks), None);
+    }
+
+    #[test]
+    fn test_wide_chars() {
+        // The character at byte 3 is 4 wide.
+        let advance = |i, _| if i == 3 { 4.0 } else { 1.0 };
+        let out = wrap("aa bb c", width(7.0), advance, |_| vec![]).unwrap();
+        assert_eq!(out.text, "aa \nbb c");
+        assert_eq!(out.soft_breaks, [3]);
+    }
+}
@@ -228,3 +228,32 @@ pub fn png_not_black(path: &str) -> bool {
 
     false
 }
+
+/// RGBA of the pixel at `x`, `y` in a PNG written by `render_test`.
+pub fn png_pixel(path: &str, x: u32, y: u32) -> [u8; 4] {
+    let decoder = png::Decoder::new(File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    let i = ((y * info.width + x) * 4) as usize;
+    [buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]
+}
+
+/// Red channel of a PNG written by `render_test`, row by row from the top.
+pub fn png_red_rows(path: &str) -> Vec<Vec<u8>> {
+    png_channel_rows(path, 0)
+}
+
+/// One channel, 0 to 3 for RGBA or 0 for grayscale, of a PNG written by
+/// `render_test` or `save_png`, row by row from the top.
+pub fn png_channel_rows(path: &str, channel: usize) -> Vec<Vec<u8>> {
+    let decoder = png::Decoder::new(File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    let samples = info.color_type.samples();
+    buf[..info.buffer_size()]
+        .chunks_exact(samples * info.width as usize)
+        .map(|row| row.iter().skip(channel).step_by(samples).copied().collect())
+        .collect()
+}
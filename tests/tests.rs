@@ -24,6 +24,47 @@ fn test_color_hex() {
     assert_eq!(c.r, 0.0);
 }
 
+#[test]
+fn test_color_css() {
+    assert_eq!(
+        Color::from_css("rebeccapurple").unwrap(),
+        Color::hex("#663399").unwrap()
+    );
+    assert_eq!(
+        Color::from_css("#abc").unwrap(),
+        Color::hex("#aabbcc").unwrap()
+    );
+    assert_eq!(
+        Color::from_css("#aabbccdd").unwrap(),
+        Color::hex("#aabbccdd").unwrap()
+    );
+    assert_eq!(
+        Color::from_css("rgb(255, 0, 0)").unwrap(),
+        Color::new(1.0, 0.0, 0.0, 1.0)
+    );
+    assert_eq!(
+        Color::from_css("rgba(255 0 0 / 50%)").unwrap(),
+        Color::new(1.0, 0.0, 0.0, 0.5)
+    );
+    assert_eq!(Color::from_css("transparent").unwrap().a, 0.0);
+    assert!(Color::from_css("not a color").is_err());
+    assert!(Color::from_css("rgb(1, 2)").is_err());
+
+    let c = Color::from_css("hsl(120, 100%, 50%)").unwrap();
+    assert_eq!(c, Color::hsl(120.0, 1.0, 0.5));
+    assert_eq!(c, Color::new(0.0, 1.0, 0.0, 1.0));
+
+    let c = Color::oklch(1.0, 0.0, 0.0);
+    assert!((c.r - 1.0).abs() < 0.001);
+    assert!((c.g - 1.0).abs() < 0.001);
+    assert!((c.b - 1.0).abs() < 0.001);
+
+    assert_eq!(
+        Color::gray(0.0).lerp(Color::gray(1.0), 0.5),
+        Color::gray(0.5)
+    );
+}
+
 #[test]
 fn fill_circle() {
     let (device, queue) = block_on(setup());
@@ -282,9 +323,10 @@ fn text_small() {
     assert!(png_not_black(png_name));
 
     let atlas_png_name = "text_small_atlas.png";
+    let glyph_cache = vger.glyph_cache();
     save_png(
-        &vger.glyph_cache.mask_atlas.atlas_texture,
-        &vger::atlas::Atlas::get_texture_desc(),
+        glyph_cache.mask_atlas.texture(),
+        &glyph_cache.mask_atlas.get_texture_desc(),
         &device,
         &queue,
         atlas_png_name,
@@ -307,9 +349,10 @@ fn text_scale() {
     assert!(png_not_black(png_name));
 
     let atlas_png_name = "text_scale_atlas.png";
+    let glyph_cache = vger.glyph_cache();
     save_png(
-        &vger.glyph_cache.mask_atlas.atlas_texture,
-        &vger::atlas::Atlas::get_texture_desc(),
+        glyph_cache.mask_atlas.texture(),
+        &glyph_cache.mask_atlas.get_texture_desc(),
         &device,
         &queue,
         atlas_png_name,
@@ -341,9 +384,10 @@ fn text_box() {
     assert!(png_not_black(png_name));
 
     let atlas_png_name = "text_box_atlas.png";
+    let glyph_cache = vger.glyph_cache();
     save_png(
-        &vger.glyph_cache.mask_atlas.atlas_texture,
-        &vger::atlas::Atlas::get_texture_desc(),
+        glyph_cache.mask_atlas.texture(),
+        &glyph_cache.mask_atlas.get_texture_desc(),
         &device,
         &queue,
         atlas_png_name,
@@ -392,3 +436,4317 @@ fn test_scissor_text() {
     render_test(&mut vger, &device, &queue, png_name, true);
     assert!(png_not_black(png_name));
 }
+
+#[test]
+fn theme_paint_var() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    let mut theme = Theme::new();
+    theme.set_color("accent", Color::CYAN);
+    vger.set_theme(theme);
+
+    vger.begin(512.0, 512.0, 1.0);
+    let accent = vger.paint_var("accent");
+    vger.fill_rect(euclid::rect(100.0, 100.0, 100.0, 100.0), 10.0, accent);
+
+    render_test(&mut vger, &device, &queue, "theme_cyan.png", false);
+    assert_eq!(png_pixel("theme_cyan.png", 150, 150), [0, 255, 255, 255]);
+
+    // Re-encode the same scene with a different theme.
+    vger.theme_mut().set_color("accent", Color::MAGENTA);
+    render_test(&mut vger, &device, &queue, "theme_magenta.png", false);
+    assert_eq!(png_pixel("theme_magenta.png", 150, 150), [255, 0, 255, 255]);
+
+    // Unknown variables are transparent.
+    vger.theme_mut().remove("accent");
+    render_test(&mut vger, &device, &queue, "theme_removed.png", false);
+    assert_eq!(png_pixel("theme_removed.png", 150, 150), [0, 0, 0, 255]);
+}
+
+#[test]
+fn polyline_gradient() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let points = [
+        [100.0, 100.0],
+        [200.0, 300.0],
+        [300.0, 150.0],
+        [400.0, 400.0],
+    ];
+    let stops = [
+        (0.0, Color::CYAN),
+        (0.5, Color::WHITE),
+        (1.0, Color::MAGENTA),
+    ];
+    vger.stroke_polyline_gradient(&points, 8.0, &stops);
+
+    let png_name = "polyline_gradient.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Cyan at the start, white halfway along and magenta at the end.
+    let start = png_pixel(png_name, 102, 104);
+    assert!(start[0] < 100);
+    assert_eq!(&start[1..], &[255, 255, 255]);
+    let middle = png_pixel(png_name, 262, 205);
+    assert!(middle.iter().all(|c| *c > 230));
+    let end = png_pixel(png_name, 398, 395);
+    assert!(end[1] < 100);
+    assert_eq!([end[0], end[2]], [255, 255]);
+    assert_eq!(png_pixel(png_name, 100, 300), [0, 0, 0, 255]);
+}
+
+#[test]
+fn polyline_var() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+    let cyan = vger.color_paint(Color::CYAN);
+
+    vger.stroke_polyline_var(
+        &[
+            ([100.0, 100.0], 2.0),
+            ([200.0, 300.0], 20.0),
+            ([300.0, 150.0], 10.0),
+        ],
+        cyan,
+    );
+
+    let points = [[100.0, 400.0], [250.0, 350.0], [400.0, 400.0]];
+    vger.stroke_polyline_profile(
+        &points,
+        |t| 1.0 + 16.0 * (t * std::f32::consts::PI).sin(),
+        cyan,
+    );
+
+    let png_name = "polyline_var.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // 4 pixels off the first segment is outside it near its thin start,
+    // and inside near its wide end.
+    let cyan = [0, 255, 255, 255];
+    assert_eq!(png_pixel(png_name, 113, 117), [0, 0, 0, 255]);
+    assert_eq!(png_pixel(png_name, 193, 277), cyan);
+
+    // The profile is widest halfway along and thin at the ends.
+    assert_eq!(png_pixel(png_name, 250, 356), cyan);
+    assert_eq!(png_pixel(png_name, 100, 406), [0, 0, 0, 255]);
+}
+
+#[test]
+fn markers() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+    let cyan = vger.color_paint(Color::CYAN);
+
+    let points = [[100.0, 100.0], [300.0, 100.0], [400.0, 400.0]];
+    vger.stroke_polyline(&points, 2.0, cyan);
+    vger.draw_markers_along(
+        &points,
+        &markers::MarkerPlacement::End,
+        &markers::MarkerShape::Arrow {
+            length: 20.0,
+            width: 12.0,
+        },
+        cyan,
+    );
+    vger.draw_markers_along(
+        &points,
+        &markers::MarkerPlacement::Spacing(40.0),
+        &markers::MarkerShape::Dot { radius: 3.0 },
+        cyan,
+    );
+
+    // Leaves its start heading down, so a start arrow points down and
+    // its base sits above the curve.
+    let start = [100.0, 300.0];
+    let quads = [([100.0, 450.0], [300.0, 450.0])];
+    vger.stroke_bezier(start, quads[0].0, quads[0].1, 2.0, cyan);
+    vger.draw_markers_along_curve(
+        start,
+        &quads,
+        &markers::MarkerPlacement::Start,
+        &markers::MarkerShape::Arrow {
+            length: 20.0,
+            width: 12.0,
+        },
+        cyan,
+    );
+
+    let png_name = "markers.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert_eq!(png_pixel(png_name, 100, 290), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 90, 290), [0, 0, 0, 255]);
+}
+
+#[test]
+fn hatch_fill() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let hatch = vger.hatch_paint(
+        std::f32::consts::PI / 4.0,
+        10.0,
+        2.0,
+        Color::CYAN,
+        Color::new(0.0, 0.0, 0.0, 0.0),
+        PatternSpace::Local,
+    );
+    vger.fill_rect(euclid::rect(50.0, 50.0, 200.0, 200.0), 10.0, hatch);
+
+    let cross = vger.cross_hatch_paint(
+        0.0,
+        8.0,
+        1.0,
+        Color::WHITE,
+        Color::gray(0.2),
+        PatternSpace::Device,
+    );
+    vger.fill_circle([350.0, 350.0], 100.0, cross);
+
+    let png_name = "hatch.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Where runs of pixels brighter than `threshold` start.
+    let starts = |row: &[u8], xs: std::ops::Range<usize>, threshold: u8| -> Vec<usize> {
+        xs.filter(|x| row[*x] > threshold && row[*x - 1] <= threshold)
+            .collect()
+    };
+
+    // Lines 10 apart at 45 degrees cross a row every 10√2 pixels, with
+    // the transparent background between them.
+    let green = &png_channel_rows(png_name, 1)[150];
+    let lines = starts(green, 60..240, 128);
+    assert!((12..=13).contains(&lines.len()));
+    for pair in lines.windows(2) {
+        let gap = (pair[1] - pair[0]) as f32;
+        assert!((gap - 10.0 * 2f32.sqrt()).abs() <= 1.5);
+    }
+    assert!(green[60..240].contains(&0));
+
+    // Vertical lines every 8 pixels on gray. One of two rows half a
+    // period apart is off the horizontal lines.
+    let red = png_red_rows(png_name);
+    let lines = starts(&red[350], 300..400, 160)
+        .len()
+        .max(starts(&red[354], 300..400, 160).len());
+    assert!((12..=13).contains(&lines));
+    assert!(red[350][300..400]
+        .iter()
+        .chain(&red[354][300..400])
+        .any(|r| (114..=134).contains(r)));
+}
+
+#[test]
+fn chart_batches() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let cyan = vger.color_paint(Color::CYAN);
+    let bars: Vec<chart::BarSpec> = (0..100)
+        .map(|i| chart::BarSpec {
+            x: i as f32 * 2.5,
+            width: 2.0,
+            y0: 0.0,
+            y1: 100.0 + 100.0 * (i as f32 * 0.1).sin(),
+            paint: cyan,
+        })
+        .collect();
+    vger.fill_bars(&bars);
+
+    let up = vger.color_paint(Color::new(0.0, 0.8, 0.3, 1.0));
+    let down = vger.color_paint(Color::new(0.9, 0.1, 0.1, 1.0));
+    let candles: Vec<chart::Candle> = (0..50)
+        .map(|i| {
+            let open = 350.0 + 30.0 * (i as f32 * 0.3).sin();
+            let close = 350.0 + 30.0 * (i as f32 * 0.3 + 0.5).sin();
+            chart::Candle {
+                x: 260.0 + i as f32 * 5.0,
+                width: 3.3,
+                open,
+                close,
+                low: open.min(close) - 10.0,
+                high: open.max(close) + 10.0,
+            }
+        })
+        .collect();
+    vger.draw_candlesticks(&candles, 1.0, up, down);
+
+    let png_name = "chart_batches.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Bars run down from the top to their heights.
+    let cyan = [0, 255, 255, 255];
+    assert_eq!(png_pixel(png_name, 1, 50), cyan);
+    assert_eq!(png_pixel(png_name, 41, 190), cyan);
+    assert_eq!(png_pixel(png_name, 118, 50), [0, 0, 0, 255]);
+
+    // The first candle closes up, the ninth down, with its wick above
+    // the body.
+    let up = png_pixel(png_name, 261, 357);
+    assert!(up[0] == 0 && up[1] > 200);
+    let down = png_pixel(png_name, 301, 364);
+    assert!(down[0] > 230 && down[1] < 128);
+    let wick = [png_pixel(png_name, 301, 375), png_pixel(png_name, 302, 375)];
+    assert!(wick.iter().any(|p| p[0] > 230));
+    assert_eq!(png_pixel(png_name, 299, 375), [0, 0, 0, 255]);
+}
+
+#[test]
+fn polyline_decimation() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    vger.set_polyline_decimation(true);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let cyan = vger.color_paint(Color::CYAN);
+    let points: Vec<LocalPoint> = (0..100_000)
+        .map(|i| {
+            let x = i as f32 * 0.005;
+            LocalPoint::new(x, 256.0 + 100.0 * (x * 0.05).sin() + 20.0 * (x * 3.0).sin())
+        })
+        .collect();
+    vger.stroke_polyline(&points, 1.0, cyan);
+
+    let png_name = "polyline_decimation.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Around x = 100 the fast wave swings 20 pixels either side of
+    // y = 160 within a column, and decimation keeps that envelope.
+    let green = png_channel_rows(png_name, 1);
+    assert!((150..=170).all(|y| green[y][100] > 128));
+    assert_eq!(green[100][100], 0);
+    assert_eq!(green[300][100], 0);
+}
+
+#[test]
+fn polyline_decimation_scaled() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    vger.set_polyline_decimation(true);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // Zoomed in, a local unit is 100 pixels, so points are bucketed per
+    // hundredth of a unit.
+    vger.scale([100.0, 100.0]);
+    let cyan = vger.color_paint(Color::CYAN);
+    let points: Vec<LocalPoint> = (0..50_000)
+        .map(|i| {
+            let x = i as f32 * 0.0001;
+            LocalPoint::new(x, 2.56 + (2.0 * x).sin())
+        })
+        .collect();
+    vger.stroke_polyline(&points, 0.05, cyan);
+
+    let png_name = "polyline_decimation_scaled.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    // On the curve, rather than on a chord between coarse buckets.
+    let y = 256.0 + 100.0 * 1.0f32.sin();
+    assert_eq!(png_pixel(png_name, 50, y as u32), [0, 255, 255, 255]);
+}
+
+#[test]
+fn grid() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let minor = vger.color_paint(Color::gray(0.3));
+    let major = vger.color_paint(Color::CYAN);
+    vger.draw_grid(euclid::rect(0.0, 0.0, 512.0, 512.0), 8.0, 8, minor, major);
+
+    let png_name = "grid.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Gray lines every 8 pixels, every 8th cyan, and nothing between.
+    let red = &png_red_rows(png_name)[100];
+    let green = &png_channel_rows(png_name, 1)[100];
+    assert_eq!(png_pixel(png_name, 4, 4), [0, 0, 0, 255]);
+    for x in [8, 16, 24, 72] {
+        let gray = (x - 1..=x).max_by_key(|x| red[*x]).unwrap();
+        assert!(red[gray] > 20);
+        assert_eq!(red[gray], green[gray]);
+        assert_eq!(red[x + 4], 0);
+    }
+    let major = (63..=64).max_by_key(|x| green[*x]).unwrap();
+    assert!(green[major] > 100);
+    assert!(red[major] < green[major]);
+}
+
+#[test]
+fn checkerboard() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    vger.fill_checkerboard(
+        euclid::rect(0.0, 0.0, 512.0, 256.0),
+        16.0,
+        Color::gray(0.8),
+        Color::WHITE,
+        PatternSpace::Device,
+    );
+
+    let paint = vger.checkerboard_paint(10.0, Color::CYAN, Color::MAGENTA, PatternSpace::Local);
+    vger.fill_circle([256.0, 384.0], 100.0, paint);
+
+    let png_name = "checkerboard.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // 16 pixel device space cells alternate between gray and white.
+    let (a, b) = (png_pixel(png_name, 8, 8), png_pixel(png_name, 24, 8));
+    assert_ne!(a, b);
+    assert_eq!(a[0].max(b[0]), 255);
+    assert!((200..250).contains(&a[0].min(b[0])));
+    assert_eq!(png_pixel(png_name, 14, 8), a);
+    assert_eq!(png_pixel(png_name, 17, 8), b);
+    assert_eq!(png_pixel(png_name, 8, 24), b);
+    assert_eq!(png_pixel(png_name, 24, 24), a);
+
+    // 10 unit local cells in the circle alternate between cyan and
+    // magenta.
+    let c = png_pixel(png_name, 255, 385);
+    let d = png_pixel(png_name, 265, 385);
+    assert!(c == [0, 255, 255, 255] || c == [255, 0, 255, 255]);
+    assert!(d == [0, 255, 255, 255] || d == [255, 0, 255, 255]);
+    assert_ne!(c, d);
+    assert_eq!(png_pixel(png_name, 265, 395), c);
+}
+
+#[test]
+fn world_camera() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    vger.set_camera(Camera64::new(WorldPoint64::new(1e8, 1e8), 1000.0));
+
+    let cyan = vger.color_paint(Color::CYAN);
+    vger.save();
+    vger.translate_world(WorldPoint64::new(1e8 + 0.25, 1e8 + 0.25));
+    vger.fill_circle([0.0, 0.0], 0.1, cyan);
+    vger.restore();
+
+    // A quarter unit from the camera at 1000x is 250 pixels away, and
+    // the circle has a 100 pixel radius.
+    let png_name = "world_camera.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert_eq!(png_pixel(png_name, 250, 250), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 250, 370), [0, 0, 0, 255]);
+}
+
+#[test]
+fn tile_renderer() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let mut tiles = tiles::TileRenderer::new(256, 64);
+    tiles.fade_duration = 0.0;
+    tiles.insert(tiles::TileKey::new(0, 0, 0), vec![255; 256 * 256 * 4]);
+
+    let camera = Camera64::new(WorldPoint64::new(0.0, 0.0), 2.0);
+    tiles.draw(&mut vger, &camera, LocalSize::new(512.0, 512.0), 0.0);
+
+    let png_name = "tiles.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // The missing z=1 tiles fall back to the z=0 tile, scaled to cover the
+    // whole viewport.
+    for (x, y) in [(10, 10), (256, 256), (500, 500)] {
+        assert_eq!(png_pixel(png_name, x, y), [255, 255, 255, 255]);
+    }
+}
+
+#[test]
+fn non_uniform_scale_aa() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let cyan = vger.color_paint(Color::CYAN);
+    vger.translate([256.0, 256.0]);
+    vger.rotate(0.3);
+    vger.scale([8.0, 0.5]);
+    vger.fill_circle([0.0, 0.0], 20.0, cyan);
+    vger.fill_rect(euclid::rect(-20.0, 100.0, 40.0, 200.0), 5.0, cyan);
+
+    let png_name = "non_uniform_scale_aa.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    let cyan = [0, 255, 255, 255];
+    let black = [0, 0, 0, 255];
+
+    // The circle becomes an ellipse 160 across and 10 thick, rotated 0.3.
+    assert_eq!(png_pixel(png_name, 256, 256), cyan);
+    assert_eq!(png_pixel(png_name, 351, 285), cyan);
+    assert_eq!(png_pixel(png_name, 399, 300), cyan);
+    assert_eq!(png_pixel(png_name, 418, 306), black);
+
+    // The thin axis keeps a sharp edge rather than smearing by the scale.
+    assert_eq!(png_pixel(png_name, 253, 263), cyan);
+    assert_eq!(png_pixel(png_name, 252, 267), black);
+    assert_eq!(png_pixel(png_name, 251, 270), black);
+
+    // The rect lands centered at 200, 100 in local units.
+    assert_eq!(png_pixel(png_name, 226, 351), cyan);
+    assert_eq!(png_pixel(png_name, 208, 408), black);
+}
+
+#[test]
+fn conservative_bounds() {
+    let (device, _queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 2.0);
+
+    let cyan = vger.color_paint(Color::CYAN);
+    vger.translate([256.0, 256.0]);
+    vger.rotate(std::f32::consts::PI / 4.0);
+    vger.stroke_segment([-50.0, 0.0], [50.0, 0.0], 20.0, cyan);
+
+    let bounds = vger.device_bounds();
+    assert_eq!(bounds.len(), 1);
+
+    // The rotated stroke reaches 50/√2 + 10/√2 ≈ 42.4 points from the
+    // center, and the quad leaves another device pixel for antialiasing.
+    let extent = 2.0 * (60.0 / 2.0_f32.sqrt() + 0.5);
+    let b = bounds[0];
+    assert!(b.min_x() <= 512.0 - extent && b.max_x() >= 512.0 + extent);
+    assert!(b.min_y() <= 512.0 - extent && b.max_y() >= 512.0 + extent);
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn golden_image() {
+    let (device, queue) = block_on(setup());
+
+    let golden = vger::testing::GoldenTest::new(std::env::temp_dir().join("vger_golden"));
+    let draw = |vger: &mut Vger| {
+        let cyan = vger.color_paint(Color::CYAN);
+        vger.fill_circle([256.0, 256.0], 100.0, cyan);
+    };
+
+    // The first run records the reference, the second compares against it.
+    golden.check(&device, &queue, "circle", draw).unwrap();
+    golden.check(&device, &queue, "circle", draw).unwrap();
+
+    let err = golden.check(&device, &queue, "circle", |vger: &mut Vger| {
+        let magenta = vger.color_paint(Color::MAGENTA);
+        vger.fill_circle([256.0, 256.0], 100.0, magenta);
+    });
+    assert!(err.is_err());
+}
+
+#[test]
+fn validation_mode() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    vger.set_validation(true);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let cyan = vger.color_paint(Color::CYAN);
+    vger.fill_circle([f32::NAN, 0.0], 10.0, cyan);
+    vger.fill_rect(euclid::rect(0.0, 0.0, 10.0, 10.0), 20.0, cyan);
+    vger.restore();
+    vger.save();
+
+    let png_name = "validation.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    assert_eq!(
+        vger.take_validation_errors(),
+        vec![
+            ValidationError::NonFinite { prim: "circle" },
+            ValidationError::RadiusOutOfRange {
+                prim: "rect",
+                radius: 20.0
+            },
+            ValidationError::UnmatchedRestore,
+            ValidationError::UnmatchedSave { depth: 1 },
+        ]
+    );
+    assert!(vger.take_validation_errors().is_empty());
+}
+
+#[test]
+fn save_guard() {
+    let (device, _queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    vger.set_validation(true);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    {
+        let mut g = vger.save_guard();
+        g.translate([100.0, 0.0]);
+        g.set_z_index(3);
+        g.scissor(euclid::rect(0.0, 0.0, 10.0, 10.0));
+        assert_eq!(g.current_transform().m31, 100.0);
+    }
+
+    assert_eq!(vger.current_transform(), LocalToWorld::identity());
+    vger.restore();
+    assert_eq!(
+        vger.take_validation_errors(),
+        vec![ValidationError::UnmatchedRestore]
+    );
+}
+
+#[test]
+fn render_options() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    let texture_desc = wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: 512,
+            height: 512,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        label: Some("render_texture"),
+        view_formats: &[wgpu::TextureFormat::Rgba8UnormSrgb],
+    };
+    let texture = device.create_texture(&texture_desc);
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    // Stand in for a host's 3D scene.
+    vger.begin(512.0, 512.0, 1.0);
+    let options = RenderOptions {
+        clear: Some(Color::new(0.0, 0.0, 0.5, 1.0)),
+    };
+    vger.encode_to_view(&device, &view, &queue, &options);
+
+    // Composite over it.
+    vger.begin(512.0, 512.0, 1.0);
+    let paint = vger.color_paint(Color::CYAN);
+    vger.fill_circle([256.0, 256.0], 100.0, paint);
+    vger.encode_to_view(&device, &view, &queue, &RenderOptions::default());
+
+    let png_name = "render_options.png";
+    save_png(&texture, &texture_desc, &device, &queue, png_name);
+    assert!(png_not_black(png_name));
+
+    // The second pass loads the first pass's clear instead of clearing.
+    assert_eq!(png_pixel(png_name, 256, 256), [0, 255, 255, 255]);
+    let [r, g, b, a] = png_pixel(png_name, 10, 10);
+    assert_eq!([r, g, a], [0, 0, 255]);
+    assert!((150..220).contains(&b), "{}", b);
+}
+
+#[test]
+fn coverage_mask() {
+    let (device, queue) = block_on(setup());
+
+    let format = wgpu::TextureFormat::R8Unorm;
+    let mut vger = Vger::new_with_mode(&device, format, OutputMode::Coverage);
+
+    let texture_desc = wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: 512,
+            height: 512,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        label: Some("mask_texture"),
+        view_formats: &[format],
+    };
+    let texture = device.create_texture(&texture_desc);
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    vger.begin(512.0, 512.0, 1.0);
+    let paint = vger.color_paint(Color::new(1.0, 1.0, 1.0, 0.5));
+    vger.fill_circle([256.0, 256.0], 100.0, paint);
+    vger.text("Mask", 64, Color::WHITE, None);
+
+    let options = RenderOptions {
+        clear: Some(Color::new(0.0, 0.0, 0.0, 0.0)),
+    };
+    vger.encode_to_view(&device, &view, &queue, &options);
+
+    let png_name = "coverage_mask.png";
+    save_png(&texture, &texture_desc, &device, &queue, png_name);
+    assert!(png_not_black(png_name));
+
+    // Coverage times paint alpha, with nothing outside the shapes.
+    let rows = png_channel_rows(png_name, 0);
+    assert!((126..=130).contains(&rows[256][256]), "{}", rows[256][256]);
+    assert_eq!(rows[256][100], 0);
+    assert_eq!(rows[450][450], 0);
+
+    // The text is full coverage, which the half alpha circle never reaches.
+    assert!(rows.iter().any(|row| row.contains(&255)));
+}
+
+#[test]
+fn linear_blit() {
+    let (device, queue) = block_on(setup());
+
+    let size = wgpu::Extent3d {
+        width: 512,
+        height: 512,
+        depth_or_array_layers: 1,
+    };
+
+    // UI layer in linear float.
+    let linear_format = wgpu::TextureFormat::Rgba16Float;
+    let ui_texture = device.create_texture(&wgpu::TextureDescriptor {
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: linear_format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        label: Some("ui_texture"),
+        view_formats: &[linear_format],
+    });
+    let ui_view = ui_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let mut vger = Vger::new_with_mode(&device, linear_format, OutputMode::Linear);
+    vger.begin(512.0, 512.0, 1.0);
+    let paint = vger.color_paint(Color::new(1.0, 0.5, 0.0, 0.75));
+    vger.fill_rect(euclid::rect(100.0, 100.0, 300.0, 300.0), 20.0, paint);
+    let options = RenderOptions {
+        clear: Some(Color::new(0.0, 0.0, 0.0, 0.0)),
+    };
+    vger.encode_to_view(&device, &ui_view, &queue, &options);
+
+    // Host's frame.
+    let texture_desc = wgpu::TextureDescriptor {
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        label: Some("render_texture"),
+        view_formats: &[wgpu::TextureFormat::Rgba8UnormSrgb],
+    };
+    let texture = device.create_texture(&texture_desc);
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let blitter = vger::blit::Blitter::new(&device, texture_desc.format);
+    blitter.blit(&device, &queue, &ui_view, &view, 1.0);
+
+    let png_name = "linear_blit.png";
+    save_png(&texture, &texture_desc, &device, &queue, png_name);
+    assert!(png_not_black(png_name));
+
+    // Premultiplied orange at 0.75 alpha, composited over nothing.
+    let [r, g, b, a] = png_pixel(png_name, 250, 250);
+    assert!((189..=193).contains(&a), "{}", a);
+    assert!(r > g && g > b && b == 0, "{:?}", [r, g, b]);
+    assert_eq!(png_pixel(png_name, 50, 50), [0, 0, 0, 0]);
+}
+
+#[test]
+fn linear_matches_srgb() {
+    let (device, queue) = block_on(setup());
+
+    let pixels = vec![200, 100, 50, 255];
+    let key = ImageKey::from_content(&pixels);
+    let draw = |vger: &mut Vger| {
+        vger.begin(512.0, 512.0, 1.0);
+        let orange = vger.color_paint(Color::new(1.0, 0.5, 0.0, 1.0));
+        vger.fill_rect(euclid::rect(0.0, 0.0, 256.0, 256.0), 0.0, orange);
+        let ramp = vger.linear_gradient(
+            [256.0, 0.0],
+            [512.0, 0.0],
+            Color::gray(0.0),
+            Color::WHITE,
+            0.0,
+        );
+        vger.fill_rect(euclid::rect(256.0, 0.0, 256.0, 256.0), 0.0, ramp);
+        vger.render_image_rect(
+            euclid::rect(0.0, 256.0, 256.0, 256.0),
+            &key,
+            1,
+            1,
+            || pixels.clone(),
+            1.0,
+        );
+    };
+    let options = RenderOptions {
+        clear: Some(Color::new(0.0, 0.0, 0.0, 0.0)),
+    };
+
+    let size = wgpu::Extent3d {
+        width: 512,
+        height: 512,
+        depth_or_array_layers: 1,
+    };
+    let target = |format: wgpu::TextureFormat, usage: wgpu::TextureUsages| {
+        let desc = wgpu::TextureDescriptor {
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | usage,
+            label: Some("render_texture"),
+            view_formats: &[],
+        };
+        let texture = device.create_texture(&desc);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view, desc)
+    };
+
+    // Colors written as sRGB, to a target which stores them as they are.
+    let format = wgpu::TextureFormat::Rgba8Unorm;
+    let (texture, view, desc) = target(format, wgpu::TextureUsages::COPY_SRC);
+    let mut vger = Vger::new(&device, format);
+    draw(&mut vger);
+    vger.encode_to_view(&device, &view, &queue, &options);
+    let srgb_png = "linear_matches_srgb_color.png";
+    save_png(&texture, &desc, &device, &queue, srgb_png);
+
+    // Drawn to a linear float target, then blitted to an sRGB one.
+    let linear_format = wgpu::TextureFormat::Rgba16Float;
+    let (_ui_texture, ui_view, _) = target(linear_format, wgpu::TextureUsages::TEXTURE_BINDING);
+    let mut vger = Vger::new_with_mode(&device, linear_format, OutputMode::Linear);
+    draw(&mut vger);
+    vger.encode_to_view(&device, &ui_view, &queue, &options);
+
+    let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let (texture, view, desc) = target(format, wgpu::TextureUsages::COPY_SRC);
+    let blitter = vger::blit::Blitter::new(&device, format);
+    blitter.blit(&device, &queue, &ui_view, &view, 1.0);
+    let linear_png = "linear_matches_srgb_linear.png";
+    save_png(&texture, &desc, &device, &queue, linear_png);
+
+    // Opaque colors, gradients and images come out the same either way.
+    let close = |a: [u8; 4], b: [u8; 4]| (0..3).all(|c| (a[c] as i32 - b[c] as i32).abs() <= 2);
+    for (x, y) in [(128, 128), (300, 128), (384, 128), (470, 128), (128, 384)] {
+        let a = png_pixel(srgb_png, x, y);
+        let b = png_pixel(linear_png, x, y);
+        assert!(close(a, b), "{:?} != {:?} at {}, {}", a, b, x, y);
+    }
+    assert!(close(png_pixel(srgb_png, 128, 128), [255, 128, 0, 255]));
+    assert!(close(png_pixel(linear_png, 128, 384), [200, 100, 50, 255]));
+}
+
+#[test]
+fn memory_trim() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+    let paint = vger.color_paint(Color::CYAN);
+    for i in 0..100000 {
+        let x = (i % 512) as f32;
+        let y = (i / 512) as f32 * 2.0;
+        vger.fill_circle([x, y], 1.0, paint);
+    }
+    render_test(&mut vger, &device, &queue, "memory_trim_large.png", false);
+
+    // Most of the memory is the atlases, which are shrunk.
+    let usage = vger.memory_usage();
+    vger.trim(&device);
+    assert!(vger.memory_usage() < usage / 2);
+
+    // Buffers and atlases grow again as needed.
+    vger.set_memory_budget(Some(0));
+    vger.begin(512.0, 512.0, 1.0);
+    let paint = vger.color_paint(Color::CYAN);
+    vger.fill_circle([256.0, 256.0], 100.0, paint);
+    vger.translate([300.0, 450.0]);
+    vger.text("trimmed", 32, Color::WHITE, None);
+
+    let png_name = "memory_trim.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert_eq!(png_pixel(png_name, 256, 256), [0, 255, 255, 255]);
+    assert!(png_red_rows(png_name)[400..]
+        .iter()
+        .any(|row| row[300..].iter().any(|r| *r > 128)));
+
+    // The mask atlas reads back at its shrunk size.
+    let glyph_cache = vger.glyph_cache();
+    let desc = glyph_cache.mask_atlas.get_texture_desc();
+    assert_eq!(desc.size.width, vger::atlas::Atlas::MIN_SIZE);
+    assert_eq!(desc.format, wgpu::TextureFormat::R8Unorm);
+    save_png(
+        glyph_cache.mask_atlas.texture(),
+        &desc,
+        &device,
+        &queue,
+        "memory_trim_atlas.png",
+    );
+}
+
+#[test]
+fn memory_budget() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+    let paint = vger.color_paint(Color::CYAN);
+    for i in 0..100000 {
+        let x = (i % 512) as f32;
+        let y = (i / 512) as f32 * 2.0;
+        vger.fill_circle([x, y], 1.0, paint);
+    }
+    render_test(&mut vger, &device, &queue, "memory_budget_large.png", false);
+    let usage = vger.memory_usage();
+
+    // The large frame's scene is trimmed once a later frame is prepared,
+    // and the frame being prepared is still drawn.
+    vger.set_memory_budget(Some(usage - 1));
+    vger.begin(512.0, 512.0, 1.0);
+    let paint = vger.color_paint(Color::CYAN);
+    vger.fill_circle([256.0, 256.0], 100.0, paint);
+
+    let png_name = "memory_budget.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(vger.memory_usage() < usage);
+    assert_eq!(png_pixel(png_name, 256, 256), [0, 255, 255, 255]);
+}
+
+#[test]
+fn z_index_pruning() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+    let paint = vger.color_paint(Color::CYAN);
+    for z_index in 0..1000 {
+        vger.set_z_index(z_index);
+        vger.fill_circle([256.0, 256.0], 100.0, paint);
+    }
+    render_test(
+        &mut vger,
+        &device,
+        &queue,
+        "z_index_pruning_many.png",
+        false,
+    );
+    let usage = vger.memory_usage();
+
+    // Z indices which go unused are dropped within a few dozen frames.
+    for _ in 0..60 {
+        vger.begin(512.0, 512.0, 1.0);
+        let paint = vger.color_paint(Color::CYAN);
+        vger.fill_circle([256.0, 256.0], 100.0, paint);
+    }
+
+    let png_name = "z_index_pruning.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(vger.memory_usage() < usage);
+    assert_eq!(png_pixel(png_name, 256, 256), [0, 255, 255, 255]);
+}
+
+#[test]
+fn premultiplied_alpha() {
+    let (device, queue) = block_on(setup());
+
+    let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let mut vger = Vger::new_with_mode(&device, format, OutputMode::Premultiplied);
+
+    let texture_desc = wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: 512,
+            height: 512,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        label: Some("render_texture"),
+        view_formats: &[format],
+    };
+    let texture = device.create_texture(&texture_desc);
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    vger.begin(512.0, 512.0, 1.0);
+    let paint = vger.color_paint(Color::new(1.0, 1.0, 1.0, 0.5));
+    vger.fill_circle([256.0, 256.0], 100.0, paint);
+
+    let options = RenderOptions {
+        clear: Some(Color::new(0.0, 0.0, 0.0, 0.0)),
+    };
+    vger.encode_to_view(&device, &view, &queue, &options);
+
+    let png_name = "premultiplied_alpha.png";
+    save_png(&texture, &texture_desc, &device, &queue, png_name);
+
+    let decoder = png::Decoder::new(std::fs::File::open(png_name).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut buf).unwrap();
+
+    // Alpha isn't squared, and is zero outside the circle.
+    let center = (256 * 512 + 256) * 4;
+    assert!((buf[center + 3] as i32 - 128).abs() <= 2);
+    assert!(buf[center] < 255);
+    assert_eq!(buf[3], 0);
+}
+
+#[test]
+fn access_nodes() {
+    let (device, _queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 2.0);
+    let paint = vger.color_paint(Color::CYAN);
+
+    vger.save();
+    vger.set_access_node(Some(AccessNode(7)));
+    vger.translate([10.0, 20.0]);
+    vger.fill_rect(euclid::rect(0.0, 0.0, 100.0, 30.0), 4.0, paint);
+    vger.text("OK", 16, Color::WHITE, None);
+    vger.restore();
+
+    // Not tagged.
+    vger.fill_circle([300.0, 300.0], 10.0, paint);
+
+    let nodes = vger.access_nodes();
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].node, AccessNode(7));
+    assert_eq!(nodes[0].text, "OK");
+    // Covers the rect, in device pixels.
+    let bounds = nodes[0].bounds;
+    assert!(bounds.min_x() <= 20.0 && bounds.min_y() <= 40.0);
+    assert!(bounds.max_x() >= 220.0 && bounds.max_y() >= 100.0);
+    assert!(!bounds.contains(ScreenPoint::new(600.0, 600.0)));
+
+    vger.begin(512.0, 512.0, 2.0);
+    assert!(vger.access_nodes().is_empty());
+}
+
+#[test]
+fn cached_layer() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    let mut draws = 0;
+
+    for _ in 0..2 {
+        vger.begin(512.0, 512.0, 1.0);
+        vger.translate([100.0, 100.0]);
+        vger.cached_layer(1, [200.0, 100.0], |vger| {
+            draws += 1;
+            let paint = vger.color_paint(Color::CYAN);
+            vger.fill_rect(euclid::rect(0.0, 0.0, 200.0, 100.0), 10.0, paint);
+            vger.text("cached", 32, Color::WHITE, None);
+        });
+
+        render_test(&mut vger, &device, &queue, "cached_layer.png", false);
+    }
+
+    // Drawn once, then reused.
+    assert_eq!(draws, 1);
+    assert_eq!(png_pixel("cached_layer.png", 285, 185), [0, 255, 255, 255]);
+
+    // Layers are kept in pooled textures, so clearing the atlases doesn't
+    // render them again.
+    vger.glyph_cache().clear();
+    vger.begin(512.0, 512.0, 1.0);
+    vger.translate([100.0, 100.0]);
+    vger.cached_layer(1, [200.0, 100.0], |_| draws += 1);
+    render_test(
+        &mut vger,
+        &device,
+        &queue,
+        "cached_layer_cleared.png",
+        false,
+    );
+    assert_eq!(draws, 1);
+    assert_eq!(
+        png_pixel("cached_layer_cleared.png", 285, 185),
+        [0, 255, 255, 255]
+    );
+
+    // A new key renders again.
+    vger.begin(512.0, 512.0, 1.0);
+    vger.cached_layer(2, [200.0, 100.0], |_| draws += 1);
+    assert_eq!(draws, 2);
+}
+
+#[test]
+fn texture_pool() {
+    let (device, _queue) = block_on(setup());
+
+    let mut pool = TexturePool::new(texture_pool::DEFAULT_BUDGET);
+    let format = wgpu::TextureFormat::Rgba8Unorm;
+    let usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+
+    let a = pool.acquire(&device, 300, 200, format, usage);
+    let b = pool.acquire(&device, 300, 200, format, usage);
+    assert_ne!(a, b);
+    assert_eq!(pool.memory_usage(), 2 * 512 * 256 * 4);
+
+    // Nearby sizes reuse last frame's textures.
+    pool.recycle();
+    pool.acquire(&device, 310, 220, format, usage);
+    pool.acquire(&device, 290, 250, format, usage);
+    assert_eq!(pool.len(), 2);
+
+    // Over budget, unused textures are dropped.
+    pool.set_budget(512 * 256 * 4);
+    pool.recycle();
+    assert_eq!(pool.len(), 1);
+    pool.set_budget(0);
+    pool.recycle();
+    assert!(pool.is_empty());
+
+    // Kept textures aren't handed out again.
+    pool.set_budget(texture_pool::DEFAULT_BUDGET);
+    let kept = pool.acquire(&device, 300, 200, format, usage);
+    pool.keep(kept, 7);
+    pool.recycle();
+    assert_ne!(pool.acquire(&device, 300, 200, format, usage), kept);
+    assert_eq!(pool.kept(7), Some(kept));
+    pool.release(7);
+    assert!(!pool.is_kept(7));
+}
+
+#[test]
+fn prepare_render() {
+    let (device, queue) = block_on(setup());
+
+    let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let mut vger = Vger::new(&device, format);
+
+    let texture_desc = wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: 512,
+            height: 512,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        label: Some("host target"),
+        view_formats: &[format],
+    };
+    let texture = device.create_texture(&texture_desc);
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    vger.begin(512.0, 512.0, 1.0);
+    let paint = vger.color_paint(Color::CYAN);
+    vger.fill_circle([256.0, 256.0], 100.0, paint);
+    vger.text("prepared", 32, Color::WHITE, None);
+
+    vger.prepare(&device, &queue);
+
+    // The host owns the encoder and the pass.
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("host encoder"),
+    });
+    {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("host pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        vger.render(&mut rpass);
+    }
+    queue.submit(Some(encoder.finish()));
+
+    let png_name = "prepare_render.png";
+    save_png(&texture, &texture_desc, &device, &queue, png_name);
+    assert!(png_not_black(png_name));
+
+    // The host's pass draws what prepare uploaded, over its own clear.
+    assert_eq!(png_pixel(png_name, 256, 256), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 256, 400), [0, 0, 0, 255]);
+}
+
+#[test]
+fn external_texture() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    let desc = wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: 64,
+            height: 64,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+        label: Some("client buffer"),
+        view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
+    };
+    let texture = device.create_texture(&desc);
+    let pixels = [0u8, 255, 255, 255].repeat(64 * 64);
+    queue.write_texture(
+        texture.as_image_copy(),
+        &pixels,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: std::num::NonZeroU32::new(64 * 4),
+            rows_per_image: None,
+        },
+        desc.size,
+    );
+
+    let image = vger.register_external_texture(texture, &desc).unwrap();
+
+    vger.begin(512.0, 512.0, 1.0);
+    vger.render_external(image, euclid::rect(100.0, 100.0, 128.0, 128.0));
+    vger.render_external(image, euclid::rect(300.0, 100.0, 64.0, 64.0));
+
+    let png_name = "external_texture.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    assert!(vger.unregister_external_texture(image).is_some());
+}
+
+#[test]
+fn linear_layers() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new_with_mode(
+        &device,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        OutputMode::Linear,
+    );
+
+    // A black texel then a white one, stretched so they're filtered.
+    let desc = wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: 2,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+        label: Some("edge"),
+        view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
+    };
+    let texture = device.create_texture(&desc);
+    queue.write_texture(
+        texture.as_image_copy(),
+        &[0, 0, 0, 255, 255, 255, 255, 255],
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: std::num::NonZeroU32::new(2 * 4),
+            rows_per_image: None,
+        },
+        desc.size,
+    );
+    let image = vger.register_external_texture(texture, &desc).unwrap();
+
+    vger.begin(512.0, 512.0, 1.0);
+    let gray = vger.color_paint(Color::gray(0.5));
+    vger.fill_rect(euclid::rect(50.0, 50.0, 100.0, 100.0), 0.0, gray);
+    vger.save();
+    vger.translate([200.0, 50.0]);
+    vger.cached_layer(1, [100.0, 100.0], |vger| {
+        let gray = vger.color_paint(Color::gray(0.5));
+        vger.fill_rect(euclid::rect(0.0, 0.0, 100.0, 100.0), 0.0, gray);
+    });
+    vger.restore();
+    vger.render_external(image, euclid::rect(50.0, 250.0, 200.0, 100.0));
+
+    let png_name = "linear_layers.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    // Layers and external textures aren't decoded as sRGB, so they come
+    // out as drawn, and halfway between the texels is half gray.
+    let direct = png_pixel(png_name, 100, 100)[0] as i32;
+    assert!((direct - 128).abs() <= 4);
+    let layer = png_pixel(png_name, 250, 100)[0] as i32;
+    assert!((layer - direct).abs() <= 3);
+    let external = png_pixel(png_name, 150, 300)[0] as i32;
+    assert!((external - direct).abs() <= 4);
+}
+
+fn plane_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    size: u32,
+    format: wgpu::TextureFormat,
+    texel: &[u8],
+) -> wgpu::TextureView {
+    let desc = wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        label: Some("video plane"),
+        view_formats: &[format],
+    };
+    let texture = device.create_texture(&desc);
+    queue.write_texture(
+        texture.as_image_copy(),
+        &texel.repeat((size * size) as usize),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: std::num::NonZeroU32::new(size * texel.len() as u32),
+            rows_per_image: None,
+        },
+        desc.size,
+    );
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+#[test]
+fn video_frame() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    // An orange NV12 frame.
+    let frame = VideoFrame {
+        planes: VideoPlanes::Nv12 {
+            y: plane_texture(&device, &queue, 64, wgpu::TextureFormat::R8Unorm, &[151]),
+            uv: plane_texture(
+                &device,
+                &queue,
+                32,
+                wgpu::TextureFormat::Rg8Unorm,
+                &[44, 185],
+            ),
+        },
+        width: 64,
+        height: 64,
+        matrix: YuvMatrix::Bt709,
+        full_range: false,
+    };
+
+    vger.begin(512.0, 512.0, 1.0);
+    vger.render_video(1, frame, euclid::rect(100.0, 100.0, 256.0, 256.0));
+
+    // Controls over the video.
+    let paint = vger.color_paint(Color::WHITE);
+    vger.fill_rect(euclid::rect(120.0, 300.0, 216.0, 32.0), 8.0, paint);
+
+    let png_name = "video_frame.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Orange: red saturates, blue clamps to zero.
+    let [r, g, b, a] = png_pixel(png_name, 200, 150);
+    assert_eq!([r, b, a], [255, 0, 255]);
+    assert!((80..230).contains(&g), "{}", g);
+
+    // The controls draw over the video, and nothing draws outside it.
+    assert_eq!(png_pixel(png_name, 228, 316), [255, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 50, 50), [0, 0, 0, 255]);
+}
+
+#[test]
+fn overlay() {
+    let (device, queue) = block_on(setup());
+
+    let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let mut vger = Vger::new(&device, format);
+
+    let texture_desc = wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: 512,
+            height: 512,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        label: Some("overlay target"),
+        view_formats: &[format],
+    };
+    let texture = device.create_texture(&texture_desc);
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let desc = wgpu::RenderPassDescriptor {
+        label: None,
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: &view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: true,
+            },
+        })],
+        depth_stencil_attachment: None,
+    };
+
+    vger.begin(512.0, 512.0, 1.0);
+    let paint = vger.color_paint(Color::CYAN);
+    vger.fill_rect(euclid::rect(50.0, 50.0, 200.0, 200.0), 10.0, paint);
+    vger.encode(&device, &desc, &queue);
+
+    // Move the cursor without redrawing the frame.
+    for x in [100.0, 200.0, 300.0] {
+        vger.draw_overlay(|vger| {
+            let paint = vger.color_paint(Color::MAGENTA);
+            vger.fill_circle([x, 300.0], 10.0, paint);
+        });
+        vger.encode_overlay(&device, &desc, &queue);
+    }
+
+    let png_name = "overlay.png";
+    save_png(&texture, &texture_desc, &device, &queue, png_name);
+    assert!(png_not_black(png_name));
+
+    // The frame is kept, and only the last cursor is drawn over it.
+    assert_eq!(png_pixel(png_name, 150, 150), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 300, 300), [255, 0, 255, 255]);
+    assert_eq!(png_pixel(png_name, 100, 300), [0, 0, 0, 255]);
+    assert_eq!(png_pixel(png_name, 200, 300), [0, 0, 0, 255]);
+}
+
+#[test]
+fn text_shadow() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    vger.translate([32.0, 256.0]);
+    let shadows = [
+        TextShadow {
+            offset: [2.0, 2.0].into(),
+            blur: 0.0,
+            color: Color::MAGENTA,
+        },
+        TextShadow {
+            offset: [4.0, 6.0].into(),
+            blur: 8.0,
+            color: Color::CYAN,
+        },
+    ];
+    vger.text_with_shadows("Shadowed", 48, Color::WHITE, None, &shadows);
+
+    let png_name = "text_shadow.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    let red = png_channel_rows(png_name, 0);
+    let green = png_channel_rows(png_name, 1);
+    let blue = png_channel_rows(png_name, 2);
+    let pixels = || {
+        (0..512)
+            .flat_map(|y| (0..512).map(move |x| (x, y)))
+            .map(|(x, y)| (x, y, [red[y][x], green[y][x], blue[y][x]]))
+    };
+
+    // The text, the hard magenta shadow and the blurred cyan one each
+    // show somewhere.
+    assert!(pixels().any(|(_, _, c)| c == [255, 255, 255]));
+    assert!(pixels().any(|(_, _, c)| c == [255, 0, 255]));
+    assert!(pixels().any(|(_, _, c)| c[0] == 0 && c[1] > 0 && c[1] == c[2]));
+
+    // All of it stays near the glyphs, allowing for the offsets and blur.
+    let bounds = vger
+        .glyph_positions("Shadowed", 48, None)
+        .iter()
+        .fold(LocalRect::zero(), |b, r| b.union(r))
+        .translate([32.0, 256.0].into())
+        .inflate(24.0, 24.0);
+    for (x, y, c) in pixels() {
+        if c != [0, 0, 0] {
+            assert!(bounds.contains([x as f32, y as f32].into()), "{} {}", x, y);
+        }
+    }
+}
+
+#[test]
+fn paint_glow() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // The plain paint drawn first keeps no glow.
+    let white = vger.color_paint(Color::WHITE);
+    vger.fill_rect(euclid::rect(300.0, 100.0, 150.0, 80.0), 12.0, white);
+
+    let paint = vger.glow_paint(white, Color::CYAN, 24.0);
+    assert_ne!(paint, white);
+    vger.fill_rect(euclid::rect(100.0, 100.0, 150.0, 80.0), 12.0, paint);
+
+    let paint = vger.color_paint(Color::gray(0.2));
+    let paint = vger.glow_paint(paint, Color::MAGENTA, 16.0);
+    vger.fill_circle([350.0, 350.0], 60.0, paint);
+
+    let png_name = "paint_glow.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    // The glow reaches past the shape's quad.
+    let glow = png_pixel(png_name, 92, 140);
+    assert!(glow[0] < 64 && glow[1] > 32 && glow[2] > 32);
+    assert_eq!(png_pixel(png_name, 292, 140), [0, 0, 0, 255]);
+    assert_eq!(png_pixel(png_name, 175, 140), [255, 255, 255, 255]);
+}
+
+#[test]
+fn focus_ring() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let button = euclid::rect(100.0, 100.0, 200.0, 60.0);
+    let paint = vger.color_paint(Color::gray(0.4));
+    vger.fill_rect(button, 8.0, paint);
+
+    let paint = vger.color_paint(Color::CYAN);
+    vger.stroke_focus_ring(button, 8.0, 3.0, 4.0, paint);
+
+    // Sharp corners round out by the offset.
+    let paint = vger.color_paint(Color::MAGENTA);
+    vger.stroke_focus_ring(euclid::rect(100.0, 300.0, 60.0, 60.0), 0.0, 2.0, 6.0, paint);
+
+    let png_name = "focus_ring.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // The ring is offset outside the button, leaving a gap.
+    assert_eq!(png_pixel(png_name, 94, 130), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 200, 94), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 98, 130), [0, 0, 0, 255]);
+    let [r, g, b, _] = png_pixel(png_name, 200, 130);
+    assert!(r == g && g == b && r > 100, "{}", r);
+
+    // The sharp rect's ring has rounded corners.
+    let [r, g, b, _] = png_pixel(png_name, 93, 330);
+    assert!(r == b && r > 100 && g == 0, "{:?}", [r, g, b]);
+    assert_eq!(png_pixel(png_name, 92, 292), [0, 0, 0, 255]);
+}
+
+#[test]
+fn gradient_and_image_borders() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paint = vger.linear_gradient([100.0, 0.0], [300.0, 0.0], Color::CYAN, Color::MAGENTA, 0.0);
+    vger.stroke_rect(
+        [100.0, 100.0].into(),
+        [300.0, 160.0].into(),
+        12.0,
+        4.0,
+        paint,
+    );
+
+    // A 12x12 frame, white with a cyan middle.
+    let mut pixels = vec![];
+    for y in 0..12 {
+        for x in 0..12 {
+            let edge = !(4..8).contains(&x) || !(4..8).contains(&y);
+            pixels.extend_from_slice(if edge {
+                &[255, 255, 255, 255]
+            } else {
+                &[0, 255, 255, 255]
+            });
+        }
+    }
+    let border = BorderImage {
+        slice: [4.0, 4.0, 4.0, 4.0],
+        widths: [8.0, 8.0, 8.0, 8.0],
+        fill: false,
+    };
+    vger.render_border_image(
+        euclid::rect(100.0, 250.0, 200.0, 100.0),
+        &ImageKey::from_content(&pixels),
+        12,
+        12,
+        || pixels.clone(),
+        &border,
+    );
+
+    let png_name = "gradient_and_image_borders.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // The stroke runs from cyan on the left to magenta on the right.
+    let [r, g, b, _] = png_pixel(png_name, 100, 130);
+    assert!(r < 30 && g > 240 && b == 255, "{:?}", [r, g, b]);
+    let [r, g, b, _] = png_pixel(png_name, 300, 130);
+    assert!(r > 240 && g < 30 && b == 255, "{:?}", [r, g, b]);
+    assert_eq!(png_pixel(png_name, 200, 130), [0, 0, 0, 255]);
+
+    // The image's white edges stretch along the border, and its middle
+    // isn't drawn without fill.
+    assert_eq!(png_pixel(png_name, 102, 252), [255, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 102, 300), [255, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 200, 346), [255, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 200, 300), [0, 0, 0, 255]);
+    assert_eq!(png_pixel(png_name, 96, 300), [0, 0, 0, 255]);
+}
+
+#[test]
+fn rect_sides() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let colors = [
+        Color::CYAN,
+        Color::MAGENTA,
+        Color::WHITE,
+        Color::new(1.0, 0.5, 0.0, 1.0),
+    ];
+    vger.stroke_rect_sides(
+        euclid::rect(50.0, 50.0, 200.0, 120.0),
+        0.0,
+        [4.0, 12.0, 20.0, 8.0],
+        colors,
+    );
+    vger.stroke_rect_sides(
+        euclid::rect(50.0, 250.0, 200.0, 120.0),
+        24.0,
+        [8.0, 8.0, 8.0, 8.0],
+        colors,
+    );
+
+    let png_name = "rect_sides.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Each side in its own color and width: top, right, bottom, left.
+    assert_eq!(png_pixel(png_name, 150, 52), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 150, 56), [0, 0, 0, 255]);
+    assert_eq!(png_pixel(png_name, 244, 110), [255, 0, 255, 255]);
+    assert_eq!(png_pixel(png_name, 150, 160), [255, 255, 255, 255]);
+    let [r, g, b, _] = png_pixel(png_name, 54, 110);
+    assert!(r == 255 && g > 100 && g < 255 && b == 0, "{:?}", [r, g, b]);
+    assert_eq!(png_pixel(png_name, 150, 110), [0, 0, 0, 255]);
+
+    // Rounded corners leave the rect's corner empty.
+    assert_eq!(png_pixel(png_name, 150, 254), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 51, 251), [0, 0, 0, 255]);
+}
+
+#[test]
+fn shimmer() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.set_time(0.4);
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paint = vger.shimmer_paint(
+        [50.0, 0.0],
+        [450.0, 100.0],
+        Color::gray(0.3),
+        Color::gray(0.6),
+        0.2,
+        1.0,
+    );
+    vger.fill_rect(euclid::rect(50.0, 100.0, 400.0, 24.0), 6.0, paint);
+    vger.fill_rect(euclid::rect(50.0, 140.0, 300.0, 24.0), 6.0, paint);
+
+    let png_name = "shimmer.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // At 0.4 seconds the band is centered 36% of the way along the axis,
+    // which crosses the first rect's middle at x = 175.
+    let red = &png_red_rows(png_name)[112];
+    assert!(red[175] > 190, "{}", red[175]);
+    assert_eq!(red[60], red[440]);
+    assert!((135..165).contains(&red[60]), "{}", red[60]);
+}
+
+#[test]
+fn noise_paints() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let kinds = [NoiseKind::Value, NoiseKind::Gradient, NoiseKind::Grain];
+    for (i, kind) in kinds.iter().enumerate() {
+        let noise = Noise {
+            kind: *kind,
+            scale: if *kind == NoiseKind::Grain { 1.0 } else { 16.0 },
+            intensity: 1.0,
+            seed: 7,
+            period: 8,
+            space: PatternSpace::Local,
+        };
+        let paint = vger.noise_paint(&noise, Color::gray(0.1), Color::gray(0.9));
+        let x = 20.0 + 164.0 * i as f32;
+        vger.fill_rect(euclid::rect(x, 100.0, 144.0, 144.0), 0.0, paint);
+    }
+
+    let png_name = "noise_paints.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    let red = png_red_rows(png_name);
+    for i in 0..3 {
+        let x0 = 20 + 164 * i;
+        let cells = &red[100..244];
+
+        // Between the two colors, and not flat.
+        let values = cells.iter().flat_map(|row| &row[x0..x0 + 144]);
+        let min = *values.clone().min().unwrap();
+        let max = *values.max().unwrap();
+        assert!(min >= 89 && max <= 243, "{} {}", min, max);
+        assert!(max - min > 40, "{} {}", min, max);
+
+        // Repeats after 8 cells.
+        let period = if i == 2 { 8 } else { 128 };
+        for row in cells.iter().step_by(16) {
+            let (a, b) = (row[x0 + 5], row[x0 + 5 + period]);
+            assert!((a as i32 - b as i32).abs() <= 1, "{} {}", a, b);
+        }
+    }
+    assert_eq!(red[170][174], 0);
+}
+
+#[test]
+fn gauge() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let background = vger.color_paint(Color::gray(0.25));
+    let value = vger.color_paint(Color::CYAN);
+    let gauge = vger::chart::Gauge {
+        center: [256.0, 256.0].into(),
+        radius: 120.0,
+        start: -std::f32::consts::FRAC_PI_2,
+        sweep: 4.0,
+        thickness: 24.0,
+    };
+    vger.draw_gauge(&gauge, background, value);
+
+    let paint = vger.color_paint(Color::MAGENTA);
+    vger.stroke_arc_round([256.0, 256.0], 60.0, 12.0, 0.0, 1.0, paint);
+
+    let png_name = "gauge.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // The value arc runs clockwise from the top for 4 radians, over the
+    // gray ring.
+    assert_eq!(png_pixel(png_name, 376, 256), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 256, 376), [0, 255, 255, 255]);
+    for (x, y) in [(136, 256), (167, 175)] {
+        let [r, g, b, _] = png_pixel(png_name, x, y);
+        assert!(r == g && g == b && r > 50 && r < 200, "{}", r);
+    }
+    assert_eq!(png_pixel(png_name, 256, 256), [0, 0, 0, 255]);
+
+    // The round arc is centered on the left at zero rotation.
+    assert_eq!(png_pixel(png_name, 196, 256), [255, 0, 255, 255]);
+    assert_eq!(png_pixel(png_name, 316, 256), [0, 0, 0, 255]);
+}
+
+#[test]
+fn pie_and_donut() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let colors = [Color::CYAN, Color::MAGENTA, Color::WHITE];
+    let sweeps = [2.0, 1.5, 2.0 * std::f32::consts::PI - 3.5];
+    let mut start = 0.0;
+    for (color, sweep) in colors.iter().zip(sweeps.iter()) {
+        let paint = vger.color_paint(*color);
+        for (center, inner_radius) in [([140.0, 256.0], 0.0), ([372.0, 256.0], 60.0)] {
+            let sector = vger::chart::Sector {
+                center: center.into(),
+                inner_radius,
+                outer_radius: 100.0,
+                start,
+                sweep: *sweep,
+            };
+            vger.fill_sector(&sector, paint);
+        }
+        start += sweep;
+    }
+
+    let png_name = "pie_and_donut.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Slices go clockwise from the positive x axis.
+    let cyan = [0, 255, 255, 255];
+    let magenta = [255, 0, 255, 255];
+    let white = [255, 255, 255, 255];
+    let black = [0, 0, 0, 255];
+    assert_eq!(png_pixel(png_name, 167, 298), cyan);
+    assert_eq!(png_pixel(png_name, 94, 275), magenta);
+    assert_eq!(png_pixel(png_name, 154, 208), white);
+    assert_eq!(png_pixel(png_name, 140, 370), black);
+
+    // The donut leaves its hole empty.
+    assert_eq!(png_pixel(png_name, 415, 323), cyan);
+    assert_eq!(png_pixel(png_name, 395, 179), white);
+    assert_eq!(png_pixel(png_name, 372, 256), black);
+}
+
+#[test]
+fn connectors() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let mut edges = vec![];
+    for i in 0..8 {
+        let y = 40.0 + 56.0 * i as f32;
+        edges.push(Connector {
+            from: [40.0, y].into(),
+            to: [220.0, 472.0 - y].into(),
+            route: ConnectorRoute::Cubic,
+            arrow_size: 10.0,
+        });
+        edges.push(Connector {
+            from: [290.0, y].into(),
+            to: [472.0, 472.0 - y].into(),
+            route: ConnectorRoute::Orthogonal { radius: 12.0 },
+            arrow_size: 10.0,
+        });
+    }
+
+    let paint = vger.color_paint(Color::CYAN);
+    vger.stroke_connectors(&edges, 2.0, paint);
+
+    let png_name = "connectors.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // The symmetric cubics all cross halfway between their ends.
+    let [r, g, _, _] = png_pixel(png_name, 130, 236);
+    assert!(r == 0 && g > 200, "{}", g);
+
+    // Orthogonal edges leave horizontally, turn at the middle and end in
+    // an arrowhead.
+    for (x, y) in [(330, 40), (381, 236)] {
+        let [r, g, _, _] = png_pixel(png_name, x, y);
+        assert!(r == 0 && g > 100, "{} {} {}", x, y, g);
+    }
+    let [r, g, _, _] = png_pixel(png_name, 466, 432);
+    assert!(r == 0 && g > 200, "{}", g);
+    assert_eq!(png_pixel(png_name, 255, 256), [0, 0, 0, 255]);
+}
+
+#[test]
+fn picking() {
+    let (device, _queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 2.0);
+
+    let paint = vger.color_paint(Color::CYAN);
+    vger.set_pick_tag(Some(PickTag(1)));
+    vger.fill_rect(euclid::rect(10.0, 10.0, 100.0, 50.0), 0.0, paint);
+
+    vger.save();
+    vger.set_pick_tag(Some(PickTag(2)));
+    vger.fill_circle([60.0, 35.0], 10.0, paint);
+    vger.restore();
+
+    vger.set_pick_tag(None);
+    vger.fill_circle([300.0, 300.0], 10.0, paint);
+
+    // Bounds are in device pixels.
+    assert_eq!(
+        vger.query_point([120.0, 70.0].into()),
+        [PickTag(2), PickTag(1)]
+    );
+    assert_eq!(vger.query_point([40.0, 40.0].into()), [PickTag(1)]);
+    assert!(vger.query_point([600.0, 600.0].into()).is_empty());
+    assert_eq!(
+        vger.query_rect(ScreenRect::new([0.0, 0.0].into(), [30.0, 30.0].into())),
+        [PickTag(1)]
+    );
+
+    vger.begin(512.0, 512.0, 2.0);
+    assert!(vger.query_point([40.0, 40.0].into()).is_empty());
+}
+
+#[test]
+fn capture_region() {
+    let (device, queue) = block_on(setup());
+
+    let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let mut vger = Vger::new(&device, format);
+
+    vger.begin(256.0, 256.0, 2.0);
+
+    let paint = vger.color_paint(Color::CYAN);
+    vger.fill_rect(euclid::rect(20.0, 20.0, 40.0, 40.0), 0.0, paint);
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: 512,
+            height: 512,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        label: Some("capture_texture"),
+        view_formats: &[format],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let options = RenderOptions {
+        clear: Some(Color::gray(0.0)),
+    };
+    vger.encode_to_view(&device, &view, &queue, &options);
+
+    // Local coordinates are scaled to device pixels.
+    let capture = vger.capture_region(
+        &device,
+        &queue,
+        &texture,
+        euclid::rect(10.0, 10.0, 30.0, 30.0),
+    );
+    device.poll(wgpu::Maintain::Wait);
+    let image = block_on(capture).unwrap();
+    assert_eq!((image.width, image.height), (60, 60));
+    assert_eq!(image.pixel(0, 0), Some([0, 0, 0, 255]));
+    assert_eq!(image.pixel(50, 50), Some([0, 255, 255, 255]));
+
+    // Regions are clipped to the texture.
+    let capture = vger.capture_region(
+        &device,
+        &queue,
+        &texture,
+        euclid::rect(200.0, 250.0, 100.0, 100.0),
+    );
+    device.poll(wgpu::Maintain::Wait);
+    let image = block_on(capture).unwrap();
+    assert_eq!((image.width, image.height), (112, 12));
+}
+
+#[test]
+fn read_pixel() {
+    let (device, queue) = block_on(setup());
+
+    let format = wgpu::TextureFormat::Bgra8Unorm;
+    let mut vger = Vger::new(&device, format);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paint = vger.color_paint(Color::MAGENTA);
+    vger.fill_rect(euclid::rect(100.0, 100.0, 100.0, 100.0), 0.0, paint);
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: 512,
+            height: 512,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        label: Some("read_pixel_texture"),
+        view_formats: &[format],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let options = RenderOptions {
+        clear: Some(Color::gray(0.0)),
+    };
+    vger.encode_to_view(&device, &view, &queue, &options);
+
+    // BGRA targets are read back as RGBA.
+    let inside = vger.read_pixel(&device, &queue, &texture, 150, 150);
+    let outside = vger.read_pixel(&device, &queue, &texture, 50, 50);
+    let off_target = vger.read_pixel(&device, &queue, &texture, 600, 50);
+    device.poll(wgpu::Maintain::Wait);
+    assert_eq!(block_on(inside), Ok([255, 0, 255, 255]));
+    assert_eq!(block_on(outside), Ok([0, 0, 0, 255]));
+    assert_eq!(block_on(off_target), Err(CaptureError::OutOfBounds));
+}
+
+#[test]
+fn paint_dedup() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let cyan = vger.color_paint(Color::CYAN);
+    for i in 0..10000 {
+        let paint = vger.color_paint(Color::CYAN);
+        assert_eq!(paint, cyan);
+        let (x, y) = ((i % 100) as f32 * 5.0, (i / 100) as f32 * 5.0);
+        vger.fill_rect(euclid::rect(x, y, 4.0, 4.0), 0.0, paint);
+    }
+    assert_ne!(vger.color_paint(Color::MAGENTA), cyan);
+
+    // A glowing paint no longer matches plain ones.
+    let white = vger.color_paint(Color::WHITE);
+    let glowing = vger.glow_paint(white, Color::CYAN, 8.0);
+    assert_ne!(white, glowing);
+    assert_eq!(vger.color_paint(Color::WHITE), white);
+    assert_eq!(vger.glow_paint(white, Color::CYAN, 8.0), glowing);
+    vger.fill_circle([256.0, 256.0], 40.0, glowing);
+
+    let png_name = "paint_dedup.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+}
+
+#[test]
+fn transform_dedup() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paint = vger.color_paint(Color::CYAN);
+    for row in 0..10 {
+        for col in 0..10 {
+            vger.save();
+            vger.translate([col as f32 * 50.0, row as f32 * 50.0]);
+            vger.fill_circle([25.0, 25.0], 10.0, paint);
+            vger.fill_rect(euclid::rect(5.0, 5.0, 10.0, 10.0), 0.0, paint);
+            vger.restore();
+            vger.fill_circle([5.0, 5.0], 2.0, paint);
+        }
+    }
+
+    // The identity, then one transform per cell.
+    let stats = vger.transform_stats();
+    assert_eq!(stats.stored, 101);
+    assert!(stats.requested > 300);
+
+    let png_name = "transform_dedup.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+}
+
+#[test]
+fn small_and_large_scenes() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    // Frames either side of the uniform buffer fast path's limit.
+    for (count, png_name) in [(10, "small_scene.png"), (1000, "large_scene.png")] {
+        vger.begin(512.0, 512.0, 1.0);
+        let paint = vger.color_paint(Color::CYAN);
+        for i in 0..count {
+            let (x, y) = ((i % 40) as f32 * 12.0, (i / 40) as f32 * 12.0);
+            vger.fill_circle([x + 6.0, y + 6.0], 5.0, paint);
+        }
+        vger.draw_overlay(|vger| {
+            let paint = vger.color_paint(Color::MAGENTA);
+            vger.fill_circle([400.0, 400.0], 20.0, paint);
+        });
+        render_test(&mut vger, &device, &queue, png_name, false);
+        assert!(png_not_black(png_name));
+
+        // Every circle is drawn, either way, with the overlay over them.
+        let last = if count == 10 { (114, 6) } else { (474, 294) };
+        assert_eq!(png_pixel(png_name, 6, 6), [0, 255, 255, 255]);
+        assert_eq!(png_pixel(png_name, last.0, last.1), [0, 255, 255, 255]);
+        assert_eq!(png_pixel(png_name, last.0 + 12, last.1), [0, 0, 0, 255]);
+        assert_eq!(png_pixel(png_name, 400, 400), [255, 0, 255, 255]);
+    }
+}
+
+#[test]
+fn indirect_draws() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    for (count, name) in [(10, "small"), (1000, "large")] {
+        // The same frame drawn directly and indirectly.
+        let mut png_names = vec![];
+        for indirect in [false, true] {
+            vger.set_indirect_draws(indirect);
+            vger.begin(512.0, 512.0, 1.0);
+            let paint = vger.color_paint(Color::CYAN);
+            for i in 0..count {
+                let (x, y) = ((i % 40) as f32 * 12.0, (i / 40) as f32 * 12.0);
+                vger.fill_rect(euclid::rect(x, y, 10.0, 10.0), 2.0, paint);
+            }
+            let png_name = format!(
+                "{}_{}.png",
+                if indirect { "indirect" } else { "direct" },
+                name
+            );
+            render_test(&mut vger, &device, &queue, &png_name, false);
+            png_names.push(png_name);
+        }
+
+        assert_eq!(png_pixel(&png_names[1], 5, 5), [0, 255, 255, 255]);
+        assert_eq!(
+            std::fs::read(&png_names[0]).unwrap(),
+            std::fs::read(&png_names[1]).unwrap()
+        );
+    }
+}
+
+#[test]
+fn packed_prims() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    vger.set_packed_prims(true);
+
+    vger.begin(512.0, 512.0, 1.0);
+    let paint = vger.color_paint(Color::CYAN);
+    for i in 0..1000 {
+        let (x, y) = ((i % 40) as f32 * 12.0, (i / 40) as f32 * 12.0);
+        if i % 2 == 0 {
+            vger.fill_circle([x + 6.0, y + 6.0], 5.0, paint);
+        } else {
+            vger.fill_rect(euclid::rect(x, y, 10.0, 10.0), 2.0, paint);
+        }
+    }
+
+    // Too large to pack, so kept at full precision.
+    vger.stroke_arc([256.0, 256.0], 5000.5, 2.0, 0.0, 0.5, paint);
+
+    // Colors out of RGBA8's range keep a full precision paint.
+    let bright = vger.color_paint(Color::new(2.0, 0.0, 0.0, 1.0));
+    vger.fill_rect(euclid::rect(300.0, 330.0, 20.0, 20.0), 0.0, bright);
+
+    vger.translate([20.0, 400.0]);
+    vger.text("packed", 32, Color::MAGENTA, None);
+
+    let png_name = "packed_prims.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert_eq!(png_pixel(png_name, 6, 6), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 17, 5), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 310, 340), [255, 0, 0, 255]);
+}
+
+#[test]
+fn deterministic() {
+    let (device, queue) = block_on(setup());
+
+    let draw = |vger: &mut Vger| {
+        vger.begin(512.0, 512.0, 1.0);
+        let paint = vger.color_paint(Color::CYAN);
+        vger.fill_circle([100.0, 100.0], 20.0, paint);
+        vger.translate([20.0, 300.0]);
+        vger.text("Same every time", 32, Color::WHITE, None);
+    };
+
+    // Glyphs cached by earlier frames don't move this one's.
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    vger.begin(512.0, 512.0, 1.0);
+    vger.text("Something else entirely", 24, Color::WHITE, None);
+    render_test(&mut vger, &device, &queue, "deterministic_other.png", false);
+
+    vger.set_deterministic(true);
+    draw(&mut vger);
+    render_test(&mut vger, &device, &queue, "deterministic_1.png", false);
+
+    // Glyphs stay cached from frame to frame.
+    draw(&mut vger);
+    assert_eq!(vger.glyph_cache_stats().glyph_misses, 0);
+    assert!(vger.glyph_cache_stats().glyph_hits > 0);
+
+    let mut fresh = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    fresh.set_deterministic(true);
+    draw(&mut fresh);
+    render_test(&mut fresh, &device, &queue, "deterministic_2.png", false);
+
+    // The same scene again after other glyphs were cached with the mode
+    // off.
+    vger.set_deterministic(false);
+    vger.begin(512.0, 512.0, 1.0);
+    vger.text(
+        "Different glyphs: qxzjkv 0123456789",
+        40,
+        Color::WHITE,
+        None,
+    );
+    render_test(
+        &mut vger,
+        &device,
+        &queue,
+        "deterministic_other2.png",
+        false,
+    );
+    vger.set_deterministic(true);
+    draw(&mut vger);
+    render_test(&mut vger, &device, &queue, "deterministic_3.png", false);
+
+    assert_eq!(
+        png_pixel("deterministic_1.png", 100, 100),
+        [0, 255, 255, 255]
+    );
+    let expected = std::fs::read("deterministic_1.png").unwrap();
+    assert_eq!(expected, std::fs::read("deterministic_2.png").unwrap());
+    assert_eq!(expected, std::fs::read("deterministic_3.png").unwrap());
+}
+
+#[test]
+fn atlas_padding() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    vger.set_atlas_padding(vger::atlas::AtlasContent::Mask, 1, true);
+
+    vger.begin(512.0, 512.0, 1.0);
+    vger.translate([20.0, 300.0]);
+    vger.text("Tightly packed glyphs", 12, Color::WHITE, None);
+
+    let png_name = "atlas_padding.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Padded glyphs are drawn, and no neighbor in the atlas bleeds in.
+    let bounds: Vec<LocalRect> = vger
+        .glyph_positions("Tightly packed glyphs", 12, None)
+        .iter()
+        .map(|r| r.translate([20.0, 300.0].into()).inflate(1.0, 1.0))
+        .collect();
+    let red = png_red_rows(png_name);
+    assert!(red.iter().flatten().any(|r| *r > 200));
+    for (y, row) in red.iter().enumerate() {
+        for (x, r) in row.iter().enumerate() {
+            let p = LocalPoint::new(x as f32 + 0.5, y as f32 + 0.5);
+            if *r != 0 {
+                assert!(bounds.iter().any(|b| b.contains(p)), "{} {}", x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn atlas_no_bleed() {
+    let (device, queue) = block_on(setup());
+
+    let format = wgpu::TextureFormat::Rgba8Unorm;
+    let mut vger = Vger::new(&device, format);
+
+    // Without padding the images are next to each other in the atlas.
+    vger.set_atlas_padding(vger::atlas::AtlasContent::Color, 0, false);
+
+    let image = |r: u8, b: u8| [r, 0, b, 255].repeat(64);
+    vger.begin(256.0, 256.0, 1.0);
+    let red = ImageKey::from_hash(&[1]);
+    vger.render_image(0.0, 0.0, &red, 8, 8, || image(255, 0));
+    vger.begin(256.0, 256.0, 1.0);
+    let blue = ImageKey::from_hash(&[2]);
+    vger.render_image_rect(
+        euclid::rect(0.0, 0.0, 256.0, 256.0),
+        &blue,
+        8,
+        8,
+        || image(0, 255),
+        1.0,
+    );
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: 256,
+            height: 256,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        label: Some("bleed_texture"),
+        view_formats: &[format],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let options = RenderOptions {
+        clear: Some(Color::gray(0.0)),
+    };
+    vger.encode_to_view(&device, &view, &queue, &options);
+
+    // Edges of the scaled image don't pick up the red image beside it.
+    for (x, y) in [(0, 128), (128, 0), (255, 128), (128, 255)] {
+        let pixel = vger.read_pixel(&device, &queue, &texture, x, y);
+        device.poll(wgpu::Maintain::Wait);
+        assert_eq!(block_on(pixel).unwrap(), [0, 0, 255, 255]);
+    }
+}
+
+#[test]
+fn image_sampling() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // Checkerboard which shimmers when minified, drawn at a third of its
+    // size, so pixel centers land on texel centers.
+    let mut checks = vec![];
+    for y in 0..64 {
+        for x in 0..64 {
+            let v = if (x + y) % 2 == 0 { 255 } else { 0 };
+            checks.extend_from_slice(&[v, v, v, 255]);
+        }
+    }
+    let checks_key = ImageKey::from_content(&checks);
+
+    // A black texel then a white one, stretched so the edge is filtered.
+    let edge = vec![0, 0, 0, 255, 255, 255, 255, 255];
+    let edge_key = ImageKey::from_content(&edge);
+
+    let samplings = [
+        ImageSampling::Linear,
+        ImageSampling::Bicubic,
+        ImageSampling::Anisotropic,
+    ];
+    for (i, sampling) in samplings.iter().enumerate() {
+        vger.save();
+        vger.set_image_sampling(*sampling);
+        vger.translate([100.0, 100.0 + 100.0 * i as f32]);
+        vger.render_image_rect(
+            euclid::rect(0.0, 0.0, 64.0 / 3.0, 64.0 / 3.0),
+            &checks_key,
+            64,
+            64,
+            || checks.clone(),
+            1.0,
+        );
+        vger.render_image_rect(
+            euclid::rect(100.0, 0.0, 100.0, 20.0),
+            &edge_key,
+            2,
+            1,
+            || edge.clone(),
+            1.0,
+        );
+        vger.restore();
+    }
+
+    let png_name = "image_sampling.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    let red = |i: u32, x: u32, y: u32| png_pixel(png_name, 100 + x, 100 + 100 * i + y)[0];
+
+    // Linear sampling picks single texels of the checkerboard, where
+    // footprint sampling averages the 3x3 texels under each pixel.
+    let linear = red(0, 10, 10);
+    assert!(linear < 16 || linear > 240);
+    let footprint = red(2, 10, 10);
+    assert!(footprint > 120 && footprint < 220);
+
+    // Between the texel centers of the edge, at 25 and 75, Catmull-Rom is
+    // steeper than the linear ramp.
+    assert!(red(1, 137, 10) + 4 < red(0, 137, 10));
+    assert!(red(1, 162, 10) > red(0, 162, 10) + 4);
+}
+
+#[test]
+fn srgb_color_atlas() {
+    let (device, queue) = block_on(setup());
+
+    let size = wgpu::Extent3d {
+        width: 512,
+        height: 512,
+        depth_or_array_layers: 1,
+    };
+
+    // Linear output samples the color atlas through an sRGB view, so the
+    // stretched edge between black and white is filtered in linear space.
+    let linear_format = wgpu::TextureFormat::Rgba16Float;
+    let ui_texture = device.create_texture(&wgpu::TextureDescriptor {
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: linear_format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        label: Some("ui_texture"),
+        view_formats: &[linear_format],
+    });
+    let ui_view = ui_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let mut vger = Vger::new_with_mode(&device, linear_format, OutputMode::Linear);
+    vger.begin(512.0, 512.0, 1.0);
+    let pixels = [[0, 0, 0, 255], [255, 255, 255, 255]].concat();
+    let key = ImageKey::from_content(&pixels);
+    vger.render_image_rect(
+        euclid::rect(0.0, 0.0, 512.0, 512.0),
+        &key,
+        2,
+        1,
+        || pixels.clone(),
+        1.0,
+    );
+    let options = RenderOptions {
+        clear: Some(Color::new(0.0, 0.0, 0.0, 0.0)),
+    };
+    vger.encode_to_view(&device, &ui_view, &queue, &options);
+
+    let texture_desc = wgpu::TextureDescriptor {
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        label: Some("render_texture"),
+        view_formats: &[wgpu::TextureFormat::Rgba8UnormSrgb],
+    };
+    let texture = device.create_texture(&texture_desc);
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let blitter = vger::blit::Blitter::new(&device, texture_desc.format);
+    blitter.blit(&device, &queue, &ui_view, &view, 1.0);
+
+    let png_name = "srgb_color_atlas.png";
+    save_png(&texture, &texture_desc, &device, &queue, png_name);
+    assert!(png_not_black(png_name));
+
+    // Halfway between black and white is linear 0.5, not sRGB 0.5.
+    let [r, g, b, a] = png_pixel(png_name, 256, 256);
+    assert!((175..200).contains(&r), "{}", r);
+    assert_eq!([r, r, 255], [g, b, a]);
+    assert_eq!(png_pixel(png_name, 10, 10), [0, 0, 0, 255]);
+    assert_eq!(png_pixel(png_name, 500, 10), [255, 255, 255, 255]);
+}
+
+#[test]
+fn flush() {
+    let (device, queue) = block_on(setup());
+
+    // A small atlas, so the text fills it several times.
+    let glyph_cache = SharedGlyphCache::with_atlas_size(&device, 256);
+    let mut vger = Vger::with_glyph_cache(
+        &device,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        OutputMode::Color,
+        glyph_cache.clone(),
+    );
+
+    let texture_desc = wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: 512,
+            height: 512,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        label: Some("render_texture"),
+        view_formats: &[wgpu::TextureFormat::Rgba8UnormSrgb],
+    };
+    let texture = device.create_texture(&texture_desc);
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    // Many sizes of text, flushing whenever the atlases fill up.
+    vger.begin(512.0, 512.0, 1.0);
+    let paint = vger.color_paint(Color::CYAN);
+    vger.fill_circle([256.0, 256.0], 100.0, paint);
+    vger.flush(&device, &queue, &view);
+    let generation = glyph_cache.lock().generation();
+    for size in 8..40 {
+        vger.save();
+        vger.translate([10.0, (size - 8) as f32 * 15.0]);
+        vger.text("The quick brown fox", size, Color::WHITE, None);
+        vger.restore();
+        if vger.should_flush() {
+            vger.flush(&device, &queue, &view);
+        }
+    }
+
+    let options = RenderOptions { clear: None };
+    vger.encode_to_view(&device, &view, &queue, &options);
+
+    let png_name = "flush.png";
+    save_png(&texture, &texture_desc, &device, &queue, png_name);
+    assert_ne!(glyph_cache.lock().generation(), generation);
+
+    // The circle drawn before the atlases were cleared is still there,
+    // right of the text.
+    assert_eq!(png_pixel(png_name, 340, 256), [0, 255, 255, 255]);
+}
+
+#[test]
+fn shared_glyph_cache() {
+    use vger::atlas::AtlasRegions;
+
+    let (device, queue) = block_on(setup());
+
+    let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let mut first = Vger::new(&device, format);
+    let mut second = Vger::with_glyph_cache(
+        &device,
+        format,
+        OutputMode::Color,
+        first.shared_glyph_cache(),
+    );
+
+    first.begin(512.0, 512.0, 1.0);
+    first.translate([20.0, 200.0]);
+    first.text("Shared glyphs", 32, Color::WHITE, None);
+    render_test(
+        &mut first,
+        &device,
+        &queue,
+        "shared_glyphs_first.png",
+        false,
+    );
+
+    // The second renderer reuses the first's glyphs.
+    let usage = first.glyph_cache().mask_atlas.usage();
+    second.begin(512.0, 512.0, 1.0);
+    second.translate([20.0, 200.0]);
+    second.text("Shared glyphs", 32, Color::WHITE, None);
+    assert_eq!(second.glyph_cache().mask_atlas.usage(), usage);
+
+    let png_name = "shared_glyphs_second.png";
+    render_test(&mut second, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // A cache cleared by the other renderer mid-frame is caught, and the
+    // first's layer isn't rendered into regions which may be reused.
+    first.set_validation(true);
+    first.begin(512.0, 512.0, 1.0);
+    first.cached_layer(1, [100.0, 100.0], |vger| {
+        let paint = vger.color_paint(Color::CYAN);
+        vger.fill_rect(euclid::rect(0.0, 0.0, 100.0, 100.0), 0.0, paint);
+    });
+    second.trim(&device);
+    render_test(
+        &mut first,
+        &device,
+        &queue,
+        "shared_glyphs_stale.png",
+        false,
+    );
+    assert_eq!(
+        first.take_validation_errors(),
+        vec![ValidationError::StaleAtlas]
+    );
+}
+
+/// Packs regions along the top row of an atlas, in 16 pixel cells.
+struct RowPacker {
+    next: i32,
+    freed: Vec<i32>,
+}
+
+impl vger::atlas::RectPackerImpl for RowPacker {
+    fn pack(&mut self, width: i32, height: i32) -> Option<rect_packer::Rect> {
+        if width > 16 || height > 16 {
+            return None;
+        }
+        let x = self.freed.pop().unwrap_or_else(|| {
+            self.next += 16;
+            self.next - 16
+        });
+        Some(rect_packer::Rect::new(x, 0, width, height))
+    }
+
+    fn free(&mut self, rect: rect_packer::Rect) -> bool {
+        self.freed.push(rect.x);
+        true
+    }
+
+    fn reset(&mut self) {
+        self.next = 0;
+        self.freed.clear();
+    }
+}
+
+#[test]
+fn atlas_custom_packer() {
+    use vger::atlas::{Atlas, AtlasContent, AtlasRegions, MemoryAtlasBackend};
+
+    let mut atlas: Atlas<MemoryAtlasBackend> = Atlas::with_size(&(), AtlasContent::Mask, 64);
+    atlas.set_packer(Box::new(RowPacker {
+        next: 0,
+        freed: vec![],
+    }));
+
+    let a = atlas.add_region(&[1; 4], 2, 2).unwrap();
+    let b = atlas.add_region(&[2; 4], 2, 2).unwrap();
+    assert_eq!((a.x, a.y, b.x, b.y), (0, 0, 16, 0));
+    assert!(atlas.add_region(&[0; 400], 20, 20).is_none());
+
+    atlas.flush();
+    let pixel =
+        |atlas: &Atlas<MemoryAtlasBackend>, x: usize, y: usize| atlas.backend.pixels[y * 64 + x];
+    assert_eq!(pixel(&atlas, 1, 1), 1);
+    assert_eq!(pixel(&atlas, 16, 0), 2);
+    assert_eq!(pixel(&atlas, 2, 0), 0);
+
+    // Freed cells are reused.
+    let usage = atlas.usage();
+    atlas.free_region(a);
+    assert!(atlas.usage() < usage);
+    assert_eq!(atlas.add_region(&[3; 4], 2, 2).unwrap().x, 0);
+    atlas.flush();
+    assert_eq!(pixel(&atlas, 0, 0), 3);
+
+    // Clearing resets the packer, which keeps packing.
+    atlas.clear();
+    atlas.flush();
+    assert!(atlas.backend.pixels.iter().all(|p| *p == 0));
+    assert_eq!(atlas.add_region(&[4; 4], 2, 2).unwrap().x, 0);
+}
+
+#[test]
+fn display_list() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    // Record on a worker thread while the device stays here.
+    let list = std::thread::spawn(|| {
+        let mut list = DisplayList::new();
+        list.save();
+        list.translate([100.0, 100.0]);
+        list.fill_rect(euclid::rect(0.0, 0.0, 200.0, 100.0), 10.0, Color::CYAN);
+        list.fill_circle([100.0, 250.0], 50.0, Color::MAGENTA);
+        list.move_to([0.0, 350.0]);
+        list.line_to([200.0, 350.0]);
+        list.quad_to([100.0, 250.0], [0.0, 350.0]);
+        list.fill(Color::WHITE);
+        list.text("Recorded", 32, Color::WHITE, None);
+        list.restore();
+        list
+    })
+    .join()
+    .unwrap();
+
+    vger.begin(512.0, 512.0, 1.0);
+    vger.draw_display_list(&list);
+
+    let png_name = "display_list.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Everything is drawn under the recorded translation.
+    assert_eq!(png_pixel(png_name, 200, 150), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 200, 350), [255, 0, 255, 255]);
+    assert_eq!(png_pixel(png_name, 200, 430), [255, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 50, 50), [0, 0, 0, 255]);
+}
+
+#[test]
+fn remote_scene() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    let mut server = SceneServer::bind("127.0.0.1:0").unwrap();
+
+    // The client only uses vger-core types.
+    let mut list = DisplayList::new();
+    list.fill_rect(euclid::rect(100.0, 100.0, 300.0, 200.0), 20.0, Color::CYAN);
+    list.text("Remote", 32, Color::WHITE, None);
+    list.restore();
+    let mut client = std::net::TcpStream::connect(server.local_addr()).unwrap();
+    wire::write_frame(&mut client, &list).unwrap();
+
+    while server.clients().next().is_none() {
+        server.poll();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+
+    vger.begin(512.0, 512.0, 1.0);
+    server.draw(&mut vger);
+
+    let png_name = "remote_scene.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+}
+
+#[test]
+fn debug_groups() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+    let cyan = vger.color_paint(Color::CYAN);
+    let magenta = vger.color_paint(Color::MAGENTA);
+    vger.fill_rect(euclid::rect(0.0, 0.0, 100.0, 100.0), 0.0, cyan);
+
+    vger.push_debug_group("sidebar");
+    vger.fill_rect(euclid::rect(0.0, 100.0, 100.0, 300.0), 0.0, magenta);
+    vger.push_debug_group("button");
+    vger.fill_circle([50.0, 200.0], 30.0, cyan);
+    vger.pop_debug_group();
+
+    // Higher z indices are drawn later, in their own groups.
+    vger.set_z_index(1);
+    vger.fill_circle([200.0, 200.0], 30.0, magenta);
+    vger.set_z_index(0);
+    vger.pop_debug_group();
+
+    vger.fill_rect(euclid::rect(200.0, 300.0, 100.0, 100.0), 10.0, cyan);
+
+    let png_name = "debug_groups.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Groups don't change what's drawn.
+    let cyan = [0, 255, 255, 255];
+    let magenta = [255, 0, 255, 255];
+    assert_eq!(png_pixel(png_name, 50, 50), cyan);
+    assert_eq!(png_pixel(png_name, 50, 300), magenta);
+    assert_eq!(png_pixel(png_name, 50, 200), cyan);
+    assert_eq!(png_pixel(png_name, 200, 200), magenta);
+    assert_eq!(png_pixel(png_name, 250, 350), cyan);
+    assert_eq!(png_pixel(png_name, 150, 150), [0, 0, 0, 255]);
+}
+
+#[test]
+fn split_draws() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    vger.set_split_draws(true);
+
+    vger.begin(512.0, 512.0, 1.0);
+    let cyan = vger.color_paint(Color::CYAN);
+    vger.fill_rect(euclid::rect(0.0, 0.0, 100.0, 100.0), 0.0, cyan);
+    vger.push_debug_group("labels");
+    vger.translate([20.0, 200.0]);
+    vger.text("Split", 32, Color::WHITE, None);
+    vger.pop_debug_group();
+    vger.fill_circle([300.0, 300.0], 50.0, cyan);
+
+    let png_name = "split_draws.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Draws split around the group still draw everything, in order.
+    assert_eq!(png_pixel(png_name, 50, 50), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 300, 300), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 200, 100), [0, 0, 0, 255]);
+    let bounds = vger
+        .text_bounds("Split", 32, None)
+        .translate([20.0, 200.0].into());
+    let red = png_red_rows(png_name);
+    assert!((bounds.min_y() as usize..bounds.max_y() as usize)
+        .any(|y| red[y][bounds.min_x() as usize..bounds.max_x() as usize].contains(&255)));
+}
+
+#[test]
+fn morph() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+    let paint = vger.color_paint(Color::CYAN);
+
+    // An avatar growing into a card, in steps down the canvas.
+    let avatar = Morph::circle([60.0, 60.0].into(), 30.0);
+    let card = Morph::rounded_rect(euclid::rect(20.0, 20.0, 300.0, 80.0), 12.0);
+    for i in 0..5 {
+        let shape = avatar.lerp(&card, i as f32 / 4.0);
+        vger.save();
+        vger.translate([0.0, i as f32 * 100.0]);
+        vger.fill_morph(&shape, paint);
+        vger.stroke_morph(&shape, 2.0, paint);
+        vger.restore();
+    }
+
+    let png_name = "morph.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // The circle, halfway and the card.
+    let cyan = [0, 255, 255, 255];
+    let black = [0, 0, 0, 255];
+    assert_eq!(png_pixel(png_name, 60, 60), cyan);
+    assert_eq!(png_pixel(png_name, 35, 35), black);
+    assert_eq!(png_pixel(png_name, 95, 60), black);
+    assert_eq!(png_pixel(png_name, 190, 260), cyan);
+    assert_eq!(png_pixel(png_name, 250, 260), black);
+    assert_eq!(png_pixel(png_name, 300, 460), cyan);
+    assert_eq!(png_pixel(png_name, 21, 421), black);
+}
+
+#[test]
+fn label_scrim() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // A busy map behind the label.
+    let draw_map = |vger: &mut Vger| {
+        let cyan = vger.color_paint(Color::CYAN);
+        let magenta = vger.color_paint(Color::MAGENTA);
+        for i in 0..16 {
+            let paint = if i % 2 == 0 { cyan } else { magenta };
+            vger.fill_rect(euclid::rect(i as f32 * 32.0, 0.0, 16.0, 512.0), 0.0, paint);
+        }
+    };
+    draw_map(&mut vger);
+
+    vger.translate([100.0, 250.0]);
+    let style = LabelScrim {
+        text_size: 32,
+        ..LabelScrim::default()
+    };
+    vger.draw_label_scrim(1, "Label", &style, |vger| {
+        vger.translate([-100.0, -250.0]);
+        draw_map(vger);
+    });
+
+    let png_name = "label_scrim.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // The map shows as is outside the scrim.
+    assert_eq!(png_pixel(png_name, 8, 50), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 104, 50), [255, 0, 255, 255]);
+
+    // Inside, the same stripe is blurred and tinted darker.
+    let rect = vger
+        .text_bounds("Label", 32, None)
+        .translate([100.0, 250.0].into());
+    let y = (rect.min_y() - 3.0) as u32;
+    let [r, g, b, _] = png_pixel(png_name, 104, y);
+    assert!(r == b && r > 0 && r < 255 && g < r, "{:?}", [r, g, b]);
+}
+
+#[test]
+fn draw_table() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+    let dark = vger.color_paint(Color::new(0.1, 0.1, 0.1, 1.0));
+    let light = vger.color_paint(Color::new(0.2, 0.2, 0.2, 1.0));
+    let border = TableBorder {
+        width: 1.0,
+        paint: vger.color_paint(Color::CYAN),
+        outer: true,
+    };
+
+    vger.draw_table(
+        [20.0, 20.0],
+        &[120.0, 80.0, 80.0, 160.0],
+        &[24.0; 16],
+        &[dark, light],
+        Some(&border),
+    );
+
+    let png_name = "draw_table.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Rows alternate between the two paints, darker first.
+    let [r, g, b, _] = png_pixel(png_name, 80, 30);
+    assert!(r == g && g == b, "{:?}", [r, g, b]);
+    assert!(r < png_pixel(png_name, 80, 54)[0]);
+    assert_eq!(png_pixel(png_name, 80, 78)[0], r);
+    assert_eq!(png_pixel(png_name, 480, 30), [0, 0, 0, 255]);
+    assert_eq!(png_pixel(png_name, 80, 420), [0, 0, 0, 255]);
+
+    // One gridline at each column and row edge, outside ones included.
+    let green = png_channel_rows(png_name, 1);
+    let red = png_red_rows(png_name);
+    let lines = |pixels: Vec<(u8, u8)>| -> Vec<usize> {
+        let mut lines: Vec<usize> = vec![];
+        for (i, (r, g)) in pixels.into_iter().enumerate() {
+            if g > r && lines.last().map_or(true, |last| i > last + 1) {
+                lines.push(i);
+            }
+        }
+        lines
+    };
+    let columns = lines((0..512).map(|x| (red[30][x], green[30][x])).collect());
+    assert_eq!(columns.len(), 5);
+    for (x, edge) in columns.iter().zip([20, 140, 220, 300, 460]) {
+        assert!((*x as i32 - edge).abs() <= 1, "{} {}", x, edge);
+    }
+    let rows = lines((0..512).map(|y| (red[y][80], green[y][80])).collect());
+    assert_eq!(rows.len(), 17);
+    for (i, y) in rows.iter().enumerate() {
+        assert!((*y as i32 - (20 + 24 * i as i32)).abs() <= 1, "{} {}", i, y);
+    }
+}
+
+#[test]
+fn text_line_cache() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    let lines: Vec<String> = (0..32)
+        .map(|i| format!("{:4} let x{} = {};", i, i, i * i))
+        .collect();
+
+    // Scroll by a line each frame. Lines still on screen reuse their
+    // layouts.
+    for frame in 0..2 {
+        vger.begin(512.0, 512.0, 1.0);
+        for (row, line) in lines[frame..frame + 24].iter().enumerate() {
+            vger.save();
+            vger.translate([8.0, 500.0 - row as f32 * 20.0]);
+            vger.text_line(line, 16, Color::WHITE, None);
+            vger.restore();
+        }
+    }
+    let stats = vger.line_cache_mut().stats();
+    assert_eq!(stats.misses, 25);
+    assert_eq!(stats.hits, 23);
+
+    vger.line_cache_mut().invalidate(&lines[1]);
+    assert_eq!(vger.line_cache_mut().len(), 24);
+
+    let png_name = "text_line_cache.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+}
+
+#[test]
+fn baseline_snapping() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    vger.set_baseline_snapping(true);
+
+    vger.begin(512.0, 512.0, 2.0);
+
+    // Lines scrolled by fractions of a pixel land on whole pixel rows.
+    for i in 0..8 {
+        vger.save();
+        vger.translate([32.0 + i as f32 * 0.3, 64.0 + i as f32 * 48.3]);
+        vger.text("Scrolling text", 24, Color::WHITE, None);
+        vger.restore();
+    }
+
+    let png_name = "baseline_snapping.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // At one device pixel per texel, lines at fractional offsets land on
+    // whole rows, so they render identically.
+    vger.begin(512.0, 512.0, 1.0);
+    for i in 0..8 {
+        vger.save();
+        vger.translate([32.0, 64.0 + i as f32 * 48.3]);
+        vger.text("Scrolling text", 24, Color::WHITE, None);
+        vger.restore();
+    }
+
+    let png_name = "baseline_snapping_1x.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    // Runs of rows with anything drawn in them, one per line.
+    let red = png_red_rows(png_name);
+    let mut lines: Vec<Vec<Vec<u8>>> = vec![];
+    let mut in_line = false;
+    for row in red {
+        let drawn = row.iter().any(|r| *r != 0);
+        if drawn && !in_line {
+            lines.push(vec![]);
+        }
+        if drawn {
+            lines.last_mut().unwrap().push(row);
+        }
+        in_line = drawn;
+    }
+    assert_eq!(lines.len(), 8);
+    for line in &lines[1..] {
+        assert!(line == &lines[0]);
+    }
+}
+
+#[test]
+fn baseline_snapping_lines() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    vger.set_baseline_snapping(true);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // Lines a fractional height apart each land on a pixel row, so the
+    // feet of their stems are fully covered rather than antialiased.
+    vger.translate([32.0, 400.37]);
+    vger.set_line_height(LineHeight::Absolute(60.3));
+    vger.text("H\nH\nH\nH", 48, Color::WHITE, None);
+
+    let png_name = "baseline_snapping_lines.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    // Rows crossing a stem are lit to the full, except each H's top row,
+    // whose cap height needn't be a whole pixel.
+    let partial = png_red_rows(png_name)
+        .iter()
+        .map(|row| row.iter().copied().max().unwrap())
+        .filter(|max| (16..240).contains(max))
+        .count();
+    assert!(partial <= 4, "{} partially covered rows", partial);
+}
+
+#[test]
+fn glyph_cache_file() {
+    let (device, queue) = block_on(setup());
+
+    let path = std::env::temp_dir().join("vger_glyph_cache_test.bin");
+    let text = "Cached glyphs";
+
+    // Font ids differ between runs, so the file names fonts by a key.
+    let mut fonts = cosmic_text::fontdb::Database::new();
+    fonts.load_font_data(include_bytes!("../src/fonts/Anodina-Regular.ttf").to_vec());
+    let font_id = fonts.faces().next().unwrap().id;
+    let font_key = |id| (id == font_id).then(|| 7);
+
+    // A square 20 pixels wide, sitting on the baseline.
+    let square = || {
+        let mut image = cosmic_text::SwashImage::new();
+        image.placement = swash::zeno::Placement {
+            left: 0,
+            top: 20,
+            width: 20,
+            height: 20,
+        };
+        image.data = vec![255; 400];
+        image
+    };
+    let subpx = (
+        cosmic_text::SubpixelBin::Zero,
+        cosmic_text::SubpixelBin::Zero,
+    );
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    vger.begin(512.0, 512.0, 1.0);
+    vger.text(text, 32, Color::WHITE, None);
+    let paint = vger.color_paint(Color::WHITE);
+    vger.render_glyph(0.0, 0.0, font_id, 1, 20, subpx, square, paint);
+    vger.save_glyph_cache(&path, font_key).unwrap();
+
+    // A new renderer starts with the glyphs already rasterized.
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    let loaded = vger.load_glyph_cache(&path, |key| (key == 7).then(|| font_id));
+    assert!(loaded.unwrap() > 1);
+    std::fs::remove_file(&path).unwrap();
+
+    vger.begin(512.0, 512.0, 1.0);
+    vger.translate([32.0, 256.0]);
+    vger.text(text, 32, Color::WHITE, None);
+    let paint = vger.color_paint(Color::WHITE);
+    let cached = || -> cosmic_text::SwashImage { panic!("rasterized a loaded glyph") };
+    vger.render_glyph(0.0, 100.0, font_id, 1, 20, subpx, cached, paint);
+
+    let png_name = "glyph_cache_file.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+    assert_eq!(png_pixel(png_name, 42, 346), [255, 255, 255, 255]);
+}
+
+#[test]
+fn text_emoji() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // Emoji come from the system's emoji font, when there is one.
+    vger.translate([32.0, 256.0]);
+    vger.text("Emoji 😀🚀", 32, Color::WHITE, None);
+
+    let png_name = "text_emoji.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // The word is drawn, and so are the emoji when a font has them.
+    let word = vger
+        .text_bounds("Emoji", 32, None)
+        .translate([32.0, 256.0].into());
+    let red = png_red_rows(png_name);
+    let (top, bottom) = (word.min_y() as usize, word.max_y() as usize);
+    let (left, right) = (word.min_x() as usize, word.max_x() as usize);
+    assert!(red[top..bottom]
+        .iter()
+        .any(|row| row[left..right].contains(&255)));
+
+    let has_emoji = vger::emoji::system_emoji_font_paths()
+        .into_iter()
+        .filter_map(|path| EmojiFont::load(path).ok())
+        .any(|font| font.has_char('\u{1F600}'));
+    if has_emoji {
+        let green = png_channel_rows(png_name, 1);
+        let blue = png_channel_rows(png_name, 2);
+        let lit = |y: usize, x: usize| red[y][x] != 0 || green[y][x] != 0 || blue[y][x] != 0;
+        assert!((0..512).any(|y| (right + 16..512).any(|x| lit(y, x))));
+    }
+}
+
+#[test]
+fn text_whitespace() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    vger.translate([32.0, 256.0]);
+    let marks = WhitespaceMarks {
+        color: Color::CYAN,
+        ..WhitespaceMarks::default()
+    };
+    vger.text_with_whitespace(
+        "fn main() {\n\tlet x = 1;\n}",
+        24,
+        Color::WHITE,
+        None,
+        &marks,
+    );
+
+    let png_name = "text_whitespace.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Cyan marks are drawn among the white text, and only near it.
+    let bounds = vger
+        .text_bounds("fn main() {\n\tlet x = 1;\n}", 24, None)
+        .translate([32.0, 256.0].into())
+        .inflate(24.0, 8.0);
+    let red = png_red_rows(png_name);
+    let green = png_channel_rows(png_name, 1);
+    let blue = png_channel_rows(png_name, 2);
+    let mut marks = 0;
+    for y in 0..512 {
+        for x in 0..512 {
+            let (r, g, b) = (red[y][x], green[y][x], blue[y][x]);
+            if r == 0 && g > 0 && g == b {
+                marks += 1;
+                let p = LocalPoint::new(x as f32, y as f32);
+                assert!(bounds.contains(p), "{} {}", x, y);
+            }
+        }
+    }
+    assert!(marks > 0);
+}
+
+#[test]
+fn text_justify() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paragraph = "Justified text stretches the gaps between words so both edges \
+                     of the paragraph line up, except on its last line.";
+    for (i, align) in [TextAlign::Justify, TextAlign::Center, TextAlign::Right]
+        .iter()
+        .enumerate()
+    {
+        vger.save();
+        vger.set_text_align(*align);
+        vger.translate([32.0, 450.0 - i as f32 * 150.0]);
+        vger.text(paragraph, 18, Color::WHITE, Some(440.0));
+        vger.restore();
+    }
+
+    let png_name = "text_justify.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    let aligned = |vger: &mut Vger, align: TextAlign| {
+        vger.save();
+        vger.set_text_align(align);
+        let lines = vger.line_metrics(paragraph, 18, Some(440.0));
+        vger.restore();
+        lines
+    };
+
+    // Justified lines but the last reach the right edge.
+    let lines = aligned(&mut vger, TextAlign::Justify);
+    assert!(lines.len() > 1);
+    let (last, rest) = lines.split_last().unwrap();
+    for line in rest {
+        assert!(
+            (line.bounds.max_x() - 440.0).abs() < 2.0,
+            "{:?}",
+            line.bounds
+        );
+    }
+    assert!(last.bounds.max_x() < 400.0, "{:?}", last.bounds);
+
+    // Centered lines are centered, and right aligned ones end at the edge.
+    for line in aligned(&mut vger, TextAlign::Center) {
+        let center = line.bounds.center().x;
+        assert!((center - 220.0).abs() < 8.0, "{:?}", line.bounds);
+    }
+    for line in aligned(&mut vger, TextAlign::Right) {
+        assert!(
+            (line.bounds.max_x() - 440.0).abs() < 8.0,
+            "{:?}",
+            line.bounds
+        );
+    }
+
+    // Nothing is drawn past the right edge.
+    let red = png_red_rows(png_name);
+    assert!(red
+        .iter()
+        .all(|row| row[32 + 442..].iter().all(|r| *r == 0)));
+}
+
+#[test]
+fn text_spacing() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let text = "Line height sets the distance between baselines.\n\
+                Paragraph spacing adds space after each line break.";
+    vger.translate([32.0, 450.0]);
+    vger.save();
+    vger.set_line_height(LineHeight::Absolute(30.0));
+    vger.set_paragraph_spacing(12.0);
+    vger.text(text, 18, Color::WHITE, Some(300.0));
+    vger.restore();
+
+    vger.translate([0.0, -250.0]);
+    vger.set_line_height(LineHeight::Multiple(1.5));
+    vger.text(text, 18, Color::WHITE, Some(300.0));
+
+    let png_name = "text_spacing.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Baselines are the line height apart, with the paragraph spacing
+    // after hard breaks.
+    let steps = |vger: &mut Vger, text: &str, max_width: Option<f32>| -> Vec<f32> {
+        let ys: Vec<f32> = vger
+            .glyph_positions(text, 18, max_width)
+            .iter()
+            .filter(|r| r.height() > 0.0)
+            .map(|r| r.min_y())
+            .collect();
+        ys.windows(2).map(|w| (w[1] - w[0]).abs()).collect()
+    };
+    vger.save();
+    vger.set_line_height(LineHeight::Absolute(30.0));
+    vger.set_paragraph_spacing(12.0);
+    for step in steps(&mut vger, "x x x", Some(10.0)) {
+        assert!((step - 30.0).abs() < 1.0, "{}", step);
+    }
+    for step in steps(&mut vger, "x\nx\nx", None) {
+        assert!((step - 42.0).abs() < 1.0, "{}", step);
+    }
+    vger.restore();
+
+    // Multiples scale the font's own line height.
+    let soft = steps(&mut vger, "x x", Some(10.0));
+    let hard = steps(&mut vger, "x\nx", None);
+    assert_eq!(soft.len(), 1);
+    assert!(
+        soft[0] > 18.0 && (soft[0] - hard[0]).abs() < 1.0,
+        "{:?}",
+        soft
+    );
+}
+
+#[test]
+fn text_indents() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    vger.translate([32.0, 450.0]);
+    vger.save();
+    vger.set_indents(24.0, 0.0);
+    vger.text(
+        "The first line of each paragraph is indented, as in books.\n\
+         Wrapped lines start at the left edge.",
+        18,
+        Color::WHITE,
+        Some(300.0),
+    );
+    vger.restore();
+
+    vger.translate([0.0, -200.0]);
+    vger.set_indents(0.0, 20.0);
+    vger.text(
+        "\u{2022}  A hanging indent lines wrapped lines up after the bullet.",
+        18,
+        Color::WHITE,
+        Some(300.0),
+    );
+
+    let png_name = "text_indents.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // First lines of paragraphs are indented, wrapped lines aren't.
+    let text = "The first line of each paragraph is indented, as in books.\n\
+                Wrapped lines start at the left edge.";
+    vger.save();
+    vger.set_indents(24.0, 0.0);
+    let lines = vger.line_metrics(text, 18, Some(300.0));
+    vger.restore();
+    let indented = |lines: &[LineMetrics]| -> Vec<bool> {
+        lines
+            .iter()
+            .map(|line| line.bounds.min_x() >= 16.0)
+            .collect()
+    };
+    let first = indented(&lines);
+    assert!(first[0] && !first[1], "{:?}", first);
+    assert_eq!(first.iter().filter(|i| **i).count(), 2);
+
+    // Hanging indents are the other way around.
+    let lines = vger.line_metrics(
+        "\u{2022}  A hanging indent lines wrapped lines up after the bullet.",
+        18,
+        Some(300.0),
+    );
+    let hanging = indented(&lines);
+    assert!(hanging.len() > 1);
+    assert!(!hanging[0] && hanging[1..].iter().all(|i| *i));
+}
+
+#[test]
+fn text_inline_objects() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    vger.translate([32.0, 400.0]);
+    let rects = vger.text_with_objects(
+        "Inline \u{FFFC} icons and a tall \u{FFFC} box flow with the text.",
+        18,
+        Color::WHITE,
+        Some(300.0),
+        &[
+            InlineObject::new(18.0, 18.0, -3.0),
+            InlineObject::new(40.0, 48.0, 0.0),
+        ],
+    );
+    assert_eq!(rects.len(), 2);
+    assert!(rects[1].min_x() > rects[0].max_x() || rects[1].min_y() < rects[0].min_y());
+
+    let paint = vger.color_paint(Color::CYAN);
+    for rect in &rects {
+        vger.fill_rect(*rect, 3.0, paint);
+    }
+
+    let png_name = "text_inline_objects.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // The boxes are drawn where the objects were placed, at their size.
+    for (rect, object_size) in rects.iter().zip([[18.0, 18.0], [40.0, 48.0]]) {
+        assert_eq!([rect.width(), rect.height()], object_size);
+        let center = rect.center() + euclid::vec2(32.0, 400.0);
+        let pixel = png_pixel(png_name, center.x as u32, center.y as u32);
+        assert_eq!(pixel, [0, 255, 255, 255]);
+    }
+}
+
+#[test]
+fn text_ruby() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // The built-in font lacks kana and kanji, so this checks placement
+    // with Latin text.
+    let text = "ruby over a base, and wider ruby over x.";
+    let base = text.find("base").unwrap();
+    let x = text.rfind('x').unwrap();
+    vger.translate([32.0, 450.0]);
+    vger.text_with_ruby(
+        text,
+        24,
+        Color::WHITE,
+        Some(400.0),
+        &[
+            Ruby::new(base..base + 4, "annotation"),
+            Ruby::new(x..x + 1, "overhanging"),
+        ],
+    );
+
+    let png_name = "text_ruby.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // The same text without ruby draws less, over fewer rows, as lines
+    // with ruby make room for it.
+    vger.begin(512.0, 512.0, 1.0);
+    vger.translate([32.0, 450.0]);
+    vger.text(text, 24, Color::WHITE, Some(400.0));
+    let plain_name = "text_ruby_plain.png";
+    render_test(&mut vger, &device, &queue, plain_name, false);
+
+    let extent = |name: &str| {
+        let red = png_red_rows(name);
+        let rows: Vec<usize> = (0..red.len())
+            .filter(|y| red[*y].iter().any(|r| *r != 0))
+            .collect();
+        let lit = red.iter().flatten().filter(|r| **r != 0).count();
+        (rows[rows.len() - 1] - rows[0], lit)
+    };
+    let (ruby_height, ruby_lit) = extent(png_name);
+    let (plain_height, plain_lit) = extent(plain_name);
+    assert!(
+        ruby_height >= plain_height + 12,
+        "{} {}",
+        ruby_height,
+        plain_height
+    );
+    assert!(
+        ruby_lit > plain_lit + plain_lit / 20,
+        "{} {}",
+        ruby_lit,
+        plain_lit
+    );
+}
+
+#[test]
+fn text_font_features() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    vger.translate([32.0, 450.0]);
+    vger.set_font_features(&[FontFeature::on(b"tnum"), FontFeature::off(b"liga")]);
+    for (i, number) in ["1,111.11", "8,888.88", "fifty"].iter().enumerate() {
+        vger.save();
+        vger.translate([0.0, -40.0 * i as f32]);
+        vger.text(number, 24, Color::WHITE, None);
+        vger.restore();
+    }
+
+    // Features only for part of the text.
+    vger.translate([0.0, -160.0]);
+    vger.set_font_features(&[FontFeature::on(b"smcp").with_range(0..5)]);
+    vger.text_line("small caps", 24, Color::WHITE, None);
+
+    let png_name = "text_font_features.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Tabular figures are all as wide, and without ligatures each letter
+    // of "fifty" keeps its glyph.
+    vger.set_font_features(&[FontFeature::on(b"tnum"), FontFeature::off(b"liga")]);
+    let ones = vger.text_bounds("1,111.11", 24, None);
+    let eights = vger.text_bounds("8,888.88", 24, None);
+    assert!(
+        (ones.max_x() - eights.max_x()).abs() < 2.0,
+        "{:?} {:?}",
+        ones,
+        eights
+    );
+    let glyphs = vger.glyph_positions("fifty", 24, None);
+    assert_eq!(glyphs.iter().filter(|r| r.height() > 0.0).count(), 5);
+}
+
+#[test]
+fn text_size_adjust() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    vger.translate([32.0, 450.0]);
+    vger.text("Unadjusted x-height \u{1F600}", 24, Color::WHITE, None);
+
+    vger.translate([0.0, -60.0]);
+    vger.set_font_size_adjust(Some(0.6));
+    let adjusted = vger.text_bounds("x", 24, None);
+    vger.text("Adjusted x-height \u{1F600}", 24, Color::WHITE, None);
+    vger.set_font_size_adjust(None);
+    assert!(adjusted.height() > vger.text_bounds("x", 24, None).height());
+
+    // The adjusted x-height is 0.6 of the size, give or take antialiasing.
+    assert!((14.0..=17.0).contains(&adjusted.height()), "{:?}", adjusted);
+
+    let png_name = "text_size_adjust.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+}
+
+#[test]
+fn text_emoji_sequences() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let text = "a\u{1F469}\u{200D}\u{1F469}\u{200D}\u{1F467}b\u{1F44D}\u{1F3FD}";
+    let clusters = vger.glyph_clusters(text, 32, None);
+    let ranges: Vec<_> = clusters.iter().map(|c| c.text_range.clone()).collect();
+    assert_eq!(ranges, [0..1, 1..19, 19..20, 20..28]);
+    assert_eq!(clusters[1].glyphs, 1..6);
+
+    vger.translate([32.0, 400.0]);
+    vger.text(text, 32, Color::WHITE, None);
+
+    let png_name = "text_emoji_sequences.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+}
+
+#[cfg(feature = "hyphenate")]
+#[test]
+fn text_hyphenate() {
+    use vger::hyphenation::{Language, Load, Standard};
+
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+    vger.set_hyphenation(Some(Standard::from_embedded(Language::EnglishUS).unwrap()));
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    vger.translate([32.0, 450.0]);
+    vger.text(
+        "Hyphenation improves the layout of extraordinarily narrow columns.",
+        24,
+        Color::WHITE,
+        Some(150.0),
+    );
+
+    let png_name = "text_hyphenate.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+
+    // Every line fits, though some words are wider than the column.
+    let lines = vger.line_metrics(
+        "Hyphenation improves the layout of extraordinarily narrow columns.",
+        24,
+        Some(150.0),
+    );
+    assert!(lines.len() > 3);
+    for line in &lines {
+        assert!(line.bounds.max_x() <= 151.0, "{:?}", line.bounds);
+    }
+    let red = png_red_rows(png_name);
+    assert!(red
+        .iter()
+        .all(|row| row[32 + 152..].iter().all(|r| *r == 0)));
+}
+
+#[cfg(feature = "numbers")]
+#[test]
+fn text_numbers() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // Past 2^53, so a round trip through f64 would change the last digit.
+    let label = NumberFormat::default().format_int(9_007_199_254_740_993);
+    assert_eq!(label, "9,007,199,254,740,993");
+
+    let devanagari = NumberFormat::for_locale("mr").format_int(-1234567);
+    assert_eq!(devanagari, "-१२,३४,५६७");
+
+    // Formatted numbers lay out like the same text typed in.
+    assert_eq!(
+        vger.text_bounds(&label, 24, None),
+        vger.text_bounds("9,007,199,254,740,993", 24, None)
+    );
+
+    vger.translate([32.0, 100.0]);
+    vger.text(&label, 24, Color::WHITE, None);
+    vger.translate([0.0, 100.0]);
+    vger.text(&devanagari, 24, Color::WHITE, None);
+
+    let png_name = "text_numbers.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+}
+
+#[test]
+fn glyph_cache_stats() {
+    let (device, _queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+    vger.text("aaa", 24, Color::WHITE, None);
+    let stats = vger.glyph_cache_stats();
+    assert_eq!(stats.glyph_misses, 1);
+    assert_eq!(stats.glyph_hits, 2);
+
+    // Counts start over each frame.
+    vger.begin(512.0, 512.0, 1.0);
+    assert_eq!(vger.glyph_cache_stats(), GlyphCacheStats::default());
+}
+
+#[test]
+fn subpixel_binning() {
+    let (device, queue) = block_on(setup());
+
+    let mut fonts = cosmic_text::fontdb::Database::new();
+    fonts.load_font_data(include_bytes!("../src/fonts/Anodina-Regular.ttf").to_vec());
+    let font_id = fonts.faces().next().unwrap().id;
+
+    let square = || {
+        let mut image = cosmic_text::SwashImage::new();
+        image.placement = swash::zeno::Placement {
+            left: 0,
+            top: 20,
+            width: 20,
+            height: 20,
+        };
+        image.data = vec![255; 400];
+        image
+    };
+    let bins = [
+        cosmic_text::SubpixelBin::Zero,
+        cosmic_text::SubpixelBin::One,
+        cosmic_text::SubpixelBin::Two,
+        cosmic_text::SubpixelBin::Three,
+    ];
+
+    // A glyph animating across every horizontal bin.
+    let draw = |binning: SubpixelBinning| {
+        let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+        vger.set_subpixel_binning(binning);
+        vger.begin(512.0, 512.0, 1.0);
+        let paint = vger.color_paint(Color::WHITE);
+        for (i, bin) in bins.iter().enumerate() {
+            let subpx = (*bin, cosmic_text::SubpixelBin::Zero);
+            let x = 100.0 + 50.0 * i as f32;
+            vger.render_glyph(x, 100.0, font_id, 1, 20, subpx, square, paint);
+        }
+        let misses = vger.glyph_cache_stats().glyph_misses;
+
+        // Text is rasterized once per glyph and size, wherever it is.
+        for i in 0..4 {
+            vger.save();
+            vger.translate([100.0 + i as f32 * 0.25, 300.0]);
+            vger.text("a", 24, Color::WHITE, None);
+            vger.restore();
+        }
+        assert_eq!(vger.glyph_cache_stats().glyph_misses, misses + 1);
+
+        let png_name = "subpixel_binning.png";
+        render_test(&mut vger, &device, &queue, png_name, false);
+        assert_eq!(png_pixel(png_name, 110, 90), [255, 255, 255, 255]);
+        misses
+    };
+
+    assert_eq!(draw(SubpixelBinning::Four), 4);
+    assert_eq!(draw(SubpixelBinning::Two), 2);
+    assert_eq!(draw(SubpixelBinning::Off), 1);
+}
+
+#[test]
+fn text_outlines() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // Over the outline threshold, so drawn as paths.
+    vger.set_outline_threshold(Some(100.0));
+    vger.translate([20.0, 256.0]);
+    let center = vger.text_bounds("I", 200, None).center();
+    let (x, y) = ((20.0 + center.x) as u32, (256.0 + center.y) as u32);
+    let shadows = [TextShadow {
+        offset: [60.0, 0.0].into(),
+        blur: 0.0,
+        color: Color::MAGENTA,
+    }];
+    vger.text_with_shadows("I", 200, Color::WHITE, None, &shadows);
+
+    // So are single lines and batches of labels.
+    vger.save();
+    vger.translate([200.0, 0.0]);
+    vger.text_line("I", 200, Color::CYAN, None);
+    vger.restore();
+    let yellow = Color::new(1.0, 1.0, 0.0, 1.0);
+    vger.draw_texts(&[([350.0, 0.0].into(), "I")], 200, yellow);
+    assert_eq!(vger.glyph_cache_stats().glyph_misses, 0);
+
+    let png_name = "text_outlines.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert_eq!(png_pixel(png_name, x, y), [255, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, x + 60, y), [255, 0, 255, 255]);
+    assert_eq!(png_pixel(png_name, x + 200, y), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, x + 350, y), [255, 255, 0, 255]);
+}
+
+#[test]
+fn text_batch() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let cells: Vec<String> = (0..200)
+        .map(|i| format!("{:.2}", i as f32 * 1.25))
+        .collect();
+    let labels: Vec<(LocalPoint, &str)> = cells
+        .iter()
+        .enumerate()
+        .map(|(i, text)| {
+            let point = LocalPoint::new(8.0 + (i % 8) as f32 * 62.0, 8.0 + (i / 8) as f32 * 20.0);
+            (point, text.as_str())
+        })
+        .collect();
+    vger.draw_texts(&labels, 12, Color::WHITE);
+
+    // Labels are laid out once, then found in the line cache.
+    assert_eq!(vger.line_cache_mut().len(), 200);
+    vger.draw_texts(&labels[..8], 12, Color::WHITE);
+    assert_eq!(vger.line_cache_mut().stats().hits, 8);
+
+    // Repeated labels share one cached line.
+    let repeated = [
+        (LocalPoint::new(8.0, 490.0), "repeated"),
+        (LocalPoint::new(256.0, 490.0), "repeated"),
+    ];
+    vger.draw_texts(&repeated, 12, Color::WHITE);
+    assert_eq!(vger.line_cache_mut().len(), 201);
+
+    let png_name = "text_batch.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert!(png_not_black(png_name));
+}
+
+#[test]
+fn conical_gradient() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // Focal point off center, as for a lit sphere.
+    let paint = vger.conical_gradient(
+        [200.0, 300.0],
+        0.0,
+        [256.0, 256.0],
+        150.0,
+        Color::WHITE,
+        Color::MAGENTA,
+    );
+    vger.fill_circle([256.0, 256.0], 150.0, paint);
+
+    // Circles moving apart draw a cone.
+    let paint = vger.conical_gradient(
+        [40.0, 40.0],
+        10.0,
+        [200.0, 60.0],
+        30.0,
+        Color::CYAN,
+        Color::gray(0.2),
+    );
+    vger.fill_rect(euclid::rect(0.0, 0.0, 512.0, 120.0), 0.0, paint);
+
+    let png_name = "conical_gradient.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    // White at the focal point, nearly magenta at the far edge.
+    let focal = png_pixel(png_name, 200, 300);
+    assert_eq!(focal[0], 255);
+    assert!(focal[1] > 250);
+    let edge = png_pixel(png_name, 396, 256);
+    assert_eq!(edge[0], 255);
+    assert!(edge[1] < 100, "{:?}", edge);
+
+    // Cyan in the start circle, gray past the end circle, and nothing
+    // outside the cone.
+    let start = png_pixel(png_name, 40, 40);
+    assert!(start[0] < 64 && start[1] > 230);
+    let end = png_pixel(png_name, 200, 60);
+    assert!(
+        end[..3].iter().all(|c| (*c as i32 - 124).abs() < 10),
+        "{:?}",
+        end
+    );
+    assert_eq!(png_pixel(png_name, 40, 110), [0, 0, 0, 255]);
+}
+
+#[test]
+fn gradient_spread() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // A short diagonal gradient repeated as a barber pole.
+    let paint = vger.linear_gradient(
+        [0.0, 0.0],
+        [20.0, 20.0],
+        Color::new(0.9, 0.1, 0.1, 1.0),
+        Color::WHITE,
+        0.0,
+    );
+    let paint = vger.spread_paint(paint, SpreadMode::Repeat);
+    vger.fill_rect(euclid::rect(0.0, 0.0, 512.0, 160.0), 0.0, paint);
+
+    let paint = vger.linear_gradient(
+        [0.0, 200.0],
+        [40.0, 200.0],
+        Color::new(0.1, 0.2, 0.9, 1.0),
+        Color::WHITE,
+        0.0,
+    );
+    let paint = vger.spread_paint(paint, SpreadMode::Reflect);
+    vger.fill_rect(euclid::rect(0.0, 180.0, 512.0, 140.0), 0.0, paint);
+
+    // Rings from a conical gradient.
+    let paint = vger.conical_gradient(
+        [256.0, 420.0],
+        0.0,
+        [256.0, 420.0],
+        16.0,
+        Color::new(0.1, 0.8, 0.3, 1.0),
+        Color::gray(0.1),
+    );
+    let paint = vger.spread_paint(paint, SpreadMode::Reflect);
+    vger.fill_circle([256.0, 420.0], 90.0, paint);
+
+    let png_name = "gradient_spread.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    // Repeated stripes restart at red every 40px along the row.
+    let red = |p: [u8; 4]| p[0] > 200 && p[1] < 140;
+    let white = |p: [u8; 4]| p[0] > 230 && p[1] > 230 && p[2] > 230;
+    assert!(red(png_pixel(png_name, 0, 80)));
+    assert!(white(png_pixel(png_name, 38, 80)));
+    assert!(red(png_pixel(png_name, 40, 80)));
+    assert!(white(png_pixel(png_name, 78, 80)));
+
+    // Reflected stripes mirror at white, so blue comes back gradually.
+    let blue = |p: [u8; 4]| p[2] > 200 && p[1] < 160;
+    assert!(blue(png_pixel(png_name, 0, 250)));
+    assert!(white(png_pixel(png_name, 39, 250)));
+    assert!(white(png_pixel(png_name, 41, 250)));
+    assert!(blue(png_pixel(png_name, 79, 250)));
+
+    // Reflected rings alternate green and gray every 16px.
+    assert!(png_pixel(png_name, 256, 420)[1] > 200);
+    assert!(png_pixel(png_name, 272, 420)[1] < 128);
+    assert!(png_pixel(png_name, 288, 420)[1] > 200);
+}
+
+#[test]
+fn image_corner_radius() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let image = || [255, 128, 0, 255].repeat(64 * 64);
+    let key = ImageKey::from_hash(&[74, 8]);
+
+    vger.save();
+    vger.set_image_corner_radius(16.0);
+    vger.render_image(32.0, 32.0, &key, 64, 64, image);
+
+    // A circular avatar, scaled up.
+    vger.set_image_corner_radius(100.0);
+    vger.render_image_rect(
+        euclid::rect(200.0, 32.0, 200.0, 200.0),
+        &key,
+        64,
+        64,
+        image,
+        1.0,
+    );
+    vger.restore();
+
+    // Restored to square corners.
+    vger.render_image(32.0, 300.0, &key, 64, 64, image);
+
+    let png_name = "image_corner_radius.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    // Corners are masked out and centers drawn opaque.
+    let orange = |[r, g, b, a]: [u8; 4]| r == 255 && g > 0 && b == 0 && a == 255;
+    assert_eq!(png_pixel(png_name, 33, 33), [0, 0, 0, 255]);
+    assert!(orange(png_pixel(png_name, 64, 64)));
+    assert_eq!(png_pixel(png_name, 210, 42), [0, 0, 0, 255]);
+    assert!(orange(png_pixel(png_name, 300, 132)));
+    assert!(orange(png_pixel(png_name, 33, 301)));
+}
+
+#[test]
+fn additive_blend() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paint = vger.color_paint(Color::new(0.2, 0.3, 0.8, 0.5));
+    vger.fill_rect(euclid::rect(0.0, 0.0, 512.0, 256.0), 0.0, paint);
+
+    // Overlapping dots brighten toward white.
+    vger.save();
+    vger.set_blend_mode(BlendMode::Additive);
+    let paint = vger.color_paint(Color::new(0.4, 0.2, 0.1, 1.0));
+    for i in 0..8 {
+        let x = 128.0 + 32.0 * i as f32;
+        vger.fill_circle([x, 256.0], 60.0, paint);
+    }
+    vger.restore();
+
+    // Drawn normally again.
+    vger.fill_circle([256.0, 420.0], 40.0, paint);
+    vger.fill_circle([276.0, 420.0], 40.0, paint);
+
+    let png_name = "additive_blend.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    // Under one dot, two dots, and two dots drawn normally.
+    let one = png_pixel(png_name, 75, 270);
+    let two = png_pixel(png_name, 240, 300);
+    let normal = png_pixel(png_name, 266, 420);
+    assert!(two[0] > one[0] + 20, "{:?} {:?}", one, two);
+    assert_eq!(normal, one);
+
+    // Cached layers are blended normally, which validation reports.
+    vger.set_validation(true);
+    vger.begin(512.0, 512.0, 1.0);
+    vger.cached_layer(1, [100.0, 100.0], |vger| {
+        vger.set_blend_mode(BlendMode::Additive);
+        let paint = vger.color_paint(Color::WHITE);
+        vger.fill_circle([50.0, 50.0], 40.0, paint);
+    });
+    assert_eq!(
+        vger.take_validation_errors(),
+        vec![ValidationError::BlendModeIgnored]
+    );
+}
+
+#[test]
+fn heatmap_paint() {
+    let (device, queue) = block_on(setup());
+
+    let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let key = ImageKey::from_hash(b"diverging");
+    let colors = [
+        Color::new(0.2, 0.3, 0.9, 1.0),
+        Color::WHITE,
+        Color::new(0.9, 0.2, 0.2, 1.0),
+    ];
+    let custom = Colormap::Custom {
+        key: &key,
+        colors: &colors,
+    };
+    let lut_key = ImageKey::from_hash(b"red ramp");
+    let ramp: Vec<u8> = (0..256).flat_map(|i| [i as u8, 0, 64, 255]).collect();
+    let lut = Colormap::Lut {
+        key: &lut_key,
+        lut: &ramp,
+    };
+    let value = |i: usize, j: usize| ((i as f32 * 0.4).sin() + (j as f32 * 0.3).cos()) * 50.0;
+
+    // The linear mode samples the color atlas through an sRGB view.
+    for mode in [OutputMode::Color, OutputMode::Linear] {
+        let mut vger = Vger::new_with_mode(&device, format, mode);
+        vger.begin(512.0, 512.0, 1.0);
+
+        // A grid of cells colored by value in the shader.
+        for i in 0..16 {
+            for j in 0..16 {
+                let paint = vger.heatmap_paint(value(i, j), [-100.0, 100.0], Colormap::Viridis);
+                let rect = euclid::rect(i as f32 * 24.0, j as f32 * 24.0, 24.0, 24.0);
+                vger.fill_rect(rect, 0.0, paint);
+            }
+        }
+
+        // Values with the same table entry share a paint.
+        let a = vger.heatmap_paint(0.5, [0.0, 1.0], Colormap::Viridis);
+        let b = vger.heatmap_paint(0.5001, [0.0, 1.0], Colormap::Viridis);
+        assert_eq!(a, b);
+
+        // Colorbars.
+        let paint = vger.heatmap_gradient([400.0, 384.0], [400.0, 0.0], Colormap::Magma);
+        vger.fill_rect(euclid::rect(400.0, 0.0, 24.0, 384.0), 0.0, paint);
+        let paint = vger.heatmap_gradient([0.0, 420.0], [384.0, 420.0], custom);
+        vger.fill_rect(euclid::rect(0.0, 400.0, 384.0, 40.0), 0.0, paint);
+        let paint = vger.heatmap_paint(0.25, [0.0, 1.0], lut);
+        vger.fill_rect(euclid::rect(0.0, 460.0, 40.0, 40.0), 0.0, paint);
+
+        let png_name = "heatmap_paint.png";
+        render_test(&mut vger, &device, &queue, png_name, false);
+
+        // The shader's lookups match the colormaps.
+        let expected = [
+            (12, 12, Colormap::Viridis.color(0.75)),
+            (
+                12 + 24 * 5,
+                12 + 24 * 9,
+                Colormap::Viridis.color((value(5, 9) + 100.0) / 200.0),
+            ),
+            (412, 96, Colormap::Magma.color(0.75)),
+            (96, 420, custom.color(0.25)),
+            (20, 480, lut.color(0.25)),
+        ];
+        for (x, y, color) in expected {
+            let pixel = png_pixel(png_name, x, y);
+            let color = [color.r, color.g, color.b, 1.0].map(|c| (c * 255.0).round() as i32);
+            for c in 0..4 {
+                assert!(
+                    (pixel[c] as i32 - color[c]).abs() <= 3,
+                    "{:?} at {}, {}: {:?} vs {:?}",
+                    mode,
+                    x,
+                    y,
+                    pixel,
+                    color
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn small_atlas() {
+    let (device, queue) = block_on(setup());
+
+    let glyph_cache = SharedGlyphCache::with_atlas_size(&device, 1024);
+    let mut vger = Vger::with_glyph_cache(
+        &device,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        OutputMode::Color,
+        glyph_cache,
+    );
+
+    vger.begin(512.0, 512.0, 1.0);
+    let pixels = [0u8, 255, 255, 255].repeat(64 * 64);
+    vger.render_image_rect(
+        euclid::rect(100.0, 100.0, 64.0, 64.0),
+        &ImageKey::from_content(&pixels),
+        64,
+        64,
+        || pixels.clone(),
+        1.0,
+    );
+
+    let png_name = "small_atlas.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    // Atlas coordinates are normalized by the atlas's size, not 4096.
+    assert_eq!(png_pixel(png_name, 132, 132), [0, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 50, 50), [0, 0, 0, 255]);
+}
+
+#[test]
+fn image_paint() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    // Takes paint 0, which images mustn't pick up.
+    let clear = vger.color_paint(Color::new(0.0, 0.0, 0.0, 0.0));
+    vger.fill_rect(euclid::rect(0.0, 0.0, 1.0, 1.0), 0.0, clear);
+
+    let pixels = [0u8, 255, 255, 255].repeat(32 * 32);
+    vger.render_image(
+        100.0,
+        100.0,
+        &ImageKey::from_content(&pixels),
+        32,
+        32,
+        || pixels.clone(),
+    );
+
+    let png_name = "image_paint.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert_eq!(png_pixel(png_name, 116, 116), [0, 255, 255, 255]);
+}
+
+#[test]
+fn path_fill_abutting() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    vger.begin(512.0, 512.0, 1.0);
+
+    let paint = vger.color_paint(Color::WHITE);
+
+    // Two fills sharing an edge at x = 200, with curved tops so each is
+    // split into several bands.
+    for (x0, x1) in [(100.0, 200.0), (200.0, 300.0)] {
+        vger.move_to([x0, 300.0]);
+        vger.line_to([x0, 150.0]);
+        vger.quad_to([(x0 + x1) / 2.0, 50.0], [x1, 150.0]);
+        vger.line_to([x1, 300.0]);
+        vger.line_to([x0, 300.0]);
+        vger.fill(paint);
+    }
+
+    let png_name = "path_fill_abutting.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    // No seam along the shared edge or between bands.
+    for y in (160..290).step_by(10) {
+        for x in [150, 199, 200, 250] {
+            assert_eq!(
+                png_pixel(png_name, x, y),
+                [255, 255, 255, 255],
+                "{}, {}",
+                x,
+                y
+            );
+        }
+    }
+}
+
+#[test]
+fn overlay_atlas_clear() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    let draw_cursor = |vger: &mut Vger| {
+        vger.draw_overlay(|vger| {
+            let paint = vger.color_paint(Color::MAGENTA);
+            vger.fill_rect(euclid::rect(100.0, 100.0, 20.0, 20.0), 0.0, paint);
+            vger.text("cursor", 12, Color::WHITE, None);
+        });
+    };
+
+    vger.begin(512.0, 512.0, 1.0);
+    draw_cursor(&mut vger);
+    assert!(vger.has_overlay());
+    let png_name = "overlay_atlas_clear.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert_eq!(png_pixel(png_name, 110, 110), [255, 0, 255, 255]);
+
+    // Clearing the atlases drops the overlay, whose glyphs are gone.
+    vger.trim(&device);
+    assert!(!vger.has_overlay());
+    vger.begin(512.0, 512.0, 1.0);
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert_eq!(png_pixel(png_name, 110, 110), [0, 0, 0, 255]);
+
+    vger.begin(512.0, 512.0, 1.0);
+    draw_cursor(&mut vger);
+    assert!(vger.has_overlay());
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert_eq!(png_pixel(png_name, 110, 110), [255, 0, 255, 255]);
+}
+
+#[test]
+fn render_glyph_scale() {
+    let (device, queue) = block_on(setup());
+
+    let mut vger = Vger::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+    let mut fonts = cosmic_text::fontdb::Database::new();
+    fonts.load_font_data(include_bytes!("../src/fonts/Anodina-Regular.ttf").to_vec());
+    let font_id = fonts.faces().next().unwrap().id;
+
+    // A square 20 pixels wide, sitting on the baseline.
+    let image = || {
+        let mut image = cosmic_text::SwashImage::new();
+        image.placement = swash::zeno::Placement {
+            left: 0,
+            top: 20,
+            width: 20,
+            height: 20,
+        };
+        image.data = vec![255; 400];
+        image
+    };
+    let subpx = (
+        cosmic_text::SubpixelBin::Zero,
+        cosmic_text::SubpixelBin::Zero,
+    );
+
+    vger.begin(256.0, 256.0, 2.0);
+    let paint = vger.color_paint(Color::WHITE);
+
+    // Placed in local units, so 40 device pixels wide.
+    vger.render_glyph(50.0, 50.0, font_id, 1, 20, subpx, image, paint);
+
+    // Placed in device pixels, so 20 device pixels wide.
+    vger.render_scaled_glyph(150.0, 50.0, font_id, 1, 10, subpx, image, paint);
+
+    let png_name = "render_glyph_scale.png";
+    render_test(&mut vger, &device, &queue, png_name, false);
+
+    assert_eq!(png_pixel(png_name, 130, 70), [255, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 145, 90), [0, 0, 0, 255]);
+
+    assert_eq!(png_pixel(png_name, 310, 90), [255, 255, 255, 255]);
+    assert_eq!(png_pixel(png_name, 330, 90), [0, 0, 0, 255]);
+    assert_eq!(png_pixel(png_name, 310, 70), [0, 0, 0, 255]);
+}
+
+#[test]
+fn atlas_shelf_eviction() {
+    let (device, queue) = block_on(setup());
+
+    let glyph_cache = SharedGlyphCache::with_atlas_size(&device, 1024);
+    let mut vger = Vger::with_glyph_cache(
+        &device,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        OutputMode::Color,
+        glyph_cache.clone(),
+    );
+
+    // Capitals, without descenders, too short for the large glyph's shelf
+    // and too tall for the small one's, drawn from the top down so
+    // shelves fill.
+    let capitals = "ABCDEFGHIKLMNOPRSTUVWXYZ";
+    let mut sizes = (20..=100).rev();
+    let mut fill_to = |vger: &mut Vger, usage: f32| {
+        while glyph_cache.lock().mask_atlas.usage() <= usage {
+            let size = sizes.next().expect("atlas didn't fill");
+            vger.save();
+            vger.translate([0.0, 60.0]);
+            vger.text(capitals, size, Color::WHITE, None);
+            vger.restore();
+        }
+    };
+    let draw_i = |vger: &mut Vger, size: u32| {
+        vger.save();
+        vger.translate([100.0, 400.0]);
+        vger.text("I", size, Color::WHITE, None);
+        vger.restore();
+    };
+    let png_name = "atlas_shelf_eviction.png";
+
+    // A frame drawing a glyph which isn't drawn in the next.
+    vger.begin(512.0, 512.0, 1.0);
+    let center = vger.text_bounds("I", 150, None).center();
+    let (x, y) = ((100.0 + center.x) as u32, (400.0 + center.y) as u32);
+    draw_i(&mut vger, 150);
+    fill_to(&mut vger, 0.6);
+    render_test(&mut vger, &device, &queue, png_name, false);
+    let generation = glyph_cache.lock().generation();
+
+    // The next frame fills the mask atlas, so the last frame's shelves
+    // are evicted, but not this one's.
+    vger.begin(512.0, 512.0, 1.0);
+    draw_i(&mut vger, 12);
+    fill_to(&mut vger, 0.7);
+    render_test(&mut vger, &device, &queue, png_name, false);
+    {
+        let glyph_cache = glyph_cache.lock();
+        assert_ne!(glyph_cache.generation(), generation);
+        let usage = glyph_cache.mask_atlas.usage();
+        assert!(usage > 0.0 && usage <= 0.5, "{}", usage);
+    }
+
+    // The evicted glyph is rasterized again into the freed shelf.
+    vger.begin(512.0, 512.0, 1.0);
+    draw_i(&mut vger, 150);
+    draw_i(&mut vger, 12);
+    assert_eq!(vger.glyph_cache_stats().glyph_hits, 1);
+    assert_eq!(vger.glyph_cache_stats().glyph_misses, 1);
+    render_test(&mut vger, &device, &queue, png_name, false);
+    assert_eq!(png_pixel(png_name, x, y), [255, 255, 255, 255]);
+}
@@ -0,0 +1,510 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::math;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+fn map_err(r: Result<u8, core::num::ParseIntError>) -> Result<u8, String> {
+    r.map_err(|e| format!("Error parsing hex: {}", e))
+}
+
+impl Color {
+    pub fn gray(lightness: f32) -> Color {
+        Self {
+            r: lightness,
+            g: lightness,
+            b: lightness,
+            a: 1.0,
+        }
+    }
+
+    pub fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub fn mix(&self, rhs: Color, s: f32) -> Color {
+        Color {
+            r: (1.0 - s) * self.r + s * rhs.r,
+            g: (1.0 - s) * self.g + s * rhs.g,
+            b: (1.0 - s) * self.b + s * rhs.b,
+            a: (1.0 - s) * self.a + s * rhs.a,
+        }
+    }
+
+    /// Linearly interpolates towards `rhs`. Same as `mix`.
+    pub fn lerp(&self, rhs: Color, t: f32) -> Color {
+        self.mix(rhs, t)
+    }
+
+    /// Color from hue (degrees), saturation and lightness (0 to 1).
+    pub fn hsl(h: f32, s: f32, l: f32) -> Color {
+        Self::hsla(h, s, l, 1.0)
+    }
+
+    /// Color from hue (degrees), saturation, lightness and alpha (0 to 1).
+    pub fn hsla(h: f32, s: f32, l: f32, a: f32) -> Color {
+        // See https://www.w3.org/TR/css-color-4/#hsl-to-rgb
+        let h = math::rem_euclid(h, 360.0);
+        let s = s.clamp(0.0, 1.0);
+        let l = l.clamp(0.0, 1.0);
+        let f = |n: f32| {
+            let k = (n + h / 30.0) % 12.0;
+            let a = s * l.min(1.0 - l);
+            l - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+        };
+        Color::new(f(0.0), f(8.0), f(4.0), a)
+    }
+
+    /// Color from OKLCH lightness (0 to 1), chroma and hue (degrees).
+    ///
+    /// The result is sRGB encoded, like `Color::hex`, and clamped to gamut.
+    pub fn oklch(l: f32, c: f32, h: f32) -> Color {
+        let h = h.to_radians();
+        let a = c * math::cos(h);
+        let b = c * math::sin(h);
+
+        // See https://bottosson.github.io/posts/oklab/
+        let l_ = l + 0.396_337_78 * a + 0.215_803_76 * b;
+        let m_ = l - 0.105_561_346 * a - 0.063_854_17 * b;
+        let s_ = l - 0.089_484_18 * a - 1.291_485_5 * b;
+
+        let l = l_ * l_ * l_;
+        let m = m_ * m_ * m_;
+        let s = s_ * s_ * s_;
+
+        let encode = |x: f32| {
+            let x = x.clamp(0.0, 1.0);
+            if x <= 0.003_130_8 {
+                12.92 * x
+            } else {
+                1.055 * math::powf(x, 1.0 / 2.4) - 0.055
+            }
+        };
+
+        Color::new(
+            encode(4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s),
+            encode(-1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s),
+            encode(-0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s),
+            1.0,
+        )
+    }
+
+    /// Parses a CSS color: a named color, `#rgb`, `#rgba`, `#rrggbb`,
+    /// `#rrggbbaa`, `rgb()`, `rgba()`, `hsl()` or `hsla()`.
+    pub fn from_css(css: &str) -> Result<Color, String> {
+        let css = css.trim().to_ascii_lowercase();
+
+        if css.starts_with('#') {
+            return match css.len() {
+                4 | 5 => {
+                    // Short form, each digit is doubled.
+                    let mut long = String::from("#");
+                    for c in css[1..].chars() {
+                        long.push(c);
+                        long.push(c);
+                    }
+                    Color::hex(&long)
+                }
+                _ => Color::hex(&css),
+            };
+        }
+
+        if let Some(args) = css_args(&css, "rgb").or_else(|| css_args(&css, "rgba")) {
+            if args.len() != 3 && args.len() != 4 {
+                return Err(format!("Expected 3 or 4 components in {:?}", css));
+            }
+            return Ok(Color::new(
+                css_channel(args[0])?,
+                css_channel(args[1])?,
+                css_channel(args[2])?,
+                css_alpha(args.get(3))?,
+            ));
+        }
+
+        if let Some(args) = css_args(&css, "hsl").or_else(|| css_args(&css, "hsla")) {
+            if args.len() != 3 && args.len() != 4 {
+                return Err(format!("Expected 3 or 4 components in {:?}", css));
+            }
+            return Ok(Color::hsla(
+                css_hue(args[0])?,
+                css_percent(args[1])?,
+                css_percent(args[2])?,
+                css_alpha(args.get(3))?,
+            ));
+        }
+
+        if css == "transparent" {
+            return Ok(Color::new(0.0, 0.0, 0.0, 0.0));
+        }
+
+        match NAMED_COLORS.binary_search_by_key(&css.as_str(), |(name, _)| *name) {
+            Ok(i) => {
+                let [r, g, b] = NAMED_COLORS[i].1;
+                Ok(Color::new(
+                    r as f32 / 255.0,
+                    g as f32 / 255.0,
+                    b as f32 / 255.0,
+                    1.0,
+                ))
+            }
+            Err(_) => Err(format!("Unknown CSS color {:?}", css)),
+        }
+    }
+
+    pub fn alpha(&self, a: f32) -> Self {
+        Self {
+            r: self.r,
+            g: self.g,
+            b: self.b,
+            a,
+        }
+    }
+
+    pub const CYAN: Color = Color {
+        r: 0.0,
+        g: 1.0,
+        b: 1.0,
+        a: 1.0,
+    };
+    pub const MAGENTA: Color = Color {
+        r: 1.0,
+        g: 0.0,
+        b: 1.0,
+        a: 1.0,
+    };
+    pub const WHITE: Color = Color {
+        r: 1.0,
+        g: 1.0,
+        b: 1.0,
+        a: 1.0,
+    };
+
+    pub fn hex(hex: &str) -> Result<Color, String> {
+        if hex.len() == 9 && hex.starts_with('#') {
+            // #FFFFFFFF (Red Green Blue Alpha)
+            Ok(Color {
+                r: map_err(u8::from_str_radix(&hex[1..3], 16))? as f32 / 255.0,
+                g: map_err(u8::from_str_radix(&hex[3..5], 16))? as f32 / 255.0,
+                b: map_err(u8::from_str_radix(&hex[5..7], 16))? as f32 / 255.0,
+                a: map_err(u8::from_str_radix(&hex[7..9], 16))? as f32 / 255.0,
+            })
+        } else if hex.len() == 7 && hex.starts_with('#') {
+            // #FFFFFF (Red Green Blue)
+            Ok(Color {
+                r: map_err(u8::from_str_radix(&hex[1..3], 16))? as f32 / 255.0,
+                g: map_err(u8::from_str_radix(&hex[3..5], 16))? as f32 / 255.0,
+                b: map_err(u8::from_str_radix(&hex[5..7], 16))? as f32 / 255.0,
+                a: 1.0,
+            })
+        } else {
+            Err("Error parsing hex. Example of valid formats: #FFFFFF or #ffffffff".to_string())
+        }
+    }
+
+    pub const fn hex_const(hex: &str) -> Color {
+        // Can't do f32 arithmetic in a const fn, so use a lookup table.
+        let lut = [
+            0.000000, 0.003922, 0.007843, 0.011765, 0.015686, 0.019608, 0.023529, 0.027451,
+            0.031373, 0.035294, 0.039216, 0.043137, 0.047059, 0.050980, 0.054902, 0.058824,
+            0.062745, 0.066667, 0.070588, 0.074510, 0.078431, 0.082353, 0.086275, 0.090196,
+            0.094118, 0.098039, 0.101961, 0.105882, 0.109804, 0.113725, 0.117647, 0.121569,
+            0.125490, 0.129412, 0.133333, 0.137255, 0.141176, 0.145098, 0.149020, 0.152941,
+            0.156863, 0.160784, 0.164706, 0.168627, 0.172549, 0.176471, 0.180392, 0.184314,
+            0.188235, 0.192157, 0.196078, 0.200000, 0.203922, 0.207843, 0.211765, 0.215686,
+            0.219608, 0.223529, 0.227451, 0.231373, 0.235294, 0.239216, 0.243137, 0.247059,
+            0.250980, 0.254902, 0.258824, 0.262745, 0.266667, 0.270588, 0.274510, 0.278431,
+            0.282353, 0.286275, 0.290196, 0.294118, 0.298039, 0.301961, 0.305882, 0.309804,
+            0.313725, 0.317647, 0.321569, 0.325490, 0.329412, 0.333333, 0.337255, 0.341176,
+            0.345098, 0.349020, 0.352941, 0.356863, 0.360784, 0.364706, 0.368627, 0.372549,
+            0.376471, 0.380392, 0.384314, 0.388235, 0.392157, 0.396078, 0.400000, 0.403922,
+            0.407843, 0.411765, 0.415686, 0.419608, 0.423529, 0.427451, 0.431373, 0.435294,
+            0.439216, 0.443137, 0.447059, 0.450980, 0.454902, 0.458824, 0.462745, 0.466667,
+            0.470588, 0.474510, 0.478431, 0.482353, 0.486275, 0.490196, 0.494118, 0.498039,
+            0.501961, 0.505882, 0.509804, 0.513725, 0.517647, 0.521569, 0.525490, 0.529412,
+            0.533333, 0.537255, 0.541176, 0.545098, 0.549020, 0.552941, 0.556863, 0.560784,
+            0.564706, 0.568627, 0.572549, 0.576471, 0.580392, 0.584314, 0.588235, 0.592157,
+            0.596078, 0.600000, 0.603922, 0.607843, 0.611765, 0.615686, 0.619608, 0.623529,
+            0.627451, 0.631373, 0.635294, 0.639216, 0.643137, 0.647059, 0.650980, 0.654902,
+            0.658824, 0.662745, 0.666667, 0.670588, 0.674510, 0.678431, 0.682353, 0.686275,
+            0.690196, 0.694118, 0.698039, 0.701961, 0.705882, 0.709804, 0.713725, 0.717647,
+            0.721569, 0.725490, 0.729412, 0.733333, 0.737255, 0.741176, 0.745098, 0.749020,
+            0.752941, 0.756863, 0.760784, 0.764706, 0.768627, 0.772549, 0.776471, 0.780392,
+            0.784314, 0.788235, 0.792157, 0.796078, 0.800000, 0.803922, 0.807843, 0.811765,
+            0.815686, 0.819608, 0.823529, 0.827451, 0.831373, 0.835294, 0.839216, 0.843137,
+            0.847059, 0.850980, 0.854902, 0.858824, 0.862745, 0.866667, 0.870588, 0.874510,
+            0.878431, 0.882353, 0.886275, 0.890196, 0.894118, 0.898039, 0.901961, 0.905882,
+            0.909804, 0.913725, 0.917647, 0.921569, 0.925490, 0.929412, 0.933333, 0.937255,
+            0.941176, 0.945098, 0.949020, 0.952941, 0.956863, 0.960784, 0.964706, 0.968627,
+            0.972549, 0.976471, 0.980392, 0.984314, 0.988235, 0.992157, 0.996078, 1.000000,
+        ];
+        let bytes = hex.as_bytes();
+        Color {
+            r: lut[hex_digit(bytes[1]) * 16 + hex_digit(bytes[2])],
+            g: lut[hex_digit(bytes[3]) * 16 + hex_digit(bytes[4])],
+            b: lut[hex_digit(bytes[5]) * 16 + hex_digit(bytes[6])],
+            a: 1.0,
+        }
+    }
+}
+
+#[cfg(feature = "wgpu")]
+impl From<Color> for wgpu::Color {
+    fn from(c: Color) -> Self {
+        wgpu::Color {
+            r: c.r as f64,
+            g: c.g as f64,
+            b: c.b as f64,
+            a: c.a as f64,
+        }
+    }
+}
+
+/// Splits the arguments of a CSS function like `rgb(1, 2, 3)` or `rgb(1 2 3 / 50%)`.
+fn css_args<'a>(css: &'a str, name: &str) -> Option<Vec<&'a str>> {
+    let inner = css
+        .strip_prefix(name)?
+        .trim_start()
+        .strip_prefix('(')?
+        .strip_suffix(')')?;
+    Some(
+        inner
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .collect(),
+    )
+}
+
+fn css_number(s: &str) -> Result<f32, String> {
+    s.parse::<f32>()
+        .map_err(|e| format!("Error parsing CSS number {:?}: {}", s, e))
+}
+
+fn css_percent(s: &str) -> Result<f32, String> {
+    match s.strip_suffix('%') {
+        Some(p) => Ok(css_number(p)? / 100.0),
+        None => Err(format!("Expected a percentage, got {:?}", s)),
+    }
+}
+
+fn css_channel(s: &str) -> Result<f32, String> {
+    let v = if s.ends_with('%') {
+        css_percent(s)?
+    } else {
+        css_number(s)? / 255.0
+    };
+    Ok(v.clamp(0.0, 1.0))
+}
+
+fn css_alpha(s: Option<&&str>) -> Result<f32, String> {
+    let v = match s {
+        Some(s) if s.ends_with('%') => css_percent(s)?,
+        Some(s) => css_number(s)?,
+        None => 1.0,
+    };
+    Ok(v.clamp(0.0, 1.0))
+}
+
+fn css_hue(s: &str) -> Result<f32, String> {
+    if let Some(deg) = s.strip_suffix("deg") {
+        css_number(deg)
+    } else if let Some(rad) = s.strip_suffix("rad") {
+        Ok(css_number(rad)?.to_degrees())
+    } else if let Some(turn) = s.strip_suffix("turn") {
+        Ok(css_number(turn)? * 360.0)
+    } else {
+        css_number(s)
+    }
+}
+
+#[cfg(feature = "palette")]
+impl From<Color> for palette::Srgba {
+    fn from(c: Color) -> Self {
+        palette::Srgba::new(c.r, c.g, c.b, c.a)
+    }
+}
+
+#[cfg(feature = "palette")]
+impl From<palette::Srgba> for Color {
+    fn from(c: palette::Srgba) -> Self {
+        Color::new(c.color.red, c.color.green, c.color.blue, c.alpha)
+    }
+}
+
+#[cfg(feature = "palette")]
+impl From<palette::Srgb> for Color {
+    fn from(c: palette::Srgb) -> Self {
+        Color::new(c.red, c.green, c.blue, 1.0)
+    }
+}
+
+/// CSS named colors, sorted by name.
+const NAMED_COLORS: &[(&str, [u8; 3])] = &[
+    ("aliceblue", [240, 248, 255]),
+    ("antiquewhite", [250, 235, 215]),
+    ("aqua", [0, 255, 255]),
+    ("aquamarine", [127, 255, 212]),
+    ("azure", [240, 255, 255]),
+    ("beige", [245, 245, 220]),
+    ("bisque", [255, 228, 196]),
+    ("black", [0, 0, 0]),
+    ("blanchedalmond", [255, 235, 205]),
+    ("blue", [0, 0, 255]),
+    ("blueviolet", [138, 43, 226]),
+    ("brown", [165, 42, 42]),
+    ("burlywood", [222, 184, 135]),
+    ("cadetblue", [95, 158, 160]),
+    ("chartreuse", [127, 255, 0]),
+    ("chocolate", [210, 105, 30]),
+    ("coral", [255, 127, 80]),
+    ("cornflowerblue", [100, 149, 237]),
+    ("cornsilk", [255, 248, 220]),
+    ("crimson", [220, 20, 60]),
+    ("cyan", [0, 255, 255]),
+    ("darkblue", [0, 0, 139]),
+    ("darkcyan", [0, 139, 139]),
+    ("darkgoldenrod", [184, 134, 11]),
+    ("darkgray", [169, 169, 169]),
+    ("darkgreen", [0, 100, 0]),
+    ("darkgrey", [169, 169, 169]),
+    ("darkkhaki", [189, 183, 107]),
+    ("darkmagenta", [139, 0, 139]),
+    ("darkolivegreen", [85, 107, 47]),
+    ("darkorange", [255, 140, 0]),
+    ("darkorchid", [153, 50, 204]),
+    ("darkred", [139, 0, 0]),
+    ("darksalmon", [233, 150, 122]),
+    ("darkseagreen", [143, 188, 143]),
+    ("darkslateblue", [72, 61, 139]),
+    ("darkslategray", [47, 79, 79]),
+    ("darkslategrey", [47, 79, 79]),
+    ("darkturquoise", [0, 206, 209]),
+    ("darkviolet", [148, 0, 211]),
+    ("deeppink", [255, 20, 147]),
+    ("deepskyblue", [0, 191, 255]),
+    ("dimgray", [105, 105, 105]),
+    ("dimgrey", [105, 105, 105]),
+    ("dodgerblue", [30, 144, 255]),
+    ("firebrick", [178, 34, 34]),
+    ("floralwhite", [255, 250, 240]),
+    ("forestgreen", [34, 139, 34]),
+    ("fuchsia", [255, 0, 255]),
+    ("gainsboro", [220, 220, 220]),
+    ("ghostwhite", [248, 248, 255]),
+    ("gold", [255, 215, 0]),
+    ("goldenrod", [218, 165, 32]),
+    ("gray", [128, 128, 128]),
+    ("green", [0, 128, 0]),
+    ("greenyellow", [173, 255, 47]),
+    ("grey", [128, 128, 128]),
+    ("honeydew", [240, 255, 240]),
+    ("hotpink", [255, 105, 180]),
+    ("indianred", [205, 92, 92]),
+    ("indigo", [75, 0, 130]),
+    ("ivory", [255, 255, 240]),
+    ("khaki", [240, 230, 140]),
+    ("lavender", [230, 230, 250]),
+    ("lavenderblush", [255, 240, 245]),
+    ("lawngreen", [124, 252, 0]),
+    ("lemonchiffon", [255, 250, 205]),
+    ("lightblue", [173, 216, 230]),
+    ("lightcoral", [240, 128, 128]),
+    ("lightcyan", [224, 255, 255]),
+    ("lightgoldenrodyellow", [250, 250, 210]),
+    ("lightgray", [211, 211, 211]),
+    ("lightgreen", [144, 238, 144]),
+    ("lightgrey", [211, 211, 211]),
+    ("lightpink", [255, 182, 193]),
+    ("lightsalmon", [255, 160, 122]),
+    ("lightseagreen", [32, 178, 170]),
+    ("lightskyblue", [135, 206, 250]),
+    ("lightslategray", [119, 136, 153]),
+    ("lightslategrey", [119, 136, 153]),
+    ("lightsteelblue", [176, 196, 222]),
+    ("lightyellow", [255, 255, 224]),
+    ("lime", [0, 255, 0]),
+    ("limegreen", [50, 205, 50]),
+    ("linen", [250, 240, 230]),
+    ("magenta", [255, 0, 255]),
+    ("maroon", [128, 0, 0]),
+    ("mediumaquamarine", [102, 205, 170]),
+    ("mediumblue", [0, 0, 205]),
+    ("mediumorchid", [186, 85, 211]),
+    ("mediumpurple", [147, 112, 219]),
+    ("mediumseagreen", [60, 179, 113]),
+    ("mediumslateblue", [123, 104, 238]),
+    ("mediumspringgreen", [0, 250, 154]),
+    ("mediumturquoise", [72, 209, 204]),
+    ("mediumvioletred", [199, 21, 133]),
+    ("midnightblue", [25, 25, 112]),
+    ("mintcream", [245, 255, 250]),
+    ("mistyrose", [255, 228, 225]),
+    ("moccasin", [255, 228, 181]),
+    ("navajowhite", [255, 222, 173]),
+    ("navy", [0, 0, 128]),
+    ("oldlace", [253, 245, 230]),
+    ("olive", [128, 128, 0]),
+    ("olivedrab", [107, 142, 35]),
+    ("orange", [255, 165, 0]),
+    ("orangered", [255, 69, 0]),
+    ("orchid", [218, 112, 214]),
+    ("palegoldenrod", [238, 232, 170]),
+    ("palegreen", [152, 251, 152]),
+    ("paleturquoise", [175, 238, 238]),
+    ("palevioletred", [219, 112, 147]),
+    ("papayawhip", [255, 239, 213]),
+    ("peachpuff", [255, 218, 185]),
+    ("peru", [205, 133, 63]),
+    ("pink", [255, 192, 203]),
+    ("plum", [221, 160, 221]),
+    ("powderblue", [176, 224, 230]),
+    ("purple", [128, 0, 128]),
+    ("rebeccapurple", [102, 51, 153]),
+    ("red", [255, 0, 0]),
+    ("rosybrown", [188, 143, 143]),
+    ("royalblue", [65, 105, 225]),
+    ("saddlebrown", [139, 69, 19]),
+    ("salmon", [250, 128, 114]),
+    ("sandybrown", [244, 164, 96]),
+    ("seagreen", [46, 139, 87]),
+    ("seashell", [255, 245, 238]),
+    ("sienna", [160, 82, 45]),
+    ("silver", [192, 192, 192]),
+    ("skyblue", [135, 206, 235]),
+    ("slateblue", [106, 90, 205]),
+    ("slategray", [112, 128, 144]),
+    ("slategrey", [112, 128, 144]),
+    ("snow", [255, 250, 250]),
+    ("springgreen", [0, 255, 127]),
+    ("steelblue", [70, 130, 180]),
+    ("tan", [210, 180, 140]),
+    ("teal", [0, 128, 128]),
+    ("thistle", [216, 191, 216]),
+    ("tomato", [255, 99, 71]),
+    ("turquoise", [64, 224, 208]),
+    ("violet", [238, 130, 238]),
+    ("wheat", [245, 222, 179]),
+    ("white", [255, 255, 255]),
+    ("whitesmoke", [245, 245, 245]),
+    ("yellow", [255, 255, 0]),
+    ("yellowgreen", [154, 205, 50]),
+];
+
+const fn hex_digit(x: u8) -> usize {
+    (if x >= b'0' && x <= b'9' {
+        x - b'0'
+    } else if x >= b'a' && x <= b'f' {
+        x - b'a' + 10
+    } else if x >= b'A' && x <= b'F' {
+        x - b'A' + 10
+    } else {
+        panic!("bad hex digit")
+    }) as usize
+}
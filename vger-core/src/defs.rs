@@ -2,6 +2,8 @@ use euclid::*;
 
 pub struct ScreenSpace;
 pub type ScreenSize = Size2D<f32, ScreenSpace>;
+pub type ScreenPoint = Point2D<f32, ScreenSpace>;
+pub type ScreenRect = Rect<f32, ScreenSpace>;
 
 pub struct WorldSpace;
 pub type WorldPoint = Point2D<f32, WorldSpace>;
@@ -16,3 +18,6 @@ pub type WorldToLocal = Transform2D<f32, WorldSpace, LocalSpace>;
 pub type LocalTransform = Transform2D<f32, LocalSpace, LocalSpace>;
 
 pub type LocalRect = Rect<f32, LocalSpace>;
+
+pub type WorldPoint64 = Point2D<f64, WorldSpace>;
+pub type WorldVector64 = Vector2D<f64, WorldSpace>;
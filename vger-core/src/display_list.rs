@@ -0,0 +1,293 @@
+//! Drawing recorded away from the device, for layout and recording on
+//! worker threads.
+//!
+//! `Vger` owns gpu resources and lives on the render thread. A
+//! `DisplayList` holds only plain data, so it is `Send` and `Sync` and can
+//! be built anywhere, then replayed with `Vger::draw_display_list`.
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::defs::*;
+use crate::Color;
+
+/// A recorded drawing call. Paints are colors rather than `PaintIndex`es,
+/// since those are only valid within the `Vger` frame that made them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DrawCommand {
+    Save,
+    Restore,
+    Translate(LocalVector),
+    Scale(LocalVector),
+    Rotate(f32),
+    Scissor(LocalRect),
+    FillRect {
+        rect: LocalRect,
+        radius: f32,
+        color: Color,
+    },
+    StrokeRect {
+        rect: LocalRect,
+        radius: f32,
+        width: f32,
+        color: Color,
+    },
+    FillCircle {
+        center: LocalPoint,
+        radius: f32,
+        color: Color,
+    },
+    StrokeSegment {
+        a: LocalPoint,
+        b: LocalPoint,
+        width: f32,
+        color: Color,
+    },
+    StrokeBezier {
+        a: LocalPoint,
+        b: LocalPoint,
+        c: LocalPoint,
+        width: f32,
+        color: Color,
+    },
+
+    /// A path of quadratic pieces, filled with the nonzero rule.
+    FillPath {
+        start: LocalPoint,
+        quads: Vec<(LocalPoint, LocalPoint)>,
+        color: Color,
+    },
+    Text {
+        text: String,
+        size: u32,
+        color: Color,
+        max_width: Option<f32>,
+    },
+}
+
+/// Drawing calls recorded for later replay, mirroring the `Vger` methods
+/// of the same names.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DisplayList {
+    commands: Vec<DrawCommand>,
+    path: Option<(LocalPoint, Vec<(LocalPoint, LocalPoint)>)>,
+}
+
+impl DisplayList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The recorded calls, in order.
+    pub fn commands(&self) -> &[DrawCommand] {
+        &self.commands
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Removes all calls, keeping the allocation for the next frame.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.path = None;
+    }
+
+    pub fn push(&mut self, command: DrawCommand) {
+        self.commands.push(command);
+    }
+
+    /// Appends the calls of another list, e.g. one recorded on another
+    /// thread. A path `other` started but didn't fill carries over, so
+    /// `fill` on this list fills it.
+    pub fn append(&mut self, other: &mut DisplayList) {
+        self.commands.append(&mut other.commands);
+        if other.path.is_some() {
+            self.path = other.path.take();
+        }
+    }
+
+    pub fn save(&mut self) {
+        self.push(DrawCommand::Save);
+    }
+
+    pub fn restore(&mut self) {
+        self.push(DrawCommand::Restore);
+    }
+
+    pub fn translate<Vec: Into<LocalVector>>(&mut self, offset: Vec) {
+        self.push(DrawCommand::Translate(offset.into()));
+    }
+
+    pub fn scale<Vec: Into<LocalVector>>(&mut self, scale: Vec) {
+        self.push(DrawCommand::Scale(scale.into()));
+    }
+
+    pub fn rotate(&mut self, theta: f32) {
+        self.push(DrawCommand::Rotate(theta));
+    }
+
+    pub fn scissor(&mut self, rect: LocalRect) {
+        self.push(DrawCommand::Scissor(rect));
+    }
+
+    pub fn fill_rect<Rect: Into<LocalRect>>(&mut self, rect: Rect, radius: f32, color: Color) {
+        self.push(DrawCommand::FillRect {
+            rect: rect.into(),
+            radius,
+            color,
+        });
+    }
+
+    pub fn stroke_rect<Rect: Into<LocalRect>>(
+        &mut self,
+        rect: Rect,
+        radius: f32,
+        width: f32,
+        color: Color,
+    ) {
+        self.push(DrawCommand::StrokeRect {
+            rect: rect.into(),
+            radius,
+            width,
+            color,
+        });
+    }
+
+    pub fn fill_circle<Pt: Into<LocalPoint>>(&mut self, center: Pt, radius: f32, color: Color) {
+        self.push(DrawCommand::FillCircle {
+            center: center.into(),
+            radius,
+            color,
+        });
+    }
+
+    pub fn stroke_segment<Pt: Into<LocalPoint>>(&mut self, a: Pt, b: Pt, width: f32, color: Color) {
+        self.push(DrawCommand::StrokeSegment {
+            a: a.into(),
+            b: b.into(),
+            width,
+            color,
+        });
+    }
+
+    pub fn stroke_bezier<Pt: Into<LocalPoint>>(
+        &mut self,
+        a: Pt,
+        b: Pt,
+        c: Pt,
+        width: f32,
+        color: Color,
+    ) {
+        self.push(DrawCommand::StrokeBezier {
+            a: a.into(),
+            b: b.into(),
+            c: c.into(),
+            width,
+            color,
+        });
+    }
+
+    pub fn move_to<Pt: Into<LocalPoint>>(&mut self, p: Pt) {
+        self.path = Some((p.into(), vec![]));
+    }
+
+    pub fn quad_to<Pt: Into<LocalPoint>>(&mut self, b: Pt, c: Pt) {
+        if let Some((_, quads)) = &mut self.path {
+            quads.push((b.into(), c.into()));
+        }
+    }
+
+    pub fn line_to<Pt: Into<LocalPoint>>(&mut self, b: Pt) {
+        if let Some((start, quads)) = &mut self.path {
+            let a = quads.last().map(|q| q.1).unwrap_or(*start);
+            let b = b.into();
+            quads.push((a.lerp(b, 0.5), b));
+        }
+    }
+
+    /// Fills the path started with `move_to`.
+    pub fn fill(&mut self, color: Color) {
+        if let Some((start, quads)) = self.path.take() {
+            self.push(DrawCommand::FillPath {
+                start,
+                quads,
+                color,
+            });
+        }
+    }
+
+    pub fn text(&mut self, text: &str, size: u32, color: Color, max_width: Option<f32>) {
+        self.push(DrawCommand::Text {
+            text: text.to_string(),
+            size,
+            color,
+            max_width,
+        });
+    }
+}
+
+/// Fails to compile if a type loses its thread safety.
+#[allow(dead_code)]
+fn assert_send_sync<T: Send + Sync>() {}
+
+const _: fn() = || {
+    assert_send_sync::<DisplayList>();
+    assert_send_sync::<DrawCommand>();
+};
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut list = DisplayList::new();
+        list.save();
+        list.translate([10.0, 0.0]);
+        list.move_to([0.0, 0.0]);
+        list.line_to([10.0, 0.0]);
+        list.quad_to([10.0, 10.0], [0.0, 10.0]);
+        list.fill(Color::WHITE);
+        list.restore();
+
+        assert_eq!(list.commands().len(), 4);
+        assert_eq!(
+            list.commands()[2],
+            DrawCommand::FillPath {
+                start: LocalPoint::new(0.0, 0.0),
+                quads: vec![
+                    (LocalPoint::new(5.0, 0.0), LocalPoint::new(10.0, 0.0)),
+                    (LocalPoint::new(10.0, 10.0), LocalPoint::new(0.0, 10.0)),
+                ],
+                color: Color::WHITE,
+            }
+        );
+
+        // Fill without a path records nothing.
+        list.fill(Color::WHITE);
+        assert_eq!(list.commands().len(), 4);
+
+        // Lists recorded on other threads are appended in order.
+        let mut other = std::thread::spawn(|| {
+            let mut list = DisplayList::new();
+            list.fill_circle([0.0, 0.0], 5.0, Color::WHITE);
+            list
+        })
+        .join()
+        .unwrap();
+        list.append(&mut other);
+        assert_eq!(list.commands().len(), 5);
+
+        // So are unfinished paths.
+        let mut other = DisplayList::new();
+        other.move_to([0.0, 0.0]);
+        other.line_to([10.0, 0.0]);
+        list.append(&mut other);
+        list.fill(Color::WHITE);
+        assert_eq!(list.commands().len(), 6);
+        assert_eq!(other, DisplayList::new());
+    }
+}
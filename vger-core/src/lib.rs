@@ -0,0 +1,25 @@
+//! The parts of vger that don't need a GPU: coordinate spaces, colors,
+//! path scanning and display lists. Clients can build scenes with these,
+//! e.g. on an embedded device, for another process to render with vger.
+//!
+//! Without the default `std` feature, the crate is `no_std` and needs
+//! `alloc` and the `libm` feature.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod math;
+
+pub mod defs;
+
+pub mod color;
+pub use color::Color;
+
+pub mod path;
+
+pub mod display_list;
+pub use display_list::{DisplayList, DrawCommand};
+
+pub mod wire;
+pub use wire::DecodeError;
@@ -0,0 +1,46 @@
+//! Float functions that are only inherent methods with `std`.
+
+#[cfg(feature = "std")]
+pub fn sin(x: f32) -> f32 {
+    x.sin()
+}
+
+#[cfg(not(feature = "std"))]
+pub fn sin(x: f32) -> f32 {
+    libm::sinf(x)
+}
+
+#[cfg(feature = "std")]
+pub fn cos(x: f32) -> f32 {
+    x.cos()
+}
+
+#[cfg(not(feature = "std"))]
+pub fn cos(x: f32) -> f32 {
+    libm::cosf(x)
+}
+
+#[cfg(feature = "std")]
+pub fn powf(x: f32, y: f32) -> f32 {
+    x.powf(y)
+}
+
+#[cfg(not(feature = "std"))]
+pub fn powf(x: f32, y: f32) -> f32 {
+    libm::powf(x, y)
+}
+
+#[cfg(feature = "std")]
+pub fn rem_euclid(x: f32, y: f32) -> f32 {
+    x.rem_euclid(y)
+}
+
+#[cfg(not(feature = "std"))]
+pub fn rem_euclid(x: f32, y: f32) -> f32 {
+    let r = libm::fmodf(x, y);
+    match (r < 0.0, y < 0.0) {
+        (true, true) => r - y,
+        (true, false) => r + y,
+        _ => r,
+    }
+}
@@ -1,5 +1,8 @@
 #![allow(dead_code)]
 
+use alloc::vec;
+use alloc::vec::Vec;
+
 use crate::defs::*;
 
 pub struct Interval {
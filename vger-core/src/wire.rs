@@ -0,0 +1,581 @@
+//! Display lists as bytes, for sending scenes to another process.
+//!
+//! A frame is a little endian `u32` length followed by that many bytes of
+//! encoded display list. Lists start with a version byte, then each
+//! command is an opcode followed by its fields: `f32`s, colors as four
+//! `f32`s, and strings and point lists prefixed with a `u32` count.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::defs::*;
+use crate::{Color, DisplayList, DrawCommand};
+
+/// Version of the encoding, bumped when it changes.
+pub const WIRE_VERSION: u8 = 1;
+
+/// Largest frame `read_frame` accepts, so a misbehaving client can't make
+/// the server allocate without bound.
+pub const MAX_FRAME_LEN: usize = 64 << 20;
+
+/// Largest text size decoded. Larger sizes are clamped, so a client can't
+/// make the server rasterize huge glyphs.
+pub const MAX_TEXT_SIZE: u32 = 1024;
+
+/// Widest stroke decoded. Wider strokes are clamped.
+pub const MAX_STROKE_WIDTH: f32 = 4096.0;
+
+/// Deepest nesting of saves decoded, so a client can't grow the server's
+/// state stack without bound.
+pub const MAX_SAVE_DEPTH: usize = 64;
+
+/// Why bytes couldn't be decoded into a display list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The list was encoded by a different version.
+    Version(u8),
+
+    /// Unknown command opcode.
+    Opcode(u8),
+
+    /// The bytes ended in the middle of a command.
+    Truncated,
+
+    /// Text wasn't valid UTF-8.
+    Utf8,
+
+    /// A number was infinite or NaN.
+    NonFinite,
+
+    /// A rect had a negative size, or text a maximum width that isn't
+    /// positive.
+    OutOfRange,
+
+    /// Saves were nested deeper than `MAX_SAVE_DEPTH`.
+    TooDeep,
+}
+
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DecodeError::Version(v) => write!(f, "unsupported wire version {}", v),
+            DecodeError::Opcode(op) => write!(f, "unknown opcode {}", op),
+            DecodeError::Truncated => write!(f, "display list is truncated"),
+            DecodeError::Utf8 => write!(f, "text is not valid UTF-8"),
+            DecodeError::NonFinite => write!(f, "number is not finite"),
+            DecodeError::OutOfRange => write!(f, "size is out of range"),
+            DecodeError::TooDeep => write!(f, "saves are nested too deeply"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+const SAVE: u8 = 0;
+const RESTORE: u8 = 1;
+const TRANSLATE: u8 = 2;
+const SCALE: u8 = 3;
+const ROTATE: u8 = 4;
+const SCISSOR: u8 = 5;
+const FILL_RECT: u8 = 6;
+const STROKE_RECT: u8 = 7;
+const FILL_CIRCLE: u8 = 8;
+const STROKE_SEGMENT: u8 = 9;
+const STROKE_BEZIER: u8 = 10;
+const FILL_PATH: u8 = 11;
+const TEXT: u8 = 12;
+
+struct Writer<'a>(&'a mut Vec<u8>);
+
+impl<'a> Writer<'a> {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn point(&mut self, p: LocalPoint) {
+        self.f32(p.x);
+        self.f32(p.y);
+    }
+
+    fn rect(&mut self, r: LocalRect) {
+        self.point(r.origin);
+        self.f32(r.size.width);
+        self.f32(r.size.height);
+    }
+
+    fn color(&mut self, c: Color) {
+        for v in [c.r, c.g, c.b, c.a] {
+            self.f32(v);
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        if self.0.len() < n {
+            return Err(DecodeError::Truncated);
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn f32(&mut self) -> Result<f32, DecodeError> {
+        let v = f32::from_bits(self.u32()?);
+        if !v.is_finite() {
+            return Err(DecodeError::NonFinite);
+        }
+        Ok(v)
+    }
+
+    fn width(&mut self) -> Result<f32, DecodeError> {
+        Ok(self.f32()?.clamp(0.0, MAX_STROKE_WIDTH))
+    }
+
+    fn point(&mut self) -> Result<LocalPoint, DecodeError> {
+        Ok(LocalPoint::new(self.f32()?, self.f32()?))
+    }
+
+    fn vector(&mut self) -> Result<LocalVector, DecodeError> {
+        Ok(LocalVector::new(self.f32()?, self.f32()?))
+    }
+
+    fn rect(&mut self) -> Result<LocalRect, DecodeError> {
+        let origin = self.point()?;
+        let size = LocalSize::new(self.f32()?, self.f32()?);
+        if size.width < 0.0 || size.height < 0.0 {
+            return Err(DecodeError::OutOfRange);
+        }
+        Ok(LocalRect::new(origin, size))
+    }
+
+    /// Corner radius of `rect`, clamped to half its shorter side, past
+    /// which the shape doesn't change.
+    fn corner_radius(&mut self, rect: &LocalRect) -> Result<f32, DecodeError> {
+        let max = rect.size.width.min(rect.size.height) / 2.0;
+        Ok(self.f32()?.clamp(0.0, max))
+    }
+
+    fn color(&mut self) -> Result<Color, DecodeError> {
+        Ok(Color::new(
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+        ))
+    }
+
+    /// A count of items at least `item_len` bytes each, checked against
+    /// what's left so a bad count can't allocate too much.
+    fn count(&mut self, item_len: usize) -> Result<usize, DecodeError> {
+        let n = self.u32()? as usize;
+        if n.saturating_mul(item_len) > self.0.len() {
+            return Err(DecodeError::Truncated);
+        }
+        Ok(n)
+    }
+}
+
+/// Appends the encoding of `list` to `bytes`.
+pub fn encode(list: &DisplayList, bytes: &mut Vec<u8>) {
+    let mut w = Writer(bytes);
+    w.u8(WIRE_VERSION);
+    for command in list.commands() {
+        match command {
+            DrawCommand::Save => w.u8(SAVE),
+            DrawCommand::Restore => w.u8(RESTORE),
+            DrawCommand::Translate(v) => {
+                w.u8(TRANSLATE);
+                w.f32(v.x);
+                w.f32(v.y);
+            }
+            DrawCommand::Scale(v) => {
+                w.u8(SCALE);
+                w.f32(v.x);
+                w.f32(v.y);
+            }
+            DrawCommand::Rotate(theta) => {
+                w.u8(ROTATE);
+                w.f32(*theta);
+            }
+            DrawCommand::Scissor(rect) => {
+                w.u8(SCISSOR);
+                w.rect(*rect);
+            }
+            DrawCommand::FillRect {
+                rect,
+                radius,
+                color,
+            } => {
+                w.u8(FILL_RECT);
+                w.rect(*rect);
+                w.f32(*radius);
+                w.color(*color);
+            }
+            DrawCommand::StrokeRect {
+                rect,
+                radius,
+                width,
+                color,
+            } => {
+                w.u8(STROKE_RECT);
+                w.rect(*rect);
+                w.f32(*radius);
+                w.f32(*width);
+                w.color(*color);
+            }
+            DrawCommand::FillCircle {
+                center,
+                radius,
+                color,
+            } => {
+                w.u8(FILL_CIRCLE);
+                w.point(*center);
+                w.f32(*radius);
+                w.color(*color);
+            }
+            DrawCommand::StrokeSegment { a, b, width, color } => {
+                w.u8(STROKE_SEGMENT);
+                w.point(*a);
+                w.point(*b);
+                w.f32(*width);
+                w.color(*color);
+            }
+            DrawCommand::StrokeBezier {
+                a,
+                b,
+                c,
+                width,
+                color,
+            } => {
+                w.u8(STROKE_BEZIER);
+                w.point(*a);
+                w.point(*b);
+                w.point(*c);
+                w.f32(*width);
+                w.color(*color);
+            }
+            DrawCommand::FillPath {
+                start,
+                quads,
+                color,
+            } => {
+                w.u8(FILL_PATH);
+                w.point(*start);
+                w.u32(quads.len() as u32);
+                for (b, c) in quads {
+                    w.point(*b);
+                    w.point(*c);
+                }
+                w.color(*color);
+            }
+            DrawCommand::Text {
+                text,
+                size,
+                color,
+                max_width,
+            } => {
+                w.u8(TEXT);
+                w.u32(text.len() as u32);
+                w.0.extend_from_slice(text.as_bytes());
+                w.u32(*size);
+                w.color(*color);
+                match max_width {
+                    Some(width) => {
+                        w.u8(1);
+                        w.f32(*width);
+                    }
+                    None => w.u8(0),
+                }
+            }
+        }
+    }
+}
+
+/// Decodes a display list encoded with `encode`. Text sizes, stroke
+/// widths and corner radii are clamped, and negative sizes and deep
+/// nesting are errors, so lists from other processes are safe to draw.
+pub fn decode(bytes: &[u8]) -> Result<DisplayList, DecodeError> {
+    let mut r = Reader(bytes);
+    let version = r.u8()?;
+    if version != WIRE_VERSION {
+        return Err(DecodeError::Version(version));
+    }
+
+    let mut list = DisplayList::new();
+    let mut depth: usize = 0;
+    while !r.0.is_empty() {
+        let command = match r.u8()? {
+            SAVE => {
+                depth += 1;
+                if depth > MAX_SAVE_DEPTH {
+                    return Err(DecodeError::TooDeep);
+                }
+                DrawCommand::Save
+            }
+            RESTORE => {
+                depth = depth.saturating_sub(1);
+                DrawCommand::Restore
+            }
+            TRANSLATE => DrawCommand::Translate(r.vector()?),
+            SCALE => DrawCommand::Scale(r.vector()?),
+            ROTATE => DrawCommand::Rotate(r.f32()?),
+            SCISSOR => DrawCommand::Scissor(r.rect()?),
+            FILL_RECT => {
+                let rect = r.rect()?;
+                DrawCommand::FillRect {
+                    rect,
+                    radius: r.corner_radius(&rect)?,
+                    color: r.color()?,
+                }
+            }
+            STROKE_RECT => {
+                let rect = r.rect()?;
+                DrawCommand::StrokeRect {
+                    rect,
+                    radius: r.corner_radius(&rect)?,
+                    width: r.width()?,
+                    color: r.color()?,
+                }
+            }
+            FILL_CIRCLE => DrawCommand::FillCircle {
+                center: r.point()?,
+                radius: r.f32()?,
+                color: r.color()?,
+            },
+            STROKE_SEGMENT => DrawCommand::StrokeSegment {
+                a: r.point()?,
+                b: r.point()?,
+                width: r.width()?,
+                color: r.color()?,
+            },
+            STROKE_BEZIER => DrawCommand::StrokeBezier {
+                a: r.point()?,
+                b: r.point()?,
+                c: r.point()?,
+                width: r.width()?,
+                color: r.color()?,
+            },
+            FILL_PATH => {
+                let start = r.point()?;
+                let n = r.count(16)?;
+                let mut quads = Vec::with_capacity(n);
+                for _ in 0..n {
+                    quads.push((r.point()?, r.point()?));
+                }
+                DrawCommand::FillPath {
+                    start,
+                    quads,
+                    color: r.color()?,
+                }
+            }
+            TEXT => {
+                let n = r.count(1)?;
+                let text =
+                    String::from_utf8(r.bytes(n)?.to_vec()).map_err(|_| DecodeError::Utf8)?;
+                DrawCommand::Text {
+                    text,
+                    size: r.u32()?.min(MAX_TEXT_SIZE),
+                    color: r.color()?,
+                    max_width: match r.u8()? {
+                        0 => None,
+                        _ => match r.f32()? {
+                            width if width > 0.0 => Some(width),
+                            _ => return Err(DecodeError::OutOfRange),
+                        },
+                    },
+                }
+            }
+            op => return Err(DecodeError::Opcode(op)),
+        };
+        list.push(command);
+    }
+    Ok(list)
+}
+
+/// Writes `list` as one length prefixed frame.
+#[cfg(feature = "std")]
+pub fn write_frame<W: std::io::Write>(writer: &mut W, list: &DisplayList) -> std::io::Result<()> {
+    let mut bytes = vec![0; 4];
+    encode(list, &mut bytes);
+    let len = (bytes.len() - 4) as u32;
+    bytes[..4].copy_from_slice(&len.to_le_bytes());
+    writer.write_all(&bytes)
+}
+
+/// Reads one length prefixed frame, or `None` if the stream ended cleanly
+/// between frames.
+#[cfg(feature = "std")]
+pub fn read_frame<R: std::io::Read>(reader: &mut R) -> std::io::Result<Option<DisplayList>> {
+    use std::io::{Error, ErrorKind};
+
+    let mut len = [0u8; 4];
+    let mut filled = 0;
+    while filled < len.len() {
+        match reader.read(&mut len[filled..])? {
+            0 if filled == 0 => return Ok(None),
+            0 => return Err(ErrorKind::UnexpectedEof.into()),
+            n => filled += n,
+        }
+    }
+
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(Error::new(ErrorKind::InvalidData, "frame too large"));
+    }
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+    decode(&bytes)
+        .map(Some)
+        .map_err(|err| Error::new(ErrorKind::InvalidData, err))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn sample() -> DisplayList {
+        let mut list = DisplayList::new();
+        list.save();
+        list.translate([10.0, 20.0]);
+        list.rotate(0.5);
+        list.scissor(LocalRect::new(
+            LocalPoint::new(0.0, 0.0),
+            LocalSize::new(5.0, 6.0),
+        ));
+        list.fill_rect(
+            LocalRect::new(LocalPoint::new(1.0, 2.0), LocalSize::new(3.0, 4.0)),
+            2.0,
+            Color::CYAN,
+        );
+        list.stroke_segment([0.0, 0.0], [1.0, 1.0], 2.0, Color::WHITE);
+        list.move_to([0.0, 0.0]);
+        list.line_to([10.0, 0.0]);
+        list.quad_to([10.0, 10.0], [0.0, 10.0]);
+        list.fill(Color::MAGENTA);
+        list.text("héllo", 18, Color::WHITE, Some(100.0));
+        list.text("", 12, Color::WHITE, None);
+        list.restore();
+        list
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let list = sample();
+        let mut bytes = vec![];
+        encode(&list, &mut bytes);
+        assert_eq!(decode(&bytes), Ok(list));
+
+        // Truncated bytes don't panic, and a cut inside a command is an
+        // error.
+        for n in 0..bytes.len() {
+            let _ = decode(&bytes[..n]);
+        }
+        assert_eq!(decode(&bytes[..4]), Err(DecodeError::Truncated));
+
+        bytes[0] = WIRE_VERSION + 1;
+        assert_eq!(decode(&bytes), Err(DecodeError::Version(WIRE_VERSION + 1)));
+        assert_eq!(decode(&[WIRE_VERSION, 200]), Err(DecodeError::Opcode(200)));
+    }
+
+    #[test]
+    fn test_hostile() {
+        let mut list = DisplayList::new();
+        list.text("a", u32::MAX, Color::WHITE, None);
+        list.stroke_segment([0.0, 0.0], [1.0, 1.0], 1e30, Color::WHITE);
+        let mut bytes = vec![];
+        encode(&list, &mut bytes);
+
+        let mut clamped = DisplayList::new();
+        clamped.text("a", MAX_TEXT_SIZE, Color::WHITE, None);
+        clamped.stroke_segment([0.0, 0.0], [1.0, 1.0], MAX_STROKE_WIDTH, Color::WHITE);
+        assert_eq!(decode(&bytes), Ok(clamped));
+
+        // Corner radii past half the shorter side don't change the shape.
+        let rect = LocalRect::new(LocalPoint::new(0.0, 0.0), LocalSize::new(10.0, 4.0));
+        let mut list = DisplayList::new();
+        list.fill_rect(rect, 1e30, Color::WHITE);
+        list.stroke_rect(rect, -1.0, 1.0, Color::WHITE);
+        let mut bytes = vec![];
+        encode(&list, &mut bytes);
+        let mut clamped = DisplayList::new();
+        clamped.fill_rect(rect, 2.0, Color::WHITE);
+        clamped.stroke_rect(rect, 0.0, 1.0, Color::WHITE);
+        assert_eq!(decode(&bytes), Ok(clamped));
+
+        let negative = LocalRect::new(LocalPoint::new(0.0, 0.0), LocalSize::new(-1.0, 4.0));
+        let mut rejected = vec![DisplayList::new(), DisplayList::new()];
+        rejected[0].fill_rect(negative, 0.0, Color::WHITE);
+        rejected[1].scissor(negative);
+        for width in [0.0, -5.0] {
+            let mut list = DisplayList::new();
+            list.text("a", 12, Color::WHITE, Some(width));
+            rejected.push(list);
+        }
+        for list in rejected {
+            let mut bytes = vec![];
+            encode(&list, &mut bytes);
+            assert_eq!(decode(&bytes), Err(DecodeError::OutOfRange));
+        }
+
+        // Saves can nest up to the limit, and restores make room again.
+        let mut list = DisplayList::new();
+        for _ in 0..MAX_SAVE_DEPTH {
+            list.save();
+        }
+        list.restore();
+        list.save();
+        let mut bytes = vec![];
+        encode(&list, &mut bytes);
+        assert!(decode(&bytes).is_ok());
+        list.save();
+        bytes.clear();
+        encode(&list, &mut bytes);
+        assert_eq!(decode(&bytes), Err(DecodeError::TooDeep));
+
+        for v in [f32::NAN, f32::INFINITY] {
+            let mut list = DisplayList::new();
+            list.rotate(v);
+            let mut bytes = vec![];
+            encode(&list, &mut bytes);
+            assert_eq!(decode(&bytes), Err(DecodeError::NonFinite));
+        }
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut stream = vec![];
+        write_frame(&mut stream, &sample()).unwrap();
+        write_frame(&mut stream, &DisplayList::new()).unwrap();
+
+        let mut reader = &stream[..];
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(sample()));
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(DisplayList::new()));
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+
+        // Huge lengths are refused before allocating.
+        let mut reader = &[0xff, 0xff, 0xff, 0xff][..];
+        assert!(read_frame(&mut reader).is_err());
+    }
+}